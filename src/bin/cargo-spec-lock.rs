@@ -10,6 +10,8 @@ use std::path::PathBuf;
 mod parser;
 #[path = "../translator/mod.rs"]
 mod translator;
+#[path = "../cache.rs"]
+mod cache;
 
 // Include CLI modules (they're in src/bin/cli/)
 mod cli;
@@ -49,13 +51,36 @@ enum Commands {
         #[arg(short, long, default_value = "1")]
         jobs: usize,
         
-        /// Timeout per function (seconds)
+        /// Solver timeout per contract (seconds, 0 = no limit)
         #[arg(long, default_value = "5")]
         timeout: u64,
         
-        /// Verbose output
+        /// Report each function on stderr as it's verified
         #[arg(short, long)]
         verbose: bool,
+        
+        /// Use bit-precise (fixed-width, wrapping) integer semantics
+        #[arg(long)]
+        bit_precise: bool,
+        
+        /// Skip the implicit arithmetic-overflow checks
+        #[arg(long)]
+        no_overflow_checks: bool,
+        
+        /// Write a regression test for each counterexample into this directory
+        /// (relative to each function's crate); only a function's first failing
+        /// postcondition has a counterexample, so each function gets at most one test
+        #[arg(long, value_name = "DIR")]
+        emit_regression_tests: Option<PathBuf>,
+        
+        /// Re-verify every function instead of reusing results from target/.spec-cache
+        #[arg(long)]
+        no_cache: bool,
+        
+        /// Path to Orange Paper, whose text is part of the cache key
+        /// (default: ../blvm-spec/THE_ORANGE_PAPER.md)
+        #[arg(long)]
+        spec_path: Option<PathBuf>,
     },
     
     /// Show coverage report
@@ -74,6 +99,40 @@ enum Commands {
         /// Filter by section
         #[arg(long)]
         section: Option<String>,
+        
+        /// Path to Orange Paper (default: ../blvm-spec/THE_ORANGE_PAPER.md)
+        #[arg(long)]
+        spec_path: Option<PathBuf>,
+        
+        /// Output format
+        #[arg(long, default_value = "human")]
+        format: OutputFormat,
+    },
+    
+    /// Pin each spec-locked function to a hash of its Orange Paper text
+    Lock {
+        /// Path to Orange Paper (default: ../blvm-spec/THE_ORANGE_PAPER.md)
+        #[arg(long)]
+        spec_path: Option<PathBuf>,
+        
+        /// Lock file path (default: spec-lock.json)
+        #[arg(long)]
+        lock_file: Option<PathBuf>,
+    },
+    
+    /// Fail if the Orange Paper text behind a locked function has changed
+    CheckLock {
+        /// Path to Orange Paper (default: ../blvm-spec/THE_ORANGE_PAPER.md)
+        #[arg(long)]
+        spec_path: Option<PathBuf>,
+        
+        /// Lock file path (default: spec-lock.json)
+        #[arg(long)]
+        lock_file: Option<PathBuf>,
+        
+        /// Output format
+        #[arg(long, default_value = "human")]
+        format: OutputFormat,
     },
     
     /// Check for spec drift (Orange Paper vs implementation)
@@ -87,6 +146,22 @@ enum Commands {
         format: OutputFormat,
     },
     
+    /// Fail if workspace constants disagree with Orange Paper Section 4
+    CheckConstants {
+        /// Path to Orange Paper (default: ../blvm-spec/THE_ORANGE_PAPER.md)
+        #[arg(long)]
+        spec_path: Option<PathBuf>,
+        
+        /// Map a spec constant to a workspace constant, e.g. H=HALVING_INTERVAL
+        /// (repeatable; defaults cover H, C and M_max)
+        #[arg(long = "map", value_name = "SPEC=RUST", action = clap::ArgAction::Append)]
+        mappings: Vec<String>,
+        
+        /// Output format
+        #[arg(long, default_value = "human")]
+        format: OutputFormat,
+    },
+    
     /// Extract constants from Orange Paper and generate Rust module
     ExtractConstants {
         /// Path to Orange Paper (default: ../blvm-spec/THE_ORANGE_PAPER.md)
@@ -108,6 +183,16 @@ enum Commands {
         #[arg(short, long)]
         output: Option<PathBuf>,
     },
+    
+    /// Generate proptest property tests from the contracts of spec-locked functions
+    GenProptests {
+        /// Directory for the generated tests, relative to each function's crate
+        #[arg(long, value_name = "DIR", default_value = "tests")]
+        dir: PathBuf,
+    },
+    
+    /// Generate Kani proof harnesses from the contracts of spec-locked functions
+    GenKani,
 }
 
 #[derive(Clone, Debug)]
@@ -142,29 +227,59 @@ fn main() {
             name, 
             section, 
             format, 
-            jobs: _,
-            timeout: _,
-            verbose: _,
+            jobs,
+            timeout,
+            verbose,
+            bit_precise,
+            no_overflow_checks,
+            emit_regression_tests,
+            no_cache,
+            spec_path,
         } => {
-            handle_verify(files, subsystem, name, section, format)
+            let options = cli::verify::VerifyOptions {
+                bit_precise,
+                no_overflow_checks,
+                // 0 disables the timeout
+                timeout: (timeout > 0).then(|| std::time::Duration::from_secs(timeout)),
+                jobs,
+                verbose,
+                summaries: Vec::new(),
+                types: Vec::new(),
+                constants: Vec::new(),
+            };
+            let extras = VerifyExtras { emit_regression_tests, no_cache, spec_path };
+            handle_verify(files, subsystem, name, section, format, &options, &extras)
         }
         Commands::Coverage { format } => {
             handle_coverage(format)
         }
-        Commands::List { subsystem, section } => {
-            eprintln!("List command not yet implemented");
-            eprintln!("Subsystem: {:?}, Section: {:?}", subsystem, section);
-            1
+        Commands::List { subsystem, section, spec_path, format } => {
+            handle_list(subsystem, section, spec_path.as_ref(), format)
+        }
+        Commands::Lock { spec_path, lock_file } => {
+            handle_lock(spec_path.as_ref(), lock_file.as_ref())
+        }
+        Commands::CheckLock { spec_path, lock_file, format } => {
+            handle_check_lock(spec_path.as_ref(), lock_file.as_ref(), format)
         }
         Commands::CheckDrift { spec_path, format } => {
             handle_check_drift(spec_path.as_ref(), format)
         }
+        Commands::CheckConstants { spec_path, mappings, format } => {
+            handle_check_constants(spec_path.as_ref(), &mappings, format)
+        }
         Commands::ExtractConstants { spec_path, output } => {
             handle_extract_constants(spec_path.as_ref(), output.as_ref())
         }
         Commands::ExtractFormulas { spec_path, output } => {
             handle_extract_formulas(spec_path.as_ref(), output.as_ref())
         }
+        Commands::GenProptests { dir } => {
+            handle_gen_proptests(&dir)
+        }
+        Commands::GenKani => {
+            handle_gen_kani()
+        }
     };
 
     std::process::exit(exit_code);
@@ -207,6 +322,181 @@ fn handle_check_drift(spec_path: Option<&PathBuf>, format: OutputFormat) -> i32
     }
 }
 
+fn handle_check_constants(spec_path: Option<&PathBuf>, mappings: &[String], format: OutputFormat) -> i32 {
+    let workspace_root = std::env::current_dir()
+        .unwrap_or_else(|_| PathBuf::from("."));
+
+    let overrides = match mappings.iter().map(|m| cli::constants::parse_mapping(m)).collect::<Result<Vec<_>, _>>() {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 1;
+        }
+    };
+
+    let checks = match cli::constants::check_constants(&workspace_root, spec_path, &overrides) {
+        Ok(checks) => checks,
+        Err(e) => {
+            eprintln!("Error checking constants: {}", e);
+            return 1;
+        }
+    };
+    if checks.is_empty() {
+        eprintln!("No constants found in Orange Paper Section 4");
+        return 1;
+    }
+
+    let output = match format {
+        OutputFormat::Human => cli::constants::format_constant_checks_human(&checks),
+        OutputFormat::Json => cli::constants::format_constant_checks_json(&checks),
+        OutputFormat::Markdown => {
+            eprintln!("Markdown format not yet implemented for constant checks");
+            return 1;
+        }
+        OutputFormat::Junit => {
+            eprintln!("JUnit format not yet implemented for constant checks");
+            return 1;
+        }
+    };
+
+    print!("{}", output);
+
+    // A value that disagrees with the spec is a consensus bug
+    if checks.iter().any(|c| c.status == cli::constants::ConstantStatus::Mismatch) {
+        1
+    } else {
+        0
+    }
+}
+
+fn handle_list(
+    subsystem: Option<String>,
+    section: Option<String>,
+    spec_path: Option<&PathBuf>,
+    format: OutputFormat,
+) -> i32 {
+    let workspace_root = std::env::current_dir()
+        .unwrap_or_else(|_| PathBuf::from("."));
+
+    let all_functions = match cli::verify::discover_functions(&workspace_root) {
+        Ok(funcs) => funcs,
+        Err(e) => {
+            eprintln!("Error discovering functions: {}", e);
+            return 1;
+        }
+    };
+
+    let sections: Vec<String> = section.into_iter().collect();
+    let functions = cli::filters::filter_functions(all_functions, subsystem.as_deref(), None, &sections);
+
+    // Still list functions without the Orange Paper, just without section checks
+    let spec = match cli::spec::load_spec(&workspace_root, spec_path) {
+        Ok(spec) => Some(spec),
+        Err(e) => {
+            eprintln!("⚠️  {} (sections will not be checked)", e);
+            None
+        }
+    };
+
+    let entries = cli::list::list_functions(&functions, spec.as_ref());
+
+    let output = match format {
+        OutputFormat::Human => cli::list::format_list_human(&entries),
+        OutputFormat::Json => cli::list::format_list_json(&entries),
+        OutputFormat::Markdown => cli::list::format_list_markdown(&entries),
+        OutputFormat::Junit => {
+            eprintln!("JUnit format not yet implemented for list");
+            return 1;
+        }
+    };
+
+    print!("{}", output);
+    0
+}
+
+fn handle_lock(spec_path: Option<&PathBuf>, lock_file: Option<&PathBuf>) -> i32 {
+    let workspace_root = std::env::current_dir()
+        .unwrap_or_else(|_| PathBuf::from("."));
+    let lock_path = lock_file
+        .cloned()
+        .unwrap_or_else(|| workspace_root.join(cli::lock::LOCK_FILE));
+
+    let functions = match cli::verify::discover_functions(&workspace_root) {
+        Ok(funcs) => funcs,
+        Err(e) => {
+            eprintln!("Error discovering functions: {}", e);
+            return 1;
+        }
+    };
+    let spec = match cli::spec::load_spec(&workspace_root, spec_path) {
+        Ok(spec) => spec,
+        Err(e) => {
+            eprintln!("Error loading Orange Paper: {}", e);
+            return 1;
+        }
+    };
+
+    let (lock, unresolved) = cli::lock::build_lock(&functions, &spec, &workspace_root);
+    if !unresolved.is_empty() {
+        for function in &unresolved {
+            eprintln!("❌ No Orange Paper text found for {}", function);
+        }
+        return 1;
+    }
+
+    if let Err(e) = lock.write(&lock_path) {
+        eprintln!("Error writing lock file: {}", e);
+        return 1;
+    }
+
+    eprintln!("✅ Locked {} functions in {}", lock.functions.len(), lock_path.display());
+    0
+}
+
+fn handle_check_lock(spec_path: Option<&PathBuf>, lock_file: Option<&PathBuf>, format: OutputFormat) -> i32 {
+    let workspace_root = std::env::current_dir()
+        .unwrap_or_else(|_| PathBuf::from("."));
+    let lock_path = lock_file
+        .cloned()
+        .unwrap_or_else(|| workspace_root.join(cli::lock::LOCK_FILE));
+
+    let lock = match cli::lock::SpecLock::read(&lock_path) {
+        Ok(lock) => lock,
+        Err(e) => {
+            eprintln!("Error reading lock file: {} (run `cargo spec-lock lock` first)", e);
+            return 1;
+        }
+    };
+    let functions = match cli::verify::discover_functions(&workspace_root) {
+        Ok(funcs) => funcs,
+        Err(e) => {
+            eprintln!("Error discovering functions: {}", e);
+            return 1;
+        }
+    };
+    let spec = match cli::spec::load_spec(&workspace_root, spec_path) {
+        Ok(spec) => spec,
+        Err(e) => {
+            eprintln!("Error loading Orange Paper: {}", e);
+            return 1;
+        }
+    };
+
+    let check = cli::lock::check_lock(&lock, &functions, &spec, &workspace_root);
+
+    let output = match format {
+        OutputFormat::Human => cli::lock::format_lock_check_human(&check),
+        OutputFormat::Json => cli::lock::format_lock_check_json(&check),
+        OutputFormat::Markdown | OutputFormat::Junit => {
+            eprintln!("Only human and JSON formats are implemented for check-lock");
+            return 1;
+        }
+    };
+
+    print!("{}", output);
+    if check.is_stale() { 1 } else { 0 }
+}
+
 fn handle_coverage(format: OutputFormat) -> i32 {
     let workspace_root = std::env::current_dir()
         .unwrap_or_else(|_| PathBuf::from("."));
@@ -233,12 +523,21 @@ fn handle_coverage(format: OutputFormat) -> i32 {
     0
 }
 
+/// `verify` settings that don't affect how a single function is verified
+struct VerifyExtras {
+    emit_regression_tests: Option<PathBuf>,
+    no_cache: bool,
+    spec_path: Option<PathBuf>,
+}
+
 fn handle_verify(
     files: Vec<String>,
     subsystem: Option<String>,
     name: Option<String>,
     sections: Vec<String>,
     format: OutputFormat,
+    options: &cli::verify::VerifyOptions,
+    extras: &VerifyExtras,
 ) -> i32 {
     // Find workspace root (simplified - would use cargo-metadata in full implementation)
     let workspace_root = std::env::current_dir()
@@ -253,6 +552,23 @@ fn handle_verify(
         }
     };
 
+    let (constants, unresolved) = cli::constants::discover_constants(&workspace_root);
+    for constant in &unresolved {
+        eprintln!("⚠️  Unresolved constant `{}`: {}", constant.name, constant.reason);
+    }
+    let (types, conflicting) = cli::verify::discover_types(&workspace_root);
+    for name in &conflicting {
+        eprintln!("⚠️  Type `{}` isn't modelled: it is defined differently in more than one place", name);
+    }
+
+    // Calls are verified against the callee's contracts, filtered out or not
+    let options = &cli::verify::VerifyOptions {
+        summaries: cli::verify::callee_summaries(&all_functions),
+        types,
+        constants,
+        ..options.clone()
+    };
+
     // Apply filters
     let filtered = cli::filters::filter_functions(
         all_functions,
@@ -266,12 +582,30 @@ fn handle_verify(
         return 1;
     }
 
-    // Verify functions
-    let mut results = Vec::new();
-    for func in &filtered {
-        let result = cli::verify::verify_function(func);
-        results.push((func.clone(), result));
-    }
+    // The spec text is part of the cache key and holds the theorems to check;
+    // without it, only code changes invalidate and no theorems are checked
+    let spec = cli::spec::load_spec(&workspace_root, extras.spec_path.as_ref()).ok();
+
+    // Verify functions and their theorems, reusing cached results for unchanged ones
+    let cache = (!extras.no_cache).then(|| cache::SpecCache::new(&workspace_root));
+    let (verified, cached): (Vec<_>, Vec<bool>) = match &cache {
+        Some(cache) => cli::incremental::verify_functions_cached(&filtered, options, spec.as_ref(), cache)
+            .into_iter()
+            .unzip(),
+        None => cli::verify::verify_functions(&filtered, options)
+            .into_iter()
+            .map(|result| (result, false))
+            .unzip(),
+    };
+    let theorems = match (&spec, &cache) {
+        (Some(spec), Some(cache)) => cli::incremental::verify_theorems_cached(&filtered, spec, options, cache),
+        (Some(spec), None) => cli::theorems::verify_theorems(&filtered, spec, options),
+        (None, _) => Vec::new(),
+    };
+    let results: Vec<_> = filtered.iter()
+        .cloned()
+        .zip(verified)
+        .collect();
 
     // Format and output results
     let format_str = match format {
@@ -281,13 +615,30 @@ fn handle_verify(
         OutputFormat::Markdown => "markdown",
     };
     
-    let output = cli::output::format_results(&results, format_str);
+    let output = cli::output::format_results(&results, format_str, &cached, &theorems);
     print!("{}", output);
 
+    if let Some(dir) = &extras.emit_regression_tests {
+        match cli::regression::emit_regression_tests(&results, dir) {
+            Ok(summary) => {
+                for (path, count) in &summary.files {
+                    eprintln!("✅ Wrote {} regression tests to {}", count, path.display());
+                }
+                for (function, reason) in &summary.skipped {
+                    eprintln!("⚠️  No regression test for {}: {}", function, reason);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error writing regression tests: {}", e);
+                return 1;
+            }
+        }
+    }
+
     // Return exit code: 0 if all passed, 1 if any failed
     let has_failures = results.iter().any(|(_, r)| {
-        matches!(r, cli::verify::VerificationResult::Failed { .. })
-    });
+        matches!(r, cli::verify::VerificationResult::Failed { .. } | cli::verify::VerificationResult::Overflow { .. })
+    }) || theorems.iter().any(|t| t.is_failure());
     
     if has_failures {
         1
@@ -432,6 +783,80 @@ fn handle_extract_formulas(spec_path: Option<&PathBuf>, output_path: Option<&Pat
     0
 }
 
+fn handle_gen_proptests(dir: &std::path::Path) -> i32 {
+    let workspace_root = std::env::current_dir()
+        .unwrap_or_else(|_| PathBuf::from("."));
+
+    let functions = match cli::verify::discover_functions(&workspace_root) {
+        Ok(functions) => functions,
+        Err(e) => {
+            eprintln!("Error discovering functions: {}", e);
+            return 1;
+        }
+    };
+    if functions.is_empty() {
+        eprintln!("No #[spec_locked] functions found");
+        return 1;
+    }
+    let (constants, _) = cli::constants::discover_constants(&workspace_root);
+
+    match cli::proptests::emit_proptests(&functions, &constants, dir) {
+        Ok(summary) => {
+            for (path, count) in &summary.files {
+                eprintln!("✅ Wrote {} property tests to {}", count, path.display());
+            }
+            for (function, reason) in &summary.skipped {
+                eprintln!("⚠️  No property test for {}: {}", function, reason);
+            }
+            for crate_root in &summary.missing_dependency {
+                eprintln!("⚠️  {} has no proptest dependency; add `proptest = \"1\"` to its [dev-dependencies]", crate_root.join("Cargo.toml").display());
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error writing property tests: {}", e);
+            1
+        }
+    }
+}
+
+fn handle_gen_kani() -> i32 {
+    let workspace_root = std::env::current_dir()
+        .unwrap_or_else(|_| PathBuf::from("."));
+
+    let functions = match cli::verify::discover_functions(&workspace_root) {
+        Ok(functions) => functions,
+        Err(e) => {
+            eprintln!("Error discovering functions: {}", e);
+            return 1;
+        }
+    };
+    if functions.is_empty() {
+        eprintln!("No #[spec_locked] functions found");
+        return 1;
+    }
+    let (constants, _) = cli::constants::discover_constants(&workspace_root);
+
+    match cli::kani::emit_kani_harnesses(&functions, &constants) {
+        Ok(summary) => {
+            for (path, count) in &summary.files {
+                eprintln!("✅ Wrote {} Kani harnesses to {}", count, path.display());
+            }
+            for (function, reason) in &summary.skipped {
+                eprintln!("⚠️  No Kani harness for {}: {}", function, reason);
+            }
+            for crate_root in &summary.undeclared {
+                eprintln!("⚠️  {} doesn't declare the harnesses; add `#[cfg(kani)] mod spec_lock_kani;` to its crate root", crate_root.display());
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("Error writing Kani harnesses: {}", e);
+            1
+        }
+    }
+}
+
 fn generate_property_helpers(functions: &[&parser::orange_paper::FunctionSpec]) -> String {
     let mut code = String::from("//! Property test helpers generated from Orange Paper formulas\n");
     code.push_str("//!\n");
@@ -447,55 +872,27 @@ fn generate_property_helpers(functions: &[&parser::orange_paper::FunctionSpec])
     code.push_str("#[cfg(test)]\n");
     code.push_str("use proptest::prelude::*;\n\n");
     
-    // Only generate helpers for functions we can actually implement
-    // Focus on economic functions first (most important for property tests)
-    let implementable_functions: Vec<&str> = vec![
-        "GetBlockSubsidy", "get_block_subsidy", "BlockSubsidy",
-        "TotalSupply", "total_supply", "Supply",
-    ];
+    // Helpers call each other for formulas that refer to other spec functions
+    let helper_names: std::collections::HashMap<String, String> = functions.iter()
+        .filter(|func| func.formula.is_some())
+        .map(|func| (func.name.clone(), cli::proptests::helper_name(&func.name)))
+        .collect();
     
     for func in functions {
         if let Some(formula) = &func.formula {
-            // Check if this function is implementable
-            let func_lower = func.name.to_lowercase();
-            let formula_lower = formula.to_lowercase();
-            let is_implementable = implementable_functions.iter().any(|&name| {
-                func_lower.contains(&name.to_lowercase()) || 
-                formula_lower.contains(&name.to_lowercase())
-            });
-            
-            if !is_implementable {
-                continue;  // Skip functions we can't implement yet
-            }
-            
-            // Generate helper function for this formula
-            let helper_name = format!("expected_{}_from_orange_paper", func.name.to_lowercase().replace(" ", "_"));
-            let rust_formula = translate_formula_to_rust(formula, &func.name);
+            // Only generate helpers for formulas that translate to Rust
+            let Some(rust_formula) = translate_formula_to_rust(formula, &func.name, &helper_names) else {
+                continue;
+            };
+            let helper_name = cli::proptests::helper_name(&func.name);
             
             code.push_str(&format!("/// Expected result from Orange Paper formula\n"));
             code.push_str(&format!("/// \n"));
             code.push_str(&format!("/// Source: Orange Paper Section {}\n", func.section));
-            // Clean formula for documentation (remove $$, limit length)
-            // For doc comments, we'll use a simplified description instead of raw LaTeX
-            let formula_cleaned = formula.replace("$$", "");
-            let formula_trimmed = formula_cleaned.trim();
-            // Extract just the function name and basic structure, avoid LaTeX
-            let formula_doc = if formula_trimmed.len() > 100 {
-                // Just show function name and section reference
-                format!("See Orange Paper Section {} for full formula", func.section)
-            } else {
-                // Try to extract readable parts, avoiding LaTeX commands
-                formula_trimmed
-                    .replace("\\text{", "")
-                    .replace("\\begin{cases}", "")
-                    .replace("\\end{cases}", "")
-                    .replace("\\times", "×")
-                    .replace("\\geq", "≥")
-                    .replace("\\leq", "≤")
-                    .chars()
-                    .take(100)
-                    .collect::<String>()
-            };
+            // Document the formula in Unicode notation rather than raw LaTeX
+            let formula_doc = parser::math::parse_math(parser::math::math_content(formula))
+                .map(|expr| expr.to_string())
+                .unwrap_or_else(|_| format!("See Orange Paper Section {} for full formula", func.section));
             code.push_str(&format!("/// Formula: {}\n", formula_doc));
             code.push_str(&format!("/// \n"));
             if let Some(desc) = &func.description {
@@ -535,60 +932,29 @@ fn generate_property_helpers(functions: &[&parser::orange_paper::FunctionSpec])
     code
 }
 
-fn translate_formula_to_rust(formula: &str, func_name: &str) -> String {
-    // Handle specific formulas with known patterns
-    let func_lower = func_name.to_lowercase();
-    let formula_lower = formula.to_lowercase();
-    
-    if func_lower.contains("getblocksubsidy") || func_lower.contains("block_subsidy") || 
-       formula_lower.contains("getblocksubsidy") || formula_lower.contains("block_subsidy") {
-        generate_get_block_subsidy_helper()
-    } else if func_lower.contains("totalsupply") || func_lower.contains("total_supply") ||
-              formula_lower.contains("totalsupply") || formula_lower.contains("total_supply") ||
-              formula_lower.contains("sum") && formula_lower.contains("getblocksubsidy") {
-        generate_total_supply_helper()
-    } else if func_lower.contains("calculatefee") || func_lower.contains("calculate_fee") ||
-              formula_lower.contains("calculatefee") || formula_lower.contains("calculate_fee") {
-        generate_calculate_fee_helper()
-    } else {
-        // Generic placeholder - will need manual implementation
-        // Only generate helpers for functions we can actually implement
-        let formula_clean = formula.replace("$$", "").trim().chars().take(80).collect::<String>();
-        format!("    // TODO: Implement formula translation for {}\n    // Formula: {}...\n    // This formula requires manual implementation\n    unimplemented!(\"Formula translation not yet implemented for {}\")", 
-            func_name, formula_clean, func_name)
-    }
-}
-
-fn generate_get_block_subsidy_helper() -> String {
-    String::from("    let halving_period = height / H;
-    let initial_subsidy = 50 * C;  // 50 BTC = 50 × C
-    if halving_period >= 64 {
-        0
-    } else {
-        initial_subsidy >> halving_period  // Uses Orange Paper formula: 50 × C × 2^(-⌊h/H⌋)
-    }")
-}
-
-fn generate_total_supply_helper() -> String {
-    String::from("    // TotalSupply(h) = sum of all block subsidies from 0 to h
-    // Formula: TotalSupply(h) = sum_{i=0}^{h} GetBlockSubsidy(i)
-    // This is computed by summing GetBlockSubsidy for each height
-    let mut total = 0i64;
-    for h in 0..=height {
-        let halving_period = h / H;
-        let initial_subsidy = 50 * C;
-        if halving_period < 64 {
-            total += (initial_subsidy >> halving_period) as i64;
-        }
+/// Translate a definition `Func(args) = expr` into the body of its helper
+///
+/// Returns None if the formula isn't a definition of `func_name` or doesn't translate.
+fn translate_formula_to_rust(
+    formula: &str,
+    func_name: &str,
+    helper_names: &std::collections::HashMap<String, String>,
+) -> Option<String> {
+    use parser::math::{self, BinaryOp, MathExpr, RustContext};
+
+    let expr = math::parse_math(math::math_content(formula)).ok()?;
+    let MathExpr::Binary { op: BinaryOp::Eq, left, right } = expr else {
+        return None;
+    };
+    match &*left {
+        MathExpr::Call { name, .. } if name.eq_ignore_ascii_case(func_name) => {}
+        _ => return None,
     }
-    total")
-}
 
-fn generate_calculate_fee_helper() -> String {
-    String::from("    // CalculateFee(inputs, outputs) = sum(inputs.value) - sum(outputs.value)
-    // Note: This is a placeholder - actual implementation needs input/output values
-    // TODO: Implement with actual transaction inputs and outputs
-    0")
+    // Constants keep their Orange Paper names (orange_paper_constants exports H, C, ...)
+    let mut ctx = RustContext::default().with_vars(math::CONVENTIONAL_PARAMS.iter().copied());
+    ctx.functions = helper_names.clone();
+    right.to_rust(&ctx).ok()
 }
 
 fn extract_formula_parameters(formula: &str, func_name: &str) -> Vec<String> {
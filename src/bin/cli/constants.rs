@@ -0,0 +1,618 @@
+//! Workspace constants: discovery and evaluation of `const` items
+//!
+//! Contracts and bodies refer to consensus constants such as `MAX_MONEY` by
+//! name. Rather than leaving them as free variables, `verify` evaluates every
+//! integer `const` item of the workspace and translates references to its
+//! value. Initializers may use integer literals, other constants (in any
+//! order), `u64::MAX`-style bounds, arithmetic, bitwise operators, shifts,
+//! `pow` and `as` casts. Types may be aliases such as `type Natural = u64;`.
+//!
+//! `check-constants` evaluates the Orange Paper's Section 4 constants the same
+//! way and compares them with the workspace constants they map to.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use quote::quote;
+use syn::{BinOp, Expr, UnOp};
+use crate::cli::verify::rust_files;
+use crate::parser::math;
+use crate::parser::orange_paper::SpecParser;
+
+/// An integer `const` item of the workspace, with its value
+#[derive(Debug, Clone)]
+pub struct WorkspaceConstant {
+    pub name: String,
+    /// Primitive integer type, e.g. `u64` (aliases are resolved)
+    pub type_name: String,
+    pub value: i128,
+}
+
+/// An integer `const` item whose value couldn't be determined
+#[derive(Debug, Clone)]
+pub struct UnresolvedConstant {
+    pub name: String,
+    pub reason: String,
+}
+
+/// A `const` item before evaluation
+struct ConstItem {
+    name: String,
+    type_name: String,
+    expr: Expr,
+}
+
+/// Why an initializer couldn't be evaluated (yet)
+enum EvalError {
+    /// It refers to a constant that hasn't been evaluated yet
+    Pending,
+    /// It can't be evaluated
+    Unsupported(String),
+}
+
+/// Discover and evaluate the integer constants defined in the workspace (including in inline modules)
+///
+/// Files that don't parse are skipped; `discover_functions` reports them.
+/// Constants of other types (strings, arrays, structs) are ignored.
+pub fn discover_constants(workspace_root: &PathBuf) -> (Vec<WorkspaceConstant>, Vec<UnresolvedConstant>) {
+    fn collect(items: &[syn::Item], consts: &mut Vec<syn::ItemConst>, aliases: &mut HashMap<String, syn::Type>) {
+        for item in items {
+            match item {
+                syn::Item::Const(item_const) => consts.push(item_const.clone()),
+                syn::Item::Type(alias) if alias.generics.params.is_empty() => {
+                    aliases.insert(alias.ident.to_string(), (*alias.ty).clone());
+                }
+                syn::Item::Mod(module) => {
+                    if let Some((_, items)) = &module.content {
+                        collect(items, consts, aliases);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    let mut consts = Vec::new();
+    let mut aliases = HashMap::new();
+    for path in rust_files(workspace_root) {
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        if let Ok(ast) = syn::parse_file(&content) {
+            collect(&ast.items, &mut consts, &mut aliases);
+        }
+    }
+    let items: Vec<ConstItem> = consts.into_iter()
+        .filter_map(|item| Some(ConstItem {
+            name: item.ident.to_string(),
+            type_name: integer_type(&item.ty, &aliases, 0)?,
+            expr: *item.expr,
+        }))
+        .collect();
+    evaluate_constants(items)
+}
+
+/// Evaluate constants that may refer to each other
+///
+/// A name defined more than once (e.g. in different modules) is only resolved
+/// if every definition has the same value.
+fn evaluate_constants(items: Vec<ConstItem>) -> (Vec<WorkspaceConstant>, Vec<UnresolvedConstant>) {
+    let mut definitions: HashMap<String, usize> = HashMap::new();
+    for item in &items {
+        *definitions.entry(item.name.clone()).or_default() += 1;
+    }
+    let mut values: HashMap<String, Vec<(String, i128)>> = HashMap::new();
+    let mut unresolved: BTreeMap<String, String> = BTreeMap::new();
+    let mut pending = items;
+    loop {
+        let mut waiting = Vec::new();
+        let mut progress = false;
+        for item in pending {
+            let lookup = |name: &str| -> Result<i128, EvalError> {
+                if let Some(reason) = unresolved.get(name) {
+                    return Err(EvalError::Unsupported(format!("`{}` is unresolved: {}", name, reason)));
+                }
+                let found = values.get(name).map_or(0, Vec::len);
+                match definitions.get(name) {
+                    None => Err(EvalError::Unsupported(format!("`{}` isn't a known constant", name))),
+                    Some(&count) if found < count => Err(EvalError::Pending),
+                    Some(_) => agreed_value(&values[name])
+                        .ok_or_else(|| EvalError::Unsupported(format!("`{}` is defined with different values", name))),
+                }
+            };
+            match evaluate(&item.expr, &item.type_name, &lookup).and_then(|value| fits(value, &item.type_name)) {
+                Ok(value) => {
+                    values.entry(item.name).or_default().push((item.type_name, value));
+                    progress = true;
+                }
+                Err(EvalError::Pending) => waiting.push(item),
+                Err(EvalError::Unsupported(reason)) => {
+                    unresolved.insert(item.name, reason);
+                    progress = true;
+                }
+            }
+        }
+        if waiting.is_empty() || !progress {
+            for item in waiting {
+                unresolved.insert(item.name, "its value depends on itself".to_string());
+            }
+            break;
+        }
+        pending = waiting;
+    }
+
+    let mut resolved = Vec::new();
+    for (name, definitions) in values {
+        if unresolved.contains_key(&name) {
+            continue;
+        }
+        match agreed_value(&definitions) {
+            Some(value) => resolved.push(WorkspaceConstant { name, type_name: definitions[0].0.clone(), value }),
+            None => {
+                unresolved.insert(name, "it is defined with different values".to_string());
+            }
+        }
+    }
+    resolved.sort_by(|a, b| a.name.cmp(&b.name));
+    let unresolved = unresolved.into_iter()
+        .map(|(name, reason)| UnresolvedConstant { name, reason })
+        .collect();
+    (resolved, unresolved)
+}
+
+/// An Orange Paper constant compared with the workspace constant it maps to
+#[derive(Debug, Clone)]
+pub struct ConstantCheck {
+    /// Name in the Orange Paper, e.g. `H`
+    pub spec_name: String,
+    pub section: String,
+    /// Workspace constant it maps to, e.g. `HALVING_INTERVAL`
+    pub code_name: String,
+    pub spec_value: Option<i128>,
+    pub code_value: Option<i128>,
+    pub status: ConstantStatus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstantStatus {
+    /// Both sides have the same value
+    Match,
+    /// The values differ
+    Mismatch,
+    /// No workspace constant has the mapped name
+    Unmapped,
+    /// One side couldn't be evaluated
+    Unresolved(String),
+}
+
+/// Parse a `SPEC=RUST` name mapping, e.g. `H=HALVING_INTERVAL`
+pub fn parse_mapping(mapping: &str) -> Result<(String, String), String> {
+    match mapping.split_once('=') {
+        Some((spec, code)) if !spec.trim().is_empty() && !code.trim().is_empty() => {
+            Ok((spec.trim().to_string(), code.trim().to_string()))
+        }
+        _ => Err(format!("Invalid constant mapping `{}` (expected SPEC=RUST, e.g. H=HALVING_INTERVAL)", mapping)),
+    }
+}
+
+/// The workspace constant a spec constant maps to
+///
+/// `overrides` take precedence over `math::CONSENSUS_CONSTANTS`; names are
+/// compared case-insensitively, since Section 4 names are upper-cased (`M_MAX`
+/// for `M_{max}`). Unmapped names map to themselves.
+fn mapped_name(spec_name: &str, overrides: &[(String, String)]) -> String {
+    overrides.iter()
+        .map(|(spec, code)| (spec.as_str(), code.as_str()))
+        .chain(math::CONSENSUS_CONSTANTS.iter().copied())
+        .find(|(spec, _)| spec.eq_ignore_ascii_case(spec_name))
+        .map_or(spec_name, |(_, code)| code)
+        .to_string()
+}
+
+/// Compare the Orange Paper's constants with the workspace's
+pub fn check_constants(
+    workspace_root: &PathBuf,
+    spec_path: Option<&PathBuf>,
+    overrides: &[(String, String)],
+) -> Result<Vec<ConstantCheck>, String> {
+    let spec = super::spec::load_spec(workspace_root, spec_path)?;
+    let (constants, unresolved) = discover_constants(workspace_root);
+    Ok(check_constants_with_spec(&spec, &constants, &unresolved, overrides))
+}
+
+/// Compare a parsed Orange Paper's constants with evaluated workspace constants
+pub fn check_constants_with_spec(
+    spec: &SpecParser,
+    constants: &[WorkspaceConstant],
+    unresolved: &[UnresolvedConstant],
+    overrides: &[(String, String)],
+) -> Vec<ConstantCheck> {
+    let extracted = spec.extract_constants();
+    // Spec constants may refer to each other (`M_MAX = 21 × 10^6 × C`)
+    let mut spec_unresolved: HashMap<String, String> = HashMap::new();
+    let items = extracted.iter()
+        .filter_map(|constant| match syn::parse_str::<Expr>(&constant.rust_expr) {
+            Ok(expr) => Some(ConstItem { name: constant.name.clone(), type_name: constant.rust_type.clone(), expr }),
+            Err(_) => {
+                spec_unresolved.insert(constant.name.clone(), format!("`{}` isn't an expression", constant.rust_expr));
+                None
+            }
+        })
+        .collect();
+    let (spec_values, spec_failures) = evaluate_constants(items);
+    spec_unresolved.extend(spec_failures.into_iter().map(|c| (c.name, c.reason)));
+
+    let mut checks: Vec<ConstantCheck> = Vec::new();
+    for constant in extracted {
+        if checks.iter().any(|check| check.spec_name == constant.name) {
+            continue;
+        }
+        let code_name = mapped_name(&constant.name, overrides);
+        let spec_value = spec_values.iter().find(|c| c.name == constant.name).map(|c| c.value);
+        let code_value = constants.iter().find(|c| c.name == code_name).map(|c| c.value);
+        let code_unresolved = unresolved.iter().find(|c| c.name == code_name);
+        let status = match (spec_value, code_value) {
+            (Some(spec), Some(code)) if spec == code => ConstantStatus::Match,
+            (Some(_), Some(_)) => ConstantStatus::Mismatch,
+            (None, _) => ConstantStatus::Unresolved(format!(
+                "Orange Paper value: {}",
+                spec_unresolved.get(&constant.name).map_or("it can't be evaluated", String::as_str),
+            )),
+            (_, None) => match code_unresolved {
+                Some(code) => ConstantStatus::Unresolved(format!("`{}`: {}", code.name, code.reason)),
+                None => ConstantStatus::Unmapped,
+            },
+        };
+        checks.push(ConstantCheck {
+            spec_name: constant.name.clone(),
+            section: constant.section.clone(),
+            code_name,
+            spec_value,
+            code_value,
+            status,
+        });
+    }
+    checks
+}
+
+/// Format constant check results for human consumption
+pub fn format_constant_checks_human(checks: &[ConstantCheck]) -> String {
+    let mut output = String::new();
+
+    output.push_str("=== Constant Check Report ===\n\n");
+
+    let value = |value: Option<i128>| value.map_or("?".to_string(), |v| v.to_string());
+    for check in checks {
+        let line = match &check.status {
+            ConstantStatus::Match => format!("✅ {} → {} = {}", check.spec_name, check.code_name, value(check.code_value)),
+            ConstantStatus::Mismatch => format!(
+                "❌ {} → {}: Orange Paper {}, implementation {}",
+                check.spec_name, check.code_name, value(check.spec_value), value(check.code_value),
+            ),
+            ConstantStatus::Unmapped => format!(
+                "⚠️  {}: no workspace constant `{}` (map it with --map {}=NAME)",
+                check.spec_name, check.code_name, check.spec_name,
+            ),
+            ConstantStatus::Unresolved(reason) => format!("⚠️  {} → {}: {}", check.spec_name, check.code_name, reason),
+        };
+        output.push_str(&format!("  {} (Section {})\n", line, check.section));
+    }
+
+    let count = |status: fn(&ConstantStatus) -> bool| checks.iter().filter(|c| status(&c.status)).count();
+    output.push_str("\nSummary:\n");
+    output.push_str("--------\n");
+    output.push_str(&format!("  Matching: {}\n", count(|s| *s == ConstantStatus::Match)));
+    output.push_str(&format!("  Mismatched: {}\n", count(|s| *s == ConstantStatus::Mismatch)));
+    output.push_str(&format!("  Unmapped: {}\n", count(|s| *s == ConstantStatus::Unmapped)));
+    output.push_str(&format!("  Unresolved: {}\n", count(|s| matches!(s, ConstantStatus::Unresolved(_)))));
+
+    output
+}
+
+/// Format constant check results as JSON
+pub fn format_constant_checks_json(checks: &[ConstantCheck]) -> String {
+    serde_json::json!({
+        "constants": checks.iter().map(|check| serde_json::json!({
+            "spec_name": check.spec_name,
+            "section": check.section,
+            "code_name": check.code_name,
+            // As strings, since values may exceed JSON's safe integer range
+            "spec_value": check.spec_value.map(|v| v.to_string()),
+            "code_value": check.code_value.map(|v| v.to_string()),
+            "status": match &check.status {
+                ConstantStatus::Match => "match",
+                ConstantStatus::Mismatch => "mismatch",
+                ConstantStatus::Unmapped => "unmapped",
+                ConstantStatus::Unresolved(_) => "unresolved",
+            },
+            "reason": match &check.status {
+                ConstantStatus::Unresolved(reason) => Some(reason),
+                _ => None,
+            },
+        })).collect::<Vec<_>>(),
+    }).to_string()
+}
+
+/// The value all definitions of a name agree on
+fn agreed_value(definitions: &[(String, i128)]) -> Option<i128> {
+    let (_, value) = definitions.first()?;
+    definitions.iter().all(|(_, other)| other == value).then_some(*value)
+}
+
+/// Evaluate an integer expression over the workspace constants, e.g. a bound in a `#[requires]`
+pub fn evaluate_with_constants(expr: &Expr, constants: &[WorkspaceConstant]) -> Option<i128> {
+    let lookup = |name: &str| {
+        constants.iter()
+            .find(|constant| constant.name == name)
+            .map(|constant| constant.value)
+            .ok_or_else(|| EvalError::Unsupported(format!("`{}` isn't a known constant", name)))
+    };
+    evaluate(expr, "i128", &lookup).ok()
+}
+
+/// Evaluate a constant initializer of the given primitive type
+fn evaluate(expr: &Expr, type_name: &str, lookup: &dyn Fn(&str) -> Result<i128, EvalError>) -> Result<i128, EvalError> {
+    let unsupported = || EvalError::Unsupported(format!("`{}` can't be evaluated", quote!(#expr)));
+    let overflow = || EvalError::Unsupported(format!("`{}` overflows", quote!(#expr)));
+    let eval = |expr: &Expr| evaluate(expr, type_name, lookup);
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            syn::Lit::Int(int) => int.base10_parse::<i128>().map_err(|_| overflow()),
+            _ => Err(unsupported()),
+        },
+        Expr::Paren(paren) => eval(&paren.expr),
+        Expr::Group(group) => eval(&group.expr),
+        Expr::Path(path) => {
+            let segments: Vec<String> = path.path.segments.iter().map(|s| s.ident.to_string()).collect();
+            match segments.as_slice() {
+                [.., ty, bound] if bound == "MAX" || bound == "MIN" => {
+                    let (min, max) = int_range(ty).ok_or_else(unsupported)?;
+                    if bound == "MAX" { Ok(max) } else { Ok(min) }
+                }
+                [.., name] => lookup(name),
+                [] => Err(unsupported()),
+            }
+        }
+        Expr::Unary(unary) => {
+            let value = eval(&unary.expr)?;
+            match unary.op {
+                UnOp::Neg(_) => value.checked_neg().ok_or_else(overflow),
+                // Bitwise complement within the constant's type
+                UnOp::Not(_) => Ok(wrap(!value, type_name)),
+                _ => Err(unsupported()),
+            }
+        }
+        Expr::Binary(bin) => {
+            let (left, right) = (eval(&bin.left)?, eval(&bin.right)?);
+            let shift = || u32::try_from(right).ok().filter(|amount| *amount < 128);
+            let value = match bin.op {
+                BinOp::Add(_) => left.checked_add(right),
+                BinOp::Sub(_) => left.checked_sub(right),
+                BinOp::Mul(_) => left.checked_mul(right),
+                BinOp::Div(_) => left.checked_div(right),
+                BinOp::Rem(_) => left.checked_rem(right),
+                BinOp::BitAnd(_) => Some(left & right),
+                BinOp::BitOr(_) => Some(left | right),
+                BinOp::BitXor(_) => Some(left ^ right),
+                BinOp::Shl(_) => shift().map(|amount| left << amount).filter(|value| value >> shift().unwrap_or(0) == left),
+                BinOp::Shr(_) => shift().map(|amount| left >> amount),
+                _ => return Err(unsupported()),
+            };
+            value.ok_or_else(overflow)
+        }
+        Expr::Cast(cast) => {
+            let value = eval(&cast.expr)?;
+            let syn::Type::Path(ty) = &*cast.ty else {
+                return Err(unsupported());
+            };
+            let target = ty.path.get_ident().map(|ident| ident.to_string()).ok_or_else(unsupported)?;
+            int_range(&target).ok_or_else(unsupported)?;
+            Ok(wrap(value, &target))
+        }
+        Expr::MethodCall(call) if call.method == "pow" && call.args.len() == 1 => {
+            let base = eval(&call.receiver)?;
+            let exponent = u32::try_from(eval(&call.args[0])?).map_err(|_| overflow())?;
+            base.checked_pow(exponent).ok_or_else(overflow)
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+/// Check that a value is representable in a primitive type
+fn fits(value: i128, type_name: &str) -> Result<i128, EvalError> {
+    let (min, max) = int_range(type_name)
+        .ok_or_else(|| EvalError::Unsupported(format!("`{}` isn't an integer type", type_name)))?;
+    if value < min || value > max {
+        return Err(EvalError::Unsupported(format!("{} doesn't fit in {}", value, type_name)));
+    }
+    Ok(value)
+}
+
+/// Wrap a value into a primitive type, like `as`
+fn wrap(value: i128, type_name: &str) -> i128 {
+    let Some((bits, signed)) = int_layout(type_name) else {
+        return value;
+    };
+    if bits >= 128 {
+        return value;
+    }
+    let truncated = value & ((1i128 << bits) - 1);
+    if signed && truncated >= 1i128 << (bits - 1) {
+        truncated - (1i128 << bits)
+    } else {
+        truncated
+    }
+}
+
+/// Smallest and largest value of a primitive integer type (`u128` is capped at `i128::MAX`)
+pub fn int_range(type_name: &str) -> Option<(i128, i128)> {
+    let (bits, signed) = int_layout(type_name)?;
+    Some(match (bits, signed) {
+        (128, true) => (i128::MIN, i128::MAX),
+        (128, false) => (0, i128::MAX),
+        (bits, true) => (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1),
+        (bits, false) => (0, (1i128 << bits) - 1),
+    })
+}
+
+/// Width and signedness of a primitive integer type (`usize`/`isize` are assumed 64-bit)
+fn int_layout(type_name: &str) -> Option<(u32, bool)> {
+    Some(match type_name {
+        "u8" => (8, false),
+        "u16" => (16, false),
+        "u32" => (32, false),
+        "u64" | "usize" => (64, false),
+        "u128" => (128, false),
+        "i8" => (8, true),
+        "i16" => (16, true),
+        "i32" => (32, true),
+        "i64" | "isize" => (64, true),
+        "i128" => (128, true),
+        _ => return None,
+    })
+}
+
+/// The primitive integer type a declared type stands for, looking through aliases
+fn integer_type(ty: &syn::Type, aliases: &HashMap<String, syn::Type>, depth: usize) -> Option<String> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if !segment.arguments.is_empty() {
+        return None;
+    }
+    let name = segment.ident.to_string();
+    if int_layout(&name).is_some() {
+        return Some(name);
+    }
+    // Bounded so that cyclic aliases terminate
+    if depth < 8 {
+        return integer_type(aliases.get(&name)?, aliases, depth + 1);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = "\
+## 4 Consensus Constants
+
+### 4.1 Monetary Constants
+
+$C = 10^8$ (satoshis per BTC)
+
+$M_{max} = 21 \\times 10^6 \\times C$ (maximum money supply)
+
+$H = 210,000$ (halving interval)
+
+$W_{max} = 4 \\times 10^6$ (maximum block weight)
+";
+
+    fn items(source: &str) -> Vec<ConstItem> {
+        let file: syn::File = syn::parse_str(source).unwrap();
+        file.items.into_iter()
+            .filter_map(|item| match item {
+                syn::Item::Const(item) => Some(ConstItem {
+                    name: item.ident.to_string(),
+                    type_name: integer_type(&item.ty, &HashMap::new(), 0)?,
+                    expr: *item.expr,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_evaluate_constants() {
+        let (resolved, unresolved) = evaluate_constants(items("
+            const MAX_MONEY: i64 = 21_000_000 * COIN as i64;
+            const COIN: u64 = 10u64.pow(8);
+            const MASK: u32 = !0 >> 4;
+            const NEAR_MAX: u64 = u64::MAX - 1;
+            const WRAPPED: u8 = 300u32 as u8;
+            const TOO_BIG: u8 = 256;
+            const LOOP: u64 = LOOP + 1;
+        "));
+        let values: Vec<(&str, i128)> = resolved.iter().map(|c| (c.name.as_str(), c.value)).collect();
+        assert_eq!(values, vec![
+            ("COIN", 100_000_000),
+            ("MASK", 0x0FFF_FFFF),
+            ("MAX_MONEY", 2_100_000_000_000_000),
+            ("NEAR_MAX", u64::MAX as i128 - 1),
+            ("WRAPPED", 44),
+        ]);
+        let unresolved: Vec<&str> = unresolved.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(unresolved, vec!["LOOP", "TOO_BIG"]);
+    }
+
+    #[test]
+    fn test_discover_constants() {
+        let root = std::env::temp_dir().join(format!("spec-lock-constants-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "
+            type Amount = i64;
+            pub const COIN: u64 = 100_000_000;
+            pub const MAX_MONEY: Amount = 21_000_000 * COIN as i64;
+            pub const NETWORK: &str = \"main\";
+            mod limits {
+                pub const MAX_BLOCK_WEIGHT: u32 = 4 * MAX_BLOCK_SIZE;
+                pub const MAX_BLOCK_SIZE: u32 = 1_000_000;
+                pub const TOO_WIDE: u8 = 1 << 8;
+                pub const FROM_FN: u64 = compute();
+                pub const FROM_UNKNOWN: u64 = MISSING + 1;
+            }
+        ").unwrap();
+        let (resolved, unresolved) = discover_constants(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        // Typed (through an alias), defined from other constants in any order,
+        // and inside modules; non-integer constants are ignored
+        let resolved: Vec<(&str, &str, i128)> = resolved.iter()
+            .map(|c| (c.name.as_str(), c.type_name.as_str(), c.value))
+            .collect();
+        assert_eq!(resolved, vec![
+            ("COIN", "u64", 100_000_000),
+            ("MAX_BLOCK_SIZE", "u32", 1_000_000),
+            ("MAX_BLOCK_WEIGHT", "u32", 4_000_000),
+            ("MAX_MONEY", "i64", 2_100_000_000_000_000),
+        ]);
+
+        // Values outside the type, function calls and unknown names can't be evaluated
+        let unresolved: Vec<(&str, &str)> = unresolved.iter()
+            .map(|c| (c.name.as_str(), c.reason.as_str()))
+            .collect();
+        assert_eq!(unresolved, vec![
+            ("FROM_FN", "`compute ()` can't be evaluated"),
+            ("FROM_UNKNOWN", "`MISSING` isn't a known constant"),
+            ("TOO_WIDE", "256 doesn't fit in u8"),
+        ]);
+    }
+
+    #[test]
+    fn test_check_constants_with_spec() {
+        let mut spec = SpecParser::new(SPEC.to_string());
+        spec.parse().unwrap();
+        let (constants, unresolved) = evaluate_constants(items("
+            const SATOSHIS_PER_BTC: u64 = 100_000_000;
+            const MAX_MONEY: i64 = 21_000_000 * SATOSHIS_PER_BTC as i64;
+            const HALVING_INTERVAL: u64 = 21_000;
+        "));
+
+        let checks = check_constants_with_spec(&spec, &constants, &unresolved, &[]);
+        let statuses: Vec<(&str, &str, ConstantStatus)> = checks.iter()
+            .map(|c| (c.spec_name.as_str(), c.code_name.as_str(), c.status.clone()))
+            .collect();
+        assert_eq!(statuses, vec![
+            ("C", "SATOSHIS_PER_BTC", ConstantStatus::Match),
+            ("M_MAX", "MAX_MONEY", ConstantStatus::Match),
+            ("H", "HALVING_INTERVAL", ConstantStatus::Mismatch),
+            ("W_MAX", "W_MAX", ConstantStatus::Unmapped),
+        ]);
+
+        // Explicit mappings take precedence over the defaults
+        let overrides = [parse_mapping("H = SATOSHIS_PER_BTC").unwrap()];
+        let checks = check_constants_with_spec(&spec, &constants, &unresolved, &overrides);
+        assert_eq!(checks[2].code_name, "SATOSHIS_PER_BTC");
+        assert!(parse_mapping("H").is_err());
+    }
+}
@@ -2,90 +2,164 @@
 //!
 //! Detects when Orange Paper and implementation diverge
 
+use std::collections::HashSet;
 use std::path::PathBuf;
-use std::collections::HashMap;
-use super::verify::{discover_functions, FunctionToVerify};
-// Note: SpecParser is not accessible from binary (proc-macro crate limitation)
-// Using simplified drift detection for now
+use super::verify::{discover_functions, ContractType, FunctionToVerify};
+use crate::parser::orange_paper::{self, SpecParser};
 
 /// Drift detection result
 #[derive(Debug, Clone)]
 pub struct DriftResult {
-    /// Functions with contracts that don't match Orange Paper
+    /// Contracts on either side without a counterpart on the other
     pub mismatched_contracts: Vec<MismatchedContract>,
-    /// Functions missing from Orange Paper
-    pub missing_from_spec: Vec<FunctionToVerify>,
-    /// Orange Paper theorems without implementations
-    pub missing_implementations: Vec<String>,
+    /// Functions whose section or spec function doesn't exist in the Orange Paper
+    pub missing_from_spec: Vec<MissingFromSpec>,
+    /// Orange Paper functions and theorems without implementations
+    pub missing_implementations: Vec<MissingImplementation>,
     /// Functions with auto-inferred sections (may need verification)
     pub auto_inferred: Vec<FunctionToVerify>,
 }
 
-/// A contract mismatch
+/// A contract stated on only one side
 #[derive(Debug, Clone)]
 pub struct MismatchedContract {
     pub function: FunctionToVerify,
-    pub orange_paper_contract: String,
-    pub implementation_contract: String,
+    /// Orange Paper function the implementation is locked to
+    pub spec_function: String,
+    pub contract_type: ContractType,
+    /// Contract from the Orange Paper (None = only in the implementation)
+    pub orange_paper_contract: Option<String>,
+    /// Contract from the implementation (None = only in the Orange Paper)
+    pub implementation_contract: Option<String>,
     pub section: String,
 }
 
+/// A spec-locked function that doesn't resolve in the Orange Paper
+#[derive(Debug, Clone)]
+pub struct MissingFromSpec {
+    pub function: FunctionToVerify,
+    pub reason: String,
+}
+
+/// An Orange Paper function or theorem that no Rust function implements
+#[derive(Debug, Clone)]
+pub struct MissingImplementation {
+    pub kind: SpecItemKind,
+    pub section: String,
+    /// Function name, or theorem number and name
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecItemKind {
+    Function,
+    Theorem,
+}
+
 /// Detect spec drift
 pub fn detect_drift(workspace_root: &PathBuf, orange_paper_path: Option<&PathBuf>) -> Result<DriftResult, String> {
     // Discover all spec-locked functions
     let functions = discover_functions(workspace_root)?;
     
     // Load Orange Paper
-    let spec_path = orange_paper_path
-        .map(|p| p.clone())
-        .unwrap_or_else(|| {
-            workspace_root.join("../blvm-spec/THE_ORANGE_PAPER.md")
-        });
-    
-    // Simplified drift detection (full implementation requires SpecParser access)
-    // For now, detect functions without contracts as potential drift
+    let spec = super::spec::load_spec(workspace_root, orange_paper_path)?;
+    
+    Ok(detect_drift_with_spec(&functions, &spec))
+}
+
+/// Compare spec-locked functions against a parsed Orange Paper
+pub fn detect_drift_with_spec(functions: &[FunctionToVerify], spec: &SpecParser) -> DriftResult {
     let mut mismatched_contracts = Vec::new();
     let mut missing_from_spec = Vec::new();
     let mut auto_inferred = Vec::new();
+    // (section, spec function) pairs with an implementation
+    let mut implemented: HashSet<(String, String)> = HashSet::new();
+    // Granular IDs (e.g. "6.1.1") functions are locked to directly
+    let mut locked_ids: HashSet<String> = HashSet::new();
     
-    // Check each function
-    for func in &functions {
-        // Check if function has section (not auto-inferred)
+    for func in functions {
         if func.section.is_none() {
             auto_inferred.push(func.clone());
+        }
+        
+        if let Some(section) = &func.section {
+            if !spec.section_exists(section) {
+                missing_from_spec.push(MissingFromSpec {
+                    function: func.clone(),
+                    reason: format!("Section {} not found in Orange Paper", section),
+                });
+                continue;
+            }
+            locked_ids.insert(section.clone());
+        }
+        
+        let lookup_name = func.spec_function.as_deref().unwrap_or(&func.function_name);
+        let Some((spec_func, section_id)) = spec.resolve_function(func.section.as_deref(), lookup_name) else {
+            let reason = match &func.section {
+                Some(section) => format!("No function matching `{}` in section {}", lookup_name, section),
+                None => format!("No function matching `{}` in Orange Paper", lookup_name),
+            };
+            missing_from_spec.push(MissingFromSpec { function: func.clone(), reason });
             continue;
+        };
+        implemented.insert((section_id.clone(), spec_func.name.clone()));
+        
+        // Spec contracts without a counterpart in the implementation
+        for spec_contract in &spec_func.contracts {
+            let Some(contract_type) = cli_contract_type(spec_contract.contract_type) else {
+                continue;
+            };
+            let matched = func.contracts.iter().any(|c| {
+                c.contract_type == contract_type && contracts_similar(&spec_contract.condition, &c.condition)
+            });
+            if !matched {
+                mismatched_contracts.push(MismatchedContract {
+                    function: func.clone(),
+                    spec_function: spec_func.name.clone(),
+                    contract_type,
+                    orange_paper_contract: Some(spec_contract.condition.clone()),
+                    implementation_contract: None,
+                    section: section_id.clone(),
+                });
+            }
         }
         
-        // Functions without contracts may indicate drift
-        if func.contracts.is_empty() {
-            missing_from_spec.push(func.clone());
+        // Implementation contracts the spec doesn't state
+        for contract in &func.contracts {
+            let matched = spec_func.contracts.iter().any(|c| {
+                cli_contract_type(c.contract_type) == Some(contract.contract_type) &&
+                contracts_similar(&c.condition, &contract.condition)
+            });
+            if !matched {
+                mismatched_contracts.push(MismatchedContract {
+                    function: func.clone(),
+                    spec_function: spec_func.name.clone(),
+                    contract_type: contract.contract_type,
+                    orange_paper_contract: None,
+                    implementation_contract: Some(contract.condition.clone()),
+                    section: section_id.clone(),
+                });
+            }
         }
     }
     
-    // Find theorems without implementations
-    // Note: Full implementation requires SpecParser access from binary
-    let missing_implementations = Vec::new();
+    let missing_implementations = find_missing_implementations(spec, &implemented, &locked_ids);
     
-    Ok(DriftResult {
+    DriftResult {
         mismatched_contracts,
         missing_from_spec,
         missing_implementations,
         auto_inferred,
-    })
-}
-
-/// Convert Rust snake_case to PascalCase
-fn rust_to_pascal_case(rust_name: &str) -> String {
-    rust_name
-        .split('_')
-        .map(|word| {
-            let mut chars = word.chars();
-            match chars.next() {
-                None => String::new(),
-                Some(first) => first.to_uppercase().collect::<String>() + &chars.as_str(),
-            }
-        })
-        .collect()
+    }
+}
+
+/// Map an Orange Paper contract type onto the implementation's (edge cases have no counterpart)
+fn cli_contract_type(contract_type: orange_paper::ContractType) -> Option<ContractType> {
+    match contract_type {
+        orange_paper::ContractType::Requires => Some(ContractType::Requires),
+        orange_paper::ContractType::Ensures | orange_paper::ContractType::Property => Some(ContractType::Ensures),
+        orange_paper::ContractType::EdgeCase => None,
+    }
 }
 
 /// Check if two contracts are similar (allows for minor formatting differences)
@@ -94,30 +168,156 @@ fn contracts_similar(spec: &str, impl_contract: &str) -> bool {
     let spec_norm = normalize_contract(spec);
     let impl_norm = normalize_contract(impl_contract);
     
+    if spec_norm.is_empty() || impl_norm.is_empty() {
+        return false;
+    }
+    
     // Check for exact match
     if spec_norm == impl_norm {
         return true;
     }
     
-    // Check if one contains the other (for partial matches)
-    spec_norm.contains(&impl_norm) || impl_norm.contains(&spec_norm)
+    // One side may state more conjuncts than the other, but each conjunct must match whole
+    let spec_conjuncts = conjuncts(&spec_norm);
+    let impl_conjuncts = conjuncts(&impl_norm);
+    spec_conjuncts.iter().all(|c| impl_conjuncts.contains(c)) ||
+        impl_conjuncts.iter().all(|c| spec_conjuncts.contains(c))
+}
+
+/// Split a normalized contract at its top-level `&&`, unwrapping each part's parentheses
+fn conjuncts(normalized: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let bytes = normalized.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'(' => depth += 1,
+            b')' => depth -= 1,
+            b'&' if depth == 0 && bytes.get(i + 1) == Some(&b'&') => {
+                parts.push(&normalized[start..i]);
+                start = i + 2;
+                i += 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    parts.push(&normalized[start..]);
+    parts.into_iter()
+        .map(|part| {
+            let mut part = part.to_string();
+            while part.starts_with('(') && part.ends_with(')') && parens_wrap(&part) {
+                part = part[1..part.len() - 1].to_string();
+            }
+            part
+        })
+        .collect()
 }
 
 /// Normalize contract string for comparison
+///
+/// Maps math notation to Rust operators, drops whitespace and LaTeX markup,
+/// treats the applied spec function (`result(h)`) as `result`, and strips
+/// redundant outer parentheses.
 fn normalize_contract(contract: &str) -> String {
-    contract
+    let mut normalized = contract
         .to_lowercase()
-        .replace(" ", "")
-        .replace(">=", ">=")
-        .replace("<=", "<=")
-        .replace("!=", "!=")
-        .replace("==", "==")
+        .replace('≥', ">=")
+        .replace('≤', "<=")
+        .replace('≠', "!=")
+        .replace('∧', "&&")
+        .replace('∨', "||")
+        .replace('¬', "!")
+        .replace(r"\geq", ">=")
+        .replace(r"\leq", "<=")
+        .replace(r"\neq", "!=")
+        .replace(r"\land", "&&")
+        .replace(r"\lor", "||")
+        .replace(['$', '{', '}'], "")
+        .replace(r"\text", "")
+        .split_whitespace()
+        .collect::<String>();
+    
+    // Theorems apply the spec function to its arguments: `result(height) <= 50`
+    let applied_result = regex::Regex::new(r"result\([^()]*\)").expect("valid regex");
+    normalized = applied_result.replace_all(&normalized, "result").into_owned();
+    
+    while normalized.starts_with('(') && normalized.ends_with(')') && parens_wrap(&normalized) {
+        normalized = normalized[1..normalized.len() - 1].to_string();
+    }
+    normalized
+}
+
+/// Check whether the first `(` of `s` closes at its last character
+fn parens_wrap(s: &str) -> bool {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i == s.len() - 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Find Orange Paper functions and theorems without corresponding implementations
+///
+/// A theorem counts as implemented if a function is locked to its number,
+/// or an implemented function in its section is mentioned in its statement
+/// (or is the only function in the section).
+fn find_missing_implementations(
+    spec: &SpecParser,
+    implemented: &HashSet<(String, String)>,
+    locked_ids: &HashSet<String>,
+) -> Vec<MissingImplementation> {
+    let mut missing = Vec::new();
+    
+    for section in spec.sections() {
+        let implemented_here: Vec<&str> = section.functions.iter()
+            .filter(|f| implemented.contains(&(section.id.clone(), f.name.clone())))
+            .map(|f| f.name.as_str())
+            .collect();
+        
+        for func in &section.functions {
+            if !implemented_here.contains(&func.name.as_str()) {
+                missing.push(MissingImplementation {
+                    kind: SpecItemKind::Function,
+                    section: section.id.clone(),
+                    name: func.name.clone(),
+                });
+            }
+        }
+        
+        for theorem in &section.theorems {
+            let statement = theorem.statement.to_lowercase();
+            let covered = locked_ids.contains(&theorem.number) ||
+                implemented_here.iter().any(|name| statement.contains(&name.to_lowercase())) ||
+                (section.functions.len() == 1 && implemented_here.len() == 1);
+            if !covered {
+                missing.push(MissingImplementation {
+                    kind: SpecItemKind::Theorem,
+                    section: section.id.clone(),
+                    name: format!("Theorem {} ({})", theorem.number, theorem.name),
+                });
+            }
+        }
+    }
+    
+    missing.sort_by(|a, b| section_key(&a.section).cmp(&section_key(&b.section)).then_with(|| a.name.cmp(&b.name)));
+    missing
 }
 
-/// Find theorems in Orange Paper without corresponding implementations
-/// Note: Implementation pending - requires SpecParser access from binary
-fn _find_missing_implementations(_functions: &[FunctionToVerify]) -> Vec<String> {
-    Vec::new()
+/// Sort key for section numbers (e.g., "5.1" < "5.2" < "6.1" < "10.1")
+fn section_key(section: &str) -> Vec<u32> {
+    section.split('.').map(|part| part.parse().unwrap_or(0)).collect()
 }
 
 /// Format drift report as human-readable text
@@ -131,10 +331,13 @@ pub fn format_drift_human(result: &DriftResult) -> String {
         output.push_str("⚠️  Mismatched Contracts:\n");
         output.push_str("------------------------\n");
         for mismatch in &result.mismatched_contracts {
-            output.push_str(&format!("  Function: {} (Section {})\n", 
-                mismatch.function.function_name, mismatch.section));
-            output.push_str(&format!("    Orange Paper: {}\n", mismatch.orange_paper_contract));
-            output.push_str(&format!("    Implementation: {}\n", mismatch.implementation_contract));
+            output.push_str(&format!("  Function: {} → {} (Section {}, {})\n", 
+                mismatch.function.function_name, mismatch.spec_function, mismatch.section,
+                contract_kind(mismatch.contract_type)));
+            output.push_str(&format!("    Orange Paper: {}\n",
+                mismatch.orange_paper_contract.as_deref().unwrap_or("(none)")));
+            output.push_str(&format!("    Implementation: {}\n",
+                mismatch.implementation_contract.as_deref().unwrap_or("(none)")));
             output.push_str("\n");
         }
     }
@@ -143,13 +346,24 @@ pub fn format_drift_human(result: &DriftResult) -> String {
     if !result.missing_from_spec.is_empty() {
         output.push_str("❌ Functions Missing from Orange Paper:\n");
         output.push_str("--------------------------------------\n");
-        for func in &result.missing_from_spec {
-            output.push_str(&format!("  {} ({})\n", 
-                func.function_name, func.file_path.display()));
+        for missing in &result.missing_from_spec {
+            output.push_str(&format!("  {} ({}:{})\n", 
+                missing.function.function_name, missing.function.file_path.display(), missing.function.line));
+            output.push_str(&format!("    {}\n", missing.reason));
         }
         output.push_str("\n");
     }
     
+    // Missing implementations
+    if !result.missing_implementations.is_empty() {
+        output.push_str("❌ Orange Paper Items Without Implementations:\n");
+        output.push_str("---------------------------------------------\n");
+        for missing in &result.missing_implementations {
+            output.push_str(&format!("  Section {}: {}\n", missing.section, missing.name));
+        }
+        output.push('\n');
+    }
+    
     // Auto-inferred
     if !result.auto_inferred.is_empty() {
         output.push_str("ℹ️  Auto-Inferred Functions (verify manually):\n");
@@ -184,19 +398,127 @@ pub fn format_drift_json(result: &DriftResult) -> String {
         "mismatched_contracts": result.mismatched_contracts.iter().map(|m| serde_json::json!({
             "function": m.function.function_name,
             "file": m.function.file_path.display().to_string(),
+            "spec_function": m.spec_function,
             "section": m.section,
+            "contract_type": contract_kind(m.contract_type),
             "orange_paper_contract": m.orange_paper_contract,
             "implementation_contract": m.implementation_contract,
         })).collect::<Vec<_>>(),
-        "missing_from_spec": result.missing_from_spec.iter().map(|f| serde_json::json!({
-            "function": f.function_name,
-            "file": f.file_path.display().to_string(),
+        "missing_from_spec": result.missing_from_spec.iter().map(|m| serde_json::json!({
+            "function": m.function.function_name,
+            "file": m.function.file_path.display().to_string(),
+            "line": m.function.line,
+            "reason": m.reason,
         })).collect::<Vec<_>>(),
         "auto_inferred": result.auto_inferred.iter().map(|f| serde_json::json!({
             "function": f.function_name,
             "file": f.file_path.display().to_string(),
         })).collect::<Vec<_>>(),
-        "missing_implementations": result.missing_implementations,
+        "missing_implementations": result.missing_implementations.iter().map(|m| serde_json::json!({
+            "kind": match m.kind {
+                SpecItemKind::Function => "function",
+                SpecItemKind::Theorem => "theorem",
+            },
+            "section": m.section,
+            "name": m.name,
+        })).collect::<Vec<_>>(),
     }).to_string()
 }
 
+fn contract_kind(contract_type: ContractType) -> &'static str {
+    match contract_type {
+        ContractType::Requires => "requires",
+        ContractType::Ensures => "ensures",
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::verify::Contract;
+
+    const SPEC: &str = "\
+## 6 Economics
+
+### 6.1 Block Subsidy
+
+**GetBlockSubsidy**: $\\mathbb{N} \\to \\mathbb{Z}$
+
+**Properties**:
+- **Non-negative**: result ≥ 0
+
+**Theorem 6.1.1** (Subsidy Bound): $GetBlockSubsidy(h) ≤ 50$
+
+### 6.2 Fees
+
+**CalculateFee**: $\\mathbb{N} \\to \\mathbb{N}$
+
+**Theorem 6.2.1** (Fee Conservation): $\\sum in = \\sum out + fee$
+";
+
+    fn function(name: &str, section: Option<&str>, ensures: &[&str]) -> FunctionToVerify {
+        FunctionToVerify {
+            file_path: PathBuf::from("src/economic.rs"),
+            function_name: name.to_string(),
+            line: 1,
+            contracts: ensures.iter().map(|c| Contract {
+                contract_type: ContractType::Ensures,
+                condition: c.to_string(),
+                expr: None,
+            }).collect(),
+            section: section.map(str::to_string),
+            spec_function: None,
+            unwind: None,
+            function_sig: None,
+        }
+    }
+
+    #[test]
+    fn test_detect_drift_with_spec() {
+        let mut spec = SpecParser::new(SPEC.to_string());
+        spec.parse().unwrap();
+
+        let functions = vec![
+            function("get_block_subsidy", Some("6.1"), &["result >= 0", "result % 2 == 0"]),
+            function("get_fee_rate", Some("6.2"), &[]),
+            function("old_rule", Some("9.9"), &[]),
+        ];
+        let result = detect_drift_with_spec(&functions, &spec);
+
+        // `result ≥ 0` matches after normalization; the extra ensures is drift
+        let mismatched: Vec<_> = result.mismatched_contracts.iter()
+            .map(|m| (m.orange_paper_contract.as_deref(), m.implementation_contract.as_deref()))
+            .collect();
+        assert!(mismatched.contains(&(None, Some("result % 2 == 0"))));
+        assert!(!mismatched.iter().any(|(_, i)| *i == Some("result >= 0")));
+
+        let missing: Vec<_> = result.missing_from_spec.iter()
+            .map(|m| m.function.function_name.as_str())
+            .collect();
+        assert_eq!(missing, vec!["get_fee_rate", "old_rule"]);
+
+        let unimplemented: Vec<_> = result.missing_implementations.iter()
+            .map(|m| (m.kind, m.name.as_str()))
+            .collect();
+        assert_eq!(unimplemented, vec![
+            (SpecItemKind::Function, "CalculateFee"),
+            (SpecItemKind::Theorem, "Theorem 6.2.1 (Fee Conservation)"),
+        ]);
+    }
+
+    #[test]
+    fn test_normalize_contract() {
+        assert_eq!(normalize_contract("(result ≥ 0)"), "result>=0");
+        assert_eq!(normalize_contract("(a) && (b)"), "(a)&&(b)");
+        assert!(contracts_similar("$result \\leq MAX$", "result <= max"));
+        assert!(contracts_similar("result(height) <= 50", "result <= 50"));
+        assert!(!contracts_similar("", "result >= 0"));
+        assert_eq!(normalize_contract(r"$a \neq b$"), "a!=b");
+        assert_eq!(normalize_contract(r"$\text{result} \geq 0$"), "result>=0");
+
+        // Conjuncts match whole: a bound of 50 isn't a bound of 500
+        assert!(!contracts_similar("result <= 50", "result <= 500"));
+        assert!(contracts_similar("result <= 50", "(result <= 50) && (result >= 0)"));
+    }
+}
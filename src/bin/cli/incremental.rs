@@ -0,0 +1,231 @@
+//! Incremental verification
+//!
+//! Caches each function's verification result in `target/.spec-cache/`, keyed
+//! by everything the result depends on, so unchanged functions skip Z3.
+
+use crate::cache::SpecCache;
+use crate::cli::lock::lock_function;
+use crate::cli::theorems::{verify_theorems, TheoremResult, TheoremStatus};
+use crate::cli::verify::{called_functions, referenced_constants, referenced_types, verify_functions, FunctionToVerify, VerificationResult, VerifyOptions};
+use crate::parser::orange_paper::SpecParser;
+use quote::quote;
+
+/// Layout of the cached entries; changes to the verifier are covered by the tool version
+const CACHE_FORMAT: u32 = 1;
+
+/// Verify functions, reusing cached results for unchanged ones
+///
+/// Returns each function's result and whether it came from the cache.
+/// Inconclusive results (e.g. timeouts) are never cached.
+pub fn verify_functions_cached(
+    functions: &[FunctionToVerify],
+    options: &VerifyOptions,
+    spec: Option<&SpecParser>,
+    cache: &SpecCache,
+) -> Vec<(VerificationResult, bool)> {
+    let keys: Vec<Option<String>> = functions.iter()
+        .map(|f| verification_key(f, spec, options))
+        .collect();
+
+    let mut results: Vec<Option<(VerificationResult, bool)>> = functions.iter()
+        .zip(&keys)
+        .map(|(func, key)| {
+            let cached = key.as_ref().and_then(|key| load_result(cache, key, func))?;
+            Some((cached, true))
+        })
+        .collect();
+
+    let misses: Vec<usize> = (0..functions.len()).filter(|&i| results[i].is_none()).collect();
+    let to_verify: Vec<FunctionToVerify> = misses.iter().map(|&i| functions[i].clone()).collect();
+    for (&index, result) in misses.iter().zip(verify_functions(&to_verify, options)) {
+        if let Some(key) = &keys[index] {
+            store_result(cache, key, &functions[index], &result);
+        }
+        results[index] = Some((result, false));
+    }
+
+    results.into_iter()
+        .map(|r| r.expect("every function is cached or verified"))
+        .collect()
+}
+
+/// Check each function's theorems, reusing cached results for unchanged functions
+///
+/// The function's key covers the spec text it's locked to, theorems included.
+/// A function with an inconclusive theorem (e.g. a timeout) isn't cached.
+pub fn verify_theorems_cached(
+    functions: &[FunctionToVerify],
+    spec: &SpecParser,
+    options: &VerifyOptions,
+    cache: &SpecCache,
+) -> Vec<TheoremResult> {
+    let mut results = Vec::new();
+    for func in functions {
+        let key = verification_key(func, Some(spec), options)
+            .map(|key| SpecCache::key_for(&[&key, "theorems"]));
+        if let Some(cached) = key.as_ref().and_then(|key| load_theorems(cache, key, func)) {
+            results.extend(cached);
+            continue;
+        }
+        let checked = verify_theorems(std::slice::from_ref(func), spec, options);
+        if let Some(key) = &key {
+            if !checked.iter().any(|t| matches!(t.status, TheoremStatus::Unknown { .. })) {
+                if let Ok(json) = serde_json::to_string(&checked) {
+                    cache.save(key, &json);
+                }
+            }
+        }
+        results.extend(checked);
+    }
+    results
+}
+
+/// Cache key for a function's verification result
+///
+/// Covers the function's tokens (signature, body and attributes), its
+/// contracts, the contracts of the functions it calls, the types and constants
+/// it uses, the spec text it's locked to, the tool version and the solver
+/// configuration. Source positions aren't part of the key; cached line numbers
+/// are stored relative to the function.
+fn verification_key(func: &FunctionToVerify, spec: Option<&SpecParser>, options: &VerifyOptions) -> Option<String> {
+    let item = func.function_sig.as_ref()?;
+    let tokens = quote!(#item).to_string();
+    let contracts: Vec<String> = func.contracts.iter()
+        .map(|c| format!("{:?}:{}", c.contract_type, c.condition))
+        .collect();
+    let called = called_functions(item);
+    let callees: Vec<&str> = options.summaries.iter()
+        .filter(|summary| called.contains(&summary.name))
+        .map(|summary| summary.source.as_str())
+        .collect();
+    let types: Vec<&str> = referenced_types(item, options).iter()
+        .map(|ty| ty.source.as_str())
+        .collect();
+    let constants: Vec<String> = referenced_constants(item, options).iter()
+        .map(|constant| format!("{}: {} = {}", constant.name, constant.type_name, constant.value))
+        .collect();
+    let spec_hash = spec
+        .and_then(|spec| lock_function(func, spec))
+        .map(|locked| locked.hash)
+        .unwrap_or_default();
+    let solver = format!(
+        "z3={} bit_precise={} overflow_checks={} timeout={:?}",
+        cfg!(feature = "z3"),
+        options.bit_precise,
+        !options.no_overflow_checks,
+        options.timeout,
+    );
+
+    Some(SpecCache::key_for(&[
+        &cache_version(),
+        &solver,
+        &tokens,
+        &contracts.join("\n"),
+        &callees.join("\n"),
+        &types.join("\n"),
+        &constants.join("\n"),
+        &spec_hash,
+    ]))
+}
+
+/// Tool version and cache layout, so results from another build are never reused
+fn cache_version() -> String {
+    format!("{}/{}", env!("CARGO_PKG_VERSION"), CACHE_FORMAT)
+}
+
+/// Load a cached result, rebasing overflow lines onto the function's current position
+fn load_result(cache: &SpecCache, key: &str, func: &FunctionToVerify) -> Option<VerificationResult> {
+    let mut result: VerificationResult = serde_json::from_str(&cache.load(key)?).ok()?;
+    if let VerificationResult::Overflow { failures } = &mut result {
+        for failure in failures {
+            failure.line += func.line;
+        }
+    }
+    Some(result)
+}
+
+/// Store a conclusive result, with overflow lines relative to the function
+fn store_result(cache: &SpecCache, key: &str, func: &FunctionToVerify, result: &VerificationResult) {
+    let mut result = result.clone();
+    match &mut result {
+        VerificationResult::Unknown { .. } => return,
+        VerificationResult::Overflow { failures } => {
+            for failure in failures {
+                failure.line = failure.line.saturating_sub(func.line);
+            }
+        }
+        _ => {}
+    }
+    if let Ok(json) = serde_json::to_string(&result) {
+        cache.save(key, &json);
+    }
+}
+
+/// Load a function's cached theorem results, attributed to its current location
+fn load_theorems(cache: &SpecCache, key: &str, func: &FunctionToVerify) -> Option<Vec<TheoremResult>> {
+    let mut results: Vec<TheoremResult> = serde_json::from_str(&cache.load(key)?).ok()?;
+    for result in &mut results {
+        result.function_name = func.function_name.clone();
+        result.file_path = func.file_path.clone();
+    }
+    Some(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::verify::discover_functions;
+    use std::path::Path;
+
+    const SPEC: &str = "## 6 Economics\n\n### 6.1 Block Subsidy\n\n**GetBlockSubsidy**: $\\mathbb{N} \\to \\mathbb{Z}$\n";
+
+    const SOURCE: &str = "
+        #[spec_locked(\"6.1\")]
+        #[ensures(result <= 50)]
+        pub fn get_block_subsidy(height: u64) -> u64 {
+            50 >> (height / 210000)
+        }
+    ";
+
+    fn spec(content: &str) -> SpecParser {
+        let mut spec = SpecParser::new(content.to_string());
+        spec.parse().unwrap();
+        spec
+    }
+
+    /// Write `source` as the workspace's only file and discover its functions
+    fn functions(root: &Path, source: &str) -> Vec<FunctionToVerify> {
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), source).unwrap();
+        discover_functions(&root.to_path_buf()).unwrap()
+    }
+
+    #[test]
+    fn test_verify_functions_cached() {
+        let root = std::env::temp_dir().join(format!("spec-lock-incremental-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let cache = SpecCache::new(&root);
+        let options = VerifyOptions::default();
+        let original_spec = spec(SPEC);
+        let original = functions(&root, SOURCE);
+
+        let first = verify_functions_cached(&original, &options, Some(&original_spec), &cache);
+        let second = verify_functions_cached(&original, &options, Some(&original_spec), &cache);
+        assert!(!first[0].1);
+        assert!(second[0].1, "unchanged function should hit the cache");
+        assert_eq!(format!("{:?}", first[0].0), format!("{:?}", second[0].0));
+
+        // Editing the body, a contract or the spec text misses the cache
+        for source in [SOURCE.replace("50 >>", "49 >>"), SOURCE.replace("<= 50", "<= 51")] {
+            let edited = functions(&root, &source);
+            assert!(!verify_functions_cached(&edited, &options, Some(&original_spec), &cache)[0].1, "{}", source);
+        }
+        let edited_spec = spec(&SPEC.replace("\\mathbb{Z}$", "\\mathbb{N}$"));
+        assert!(!verify_functions_cached(&original, &options, Some(&edited_spec), &cache)[0].1);
+        // The solver options are part of the key too
+        let bit_precise = VerifyOptions { bit_precise: true, ..VerifyOptions::default() };
+        assert!(!verify_functions_cached(&original, &bit_precise, Some(&original_spec), &cache)[0].1);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,308 @@
+//! Kani proof harness generation from contracts
+//!
+//! Writes a `#[kani::proof]` harness for each spec-locked function into the
+//! `#[cfg(kani)]` module `spec_lock_kani` of its crate. Each harness draws the
+//! arguments with `kani::any()` (`kani::vec::any_vec` for vectors and slices),
+//! `kani::assume`s every `#[requires]`, calls the function and `assert!`s every
+//! `#[ensures]`, so Kani checks the contracts for all inputs rather than a
+//! sample. The `unwind = N` argument of `#[spec_locked]` becomes the harness's
+//! `#[kani::unwind(N)]`.
+
+use crate::cli::constants::WorkspaceConstant;
+use crate::cli::proptests::{borrow, is_copy, mentions, requires_bounds, substitute_constants, Bounds, Passing};
+use crate::cli::regression::{find_crate_root, module_path};
+use crate::cli::verify::{ContractType, FunctionToVerify};
+use crate::parser::contracts::capture_old;
+use quote::quote;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Module of each crate holding the generated harnesses
+const KANI_MODULE: &str = "spec_lock_kani";
+
+/// Most elements in a generated vector when the function has no unwind bound
+const DEFAULT_MAX_ELEMENTS: i128 = 4;
+
+/// Summary of generated Kani harnesses
+#[derive(Debug, Clone, Default)]
+pub struct KaniSummary {
+    /// Files written, with the number of harnesses in each
+    pub files: Vec<(PathBuf, usize)>,
+    /// Functions without a harness, with the reason
+    pub skipped: Vec<(String, String)>,
+    /// Crates whose root doesn't declare the harness module
+    pub undeclared: Vec<PathBuf>,
+}
+
+/// Write a Kani proof harness for every spec-locked function
+///
+/// Harnesses are grouped per crate into `<crate>/src/spec_lock_kani.rs`,
+/// which the crate root declares with `#[cfg(kani)] mod spec_lock_kani;`.
+/// `constants` resolve the names used in contracts.
+pub fn emit_kani_harnesses(
+    functions: &[FunctionToVerify],
+    constants: &[WorkspaceConstant],
+) -> Result<KaniSummary, String> {
+    let mut summary = KaniSummary::default();
+    let mut harnesses_by_file: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    let mut used_names: HashMap<PathBuf, HashMap<String, usize>> = HashMap::new();
+
+    for func in functions {
+        let label = format!("{}::{}", func.file_path.display(), func.function_name);
+        let Some(item) = &func.function_sig else {
+            summary.skipped.push((label, "missing parsed function".to_string()));
+            continue;
+        };
+        let Some(crate_root) = find_crate_root(&func.file_path) else {
+            summary.skipped.push((label, "no Cargo.toml found".to_string()));
+            continue;
+        };
+        let Some(mut path) = module_path(&crate_root, &func.file_path) else {
+            summary.skipped.push((label, "function is not under the crate's src/".to_string()));
+            continue;
+        };
+        // Private functions are only visible to the harness module from the crate root
+        if !path.is_empty() && matches!(item.vis, syn::Visibility::Inherited) {
+            summary.skipped.push((label, "private functions outside the crate root can't be called from the harness module".to_string()));
+            continue;
+        }
+        path.insert(0, "crate".to_string());
+        path.push(func.function_name.clone());
+        let function_path = path.join("::");
+
+        let output_file = crate_root.join("src").join(format!("{}.rs", KANI_MODULE));
+        let names = used_names.entry(output_file.clone()).or_default();
+        let count = names.entry(func.function_name.clone()).or_insert(0);
+        let harness_name = if *count == 0 {
+            func.function_name.clone()
+        } else {
+            format!("{}_{}", func.function_name, count)
+        };
+        *count += 1;
+
+        match generate_harness(&harness_name, &function_path, item, func, constants) {
+            Ok(harness) => harnesses_by_file.entry(output_file).or_default().push(harness),
+            Err(reason) => summary.skipped.push((label, reason)),
+        }
+    }
+
+    for (path, harnesses) in harnesses_by_file {
+        let mut code = String::from("//! Kani proof harnesses generated from spec-lock contracts\n");
+        code.push_str("//!\n");
+        code.push_str("//! This file is AUTO-GENERATED by `cargo spec-lock gen-kani`\n");
+        code.push_str("//! Each harness checks a function's #[ensures] for all inputs satisfying its #[requires].\n");
+        code.push_str(&format!("//! Declare it in the crate root with `#[cfg(kani)] mod {};`.\n\n", KANI_MODULE));
+        code.push_str("#![cfg(kani)]\n");
+        // Contracts are copied as written, and `old(..)` values are cloned whatever their type
+        code.push_str("#![allow(clippy::clone_on_copy, clippy::nonminimal_bool)]\n");
+        for harness in &harnesses {
+            code.push('\n');
+            code.push_str(harness);
+        }
+        std::fs::write(&path, code)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+        let crate_root = path.parent().and_then(Path::parent).unwrap_or(Path::new("."));
+        let declared = ["lib.rs", "main.rs"].iter().any(|root| {
+            std::fs::read_to_string(crate_root.join("src").join(root))
+                .is_ok_and(|source| source.contains(&format!("mod {};", KANI_MODULE)))
+        });
+        if !declared {
+            summary.undeclared.push(crate_root.to_path_buf());
+        }
+        summary.files.push((path, harnesses.len()));
+    }
+
+    Ok(summary)
+}
+
+/// Generate the proof harness of one function
+fn generate_harness(
+    harness_name: &str,
+    function_path: &str,
+    item: &syn::ItemFn,
+    func: &FunctionToVerify,
+    constants: &[WorkspaceConstant],
+) -> Result<String, String> {
+    let sig = &item.sig;
+    if sig.asyncness.is_some() || !sig.generics.params.is_empty() {
+        return Err("async and generic functions are not supported".to_string());
+    }
+    let mut requires = Vec::new();
+    let mut ensures = Vec::new();
+    for contract in &func.contracts {
+        let expr = contract.expr.as_ref()
+            .ok_or_else(|| format!("contract `{}` doesn't parse", contract.condition))?;
+        let tokens = substitute_constants(quote!(#expr), constants);
+        match contract.contract_type {
+            ContractType::Requires => requires.push((expr, tokens)),
+            ContractType::Ensures => ensures.push((tokens, &contract.condition)),
+        }
+    }
+    let required: Vec<&syn::Expr> = requires.iter().map(|(expr, _)| *expr).collect();
+    let bounds = requires_bounds(&required, constants);
+    // A loop over a vector needs one more unwinding than it has elements
+    let max_elements = func.unwind.map_or(DEFAULT_MAX_ELEMENTS, |unwind| i128::from(unwind) - 1);
+
+    let mut body = Vec::new();
+    let mut args = Vec::new();
+    for input in &sig.inputs {
+        let syn::FnArg::Typed(pat_type) = input else {
+            return Err("methods with a receiver are not supported".to_string());
+        };
+        let syn::Pat::Ident(ident) = &*pat_type.pat else {
+            return Err("only identifier parameters are supported".to_string());
+        };
+        let name = ident.ident.to_string();
+        let (pass, ty) = match &*pat_type.ty {
+            syn::Type::Reference(reference) if reference.mutability.is_some() => (Passing::RefMut, &*reference.elem),
+            syn::Type::Reference(reference) => (Passing::Ref, &*reference.elem),
+            ty => (Passing::Value, ty),
+        };
+        let no_bounds = Bounds::default();
+        let len_bounds = bounds.get(&format!("{}.len()", name)).unwrap_or(&no_bounds);
+        let value = any_value(ty, len_bounds, max_elements)
+            .map_err(|reason| format!("parameter `{}`: {}", name, reason))?;
+        match pass {
+            Passing::RefMut => body.push(format!("let mut {} = {};", name, value)),
+            _ => body.push(format!("let {} = {};", name, value)),
+        }
+        match pass {
+            // The ensures may still read a parameter that the call consumes
+            Passing::Value if !is_copy(ty) && ensures.iter().any(|(tokens, _)| mentions(tokens.clone(), &name)) => {
+                args.push(format!("{}.clone()", name));
+            }
+            Passing::Value => args.push(name),
+            Passing::Ref | Passing::RefMut => {
+                body.push(format!("let {} = {};", name, borrow(ty, &name, pass == Passing::RefMut)));
+                args.push(name);
+            }
+        }
+    }
+
+    for (_, tokens) in &requires {
+        let condition = capture_old(tokens.clone(), &mut Vec::new())
+            .ok_or_else(|| "a #[requires] with forall/exists can't be assumed".to_string())?;
+        body.push(format!("kani::assume({});", condition));
+    }
+    let mut old_values = Vec::new();
+    let mut checks = Vec::new();
+    for (tokens, text) in &ensures {
+        match capture_old(tokens.clone(), &mut old_values) {
+            Some(condition) => checks.push(format!("assert!({}, \"{{}}\", {:?});", condition, format!("ensures `{}`", text))),
+            None => checks.push(format!("// Not checked (quantified): #[ensures({})]", text)),
+        }
+    }
+    for (name, value) in &old_values {
+        body.push(format!("let {} = ({}).clone();", name, value));
+    }
+    let call = format!("{}({})", function_path, args.join(", "));
+    match (&sig.output, ensures.is_empty()) {
+        (syn::ReturnType::Default, _) => body.push(format!("{};", call)),
+        (syn::ReturnType::Type(..), true) => body.push(format!("let _ = {};", call)),
+        (syn::ReturnType::Type(..), false) => body.push(format!("let result = {};", call)),
+    }
+    body.extend(checks);
+
+    let section = func.section.as_deref().map(|section| format!(" (Orange Paper Section {})", section)).unwrap_or_default();
+    let mut harness = format!("/// `{}`{}\n", function_path, section);
+    harness.push_str("#[kani::proof]\n");
+    if let Some(unwind) = func.unwind {
+        harness.push_str(&format!("#[kani::unwind({})]\n", unwind));
+    }
+    harness.push_str(&format!("fn {}() {{\n", harness_name));
+    for line in body {
+        harness.push_str(&format!("    {}\n", line));
+    }
+    harness.push_str("}\n");
+    Ok(harness)
+}
+
+/// A symbolic value of `ty`: `kani::any()`, or a vector of at most `max_elements`
+/// (but at least the length its `#[requires]` demand) for `Vec` and slices
+///
+/// Other types must implement `kani::Arbitrary` (e.g. with
+/// `#[cfg_attr(kani, derive(kani::Arbitrary))]`).
+fn any_value(ty: &syn::Type, len: &Bounds, max_elements: i128) -> Result<String, String> {
+    let any_vec = |elem: &syn::Type| {
+        let min = len.min.unwrap_or(0).max(0);
+        let max = len.max.unwrap_or(i128::MAX).min(max_elements).max(min);
+        format!("kani::vec::any_vec::<{}, {}>()", quote!(#elem), max)
+    };
+    match ty {
+        syn::Type::Paren(paren) => any_value(&paren.elem, len, max_elements),
+        syn::Type::Slice(slice) => Ok(any_vec(&slice.elem)),
+        syn::Type::Path(path) if path.qself.is_none() => {
+            let Some(segment) = path.path.segments.last() else {
+                return Err(format!("no symbolic value for `{}`", quote!(#ty)));
+            };
+            match (segment.ident.to_string().as_str(), &segment.arguments) {
+                ("String" | "str", syn::PathArguments::None) => Err(format!("Kani has no `kani::Arbitrary` for `{}`", segment.ident)),
+                ("Vec", syn::PathArguments::AngleBracketed(args)) => match args.args.first() {
+                    Some(syn::GenericArgument::Type(elem)) if args.args.len() == 1 => Ok(any_vec(elem)),
+                    _ => Err(format!("no symbolic value for `{}`", quote!(#ty))),
+                },
+                _ => Ok(format!("kani::any::<{}>()", quote!(#ty))),
+            }
+        }
+        syn::Type::Array(_) | syn::Type::Tuple(_) => Ok(format!("kani::any::<{}>()", quote!(#ty))),
+        _ => Err(format!("no symbolic value for `{}`", quote!(#ty))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::verify::Contract;
+    use syn::{parse_quote, Expr};
+
+    fn contract(contract_type: ContractType, expr: Expr) -> Contract {
+        Contract { contract_type, condition: quote!(#expr).to_string(), expr: Some(expr) }
+    }
+
+    #[test]
+    fn test_generate_harness() {
+        let item: syn::ItemFn = parse_quote! {
+            pub fn add_fee(total: u64, outputs: &[u64], tx: Transaction, fee: &mut u64) -> Result<u64, String> { Ok(total) }
+        };
+        let mut func = FunctionToVerify {
+            file_path: PathBuf::from("/ws/consensus/src/economic.rs"),
+            function_name: "add_fee".to_string(),
+            line: 1,
+            contracts: vec![
+                contract(ContractType::Requires, parse_quote!(total <= MAX_MONEY && !outputs.is_empty() && outputs.len() < 100)),
+                contract(ContractType::Ensures, parse_quote!(*fee >= old(*fee))),
+                contract(ContractType::Ensures, parse_quote!(result.is_err() || tx.version == 1)),
+                contract(ContractType::Ensures, parse_quote!(forall(|i: usize| i < outputs.len()))),
+            ],
+            section: Some("6.2".to_string()),
+            spec_function: None,
+            unwind: Some(9),
+            function_sig: Some(item.clone()),
+        };
+        let constants = vec![WorkspaceConstant { name: "MAX_MONEY".to_string(), type_name: "u64".to_string(), value: 2_100_000_000_000_000 }];
+        let harness = generate_harness("add_fee", "crate::economic::add_fee", &item, &func, &constants).unwrap();
+
+        assert!(harness.starts_with("/// `crate::economic::add_fee` (Orange Paper Section 6.2)\n#[kani::proof]\n#[kani::unwind(9)]\nfn add_fee() {\n"), "{}", harness);
+        assert!(harness.contains("let total = kani::any::<u64>();"), "{}", harness);
+        // Vectors hold up to one element fewer than the unwind bound
+        assert!(harness.contains("let outputs = kani::vec::any_vec::<u64, 8>();\n    let outputs = outputs.as_slice();"), "{}", harness);
+        assert!(harness.contains("let mut fee = kani::any::<u64>();\n    let fee = &mut fee;"), "{}", harness);
+        assert!(harness.contains("kani::assume(total <= (2100000000000000u64) && ! outputs . is_empty () && outputs . len () < 100);"), "{}", harness);
+        assert!(harness.contains("let __spec_lock_old_0 = (* fee).clone();"), "{}", harness);
+        // `tx` is moved into the call but read by an ensures
+        assert!(harness.contains("let result = crate::economic::add_fee(total, outputs, tx.clone(), fee);"), "{}", harness);
+        assert!(harness.contains("assert!(* fee >= __spec_lock_old_0, \"{}\", \"ensures `* fee >= old (* fee)`\");"), "{}", harness);
+        assert!(harness.contains("// Not checked (quantified)"), "{}", harness);
+
+        // Without an unwind bound vectors are kept small, and there's no #[kani::unwind]
+        func.unwind = None;
+        let harness = generate_harness("add_fee", "crate::economic::add_fee", &item, &func, &constants).unwrap();
+        assert!(harness.contains("kani::vec::any_vec::<u64, 4>()"), "{}", harness);
+        assert!(!harness.contains("kani::unwind"), "{}", harness);
+
+        // Strings have no symbolic values
+        let item: syn::ItemFn = parse_quote! { pub fn check(name: &str) -> bool { true } };
+        assert!(generate_harness("check", "crate::check", &item, &func, &[]).unwrap_err().contains("no `kani::Arbitrary` for `str`"));
+    }
+}
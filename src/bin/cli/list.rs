@@ -0,0 +1,208 @@
+//! Inventory of spec-locked functions
+//!
+//! Lists every #[spec_locked] function with its location, Orange Paper
+//! section, matched spec function and contract counts.
+
+use crate::cli::verify::{ContractType, FunctionToVerify};
+use crate::parser::orange_paper::SpecParser;
+use std::path::PathBuf;
+
+/// One row of the inventory
+#[derive(Debug, Clone)]
+pub struct ListEntry {
+    pub function_name: String,
+    pub file_path: PathBuf,
+    pub line: usize,
+    /// Section given in #[spec_locked] (None = auto-inferred)
+    pub section: Option<String>,
+    /// Section the function resolves to in the Orange Paper
+    pub resolved_section: Option<String>,
+    /// Matched Orange Paper function name
+    pub spec_function: Option<String>,
+    /// Whether the section resolves in the Orange Paper (None = spec not loaded)
+    pub section_resolves: Option<bool>,
+    pub requires: usize,
+    pub ensures: usize,
+}
+
+/// Build the inventory, resolving sections against the Orange Paper if available
+pub fn list_functions(functions: &[FunctionToVerify], spec: Option<&SpecParser>) -> Vec<ListEntry> {
+    let mut entries: Vec<ListEntry> = functions.iter().map(|func| {
+        let lookup_name = func.spec_function.as_deref().unwrap_or(&func.function_name);
+        let resolved = spec.and_then(|spec| spec.resolve_function(func.section.as_deref(), lookup_name));
+        let section_resolves = spec.map(|spec| match &func.section {
+            Some(section) => spec.section_exists(section),
+            None => resolved.is_some(),
+        });
+
+        ListEntry {
+            function_name: func.function_name.clone(),
+            file_path: func.file_path.clone(),
+            line: func.line,
+            section: func.section.clone(),
+            resolved_section: resolved.as_ref().map(|(_, section_id)| section_id.clone()),
+            spec_function: resolved.map(|(spec_func, _)| spec_func.name.clone())
+                .or_else(|| func.spec_function.clone()),
+            section_resolves,
+            requires: func.contracts.iter().filter(|c| c.contract_type == ContractType::Requires).count(),
+            ensures: func.contracts.iter().filter(|c| c.contract_type == ContractType::Ensures).count(),
+        }
+    }).collect();
+
+    entries.sort_by(|a, b| (&a.file_path, a.line).cmp(&(&b.file_path, b.line)));
+    entries
+}
+
+/// Section as shown to users: explicit section, or the inferred one marked as such
+fn display_section(entry: &ListEntry) -> String {
+    match (&entry.section, &entry.resolved_section) {
+        (Some(section), _) => section.clone(),
+        (None, Some(resolved)) => format!("{} (inferred)", resolved),
+        (None, None) => "-".to_string(),
+    }
+}
+
+fn resolves_label(section_resolves: Option<bool>) -> &'static str {
+    match section_resolves {
+        Some(true) => "✅",
+        Some(false) => "❌",
+        None => "?",
+    }
+}
+
+/// Format the inventory as human-readable text
+pub fn format_list_human(entries: &[ListEntry]) -> String {
+    let mut output = String::new();
+
+    output.push_str("=== Spec-Locked Functions ===\n\n");
+
+    for entry in entries {
+        output.push_str(&format!("{} {}\n", resolves_label(entry.section_resolves), entry.function_name));
+        output.push_str(&format!("   Location: {}:{}\n", entry.file_path.display(), entry.line));
+        output.push_str(&format!("   Section: {}\n", display_section(entry)));
+        output.push_str(&format!("   Spec function: {}\n", entry.spec_function.as_deref().unwrap_or("-")));
+        output.push_str(&format!("   Contracts: {} requires, {} ensures\n", entry.requires, entry.ensures));
+        output.push('\n');
+    }
+
+    let unresolved = entries.iter().filter(|e| e.section_resolves == Some(false)).count();
+    output.push_str(&format!("Total: {} functions", entries.len()));
+    if entries.iter().any(|e| e.section_resolves.is_some()) {
+        output.push_str(&format!(", {} with unresolved sections", unresolved));
+    } else {
+        output.push_str(" (Orange Paper not loaded, sections unchecked)");
+    }
+    output.push('\n');
+
+    output
+}
+
+/// Format the inventory as JSON
+pub fn format_list_json(entries: &[ListEntry]) -> String {
+    let functions: Vec<_> = entries.iter().map(|entry| serde_json::json!({
+        "name": entry.function_name,
+        "file": entry.file_path.display().to_string(),
+        "line": entry.line,
+        "section": entry.section,
+        "resolved_section": entry.resolved_section,
+        "spec_function": entry.spec_function,
+        "section_resolves": entry.section_resolves,
+        "requires": entry.requires,
+        "ensures": entry.ensures,
+    })).collect();
+
+    let json = serde_json::json!({
+        "functions": functions,
+        "total": entries.len(),
+    });
+
+    serde_json::to_string_pretty(&json).unwrap_or_else(|_| "{}".to_string())
+}
+
+/// Format the inventory as Markdown
+pub fn format_list_markdown(entries: &[ListEntry]) -> String {
+    let mut output = String::new();
+
+    output.push_str("# Spec-Locked Functions\n\n");
+    output.push_str("| Function | Location | Section | Spec Function | Requires | Ensures | Resolves |\n");
+    output.push_str("|----------|----------|---------|---------------|----------|---------|----------|\n");
+
+    for entry in entries {
+        output.push_str(&format!("| `{}` | {}:{} | {} | {} | {} | {} | {} |\n",
+            entry.function_name,
+            entry.file_path.display(),
+            entry.line,
+            display_section(entry),
+            entry.spec_function.as_deref().unwrap_or("-"),
+            entry.requires,
+            entry.ensures,
+            resolves_label(entry.section_resolves),
+        ));
+    }
+
+    output.push_str(&format!("\n**Total:** {} functions\n", entries.len()));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::verify::Contract;
+
+    const SPEC: &str = "\
+## 6 Economics
+
+### 6.1 Block Subsidy
+
+**GetBlockSubsidy**: $\\mathbb{N} \\to \\mathbb{Z}$
+
+### 6.2 Total Supply
+
+**TotalSupply**: $\\mathbb{N} \\to \\mathbb{Z}$
+";
+
+    fn function(name: &str, line: usize, section: Option<&str>, contracts: &[ContractType]) -> FunctionToVerify {
+        FunctionToVerify {
+            file_path: PathBuf::from("/ws/src/economic.rs"),
+            function_name: name.to_string(),
+            line,
+            contracts: contracts.iter().map(|&contract_type| Contract {
+                contract_type,
+                condition: "true".to_string(),
+                expr: None,
+            }).collect(),
+            section: section.map(str::to_string),
+            spec_function: None,
+            unwind: None,
+            function_sig: None,
+        }
+    }
+
+    #[test]
+    fn test_list_functions() {
+        let mut spec = SpecParser::new(SPEC.to_string());
+        spec.parse().unwrap();
+        let functions = vec![
+            function("total_supply", 20, None, &[ContractType::Ensures]),
+            function("get_block_subsidy", 5, Some("6.1"), &[ContractType::Requires, ContractType::Ensures, ContractType::Ensures]),
+            function("check_fee", 40, Some("9.9"), &[]),
+        ];
+
+        let entries = list_functions(&functions, Some(&spec));
+        let rows: Vec<_> = entries.iter()
+            .map(|e| (e.function_name.as_str(), e.resolved_section.as_deref(), e.spec_function.as_deref(), e.section_resolves, e.requires, e.ensures))
+            .collect();
+        assert_eq!(rows, vec![
+            ("get_block_subsidy", Some("6.1"), Some("GetBlockSubsidy"), Some(true), 1, 2),
+            // No section given: inferred from the function name
+            ("total_supply", Some("6.2"), Some("TotalSupply"), Some(true), 0, 1),
+            ("check_fee", None, None, Some(false), 0, 0),
+        ]);
+        assert_eq!(display_section(&entries[1]), "6.2 (inferred)");
+
+        // Without the Orange Paper nothing is resolved or checked
+        let entries = list_functions(&functions, None);
+        assert!(entries.iter().all(|e| e.section_resolves.is_none() && e.resolved_section.is_none()));
+        assert_eq!((entries[0].requires, entries[0].ensures), (1, 2));
+    }
+}
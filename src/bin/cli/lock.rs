@@ -0,0 +1,261 @@
+//! Spec lock file
+//!
+//! Pins each #[spec_locked] function to a SHA-256 of the Orange Paper text it
+//! implements, so spec edits force a re-review of the affected functions.
+
+use crate::cli::verify::FunctionToVerify;
+use crate::parser::orange_paper::SpecParser;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Default lock file name, in the workspace root
+pub const LOCK_FILE: &str = "spec-lock.json";
+
+/// Lock file format version
+const LOCK_VERSION: u32 = 1;
+
+/// Contents of `spec-lock.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpecLock {
+    pub version: u32,
+    /// Locked functions keyed by `file::function` (file relative to the workspace root)
+    pub functions: BTreeMap<String, LockedFunction>,
+}
+
+/// The Orange Paper text a function is pinned to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedFunction {
+    pub section: Option<String>,
+    pub spec_function: Option<String>,
+    /// What was hashed: the spec function block or the whole section
+    pub scope: LockScope,
+    /// SHA-256 of the spec text (hex)
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockScope {
+    Function,
+    Section,
+}
+
+/// Differences between a lock file and the current spec
+#[derive(Debug, Clone, Default)]
+pub struct LockCheck {
+    /// Functions whose spec text changed since locking
+    pub changed: Vec<String>,
+    /// Functions not in the lock file
+    pub unlocked: Vec<String>,
+    /// Locked functions that no longer exist
+    pub removed: Vec<String>,
+    /// Functions whose spec text can't be found anymore
+    pub unresolved: Vec<String>,
+}
+
+impl LockCheck {
+    /// Whether the lock needs to be reviewed and rewritten
+    pub fn is_stale(&self) -> bool {
+        !self.changed.is_empty() || !self.unlocked.is_empty() || !self.unresolved.is_empty()
+    }
+}
+
+impl SpecLock {
+    /// Read a lock file
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let lock: SpecLock = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+        if lock.version != LOCK_VERSION {
+            return Err(format!("Unsupported lock file version {} in {}", lock.version, path.display()));
+        }
+        Ok(lock)
+    }
+
+    /// Write the lock file
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let mut content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize lock file: {}", e))?;
+        content.push('\n');
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// Lock every function to the current spec text
+///
+/// Returns the lock and the functions whose spec text couldn't be found.
+pub fn build_lock(functions: &[FunctionToVerify], spec: &SpecParser, workspace_root: &Path) -> (SpecLock, Vec<String>) {
+    let mut locked = BTreeMap::new();
+    let mut unresolved = Vec::new();
+
+    for func in functions {
+        let key = lock_key(func, workspace_root);
+        match lock_function(func, spec) {
+            Some(entry) => {
+                locked.insert(key, entry);
+            }
+            None => unresolved.push(key),
+        }
+    }
+
+    (SpecLock { version: LOCK_VERSION, functions: locked }, unresolved)
+}
+
+/// Compare the lock file against the current spec
+pub fn check_lock(lock: &SpecLock, functions: &[FunctionToVerify], spec: &SpecParser, workspace_root: &Path) -> LockCheck {
+    let mut check = LockCheck::default();
+    let mut seen = Vec::new();
+
+    for func in functions {
+        let key = lock_key(func, workspace_root);
+        seen.push(key.clone());
+        let Some(locked) = lock.functions.get(&key) else {
+            check.unlocked.push(key);
+            continue;
+        };
+        match lock_function(func, spec) {
+            Some(current) if current == *locked => {}
+            Some(_) => check.changed.push(key),
+            None => check.unresolved.push(key),
+        }
+    }
+
+    check.removed = lock.functions.keys()
+        .filter(|key| !seen.contains(key))
+        .cloned()
+        .collect();
+    check
+}
+
+/// Hash the spec text behind a function
+///
+/// Functions that resolve to a spec function are pinned to its block and its
+/// theorems; otherwise to their whole section.
+pub fn lock_function(func: &FunctionToVerify, spec: &SpecParser) -> Option<LockedFunction> {
+    let lookup_name = func.spec_function.as_deref().unwrap_or(&func.function_name);
+    if let Some((spec_func, section_id)) = spec.resolve_function(func.section.as_deref(), lookup_name) {
+        let mut text = spec_func.content.clone();
+        for theorem in &spec_func.theorems {
+            text.push_str(&format!("\nTheorem {} ({}): {}", theorem.number, theorem.name, theorem.statement));
+        }
+        return Some(LockedFunction {
+            section: Some(section_id),
+            spec_function: Some(spec_func.name.clone()),
+            scope: LockScope::Function,
+            hash: hash_text(&text),
+        });
+    }
+
+    let section_id = func.section.as_deref()?;
+    let section = spec.find_section(section_id)
+        .or_else(|| spec.find_subsection(section_id).map(|(s, _)| s))?;
+    Some(LockedFunction {
+        section: Some(section_id.to_string()),
+        spec_function: None,
+        scope: LockScope::Section,
+        hash: hash_text(&section.content),
+    })
+}
+
+/// SHA-256 of spec text, ignoring line-ending and trailing-whitespace differences
+fn hash_text(text: &str) -> String {
+    let normalized: Vec<&str> = text.trim().lines().map(str::trim_end).collect();
+    format!("{:x}", Sha256::digest(normalized.join("\n").as_bytes()))
+}
+
+/// Stable key for a function: `path/relative/to/workspace.rs::name`
+fn lock_key(func: &FunctionToVerify, workspace_root: &Path) -> String {
+    let path: PathBuf = func.file_path.strip_prefix(workspace_root)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| func.file_path.clone());
+    format!("{}::{}", path.display(), func.function_name)
+}
+
+/// Format a lock check as human-readable text
+pub fn format_lock_check_human(check: &LockCheck) -> String {
+    let mut output = String::new();
+
+    output.push_str("=== Spec Lock Check ===\n\n");
+
+    let groups = [
+        ("❌ Spec text changed (re-review, then run `cargo spec-lock lock`):", &check.changed),
+        ("❌ Spec text no longer found:", &check.unresolved),
+        ("⚠️  Not in lock file:", &check.unlocked),
+        ("ℹ️  Locked but no longer present:", &check.removed),
+    ];
+    for (title, functions) in groups {
+        if functions.is_empty() {
+            continue;
+        }
+        output.push_str(&format!("{}\n", title));
+        for function in functions {
+            output.push_str(&format!("  {}\n", function));
+        }
+        output.push('\n');
+    }
+
+    if check.is_stale() {
+        output.push_str(&format!(
+            "Summary: {} changed, {} unresolved, {} unlocked, {} removed\n",
+            check.changed.len(), check.unresolved.len(), check.unlocked.len(), check.removed.len()
+        ));
+    } else {
+        output.push_str("✅ Spec lock is up to date.\n");
+    }
+
+    output
+}
+
+/// Format a lock check as JSON
+pub fn format_lock_check_json(check: &LockCheck) -> String {
+    serde_json::json!({
+        "up_to_date": !check.is_stale(),
+        "changed": check.changed,
+        "unresolved": check.unresolved,
+        "unlocked": check.unlocked,
+        "removed": check.removed,
+    }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(content: &str) -> SpecParser {
+        let mut spec = SpecParser::new(content.to_string());
+        spec.parse().unwrap();
+        spec
+    }
+
+    #[test]
+    fn test_check_lock_detects_spec_edits() {
+        let original = "### 6.1 Block Subsidy\n\n**GetBlockSubsidy**: $\\mathbb{N} \\to \\mathbb{Z}$\n\n**CalculateFee**: $\\mathbb{N} \\to \\mathbb{N}$\n";
+        let edited = original.replace("\\mathbb{N} \\to \\mathbb{Z}", "\\mathbb{N} \\to \\mathbb{N}");
+        let root = Path::new("/ws");
+        let functions: Vec<_> = ["get_block_subsidy", "calculate_fee"].iter().map(|name| FunctionToVerify {
+            file_path: root.join("src/economic.rs"),
+            function_name: name.to_string(),
+            line: 1,
+            contracts: Vec::new(),
+            section: Some("6.1".to_string()),
+            spec_function: None,
+            unwind: None,
+            function_sig: None,
+        }).collect();
+
+        let (lock, unresolved) = build_lock(&functions, &spec(original), root);
+        assert!(unresolved.is_empty());
+        assert_eq!(lock.functions["src/economic.rs::get_block_subsidy"].scope, LockScope::Function);
+
+        assert!(!check_lock(&lock, &functions, &spec(original), root).is_stale());
+
+        // Only the function whose block changed needs re-review
+        let check = check_lock(&lock, &functions, &spec(&edited), root);
+        assert_eq!(check.changed, vec!["src/economic.rs::get_block_subsidy".to_string()]);
+        assert!(check.unlocked.is_empty() && check.removed.is_empty());
+    }
+}
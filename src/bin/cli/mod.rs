@@ -7,6 +7,15 @@ pub mod filters;
 pub mod output;
 pub mod coverage;
 pub mod drift;
+pub mod regression;
+pub mod proptests;
+pub mod kani;
+pub mod list;
+pub mod lock;
+pub mod incremental;
+pub mod spec;
+pub mod theorems;
+pub mod constants;
 
 pub use verify::*;
 pub use filters::*;
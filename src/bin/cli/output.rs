@@ -2,44 +2,86 @@
 //!
 //! Formats results as human-readable, JSON, JUnit XML, or Markdown
 
-use crate::cli::verify::{VerificationResult, FunctionToVerify};
+use crate::cli::theorems::{TheoremResult, TheoremStatus};
+use crate::cli::verify::{Counterexample, VerificationResult, FunctionToVerify};
+
+/// Check whether a result counts as a failure (failed contract or possible overflow)
+fn is_failure(result: &VerificationResult) -> bool {
+    matches!(result, VerificationResult::Failed { .. } | VerificationResult::Overflow { .. })
+}
 
 /// Format verification results
+///
+/// `cached[i]` marks results reused from the verification cache (empty = none);
+/// `theorems` are the Orange Paper theorems checked against the functions.
 pub fn format_results(
     results: &[(FunctionToVerify, VerificationResult)],
     format: &str,
+    cached: &[bool],
+    theorems: &[TheoremResult],
 ) -> String {
     match format {
-        "human" => format_human(results),
-        "json" => format_json(results),
-        "junit" => format_junit(results),
-        "markdown" => format_markdown(results),
-        _ => format_human(results),
+        "human" => format_human(results, cached, theorems),
+        "json" => format_json(results, cached, theorems),
+        "junit" => format_junit(results, cached, theorems),
+        "markdown" => format_markdown(results, cached, theorems),
+        _ => format_human(results, cached, theorems),
     }
 }
 
+fn is_cached(cached: &[bool], index: usize) -> bool {
+    cached.get(index).copied().unwrap_or(false)
+}
+
 /// Format as human-readable text
-fn format_human(results: &[(FunctionToVerify, VerificationResult)]) -> String {
+fn format_human(results: &[(FunctionToVerify, VerificationResult)], cached: &[bool], theorems: &[TheoremResult]) -> String {
     let mut output = String::new();
     output.push_str("Running BLVM Spec Lock verification...\n\n");
     
-    for (func, result) in results {
-        output.push_str(&format!("{}::{}\n", 
+    for (index, (func, result)) in results.iter().enumerate() {
+        output.push_str(&format!("{}::{}{}\n", 
             func.file_path.display(), 
-            func.function_name));
+            func.function_name,
+            if is_cached(cached, index) { " (cached)" } else { "" }));
         
         match result {
             VerificationResult::Passed => {
                 output.push_str("  ✅ Status: PASSED\n");
             }
-            VerificationResult::Failed { contract, reason } => {
+            VerificationResult::Failed { contract, reason, counterexample, .. } => {
                 output.push_str(&format!("  ❌ Status: FAILED\n"));
                 output.push_str(&format!("    Contract: {}\n", contract));
                 output.push_str(&format!("    Reason: {}\n", reason));
+                if let Some(ce) = counterexample {
+                    output.push_str("    Counterexample:\n");
+                    for (name, value) in ce {
+                        output.push_str(&format!("      {} = {}\n", name, value));
+                    }
+                }
             }
             VerificationResult::Partial { verified, total } => {
                 output.push_str(&format!("  ⚠️  Status: PARTIAL ({} of {} verified)\n", verified, total));
             }
+            VerificationResult::Unknown { contract, reason } => {
+                output.push_str("  ❓ Status: UNKNOWN\n");
+                output.push_str(&format!("    Contract: {}\n", contract));
+                output.push_str(&format!("    Reason: {}\n", reason));
+            }
+            VerificationResult::Overflow { failures } => {
+                output.push_str(&format!("  ❌ Status: OVERFLOW ({} possible)\n", failures.len()));
+                for failure in failures {
+                    output.push_str(&format!("    {}:{}:{}: `{}` ({})\n",
+                        func.file_path.display(),
+                        failure.line,
+                        failure.column,
+                        failure.expression,
+                        failure.operation));
+                    output.push_str(&format!("      Reason: {}\n", failure.reason));
+                    if let Some(ce) = &failure.counterexample {
+                        output.push_str(&format!("      Counterexample: {}\n", counterexample_inline(ce)));
+                    }
+                }
+            }
             VerificationResult::NotImplemented => {
                 output.push_str("  ⏳ Status: NOT IMPLEMENTED\n");
             }
@@ -47,17 +89,44 @@ fn format_human(results: &[(FunctionToVerify, VerificationResult)]) -> String {
         output.push('\n');
     }
     
+    if !theorems.is_empty() {
+        output.push_str("Theorems:\n");
+        for theorem in theorems {
+            let line = match &theorem.status {
+                TheoremStatus::Proven => format!("  ✅ Theorem {} proven for {}\n", theorem.number, theorem.function_name),
+                TheoremStatus::Failed { counterexample } => {
+                    let mut line = format!("  ❌ Theorem {} failed for {}\n", theorem.number, theorem.function_name);
+                    if let Some(ce) = counterexample {
+                        line.push_str(&format!("    Counterexample: {}\n", counterexample_inline(ce)));
+                    }
+                    line
+                }
+                TheoremStatus::Unknown { reason } => {
+                    format!("  ❓ Theorem {} unknown for {}: {}\n", theorem.number, theorem.function_name, reason)
+                }
+                TheoremStatus::Skipped { reason } => {
+                    format!("  ⏭️  Theorem {} skipped for {}: {}\n", theorem.number, theorem.function_name, reason)
+                }
+            };
+            output.push_str(&line);
+        }
+        output.push('\n');
+    }
+    
     // Summary
     let passed = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Passed)).count();
-    let failed = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Failed { .. })).count();
+    let failed = results.iter().filter(|(_, r)| is_failure(r)).count()
+        + theorems.iter().filter(|t| t.is_failure()).count();
     let partial = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Partial { .. })).count();
+    let unknown = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Unknown { .. })).count();
     
     output.push_str(&format!(
-        "test result: {}. {} passed; {} failed; {} partial; 0 skipped\n",
+        "test result: {}. {} passed; {} failed; {} partial; {} unknown; 0 skipped\n",
         if failed > 0 { "FAILED" } else { "ok" },
         passed,
         failed,
-        partial
+        partial,
+        unknown
     ));
     
     // Add duration and summary stats
@@ -65,23 +134,33 @@ fn format_human(results: &[(FunctionToVerify, VerificationResult)]) -> String {
         "  Functions verified: {}\n",
         results.len()
     ));
+    let cached_count = cached.iter().filter(|&&c| c).count();
+    if cached_count > 0 {
+        output.push_str(&format!("  Cached (unchanged): {}\n", cached_count));
+    }
+    if !theorems.is_empty() {
+        let proven = theorems.iter().filter(|t| matches!(t.status, TheoremStatus::Proven)).count();
+        output.push_str(&format!("  Theorems proven: {} of {}\n", proven, theorems.len()));
+    }
     
     output
 }
 
 /// Format as JSON
-fn format_json(results: &[(FunctionToVerify, VerificationResult)]) -> String {
+fn format_json(results: &[(FunctionToVerify, VerificationResult)], cached: &[bool], theorems: &[TheoremResult]) -> String {
     use serde_json::{json, Value};
     
     let passed = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Passed)).count();
-    let failed = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Failed { .. })).count();
+    let failed = results.iter().filter(|(_, r)| is_failure(r)).count();
     let partial = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Partial { .. })).count();
+    let unknown = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Unknown { .. })).count();
     
     let mut json_results = Vec::new();
-    for (func, result) in results {
+    for (index, (func, result)) in results.iter().enumerate() {
         let mut result_obj = json!({
             "file": func.file_path.to_string_lossy(),
             "function": func.function_name,
+            "cached": is_cached(cached, index),
         });
         
         if let Some(ref section) = func.section {
@@ -92,16 +171,40 @@ fn format_json(results: &[(FunctionToVerify, VerificationResult)]) -> String {
             VerificationResult::Passed => {
                 result_obj["status"] = json!("passed");
             }
-            VerificationResult::Failed { contract, reason } => {
+            VerificationResult::Failed { contract, reason, counterexample, .. } => {
                 result_obj["status"] = json!("failed");
                 result_obj["contract"] = json!(contract);
                 result_obj["reason"] = json!(reason);
+                if let Some(ce) = counterexample {
+                    result_obj["counterexample"] = counterexample_json(ce);
+                }
             }
             VerificationResult::Partial { verified, total } => {
                 result_obj["status"] = json!("partial");
                 result_obj["verified"] = json!(*verified);
                 result_obj["total"] = json!(*total);
             }
+            VerificationResult::Unknown { contract, reason } => {
+                result_obj["status"] = json!("unknown");
+                result_obj["contract"] = json!(contract);
+                result_obj["reason"] = json!(reason);
+            }
+            VerificationResult::Overflow { failures } => {
+                result_obj["status"] = json!("overflow");
+                result_obj["overflows"] = Value::Array(failures.iter().map(|failure| {
+                    let mut overflow = json!({
+                        "operation": failure.operation,
+                        "expression": failure.expression,
+                        "line": failure.line,
+                        "column": failure.column,
+                        "reason": failure.reason,
+                    });
+                    if let Some(ce) = &failure.counterexample {
+                        overflow["counterexample"] = counterexample_json(ce);
+                    }
+                    overflow
+                }).collect());
+            }
             VerificationResult::NotImplemented => {
                 result_obj["status"] = json!("not_implemented");
             }
@@ -110,33 +213,68 @@ fn format_json(results: &[(FunctionToVerify, VerificationResult)]) -> String {
         json_results.push(result_obj);
     }
     
+    let json_theorems: Vec<Value> = theorems.iter().map(|theorem| {
+        let mut theorem_obj = json!({
+            "number": theorem.number,
+            "name": theorem.name,
+            "file": theorem.file_path.to_string_lossy(),
+            "function": theorem.function_name,
+        });
+        match &theorem.status {
+            TheoremStatus::Proven => {
+                theorem_obj["status"] = json!("proven");
+            }
+            TheoremStatus::Failed { counterexample } => {
+                theorem_obj["status"] = json!("failed");
+                if let Some(ce) = counterexample {
+                    theorem_obj["counterexample"] = counterexample_json(ce);
+                }
+            }
+            TheoremStatus::Unknown { reason } => {
+                theorem_obj["status"] = json!("unknown");
+                theorem_obj["reason"] = json!(reason);
+            }
+            TheoremStatus::Skipped { reason } => {
+                theorem_obj["status"] = json!("skipped");
+                theorem_obj["reason"] = json!(reason);
+            }
+        }
+        theorem_obj
+    }).collect();
+    
     let output = json!({
         "summary": {
             "total": results.len(),
             "passed": passed,
             "failed": failed,
             "partial": partial,
+            "unknown": unknown,
+            "cached": cached.iter().filter(|&&c| c).count(),
+            "theorems_proven": theorems.iter().filter(|t| matches!(t.status, TheoremStatus::Proven)).count(),
+            "theorems_failed": theorems.iter().filter(|t| t.is_failure()).count(),
         },
         "results": json_results,
+        "theorems": json_theorems,
     });
     
     serde_json::to_string_pretty(&output).unwrap_or_else(|_| "{}".to_string())
 }
 
 /// Format as JUnit XML
-fn format_junit(results: &[(FunctionToVerify, VerificationResult)]) -> String {
+fn format_junit(results: &[(FunctionToVerify, VerificationResult)], cached: &[bool], theorems: &[TheoremResult]) -> String {
     use std::fmt::Write;
     
     let passed = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Passed)).count();
-    let failed = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Failed { .. })).count();
+    let failed = results.iter().filter(|(_, r)| is_failure(r)).count();
     let total = results.len();
+    let theorem_failures = theorems.iter().filter(|t| t.is_failure()).count();
     
     let mut xml = String::new();
     xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
     write!(
         &mut xml,
         "<testsuites name=\"blvm-spec-lock\" tests=\"{}\" failures=\"{}\" time=\"0.0\">\n",
-        total, failed
+        total + theorems.len(), failed + theorem_failures
     ).unwrap();
     write!(
         &mut xml,
@@ -144,7 +282,7 @@ fn format_junit(results: &[(FunctionToVerify, VerificationResult)]) -> String {
         total, failed
     ).unwrap();
     
-    for (func, result) in results {
+    for (index, (func, result)) in results.iter().enumerate() {
         let classname = func.file_path
             .file_stem()
             .and_then(|s| s.to_str())
@@ -154,6 +292,8 @@ fn format_junit(results: &[(FunctionToVerify, VerificationResult)]) -> String {
             VerificationResult::Passed => "",
             VerificationResult::Failed { .. } => " status=\"failed\"",
             VerificationResult::Partial { .. } => " status=\"partial\"",
+            VerificationResult::Unknown { .. } => " status=\"unknown\"",
+            VerificationResult::Overflow { .. } => " status=\"overflow\"",
             VerificationResult::NotImplemented => " status=\"not_implemented\"",
         };
         
@@ -165,23 +305,57 @@ fn format_junit(results: &[(FunctionToVerify, VerificationResult)]) -> String {
             status_attr
         ).unwrap();
         
-        if let Some(ref section) = func.section {
-            write!(
-                &mut xml,
-                "      <properties>\n        <property name=\"section\" value=\"{}\"/>\n      </properties>\n",
-                section
-            ).unwrap();
+        if func.section.is_some() || is_cached(cached, index) {
+            xml.push_str("      <properties>\n");
+            if let Some(ref section) = func.section {
+                writeln!(&mut xml, "        <property name=\"section\" value=\"{}\"/>", section).unwrap();
+            }
+            if is_cached(cached, index) {
+                xml.push_str("        <property name=\"cached\" value=\"true\"/>\n");
+            }
+            xml.push_str("      </properties>\n");
         }
         
         match result {
-            VerificationResult::Failed { contract, reason } => {
+            VerificationResult::Failed { contract, reason, counterexample, .. } => {
+                let counterexample_text = counterexample.as_ref()
+                    .map(|ce| format!("\nCounterexample: {}", xml_escape(&counterexample_inline(ce))))
+                    .unwrap_or_default();
                 write!(
                     &mut xml,
-                    "      <failure message=\"{}\">Contract: {}</failure>\n",
-                    reason.replace('"', "&quot;"),
-                    contract.replace('"', "&quot;")
+                    "      <failure message=\"{}\">Contract: {}{}</failure>\n",
+                    xml_escape(reason),
+                    xml_escape(contract),
+                    counterexample_text
                 ).unwrap();
             }
+            VerificationResult::Unknown { contract, reason } => {
+                // Not a failure: the solver gave up (e.g. timeout)
+                write!(
+                    &mut xml,
+                    "      <skipped message=\"{}\">Contract: {}</skipped>\n",
+                    xml_escape(reason),
+                    xml_escape(contract)
+                ).unwrap();
+            }
+            VerificationResult::Overflow { failures } => {
+                for failure in failures {
+                    let counterexample_text = failure.counterexample.as_ref()
+                        .map(|ce| format!("\nCounterexample: {}", xml_escape(&counterexample_inline(ce))))
+                        .unwrap_or_default();
+                    write!(
+                        &mut xml,
+                        "      <failure message=\"{}\" type=\"overflow\">{}:{}:{}: {} ({}){}</failure>\n",
+                        xml_escape(&failure.reason),
+                        func.file_path.display(),
+                        failure.line,
+                        failure.column,
+                        xml_escape(&failure.expression),
+                        xml_escape(&failure.operation),
+                        counterexample_text
+                    ).unwrap();
+                }
+            }
             _ => {}
         }
         
@@ -189,13 +363,49 @@ fn format_junit(results: &[(FunctionToVerify, VerificationResult)]) -> String {
     }
     
     xml.push_str("  </testsuite>\n");
+    
+    if !theorems.is_empty() {
+        writeln!(
+            &mut xml,
+            "  <testsuite name=\"theorems\" tests=\"{}\" failures=\"{}\" time=\"0.0\">",
+            theorems.len(), theorem_failures
+        ).unwrap();
+        for theorem in theorems {
+            writeln!(
+                &mut xml,
+                "    <testcase name=\"Theorem {}\" classname=\"{}\">",
+                theorem.number,
+                theorem.function_name
+            ).unwrap();
+            match &theorem.status {
+                TheoremStatus::Proven => {}
+                TheoremStatus::Failed { counterexample } => {
+                    let counterexample_text = counterexample.as_ref()
+                        .map(|ce| format!("Counterexample: {}", xml_escape(&counterexample_inline(ce))))
+                        .unwrap_or_default();
+                    writeln!(
+                        &mut xml,
+                        "      <failure message=\"{}\">{}</failure>",
+                        xml_escape(&theorem.name),
+                        counterexample_text
+                    ).unwrap();
+                }
+                TheoremStatus::Unknown { reason } | TheoremStatus::Skipped { reason } => {
+                    writeln!(&mut xml, "      <skipped message=\"{}\"/>", xml_escape(reason)).unwrap();
+                }
+            }
+            xml.push_str("    </testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    
     xml.push_str("</testsuites>\n");
     
     xml
 }
 
 /// Format as Markdown
-fn format_markdown(results: &[(FunctionToVerify, VerificationResult)]) -> String {
+fn format_markdown(results: &[(FunctionToVerify, VerificationResult)], cached: &[bool], theorems: &[TheoremResult]) -> String {
     let mut md = String::new();
     
     md.push_str("# BLVM Spec Lock Verification Report\n\n");
@@ -204,21 +414,29 @@ fn format_markdown(results: &[(FunctionToVerify, VerificationResult)]) -> String
     
     // Summary
     let passed = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Passed)).count();
-    let failed = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Failed { .. })).count();
+    let failed = results.iter().filter(|(_, r)| is_failure(r)).count();
     let partial = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Partial { .. })).count();
+    let unknown = results.iter().filter(|(_, r)| matches!(r, VerificationResult::Unknown { .. })).count();
     
     md.push_str("## Summary\n\n");
     md.push_str(&format!("- **Total Functions:** {}\n", results.len()));
     md.push_str(&format!("- **Passed:** {} ✅\n", passed));
     md.push_str(&format!("- **Failed:** {} ❌\n", failed));
-    md.push_str(&format!("- **Partial:** {} ⚠️\n\n", partial));
+    md.push_str(&format!("- **Partial:** {} ⚠️\n", partial));
+    md.push_str(&format!("- **Unknown:** {} ❓\n", unknown));
+    md.push_str(&format!("- **Cached:** {}\n", cached.iter().filter(|&&c| c).count()));
+    if !theorems.is_empty() {
+        let proven = theorems.iter().filter(|t| matches!(t.status, TheoremStatus::Proven)).count();
+        md.push_str(&format!("- **Theorems Proven:** {} of {}\n", proven, theorems.len()));
+    }
+    md.push('\n');
     
     // Results table
     md.push_str("## Results\n\n");
     md.push_str("| File | Function | Section | Status |\n");
     md.push_str("|------|----------|---------|--------|\n");
     
-    for (func, result) in results {
+    for (index, (func, result)) in results.iter().enumerate() {
         let file_name = func.file_path
             .file_name()
             .and_then(|s| s.to_str())
@@ -232,8 +450,17 @@ fn format_markdown(results: &[(FunctionToVerify, VerificationResult)]) -> String
             VerificationResult::Partial { verified, total } => {
                 format!("⚠️ Partial ({}/{})", verified, total)
             }
+            VerificationResult::Unknown { reason, .. } => format!("❓ Unknown ({})", reason),
+            VerificationResult::Overflow { failures } => {
+                format!("❌ Overflow ({})", failures.len())
+            }
             VerificationResult::NotImplemented => "⏳ Not Implemented".to_string(),
         };
+        let status = if is_cached(cached, index) {
+            format!("{} (cached)", status)
+        } else {
+            status
+        };
         
         md.push_str(&format!(
             "| `{}` | `{}` | {} | {} |\n",
@@ -244,6 +471,30 @@ fn format_markdown(results: &[(FunctionToVerify, VerificationResult)]) -> String
         ));
     }
     
+    if !theorems.is_empty() {
+        md.push_str("\n## Theorems\n\n");
+        md.push_str("| Theorem | Name | Function | Status |\n");
+        md.push_str("|---------|------|----------|--------|\n");
+        for theorem in theorems {
+            let status = match &theorem.status {
+                TheoremStatus::Proven => "✅ Proven".to_string(),
+                TheoremStatus::Failed { counterexample: Some(ce) } => {
+                    format!("❌ Failed (`{}`)", counterexample_inline(ce))
+                }
+                TheoremStatus::Failed { counterexample: None } => "❌ Failed".to_string(),
+                TheoremStatus::Unknown { reason } => format!("❓ Unknown ({})", reason.replace('|', "\\|")),
+                TheoremStatus::Skipped { reason } => format!("⏭️ Skipped ({})", reason.replace('|', "\\|")),
+            };
+            md.push_str(&format!(
+                "| {} | {} | `{}` | {} |\n",
+                theorem.number,
+                theorem.name,
+                theorem.function_name,
+                status
+            ));
+        }
+    }
+    
     // Failed details
     let failed_results: Vec<_> = results.iter()
         .filter(|(_, r)| matches!(r, VerificationResult::Failed { .. }))
@@ -252,12 +503,52 @@ fn format_markdown(results: &[(FunctionToVerify, VerificationResult)]) -> String
     if !failed_results.is_empty() {
         md.push_str("\n## Failed Verifications\n\n");
         for (func, result) in failed_results {
-            if let VerificationResult::Failed { contract, reason } = result {
+            if let VerificationResult::Failed { contract, reason, counterexample, .. } = result {
                 md.push_str(&format!("### `{}::{}`\n\n", 
                     func.file_path.display(), 
                     func.function_name));
                 md.push_str(&format!("- **Contract:** {}\n", contract));
-                md.push_str(&format!("- **Reason:** {}\n\n", reason));
+                md.push_str(&format!("- **Reason:** {}\n", reason));
+                if let Some(ce) = counterexample {
+                    md.push_str("- **Counterexample:**\n");
+                    for (name, value) in ce {
+                        md.push_str(&format!("  - `{} = {}`\n", name, value));
+                    }
+                }
+                md.push('\n');
+            }
+        }
+    }
+    
+    // Overflow details
+    let overflow_results: Vec<_> = results.iter()
+        .filter(|(_, r)| matches!(r, VerificationResult::Overflow { .. }))
+        .collect();
+    
+    if !overflow_results.is_empty() {
+        md.push_str("\n## Possible Overflows\n\n");
+        for (func, result) in overflow_results {
+            if let VerificationResult::Overflow { failures } = result {
+                md.push_str(&format!("### `{}::{}`\n\n", 
+                    func.file_path.display(), 
+                    func.function_name));
+                md.push_str("| Location | Operation | Expression | Reason | Counterexample |\n");
+                md.push_str("|----------|-----------|------------|--------|----------------|\n");
+                for failure in failures {
+                    let counterexample = failure.counterexample.as_ref()
+                        .map(|ce| format!("`{}`", counterexample_inline(ce)))
+                        .unwrap_or_else(|| "-".to_string());
+                    md.push_str(&format!(
+                        "| {}:{} | `{}` | `{}` | {} | {} |\n",
+                        failure.line,
+                        failure.column,
+                        failure.operation,
+                        failure.expression,
+                        failure.reason.replace('|', "\\|"),
+                        counterexample
+                    ));
+                }
+                md.push('\n');
             }
         }
     }
@@ -265,3 +556,56 @@ fn format_markdown(results: &[(FunctionToVerify, VerificationResult)]) -> String
     md
 }
 
+/// Render a counterexample on one line, e.g. `a = 1u64, result = 0u64`
+fn counterexample_inline(counterexample: &Counterexample) -> String {
+    counterexample.iter()
+        .map(|(name, value)| format!("{} = {}", name, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Render a counterexample as a JSON array of `{"name", "value"}` objects,
+/// keeping the model's order
+fn counterexample_json(counterexample: &Counterexample) -> serde_json::Value {
+    serde_json::Value::Array(
+        counterexample.iter()
+            .map(|(name, value)| serde_json::json!({ "name": name, "value": value }))
+            .collect()
+    )
+}
+
+/// Escape text for use in XML element content and attribute values
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_junit_escapes_failures() {
+        let func = FunctionToVerify {
+            file_path: PathBuf::from("src/economic.rs"),
+            function_name: "fee".to_string(),
+            line: 1,
+            contracts: Vec::new(),
+            section: None,
+            spec_function: None,
+            unwind: None,
+            function_sig: None,
+        };
+        let result = VerificationResult::Failed {
+            contract: "result < a && b > \"0\"".to_string(),
+            reason: "Z3 found a <counterexample> for \"fee\"".to_string(),
+            counterexample: None,
+            condition: None,
+        };
+        let xml = format_results(&[(func, result)], "junit", &[], &[]);
+        assert!(xml.contains(
+            "<failure message=\"Z3 found a &lt;counterexample&gt; for &quot;fee&quot;\">Contract: result &lt; a &amp;&amp; b &gt; &quot;0&quot;</failure>"
+        ), "{}", xml);
+    }
+}
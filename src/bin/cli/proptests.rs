@@ -0,0 +1,655 @@
+//! Property test generation from contracts
+//!
+//! Writes a proptest module for each spec-locked function. Arguments are drawn
+//! from strategies for the parameter types, narrowed by simple `#[requires]`
+//! bounds (`x < 1000`, `v.len() <= MAX_INPUTS`) and filtered by every
+//! `#[requires]` with `prop_assume!`; each `#[ensures]` is then asserted on the
+//! real return value. If the crate has the `extract-formulas` helpers
+//! (`src/orange_paper_property_helpers.rs`), the helper for the function's
+//! spec function is also used as an oracle for the result.
+
+use crate::cli::constants::{evaluate_with_constants, int_range, WorkspaceConstant};
+use crate::cli::regression::{find_crate_root, module_path, read_crate_name};
+use crate::cli::verify::{ContractType, FunctionToVerify};
+use crate::parser::contracts::capture_old;
+use crate::parser::orange_paper::rust_to_pascal_case;
+use proc_macro2::{TokenStream, TokenTree};
+use quote::quote;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use syn::{BinOp, Expr, UnOp};
+
+/// Name of the generated test file in each crate
+const PROPTEST_FILE: &str = "spec_lock_proptests.rs";
+
+/// Module of a crate holding the `extract-formulas` helpers
+const HELPERS_MODULE: &str = "orange_paper_property_helpers";
+
+/// Most elements in a generated collection beyond its required minimum
+const MAX_EXTRA_ELEMENTS: i128 = 16;
+
+/// Summary of generated property tests
+#[derive(Debug, Clone, Default)]
+pub struct ProptestSummary {
+    /// Files written, with the number of tests in each
+    pub files: Vec<(PathBuf, usize)>,
+    /// Functions without a property test, with the reason
+    pub skipped: Vec<(String, String)>,
+    /// Crates whose Cargo.toml doesn't mention proptest
+    pub missing_dependency: Vec<PathBuf>,
+}
+
+/// Name of the `extract-formulas` helper computing a spec function's expected result
+pub fn helper_name(func_name: &str) -> String {
+    format!("expected_{}_from_orange_paper", func_name.to_lowercase().replace(" ", "_"))
+}
+
+/// Write a property test for every spec-locked function
+///
+/// Tests are grouped per crate into `<crate>/<dir>/spec_lock_proptests.rs`
+/// (or `<dir>/spec_lock_proptests.rs` if `dir` is absolute). `constants`
+/// resolve the names used in `#[requires]` bounds and contracts.
+pub fn emit_proptests(
+    functions: &[FunctionToVerify],
+    constants: &[WorkspaceConstant],
+    dir: &Path,
+) -> Result<ProptestSummary, String> {
+    let mut summary = ProptestSummary::default();
+    let mut tests_by_file: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    let mut used_names: HashMap<PathBuf, HashMap<String, usize>> = HashMap::new();
+    let mut helpers_by_crate: HashMap<PathBuf, HashMap<String, syn::Signature>> = HashMap::new();
+
+    for func in functions {
+        let label = format!("{}::{}", func.file_path.display(), func.function_name);
+        let Some(item) = &func.function_sig else {
+            summary.skipped.push((label, "missing parsed function".to_string()));
+            continue;
+        };
+        let Some(crate_root) = find_crate_root(&func.file_path) else {
+            summary.skipped.push((label, "no Cargo.toml found".to_string()));
+            continue;
+        };
+        let crate_name = read_crate_name(&crate_root)?;
+        let function_path = match module_path(&crate_root, &func.file_path) {
+            Some(mut path) => {
+                path.insert(0, crate_name.clone());
+                path.push(func.function_name.clone());
+                path.join("::")
+            }
+            None => {
+                summary.skipped.push((label, "function is not under the crate's src/".to_string()));
+                continue;
+            }
+        };
+
+        let helpers = helpers_by_crate.entry(crate_root.clone()).or_insert_with(|| read_helpers(&crate_root));
+        let spec_function = func.spec_function.clone().unwrap_or_else(|| rust_to_pascal_case(&func.function_name));
+        let oracle = helpers.get(&helper_name(&spec_function))
+            .map(|sig| (format!("{}::{}::{}", crate_name, HELPERS_MODULE, sig.ident), sig));
+
+        let output_file = crate_root.join(dir).join(PROPTEST_FILE);
+        let names = used_names.entry(output_file.clone()).or_default();
+        let count = names.entry(func.function_name.clone()).or_insert(0);
+        let module_name = if *count == 0 {
+            func.function_name.clone()
+        } else {
+            format!("{}_{}", func.function_name, count)
+        };
+        *count += 1;
+
+        let target = TestTarget { function_path: &function_path, item, section: func.section.as_deref(), oracle };
+        match generate_module(&module_name, &target, func, constants) {
+            Ok(module) => tests_by_file.entry(output_file).or_default().push(module),
+            Err(reason) => summary.skipped.push((label, reason)),
+        }
+    }
+
+    for (path, modules) in tests_by_file {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let mut code = String::from("//! Property tests generated from spec-lock contracts\n");
+        code.push_str("//!\n");
+        code.push_str("//! This file is AUTO-GENERATED by `cargo spec-lock gen-proptests`\n");
+        code.push_str("//! Each test checks a function's #[ensures] on inputs satisfying its #[requires].\n\n");
+        // Contracts are copied as written, and `old(..)` values are cloned whatever their type
+        code.push_str("#![allow(clippy::clone_on_copy, clippy::nonminimal_bool)]\n\n");
+        code.push_str("use proptest::prelude::*;\n");
+        for module in &modules {
+            code.push('\n');
+            code.push_str(module);
+        }
+        std::fs::write(&path, code)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        summary.files.push((path, modules.len()));
+    }
+
+    let mut crate_roots: Vec<&PathBuf> = helpers_by_crate.keys().collect();
+    crate_roots.sort();
+    for crate_root in crate_roots {
+        let manifest = std::fs::read_to_string(crate_root.join("Cargo.toml")).unwrap_or_default();
+        if !manifest.contains("proptest") {
+            summary.missing_dependency.push(crate_root.clone());
+        }
+    }
+
+    Ok(summary)
+}
+
+/// The `extract-formulas` helpers of a crate, by name
+///
+/// Empty unless `src/orange_paper_property_helpers.rs` exists and is declared
+/// as a module of the crate root.
+fn read_helpers(crate_root: &Path) -> HashMap<String, syn::Signature> {
+    let declared = ["lib.rs", "main.rs"].iter().any(|root| {
+        std::fs::read_to_string(crate_root.join("src").join(root))
+            .is_ok_and(|source| source.contains(&format!("mod {};", HELPERS_MODULE)))
+    });
+    if !declared {
+        return HashMap::new();
+    }
+    let Ok(source) = std::fs::read_to_string(crate_root.join("src").join(format!("{}.rs", HELPERS_MODULE))) else {
+        return HashMap::new();
+    };
+    let Ok(file) = syn::parse_file(&source) else {
+        return HashMap::new();
+    };
+    file.items.into_iter()
+        .filter_map(|item| match item {
+            syn::Item::Fn(func) if matches!(func.vis, syn::Visibility::Public(_)) => Some((func.sig.ident.to_string(), func.sig)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The function a property test calls
+struct TestTarget<'a> {
+    /// Path from an integration test, e.g. `my_crate::economic::get_block_subsidy`
+    function_path: &'a str,
+    item: &'a syn::ItemFn,
+    section: Option<&'a str>,
+    /// Path and signature of the `extract-formulas` helper for its spec function
+    oracle: Option<(String, &'a syn::Signature)>,
+}
+
+/// How an argument is passed to the function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Passing {
+    Value,
+    Ref,
+    RefMut,
+}
+
+/// Generate the module with the property test of one function
+fn generate_module(
+    module_name: &str,
+    target: &TestTarget,
+    func: &FunctionToVerify,
+    constants: &[WorkspaceConstant],
+) -> Result<String, String> {
+    let sig = &target.item.sig;
+    if sig.asyncness.is_some() || !sig.generics.params.is_empty() {
+        return Err("async and generic functions are not supported".to_string());
+    }
+    let mut requires = Vec::new();
+    let mut ensures = Vec::new();
+    for contract in &func.contracts {
+        let expr = contract.expr.as_ref()
+            .ok_or_else(|| format!("contract `{}` doesn't parse", contract.condition))?;
+        match contract.contract_type {
+            ContractType::Requires => requires.push(expr),
+            ContractType::Ensures => ensures.push((expr, &contract.condition)),
+        }
+    }
+    let bounds = requires_bounds(&requires, constants);
+
+    let mut params = Vec::new();
+    let mut bindings = Vec::new();
+    let mut args = Vec::new();
+    let mut passing = Vec::new();
+    for input in &sig.inputs {
+        let syn::FnArg::Typed(pat_type) = input else {
+            return Err("methods with a receiver are not supported".to_string());
+        };
+        let syn::Pat::Ident(ident) = &*pat_type.pat else {
+            return Err("only identifier parameters are supported".to_string());
+        };
+        let name = ident.ident.to_string();
+        let (pass, ty) = match &*pat_type.ty {
+            syn::Type::Reference(reference) if reference.mutability.is_some() => (Passing::RefMut, &*reference.elem),
+            syn::Type::Reference(reference) => (Passing::Ref, &*reference.elem),
+            ty => (Passing::Value, ty),
+        };
+        let no_bounds = Bounds::default();
+        let value_bounds = bounds.get(&name).unwrap_or(&no_bounds);
+        let len_bounds = bounds.get(&format!("{}.len()", name)).unwrap_or(&no_bounds);
+        let strategy = type_strategy(ty, value_bounds, len_bounds)
+            .map_err(|reason| format!("parameter `{}`: {}", name, reason))?;
+        params.push(format!("{} in {}", name, strategy));
+        match pass {
+            Passing::Value if is_copy(ty) => args.push(name.clone()),
+            Passing::Value => args.push(format!("{}.clone()", name)),
+            Passing::Ref => {
+                bindings.push(format!("let {} = {};", name, borrow(ty, &name, false)));
+                args.push(name.clone());
+            }
+            Passing::RefMut => {
+                bindings.push(format!("let mut {0} = {0};", name));
+                bindings.push(format!("let {} = {};", name, borrow(ty, &name, true)));
+                args.push(name.clone());
+            }
+        }
+        passing.push((name, pass, ty));
+    }
+    if params.is_empty() {
+        return Err("no parameters to generate".to_string());
+    }
+
+    let mut body = bindings;
+    for expr in &requires {
+        let condition = capture_old(substitute_constants(quote!(#expr), constants), &mut Vec::new())
+            .ok_or_else(|| "a #[requires] with forall/exists can't filter inputs".to_string())?;
+        body.push(format!("prop_assume!({});", condition));
+    }
+    let mut old_values = Vec::new();
+    let mut checks = Vec::new();
+    for (expr, text) in &ensures {
+        match capture_old(substitute_constants(quote!(#expr), constants), &mut old_values) {
+            Some(condition) => checks.push(format!("prop_assert!({}, \"{{}}\", {:?});", condition, format!("ensures `{}`", text))),
+            None => checks.push(format!("// Not checked (quantified): #[ensures({})]", text)),
+        }
+    }
+    for (name, value) in &old_values {
+        body.push(format!("let {} = ({}).clone();", name, value));
+    }
+    let call = format!("{}({})", target.function_path, args.join(", "));
+    match sig.output {
+        syn::ReturnType::Default => body.push(format!("{};", call)),
+        syn::ReturnType::Type(..) => body.push(format!("let result = {};", call)),
+    }
+    body.extend(checks);
+    if let Some((helper_path, helper)) = &target.oracle {
+        if let Some(check) = oracle_check(helper_path, helper, &passing, &sig.output) {
+            body.push(check);
+        }
+    }
+
+    let section = target.section.map(|section| format!(" (Orange Paper Section {})", section)).unwrap_or_default();
+    let mut module = format!("/// `{}`{}\n", target.function_path, section);
+    module.push_str(&format!("mod {} {{\n", module_name));
+    module.push_str("    use super::*;\n\n");
+    module.push_str("    proptest! {\n");
+    module.push_str("        #[test]\n");
+    module.push_str(&format!("        fn contracts({}) {{\n", params.join(", ")));
+    for line in body {
+        module.push_str(&format!("            {}\n", line));
+    }
+    module.push_str("        }\n");
+    module.push_str("    }\n");
+    module.push_str("}\n");
+    Ok(module)
+}
+
+/// Inclusive bounds on an integer parameter or a length, from `#[requires]`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Bounds {
+    pub min: Option<i128>,
+    pub max: Option<i128>,
+}
+
+impl Bounds {
+    fn at_least(&mut self, value: i128) {
+        self.min = Some(self.min.map_or(value, |min| min.max(value)));
+    }
+
+    fn at_most(&mut self, value: i128) {
+        self.max = Some(self.max.map_or(value, |max| max.min(value)));
+    }
+}
+
+/// Bounds implied by comparisons of a parameter (`x`, `*x`) or its length
+/// (`v.len()`, `!v.is_empty()`) with a constant, keyed by `x` or `v.len()`
+///
+/// Other conditions don't narrow the strategies; they only filter inputs.
+pub fn requires_bounds(requires: &[&Expr], constants: &[WorkspaceConstant]) -> HashMap<String, Bounds> {
+    fn conjuncts<'e>(expr: &'e Expr, out: &mut Vec<&'e Expr>) {
+        match expr {
+            Expr::Binary(bin) if matches!(bin.op, BinOp::And(_)) => {
+                conjuncts(&bin.left, out);
+                conjuncts(&bin.right, out);
+            }
+            Expr::Paren(paren) => conjuncts(&paren.expr, out),
+            _ => out.push(expr),
+        }
+    }
+
+    let mut bounds: HashMap<String, Bounds> = HashMap::new();
+    let mut parts = Vec::new();
+    for expr in requires {
+        conjuncts(expr, &mut parts);
+    }
+    for part in parts {
+        match part {
+            Expr::Unary(unary) if matches!(unary.op, UnOp::Not(_)) => {
+                if let Expr::MethodCall(call) = &*unary.expr {
+                    if let (true, Some(name)) = (call.method == "is_empty" && call.args.is_empty(), bound_term(&call.receiver)) {
+                        bounds.entry(format!("{}.len()", name)).or_default().at_least(1);
+                    }
+                }
+            }
+            Expr::Binary(bin) => {
+                let (term, value, term_on_left) = match (bound_term(&bin.left), bound_term(&bin.right)) {
+                    (Some(term), _) => match evaluate_with_constants(&bin.right, constants) {
+                        Some(value) => (term, value, true),
+                        None => continue,
+                    },
+                    (None, Some(term)) => match evaluate_with_constants(&bin.left, constants) {
+                        Some(value) => (term, value, false),
+                        None => continue,
+                    },
+                    (None, None) => continue,
+                };
+                let entry = bounds.entry(term).or_default();
+                // With the term on the right, `c < x` bounds it like `x > c`
+                match (&bin.op, term_on_left) {
+                    (BinOp::Lt(_), true) | (BinOp::Gt(_), false) => entry.at_most(value - 1),
+                    (BinOp::Le(_), true) | (BinOp::Ge(_), false) => entry.at_most(value),
+                    (BinOp::Gt(_), true) | (BinOp::Lt(_), false) => entry.at_least(value + 1),
+                    (BinOp::Ge(_), true) | (BinOp::Le(_), false) => entry.at_least(value),
+                    (BinOp::Eq(_), _) => {
+                        entry.at_least(value);
+                        entry.at_most(value);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    bounds
+}
+
+/// The key of a bounded term: `x` for `x` or `*x`, `v.len()` for `v.len()`
+fn bound_term(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Path(path) => path.path.get_ident().map(|ident| ident.to_string()),
+        Expr::Unary(unary) if matches!(unary.op, UnOp::Deref(_)) => match &*unary.expr {
+            Expr::Path(_) => bound_term(&unary.expr),
+            _ => None,
+        },
+        Expr::Paren(paren) => bound_term(&paren.expr),
+        Expr::MethodCall(call) if call.method == "len" && call.args.is_empty() => {
+            let name = bound_term(&call.receiver)?;
+            (!name.ends_with(')')).then(|| format!("{}.len()", name))
+        }
+        _ => None,
+    }
+}
+
+/// A proptest strategy for values of `ty`, within `value` bounds (integers) or `len` bounds (collections)
+fn type_strategy(ty: &syn::Type, value: &Bounds, len: &Bounds) -> Result<String, String> {
+    let unsupported = || Err(format!("no strategy for `{}`", quote!(#ty)));
+    match ty {
+        syn::Type::Paren(paren) => type_strategy(&paren.elem, value, len),
+        syn::Type::Slice(slice) => collection_strategy(&slice.elem, len),
+        syn::Type::Path(path) if path.qself.is_none() => {
+            let Some(segment) = path.path.segments.last() else {
+                return unsupported();
+            };
+            let name = segment.ident.to_string();
+            if let (Some(ident), Some((type_min, type_max))) = (path.path.get_ident(), int_range(&name)) {
+                let min = value.min.map_or(type_min, |min| min.max(type_min));
+                let max = value.max.map_or(type_max, |max| max.min(type_max));
+                if min > max {
+                    return Err("its #[requires] bounds are unsatisfiable".to_string());
+                }
+                if (min, max) == (type_min, type_max) {
+                    return Ok(format!("any::<{}>()", ident));
+                }
+                return Ok(format!("{}{}..={}{}", min, ident, max, ident));
+            }
+            let argument = match &segment.arguments {
+                syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+                    Some(syn::GenericArgument::Type(ty)) if args.args.len() == 1 => Some(ty),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match (name.as_str(), argument) {
+                ("bool", None) => Ok("any::<bool>()".to_string()),
+                ("String" | "str", None) => Ok("any::<String>()".to_string()),
+                ("Vec", Some(elem)) => collection_strategy(elem, len),
+                ("Option", Some(inner)) => Ok(format!("proptest::option::of({})", type_strategy(inner, &Bounds::default(), &Bounds::default())?)),
+                _ => unsupported(),
+            }
+        }
+        _ => unsupported(),
+    }
+}
+
+/// A strategy for a `Vec` of `elem` with a length within `len`
+///
+/// The length is capped a few elements above its minimum to keep tests fast.
+fn collection_strategy(elem: &syn::Type, len: &Bounds) -> Result<String, String> {
+    let min = len.min.unwrap_or(0).max(0);
+    let max = len.max.unwrap_or(i128::MAX).min(min + MAX_EXTRA_ELEMENTS);
+    if min > max {
+        return Err("its #[requires] length bounds are unsatisfiable".to_string());
+    }
+    let elem = type_strategy(elem, &Bounds::default(), &Bounds::default())?;
+    Ok(format!("proptest::collection::vec({}, {}..={})", elem, min, max))
+}
+
+/// Whether values of a type (as generated by [`type_strategy`]) are `Copy`
+pub fn is_copy(ty: &syn::Type) -> bool {
+    let syn::Type::Path(path) = ty else {
+        return false;
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return false;
+    };
+    let name = segment.ident.to_string();
+    match &segment.arguments {
+        syn::PathArguments::None => name == "bool" || int_range(&name).is_some(),
+        syn::PathArguments::AngleBracketed(args) if name == "Option" => {
+            matches!(args.args.first(), Some(syn::GenericArgument::Type(inner)) if is_copy(inner))
+        }
+        _ => false,
+    }
+}
+
+/// Borrow a generated value as the parameter's reference type
+pub fn borrow(ty: &syn::Type, name: &str, mutable: bool) -> String {
+    let is_str = matches!(ty, syn::Type::Path(path) if path.path.is_ident("str"));
+    match (ty, mutable) {
+        (_, false) if is_str => format!("{}.as_str()", name),
+        (syn::Type::Slice(_), false) => format!("{}.as_slice()", name),
+        (syn::Type::Slice(_), true) => format!("{}.as_mut_slice()", name),
+        (_, false) => format!("&{}", name),
+        (_, true) => format!("&mut {}", name),
+    }
+}
+
+/// Whether `tokens` mention the identifier `name`
+pub fn mentions(tokens: TokenStream, name: &str) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == name,
+        TokenTree::Group(group) => mentions(group.stream(), name),
+        _ => false,
+    })
+}
+
+/// Replace workspace constants in a contract with their values
+///
+/// The tests live outside the crate (or, for Kani harnesses, outside the
+/// constants' modules), where the constants may not be in scope.
+pub fn substitute_constants(tokens: TokenStream, constants: &[WorkspaceConstant]) -> TokenStream {
+    let mut substituted = Vec::new();
+    let mut after_dot = false;
+    for token in tokens {
+        let is_dot = matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '.');
+        match token {
+            TokenTree::Ident(ident) if !after_dot => match constants.iter().find(|constant| ident == constant.name) {
+                Some(constant) => {
+                    let value: TokenStream = format!("({}{})", constant.value, constant.type_name).parse()
+                        .expect("an integer literal is a valid token stream");
+                    substituted.extend(value);
+                }
+                None => substituted.push(TokenTree::Ident(ident)),
+            },
+            TokenTree::Group(group) => {
+                let mut inner = proc_macro2::Group::new(group.delimiter(), substitute_constants(group.stream(), constants));
+                inner.set_span(group.span());
+                substituted.push(TokenTree::Group(inner));
+            }
+            other => substituted.push(other),
+        }
+        after_dot = is_dot;
+    }
+    substituted.into_iter().collect()
+}
+
+/// An assertion that the result equals the spec function's formula, if the helper fits
+///
+/// The helper must take an integer for each (integer, not `&mut`) parameter,
+/// and return an integer for an integer result (or `Ok`/`Some` payload) or a
+/// `bool` for a `bool` result.
+fn oracle_check(
+    helper_path: &str,
+    helper: &syn::Signature,
+    params: &[(String, Passing, &syn::Type)],
+    output: &syn::ReturnType,
+) -> Option<String> {
+    if helper.inputs.len() != params.len() {
+        return None;
+    }
+    let mut args = Vec::new();
+    for (input, (name, pass, ty)) in helper.inputs.iter().zip(params) {
+        let syn::FnArg::Typed(helper_param) = input else {
+            return None;
+        };
+        let helper_type = primitive_name(&helper_param.ty)?;
+        let param_type = primitive_name(ty)?;
+        int_range(&helper_type)?;
+        int_range(&param_type)?;
+        let value = match pass {
+            Passing::Value => name.clone(),
+            Passing::Ref => format!("*{}", name),
+            Passing::RefMut => return None,
+        };
+        if param_type == helper_type {
+            args.push(value);
+        } else {
+            args.push(format!("{} as {}", value, helper_type));
+        }
+    }
+    let expected = format!("{}({})", helper_path, args.join(", "));
+    let syn::ReturnType::Type(_, helper_output) = &helper.output else {
+        return None;
+    };
+    let syn::ReturnType::Type(_, output) = output else {
+        return None;
+    };
+    let helper_output = primitive_name(helper_output)?;
+    let message = "\"{}\", \"result matches the Orange Paper formula\"";
+    if helper_output == "bool" {
+        return (primitive_name(output)? == "bool")
+            .then(|| format!("prop_assert_eq!(result, {}, {});", expected, message));
+    }
+    int_range(&helper_output)?;
+    if let Some(name) = primitive_name(output) {
+        int_range(&name)?;
+        return Some(format!("prop_assert_eq!(result as i128, {} as i128, {});", expected, message));
+    }
+    // The formula gives the payload of a successful `Result`/`Option`
+    let syn::Type::Path(path) = &**output else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    let pattern = match segment.ident.to_string().as_str() {
+        "Result" => "Ok",
+        "Option" => "Some",
+        _ => return None,
+    };
+    let syn::PathArguments::AngleBracketed(generic_args) = &segment.arguments else {
+        return None;
+    };
+    let Some(syn::GenericArgument::Type(payload)) = generic_args.args.first() else {
+        return None;
+    };
+    int_range(&primitive_name(payload)?)?;
+    Some(format!(
+        "if let {}(value) = &result {{ prop_assert_eq!(*value as i128, {} as i128, {}); }}",
+        pattern, expected, message
+    ))
+}
+
+/// The name of a primitive type written as a single identifier (`u64`, `bool`)
+fn primitive_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(path) if path.qself.is_none() => path.path.get_ident().map(|ident| ident.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::verify::Contract;
+    use syn::parse_quote;
+
+    fn contract(contract_type: ContractType, expr: Expr) -> Contract {
+        Contract { contract_type, condition: quote!(#expr).to_string(), expr: Some(expr) }
+    }
+
+    #[test]
+    fn test_generate_module() {
+        let item: syn::ItemFn = parse_quote! {
+            pub fn add_fee(total: u64, outputs: &[u64], fee: &mut u64) -> Result<u64, String> { Ok(total) }
+        };
+        let func = FunctionToVerify {
+            file_path: PathBuf::from("/ws/consensus/src/economic.rs"),
+            function_name: "add_fee".to_string(),
+            line: 1,
+            contracts: vec![
+                contract(ContractType::Requires, parse_quote!(total <= MAX_MONEY && 1 <= total)),
+                contract(ContractType::Requires, parse_quote!(!outputs.is_empty() && outputs.len() < 100)),
+                contract(ContractType::Requires, parse_quote!(*fee < total)),
+                contract(ContractType::Ensures, parse_quote!(*fee >= old(*fee))),
+                contract(ContractType::Ensures, parse_quote!(forall(|i: usize| i < outputs.len()))),
+            ],
+            section: Some("6.2".to_string()),
+            spec_function: None,
+            unwind: None,
+            function_sig: Some(item.clone()),
+        };
+        let constants = vec![WorkspaceConstant { name: "MAX_MONEY".to_string(), type_name: "u64".to_string(), value: 2_100_000_000_000_000 }];
+        let target = TestTarget { function_path: "consensus::economic::add_fee", item: &item, section: Some("6.2"), oracle: None };
+        let module = generate_module("add_fee", &target, &func, &constants).unwrap();
+
+        // Simple bounds narrow the strategies; every requires filters the inputs
+        assert!(module.contains("fn contracts(total in 1u64..=2100000000000000u64, outputs in proptest::collection::vec(any::<u64>(), 1..=17), fee in any::<u64>())"), "{}", module);
+        assert!(module.contains("prop_assume!(total <= (2100000000000000u64) && 1 <= total);"), "{}", module);
+        assert!(module.contains("let outputs = outputs.as_slice();"), "{}", module);
+        assert!(module.contains("let mut fee = fee;\n            let fee = &mut fee;"), "{}", module);
+        assert!(module.contains("let __spec_lock_old_0 = (* fee).clone();"), "{}", module);
+        assert!(module.contains("let result = consensus::economic::add_fee(total, outputs, fee);"), "{}", module);
+        assert!(module.contains("prop_assert!(* fee >= __spec_lock_old_0, \"{}\", \"ensures `* fee >= old (* fee)`\");"), "{}", module);
+        assert!(module.contains("// Not checked (quantified)"), "{}", module);
+
+        // The Orange Paper helper checks the `Ok` payload
+        let helper: syn::ItemFn = parse_quote! { pub fn expected_addfee_from_orange_paper(total: u64, outputs: u64) -> i64 { 0 } };
+        let sig = &helper.sig;
+        let params = [("total".to_string(), Passing::Value, &parse_quote!(u64)), ("height".to_string(), Passing::Ref, &parse_quote!(u32))];
+        let output: syn::ReturnType = parse_quote!(-> Result<u64, String>);
+        let check = oracle_check("c::h::expected", sig, &params, &output).unwrap();
+        assert_eq!(check, "if let Ok(value) = &result { prop_assert_eq!(*value as i128, c::h::expected(total, *height as u64) as i128, \"{}\", \"result matches the Orange Paper formula\"); }");
+        assert!(oracle_check("c::h::expected", sig, &params[..1], &output).is_none());
+
+        // Types without a strategy are reported
+        let item: syn::ItemFn = parse_quote! { pub fn check(tx: &Transaction) -> bool { true } };
+        let target = TestTarget { function_path: "c::check", item: &item, section: None, oracle: None };
+        assert!(generate_module("check", &target, &func, &[]).unwrap_err().contains("no strategy for `Transaction`"));
+    }
+}
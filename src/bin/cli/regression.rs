@@ -0,0 +1,409 @@
+//! Regression test generation from counterexamples
+//!
+//! Turns each failed `#[ensures]` counterexample into a `#[test]` that calls
+//! the real function with the model's inputs and asserts the postcondition.
+//! Verification reports only a function's first failing contract, so a
+//! function gets at most one test per run.
+
+use crate::cli::proptests::{is_copy, mentions};
+use crate::cli::verify::{ContractType, FunctionToVerify, VerificationResult};
+use crate::parser::contracts::capture_old;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Name of the generated test file in each crate
+const REGRESSION_FILE: &str = "spec_lock_regressions.rs";
+
+/// Summary of generated regression tests
+#[derive(Debug, Clone, Default)]
+pub struct RegressionSummary {
+    /// Files written, with the number of tests in each
+    pub files: Vec<(PathBuf, usize)>,
+    /// Functions whose counterexample couldn't be turned into a test, with the reason
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Write regression tests for every failed postcondition with a counterexample
+///
+/// Tests are grouped per crate into `<crate>/<dir>/spec_lock_regressions.rs`
+/// (or `<dir>/spec_lock_regressions.rs` if `dir` is absolute). Tests already
+/// in the file are kept; one with the same name as a new test is replaced.
+pub fn emit_regression_tests(
+    results: &[(FunctionToVerify, VerificationResult)],
+    dir: &Path,
+) -> Result<RegressionSummary, String> {
+    let mut summary = RegressionSummary::default();
+    let mut tests_by_file: BTreeMap<PathBuf, Vec<(String, String)>> = BTreeMap::new();
+    let mut used_names: HashMap<PathBuf, HashMap<String, usize>> = HashMap::new();
+
+    for (func, result) in results {
+        let VerificationResult::Failed { counterexample: Some(counterexample), condition: Some(condition), .. } = result else {
+            continue;
+        };
+        let Some(contract) = func.contracts.iter().find(|c| {
+            c.contract_type == ContractType::Ensures && &c.condition == condition
+        }) else {
+            continue;
+        };
+        let label = format!("{}::{}", func.file_path.display(), func.function_name);
+        let (Some(expr), Some(item)) = (&contract.expr, &func.function_sig) else {
+            summary.skipped.push((label, "missing parsed function or contract".to_string()));
+            continue;
+        };
+        let Some(crate_root) = find_crate_root(&func.file_path) else {
+            summary.skipped.push((label, "no Cargo.toml found".to_string()));
+            continue;
+        };
+        let crate_name = read_crate_name(&crate_root)?;
+        let function_path = match module_path(&crate_root, &func.file_path) {
+            Some(mut path) => {
+                path.insert(0, crate_name);
+                path.push(func.function_name.clone());
+                path.join("::")
+            }
+            None => {
+                summary.skipped.push((label, "function is not under the crate's src/".to_string()));
+                continue;
+            }
+        };
+
+        let output_file = crate_root.join(dir).join(REGRESSION_FILE);
+        let names = used_names.entry(output_file.clone()).or_default();
+        let count = names.entry(func.function_name.clone()).or_insert(0);
+        let test_name = if *count == 0 {
+            format!("{}_regression", func.function_name)
+        } else {
+            format!("{}_regression_{}", func.function_name, count)
+        };
+        *count += 1;
+
+        match generate_test(&test_name, &function_path, item, expr, counterexample) {
+            Ok(test) => tests_by_file.entry(output_file).or_default().push((test_name, test)),
+            Err(reason) => summary.skipped.push((label, reason)),
+        }
+    }
+
+    for (path, tests) in tests_by_file {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let existing = match std::fs::read_to_string(&path) {
+            Ok(existing) => existing,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        std::fs::write(&path, merge_tests(&existing, &tests))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        summary.files.push((path, tests.len()));
+    }
+
+    Ok(summary)
+}
+
+/// The regression file with `tests` merged into its `existing` contents
+///
+/// Existing items keep their order, a test named like a new one is replaced
+/// in place, and the other new tests are appended.
+fn merge_tests(existing: &str, tests: &[(String, String)]) -> String {
+    let mut items = split_items(existing);
+    for (name, test) in tests {
+        match items.iter_mut().find(|(item_name, _)| item_name.as_deref() == Some(name.as_str())) {
+            Some(item) => item.1 = test.clone(),
+            None => items.push((Some(name.clone()), test.clone())),
+        }
+    }
+
+    let mut code = String::from("//! Regression tests generated from spec-lock counterexamples\n");
+    code.push_str("//!\n");
+    code.push_str("//! This file is AUTO-GENERATED by `cargo spec-lock verify --emit-regression-tests`\n");
+    code.push_str("//! Each test reproduces a postcondition violation found by Z3.\n");
+    for (_, item) in &items {
+        code.push('\n');
+        code.push_str(item);
+    }
+    code
+}
+
+/// Split a regression file into its top-level items, named after their `fn`
+///
+/// The `//!` header is dropped. An item ends at an unindented line closing a
+/// block or a statement (`}` or `;`); items that aren't functions have no name.
+fn split_items(code: &str) -> Vec<(Option<String>, String)> {
+    let item_name = |item: &str| item.lines().find_map(|line| {
+        let rest = line.trim_start().trim_start_matches("pub ").strip_prefix("fn ")?;
+        rest.split(['(', '<']).next().map(|name| name.trim().to_string())
+    });
+    let mut items = Vec::new();
+    let mut current = String::new();
+    for line in code.lines() {
+        if line.starts_with("//!") || (current.is_empty() && line.trim().is_empty()) {
+            continue;
+        }
+        current.push_str(line);
+        current.push('\n');
+        let top_level = !line.starts_with(char::is_whitespace) && !line.starts_with("//");
+        if top_level && (line.ends_with('}') || line.ends_with(';')) {
+            items.push((item_name(&current), std::mem::take(&mut current)));
+        }
+    }
+    if !current.trim().is_empty() {
+        items.push((item_name(&current), current));
+    }
+    items
+}
+
+/// Generate one `#[test]` calling `function_path` with the counterexample's inputs
+fn generate_test(
+    test_name: &str,
+    function_path: &str,
+    item: &syn::ItemFn,
+    postcondition: &syn::Expr,
+    counterexample: &[(String, String)],
+) -> Result<String, String> {
+    let values: HashMap<&str, &str> = counterexample.iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+
+    let mut bindings = String::new();
+    let mut args = Vec::new();
+    for input in &item.sig.inputs {
+        let syn::FnArg::Typed(pat_type) = input else {
+            return Err("methods with a receiver are not supported".to_string());
+        };
+        let syn::Pat::Ident(ident) = &*pat_type.pat else {
+            return Err("only identifier parameters are supported".to_string());
+        };
+        let name = ident.ident.to_string();
+        let ty = &pat_type.ty;
+        // A `&mut` parameter's input is its pre-state, `old(x)` in the model
+        let is_mut = matches!(&**ty, syn::Type::Reference(reference) if reference.mutability.is_some());
+        let key = if is_mut { format!("old({})", name) } else { name.clone() };
+        let value = values.get(key.as_str())
+            .ok_or_else(|| format!("no counterexample value for parameter `{}`", name))?;
+        match &**ty {
+            syn::Type::Reference(reference) if is_mut => match &*reference.elem {
+                // A `&mut [T]` borrows a `Vec<T>` owned by the test
+                syn::Type::Slice(slice) => {
+                    let elem = &slice.elem;
+                    bindings.push_str(&format!("    let mut {}: Vec<{}> = vec!{};\n", name, quote::quote!(#elem), literal(&reference.elem, value, &name)?));
+                    bindings.push_str(&format!("    let {} = {}.as_mut_slice();\n", name, name));
+                }
+                elem => {
+                    bindings.push_str(&format!("    let mut {}: {} = {};\n", name, quote::quote!(#elem), literal(elem, value, &name)?));
+                    bindings.push_str(&format!("    let {} = &mut {};\n", name, name));
+                }
+            },
+            syn::Type::Reference(reference) => {
+                let value = format!("&{}", literal(&reference.elem, value, &name)?);
+                bindings.push_str(&format!("    let {}: {} = {};\n", name, quote::quote!(#ty), value));
+            }
+            ty => bindings.push_str(&format!("    let {}: {} = {};\n", name, quote::quote!(#ty), literal(ty, value, &name)?)),
+        }
+        // The postcondition may still read a parameter that the call consumes
+        if !matches!(**ty, syn::Type::Reference(_)) && !is_copy(ty) && mentions(quote::quote!(#postcondition), &name) {
+            args.push(format!("{}.clone()", name));
+        } else {
+            args.push(name);
+        }
+    }
+
+    let condition = quote::quote!(#postcondition).to_string();
+    // `old(e)` is captured into a local before the call; quantifiers have no run-time form
+    let mut old_values = Vec::new();
+    let check = capture_old(quote::quote!(#postcondition), &mut old_values)
+        .ok_or_else(|| "quantified (forall/exists) postconditions can't be checked in a test".to_string())?;
+    for (name, value) in &old_values {
+        bindings.push_str(&format!("    let {} = ({}).clone();\n", name, value));
+    }
+
+    let call = format!("{}({})", function_path, args.join(", "));
+    let mut test = format!("/// Counterexample for `#[ensures({})]`\n", condition);
+    test.push_str("#[test]\n");
+    test.push_str(&format!("fn {}() {{\n", test_name));
+    test.push_str(&bindings);
+    match &item.sig.output {
+        syn::ReturnType::Default => test.push_str(&format!("    {};\n", call)),
+        syn::ReturnType::Type(..) => test.push_str(&format!("    let result = {};\n", call)),
+    }
+    test.push_str(&format!("    assert!({}, \"postcondition violated: {{}}\", {:?});\n", check, condition));
+    test.push_str("}\n");
+    Ok(test)
+}
+
+/// A model value as an expression of type `ty`
+///
+/// Sequences are rendered as array literals, which become `vec![..]` for a
+/// `Vec`. Sequences too long to have been rendered in full can't be reproduced.
+fn literal(ty: &syn::Type, value: &str, name: &str) -> Result<String, String> {
+    if value.contains(", ..]") {
+        return Err(format!("the counterexample value of `{}` is too long to reproduce", name));
+    }
+    let is_vec = matches!(ty, syn::Type::Path(path) if path.path.segments.last().is_some_and(|segment| segment.ident == "Vec"));
+    if is_vec && value.starts_with('[') {
+        return Ok(format!("vec!{}", value));
+    }
+    Ok(value.to_string())
+}
+
+/// Find the nearest directory containing a Cargo.toml
+pub fn find_crate_root(file_path: &Path) -> Option<PathBuf> {
+    file_path.ancestors()
+        .skip(1)
+        .find(|dir| dir.join("Cargo.toml").is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Read the package name from a crate's Cargo.toml, as a Rust identifier
+pub fn read_crate_name(crate_root: &Path) -> Result<String, String> {
+    let manifest_path = crate_root.join("Cargo.toml");
+    let manifest = std::fs::read_to_string(&manifest_path)
+        .map_err(|e| format!("Failed to read {}: {}", manifest_path.display(), e))?;
+
+    let mut in_package = false;
+    for line in manifest.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+        } else if in_package {
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim() == "name" {
+                    return Ok(value.trim().trim_matches('"').replace('-', "_"));
+                }
+            }
+        }
+    }
+    Err(format!("No package name in {}", manifest_path.display()))
+}
+
+/// Module path of a source file relative to the crate's `src/` directory
+///
+/// `src/lib.rs` is the crate root, `src/a/b.rs` and `src/a/b/mod.rs` are `a::b`.
+pub fn module_path(crate_root: &Path, file_path: &Path) -> Option<Vec<String>> {
+    let relative = file_path.strip_prefix(crate_root.join("src")).ok()?;
+    let mut segments: Vec<String> = relative.with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    if segments.len() == 1 && (segments[0] == "lib" || segments[0] == "main") {
+        segments.clear();
+    }
+    if segments.last().is_some_and(|last| last == "mod") {
+        segments.pop();
+    }
+    Some(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_generate_test() {
+        let item: syn::ItemFn = parse_quote! {
+            pub fn scale(a: u64, b: &i32) -> u64 { a }
+        };
+        let postcondition: syn::Expr = parse_quote! { result > a };
+        let counterexample = vec![
+            ("a".to_string(), "0u64".to_string()),
+            ("b".to_string(), "-1i32".to_string()),
+            ("result".to_string(), "0u64".to_string()),
+        ];
+
+        let test = generate_test("scale_regression", "my_crate::math::scale", &item, &postcondition, &counterexample).unwrap();
+        assert!(test.contains("fn scale_regression() {"));
+        assert!(test.contains("    let a: u64 = 0u64;\n"));
+        assert!(test.contains("    let b: & i32 = &-1i32;\n"));
+        assert!(test.contains("    let result = my_crate::math::scale(a, b);\n"));
+        assert!(test.contains("    assert!(result > a, \"postcondition violated: {}\", \"result > a\");\n"));
+
+        // Sequences become `vec![..]` for a `Vec`, which is cloned if the postcondition reads it
+        let item: syn::ItemFn = parse_quote! {
+            pub fn total(values: Vec<u64>, outputs: &[u64]) -> u64 { 0 }
+        };
+        let postcondition: syn::Expr = parse_quote! { result >= values.len() as u64 };
+        let counterexample = vec![
+            ("values".to_string(), "[2u64]".to_string()),
+            ("outputs".to_string(), "[]".to_string()),
+        ];
+        let test = generate_test("total_regression", "c::total", &item, &postcondition, &counterexample).unwrap();
+        assert!(test.contains("    let values: Vec < u64 > = vec![2u64];\n"), "{}", test);
+        assert!(test.contains("    let outputs: & [u64] = &[];\n"), "{}", test);
+        assert!(test.contains("    let result = c::total(values.clone(), outputs);\n"), "{}", test);
+
+        // Truncated sequences can't be reproduced
+        let counterexample = vec![
+            ("values".to_string(), "[0u64, 1u64, ..] (len 40)".to_string()),
+            ("outputs".to_string(), "[]".to_string()),
+        ];
+        let error = generate_test("total_regression", "c::total", &item, &postcondition, &counterexample).unwrap_err();
+        assert!(error.contains("too long"), "{}", error);
+
+        // `&mut` parameters start from their pre-state and `old(..)` is captured before the call
+        let item: syn::ItemFn = parse_quote! {
+            pub fn bump(x: &mut u64) { *x += 1; }
+        };
+        let postcondition: syn::Expr = parse_quote! { *x == old(*x) + 2 };
+        let counterexample = vec![
+            ("old(x)".to_string(), "3u64".to_string()),
+            ("x".to_string(), "4u64".to_string()),
+        ];
+        let test = generate_test("bump_regression", "c::bump", &item, &postcondition, &counterexample).unwrap();
+        assert!(test.contains("    let mut x: u64 = 3u64;\n    let x = &mut x;\n"), "{}", test);
+        assert!(test.contains("    let __spec_lock_old_0 = (* x).clone();\n    c::bump(x);\n"), "{}", test);
+        assert!(test.contains("    assert!(* x == __spec_lock_old_0 + 2,"), "{}", test);
+
+        // Quantified postconditions are skipped
+        let postcondition: syn::Expr = parse_quote! { forall(|i: usize| i >= 3 || *x > 0) };
+        let error = generate_test("bump_regression", "c::bump", &item, &postcondition, &counterexample).unwrap_err();
+        assert!(error.contains("quantified"), "{}", error);
+
+        // Parameters without a model value can't be reproduced
+        let item: syn::ItemFn = parse_quote! {
+            pub fn check(tx: &Transaction) -> bool { true }
+        };
+        assert!(generate_test("check_regression", "c::check", &item, &postcondition, &[]).is_err());
+    }
+
+    #[test]
+    fn test_merge_tests() {
+        let first = merge_tests("", &[
+            ("scale_regression".to_string(), "#[test]\nfn scale_regression() {\n    assert!(false);\n}\n".to_string()),
+            ("total_regression".to_string(), "#[test]\nfn total_regression() {\n}\n".to_string()),
+        ]);
+        assert!(first.starts_with("//! Regression tests generated"));
+
+        // Hand-written items are kept, and a test with a known name is replaced in place
+        let edited = first.replace("//! Each test", "use std::fmt;\n\n//! Each test");
+        let merged = merge_tests(&edited, &[
+            ("scale_regression".to_string(), "#[test]\nfn scale_regression() {\n    assert!(true);\n}\n".to_string()),
+            ("bump_regression".to_string(), "#[test]\nfn bump_regression() {\n}\n".to_string()),
+        ]);
+        let names: Vec<_> = split_items(&merged).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec![
+            None,
+            Some("scale_regression".to_string()),
+            Some("total_regression".to_string()),
+            Some("bump_regression".to_string()),
+        ]);
+        assert!(merged.contains("assert!(true)") && !merged.contains("assert!(false)"), "{}", merged);
+        assert!(merged.contains("use std::fmt;\n"), "{}", merged);
+        assert_eq!(merge_tests(&merged, &[]), merged);
+    }
+
+    #[test]
+    fn test_module_path() {
+        let root = Path::new("/ws/consensus");
+        assert_eq!(module_path(root, Path::new("/ws/consensus/src/lib.rs")), Some(vec![]));
+        assert_eq!(
+            module_path(root, Path::new("/ws/consensus/src/economic/mod.rs")),
+            Some(vec!["economic".to_string()])
+        );
+        assert_eq!(
+            module_path(root, Path::new("/ws/consensus/src/script/eval.rs")),
+            Some(vec!["script".to_string(), "eval".to_string()])
+        );
+        assert_eq!(module_path(root, Path::new("/ws/other/src/lib.rs")), None);
+    }
+}
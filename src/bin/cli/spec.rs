@@ -0,0 +1,24 @@
+//! Orange Paper loading for CLI commands
+
+use crate::parser::orange_paper::SpecParser;
+use std::path::{Path, PathBuf};
+
+/// Default Orange Paper location relative to the workspace root
+pub fn default_spec_path(workspace_root: &Path) -> PathBuf {
+    workspace_root.join("../blvm-spec/THE_ORANGE_PAPER.md")
+}
+
+/// Read and parse the Orange Paper (default: ../blvm-spec/THE_ORANGE_PAPER.md)
+pub fn load_spec(workspace_root: &Path, spec_path: Option<&PathBuf>) -> Result<SpecParser, String> {
+    let spec_path = spec_path
+        .cloned()
+        .unwrap_or_else(|| default_spec_path(workspace_root));
+    
+    let content = std::fs::read_to_string(&spec_path)
+        .map_err(|e| format!("Failed to read Orange Paper from {}: {}", spec_path.display(), e))?;
+    
+    let mut parser = SpecParser::new(content);
+    parser.parse()
+        .map_err(|e| format!("Failed to parse Orange Paper: {}", e))?;
+    Ok(parser)
+}
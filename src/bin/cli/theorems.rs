@@ -0,0 +1,121 @@
+//! Theorem verification
+//!
+//! Proves the Orange Paper theorems attached to each spec-locked function's
+//! spec function directly against its implementation.
+
+use crate::cli::verify::{Counterexample, FunctionToVerify, VerifyOptions};
+use crate::parser::math::{self, MathExpr};
+use crate::parser::orange_paper::{SpecParser, Theorem};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Outcome of checking one theorem against one implementation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TheoremResult {
+    /// Rust function the theorem was checked against
+    pub function_name: String,
+    pub file_path: PathBuf,
+    /// Theorem number (e.g., "6.1.1")
+    pub number: String,
+    pub name: String,
+    pub status: TheoremStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TheoremStatus {
+    Proven,
+    Failed {
+        counterexample: Option<Counterexample>,
+    },
+    /// Z3 neither proved nor refuted the theorem (e.g. timeout)
+    Unknown {
+        reason: String,
+    },
+    /// The theorem couldn't be checked (not a formula, unsupported construct, ...)
+    Skipped {
+        reason: String,
+    },
+}
+
+impl TheoremResult {
+    pub fn is_failure(&self) -> bool {
+        matches!(self.status, TheoremStatus::Failed { .. })
+    }
+}
+
+/// Check every theorem of each function's spec function against its body
+///
+/// Functions without a parsed body, or whose spec function doesn't resolve,
+/// have no theorems to check.
+pub fn verify_theorems(functions: &[FunctionToVerify], spec: &SpecParser, options: &VerifyOptions) -> Vec<TheoremResult> {
+    let mut results = Vec::new();
+    for func in functions {
+        let Some(item_fn) = func.function_sig.as_ref() else {
+            continue;
+        };
+        let lookup_name = func.spec_function.as_deref().unwrap_or(&func.function_name);
+        let Some((spec_func, _)) = spec.resolve_function(func.section.as_deref(), lookup_name) else {
+            continue;
+        };
+        for theorem in &spec_func.theorems {
+            let status = match math::parse_math(math::math_content(&theorem.statement)) {
+                Ok(statement) if statement.find_call(&spec_func.name).is_none() => TheoremStatus::Skipped {
+                    reason: format!("Statement doesn't apply {}", spec_func.name),
+                },
+                Ok(statement) => check_theorem(&statement, &spec_func.name, func, item_fn, options),
+                Err(e) => TheoremStatus::Skipped {
+                    reason: format!("Statement is not a formula: {}", e),
+                },
+            };
+            results.push(theorem_result(func, theorem, status));
+        }
+    }
+    results
+}
+
+fn theorem_result(func: &FunctionToVerify, theorem: &Theorem, status: TheoremStatus) -> TheoremResult {
+    TheoremResult {
+        function_name: func.function_name.clone(),
+        file_path: func.file_path.clone(),
+        number: theorem.number.clone(),
+        name: theorem.name.clone(),
+        status,
+    }
+}
+
+#[cfg(feature = "z3")]
+fn check_theorem(
+    statement: &MathExpr,
+    spec_function: &str,
+    func: &FunctionToVerify,
+    item_fn: &syn::ItemFn,
+    options: &VerifyOptions,
+) -> TheoremStatus {
+    use crate::cli::verify::{library_requires, z3_verifier};
+    use crate::translator::z3_verifier::VerificationResult;
+
+    let mut verifier = z3_verifier(options, Some(item_fn));
+    let requires_library = library_requires(func);
+
+    match verifier.verify_theorem(statement, spec_function, item_fn, &requires_library) {
+        VerificationResult::Verified => TheoremStatus::Proven,
+        VerificationResult::Failed { counterexample } => TheoremStatus::Failed {
+            counterexample: counterexample.map(|ce| ce.assignments),
+        },
+        VerificationResult::Unknown { reason } => TheoremStatus::Unknown { reason },
+        VerificationResult::Error { error } => TheoremStatus::Skipped { reason: error },
+    }
+}
+
+#[cfg(not(feature = "z3"))]
+fn check_theorem(
+    _statement: &MathExpr,
+    _spec_function: &str,
+    _func: &FunctionToVerify,
+    _item_fn: &syn::ItemFn,
+    _options: &VerifyOptions,
+) -> TheoremStatus {
+    TheoremStatus::Skipped {
+        reason: "Z3 feature not enabled".to_string(),
+    }
+}
@@ -2,10 +2,17 @@
 //!
 //! Discovers functions, extracts contracts, and runs verification
 
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
 use walkdir::WalkDir;
 use syn::{File, ItemFn, Attribute};
 use quote::quote;
+use serde::{Deserialize, Serialize};
+use crate::cli::constants::WorkspaceConstant;
+use crate::parser::contracts::{is_contract_attribute, rewrite_implications};
+use crate::translator::static_checker::mentions_old;
 
 /// Simplified contract structure for CLI
 #[derive(Debug, Clone)]
@@ -26,25 +33,18 @@ fn extract_contracts(func: &ItemFn) -> Vec<Contract> {
     let mut contracts = Vec::new();
     
     for attr in &func.attrs {
-        let path = attr.path();
-        
         // Check for #[requires(...)] or #[ensures(...)]
-        let is_requires = path.is_ident("requires") ||
-            (path.segments.len() == 2 &&
-             path.segments[0].ident == "blvm_spec_lock" &&
-             path.segments[1].ident == "requires");
-        
-        let is_ensures = path.is_ident("ensures") ||
-            (path.segments.len() == 2 &&
-             path.segments[0].ident == "blvm_spec_lock" &&
-             path.segments[1].ident == "ensures");
+        let is_requires = is_contract_attribute(attr, "requires");
+        let is_ensures = is_contract_attribute(attr, "ensures");
         
         if is_requires || is_ensures {
             // Parse the condition expression from the attribute
             // The attribute format is: #[requires(condition)] or #[ensures(condition)]
-            if let Ok(expr) = attr.parse_args::<syn::Expr>() {
-                // Convert expression to string for storage
-                let condition_str = quote::quote!(#expr).to_string();
+            let tokens = attr.parse_args::<proc_macro2::TokenStream>().ok();
+            let expr = tokens.clone().and_then(|tokens| syn::parse2::<syn::Expr>(rewrite_implications(tokens)).ok());
+            if let (Some(tokens), Some(expr)) = (tokens, expr) {
+                // Store the condition as written (`==>` is rewritten in `expr`)
+                let condition_str = tokens.to_string();
                 
                 contracts.push(Contract {
                     contract_type: if is_requires {
@@ -79,40 +79,106 @@ fn extract_contracts(func: &ItemFn) -> Vec<Contract> {
 pub struct FunctionToVerify {
     pub file_path: PathBuf,
     pub function_name: String,
+    pub line: usize,
     pub contracts: Vec<Contract>,
     pub section: Option<String>,
+    pub spec_function: Option<String>, // Explicit spec function name, if given
+    pub unwind: Option<u32>, // Loop bound for the Kani harness (`unwind = N`), if given
     pub function_sig: Option<syn::ItemFn>, // Store function signature for type inference
 }
 
+/// Options controlling how functions are verified
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    /// Use fixed-width bit-vector semantics instead of unbounded integers
+    pub bit_precise: bool,
+    /// Skip the implicit arithmetic-overflow obligations
+    pub no_overflow_checks: bool,
+    /// Solver timeout for each contract or obligation (none = unbounded)
+    pub timeout: Option<std::time::Duration>,
+    /// Number of worker threads (0 or 1 = verify serially)
+    pub jobs: usize,
+    /// Report each function on stderr as soon as it's verified
+    pub verbose: bool,
+    /// Contracts of the spec-locked functions that verified functions may call
+    pub summaries: Vec<CalleeSummary>,
+    /// Enums and structs defined in the workspace, modelled as datatypes
+    pub types: Vec<TypeDefinition>,
+    /// Integer constants defined in the workspace, translated to their values
+    pub constants: Vec<WorkspaceConstant>,
+}
+
+/// A callee's signature and contracts, for verifying calls to it
+///
+/// Kept as source text so the options can be shared with worker threads.
+#[derive(Debug, Clone)]
+pub struct CalleeSummary {
+    pub name: String,
+    /// The function with its attributes and an empty body
+    pub source: String,
+}
+
+/// A type defined in the workspace, kept as source text like [`CalleeSummary`]
+#[derive(Debug, Clone)]
+pub struct TypeDefinition {
+    pub name: String,
+    pub source: String,
+}
+
+/// Summarize functions so that calls between them are verified against their contracts
+pub fn callee_summaries(functions: &[FunctionToVerify]) -> Vec<CalleeSummary> {
+    functions.iter()
+        .filter_map(|function| {
+            let mut signature = function.function_sig.clone()?;
+            *signature.block = syn::parse_quote!({});
+            Some(CalleeSummary {
+                name: function.function_name.clone(),
+                source: quote!(#signature).to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Names of the functions a function calls in its body or contracts
+///
+/// Any name directly followed by parenthesized arguments counts, so method
+/// calls and macros are excluded but tuple-struct constructors are not.
+pub fn called_functions(func: &ItemFn) -> BTreeSet<String> {
+    fn collect(tokens: proc_macro2::TokenStream, names: &mut BTreeSet<String>) {
+        use proc_macro2::{Delimiter, TokenTree};
+        let mut previous: Option<TokenTree> = None;
+        for token in tokens {
+            if let TokenTree::Group(group) = &token {
+                if let Some(TokenTree::Ident(ident)) = &previous {
+                    if group.delimiter() == Delimiter::Parenthesis {
+                        names.insert(ident.to_string());
+                    }
+                }
+                collect(group.stream(), names);
+            }
+            // `x.f(...)` is a method call; skip `f` by forgetting it
+            let after_dot = matches!(&previous, Some(TokenTree::Punct(p)) if p.as_char() == '.');
+            previous = if after_dot { None } else { Some(token) };
+        }
+    }
+    let mut names = BTreeSet::new();
+    collect(quote!(#func), &mut names);
+    names
+}
+
 /// Discover all functions with #[spec_locked] attributes
 pub fn discover_functions(workspace_root: &PathBuf) -> Result<Vec<FunctionToVerify>, String> {
     let mut functions = Vec::new();
     let mut errors = Vec::new();
     
-    // Walk through Rust source files
-    for entry in WalkDir::new(workspace_root)
-        .into_iter()
-        .filter_entry(|e| {
-            let path = e.path();
-            // Skip target directory and other build artifacts
-            !path.to_string_lossy().contains("/target/") &&
-            !path.to_string_lossy().contains("/.git/") &&
-            !path.to_string_lossy().contains("/.cargo/")
-        })
-        .filter_map(|e| e.ok())
-    {
-        let path = entry.path();
-        
-        // Only process .rs files
-        if path.extension().and_then(|s| s.to_str()) == Some("rs") {
-            match parse_file_for_functions(path) {
-                Ok(mut file_functions) => {
-                    functions.append(&mut file_functions);
-                }
-                Err(e) => {
-                    // Collect errors but continue processing
-                    errors.push(format!("{}: {}", path.display(), e));
-                }
+    for path in rust_files(workspace_root) {
+        match parse_file_for_functions(&path) {
+            Ok(mut file_functions) => {
+                functions.append(&mut file_functions);
+            }
+            Err(e) => {
+                // Collect errors but continue processing
+                errors.push(format!("{}: {}", path.display(), e));
             }
         }
     }
@@ -126,6 +192,133 @@ pub fn discover_functions(workspace_root: &PathBuf) -> Result<Vec<FunctionToVeri
     }
 }
 
+/// Discover the enums and structs defined in the workspace (including in inline modules)
+///
+/// Types are matched by bare name, so a name defined differently in more than
+/// one place (e.g. in different modules) is left out and returned separately.
+/// Files that don't parse are skipped; `discover_functions` reports them.
+pub fn discover_types(workspace_root: &PathBuf) -> (Vec<TypeDefinition>, Vec<String>) {
+    fn collect(items: &[syn::Item], types: &mut Vec<TypeDefinition>) {
+        for item in items {
+            match item {
+                syn::Item::Enum(item_enum) => types.push(TypeDefinition {
+                    name: item_enum.ident.to_string(),
+                    source: quote!(#item_enum).to_string(),
+                }),
+                syn::Item::Struct(item_struct) => types.push(TypeDefinition {
+                    name: item_struct.ident.to_string(),
+                    source: quote!(#item_struct).to_string(),
+                }),
+                syn::Item::Mod(module) => {
+                    if let Some((_, items)) = &module.content {
+                        collect(items, types);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    let mut types = Vec::new();
+    for path in rust_files(workspace_root) {
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        if let Ok(ast) = syn::parse_file(&content) {
+            collect(&ast.items, &mut types);
+        }
+    }
+
+    let mut definitions: BTreeMap<String, Vec<TypeDefinition>> = BTreeMap::new();
+    for ty in types {
+        let same_name = definitions.entry(ty.name.clone()).or_default();
+        if !same_name.iter().any(|existing| existing.source == ty.source) {
+            same_name.push(ty);
+        }
+    }
+    let mut unique = Vec::new();
+    let mut conflicting = Vec::new();
+    for (name, mut same_name) in definitions {
+        if same_name.len() == 1 {
+            unique.append(&mut same_name);
+        } else {
+            conflicting.push(name);
+        }
+    }
+    (unique, conflicting)
+}
+
+/// Identifiers appearing in a token stream, including nested groups
+fn collect_idents(tokens: proc_macro2::TokenStream, names: &mut BTreeSet<String>) {
+    for token in tokens {
+        match token {
+            proc_macro2::TokenTree::Ident(ident) => {
+                names.insert(ident.to_string());
+            }
+            proc_macro2::TokenTree::Group(group) => collect_idents(group.stream(), names),
+            _ => {}
+        }
+    }
+}
+
+/// Identifiers appearing in a function or in the signatures and contracts of its callees
+fn referenced_idents(func: &ItemFn, options: &VerifyOptions) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    collect_idents(quote!(#func), &mut names);
+    let called = called_functions(func);
+    for summary in options.summaries.iter().filter(|summary| called.contains(&summary.name)) {
+        if let Ok(tokens) = summary.source.parse() {
+            collect_idents(tokens, &mut names);
+        }
+    }
+    names
+}
+
+/// The types a function refers to, directly or through its callees' signatures or other types
+pub fn referenced_types<'o>(func: &ItemFn, options: &'o VerifyOptions) -> Vec<&'o TypeDefinition> {
+    let mut names = referenced_idents(func, options);
+    let mut referenced: Vec<&TypeDefinition> = Vec::new();
+    loop {
+        let found: Vec<&TypeDefinition> = options.types.iter()
+            .filter(|ty| names.contains(&ty.name))
+            .filter(|ty| !referenced.iter().any(|r| std::ptr::eq(*r, *ty)))
+            .collect();
+        if found.is_empty() {
+            return referenced;
+        }
+        for ty in found {
+            if let Ok(tokens) = ty.source.parse() {
+                collect_idents(tokens, &mut names);
+            }
+            referenced.push(ty);
+        }
+    }
+}
+
+/// The workspace constants a function refers to, directly or through its callees' contracts
+pub fn referenced_constants<'o>(func: &ItemFn, options: &'o VerifyOptions) -> Vec<&'o WorkspaceConstant> {
+    let names = referenced_idents(func, options);
+    options.constants.iter()
+        .filter(|constant| names.contains(&constant.name))
+        .collect()
+}
+
+/// The Rust source files under the workspace root, skipping build artifacts
+pub(crate) fn rust_files(workspace_root: &PathBuf) -> Vec<PathBuf> {
+    WalkDir::new(workspace_root)
+        .into_iter()
+        .filter_entry(|e| {
+            let path = e.path();
+            // Skip target directory and other build artifacts
+            !path.to_string_lossy().contains("/target/") &&
+            !path.to_string_lossy().contains("/.git/") &&
+            !path.to_string_lossy().contains("/.cargo/")
+        })
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.path().extension().and_then(|s| s.to_str()) == Some("rs"))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
 /// Parse a Rust file for functions with #[spec_locked]
 fn parse_file_for_functions(file_path: &std::path::Path) -> Result<Vec<FunctionToVerify>, String> {
     let content = std::fs::read_to_string(file_path)
@@ -143,12 +336,17 @@ fn parse_file_for_functions(file_path: &std::path::Path) -> Result<Vec<FunctionT
             if has_spec_locked(&func.attrs) {
                 let contracts = extract_contracts(&func);
                 let section = extract_section(&func.attrs);
+                let spec_function = extract_spec_function(&func.attrs);
+                let unwind = extract_unwind(&func.attrs);
                 
                 functions.push(FunctionToVerify {
                     file_path: file_path.to_path_buf(),
                     function_name: func.sig.ident.to_string(),
+                    line: func.sig.ident.span().start().line,
                     contracts,
                     section,
+                    spec_function,
+                    unwind,
                     function_sig: Some(func.clone()),
                 });
             }
@@ -160,47 +358,205 @@ fn parse_file_for_functions(file_path: &std::path::Path) -> Result<Vec<FunctionT
 
 /// Check if function has #[spec_locked] attribute
 fn has_spec_locked(attrs: &[Attribute]) -> bool {
-    attrs.iter().any(|attr| {
-        let path = attr.path();
-        path.is_ident("spec_locked") ||
-        (path.segments.len() == 2 &&
-         path.segments[0].ident == "blvm_spec_lock" &&
-         path.segments[1].ident == "spec_locked")
-    })
+    attrs.iter().any(|attr| is_contract_attribute(attr, "spec_locked"))
 }
 
 /// Extract Orange Paper section from #[spec_locked] attribute
 fn extract_section(attrs: &[Attribute]) -> Option<String> {
-    for attr in attrs {
-        let path = attr.path();
-        let is_spec_locked = path.is_ident("spec_locked") ||
-            (path.segments.len() == 2 &&
-             path.segments[0].ident == "blvm_spec_lock" &&
-             path.segments[1].ident == "spec_locked");
-        
-        if is_spec_locked {
-            // Try to parse the section from the attribute tokens
-            // The attribute format is: #[spec_locked("6.1")] or #[spec_locked("6.1", "FunctionName")]
-            let tokens = quote::quote!(#attr).to_string();
-            
-            // Simple regex-like extraction: look for quoted strings
-            // This is a simplified parser - full implementation would use syn::parse
-            if let Some(start) = tokens.find('"') {
-                if let Some(end) = tokens[start+1..].find('"') {
-                    let section = &tokens[start+1..start+1+end];
-                    // Check if it looks like a section number (e.g., "6.1", "5.2.3")
-                    if section.chars().any(|c| c.is_ascii_digit()) {
-                        return Some(section.to_string());
+    extract_spec_locked_args(attrs).and_then(|(section, _, _)| section)
+}
+
+/// Extract the explicit spec function name from #[spec_locked] attribute
+fn extract_spec_function(attrs: &[Attribute]) -> Option<String> {
+    extract_spec_locked_args(attrs).and_then(|(_, function, _)| function)
+}
+
+/// Extract the Kani loop bound (`unwind = N`) from #[spec_locked] attribute
+fn extract_unwind(attrs: &[Attribute]) -> Option<u32> {
+    extract_spec_locked_args(attrs).and_then(|(_, _, unwind)| unwind)
+}
+
+/// Parse the section, function name and unwind bound out of #[spec_locked] arguments
+///
+/// Accepts the same forms as the macro: `("6.1")`, `("6.1", "GetBlockSubsidy")`,
+/// `("6.1::GetBlockSubsidy")`, `section = "..."` / `function = "..."`, and
+/// `unwind = N` with any of them.
+fn extract_spec_locked_args(attrs: &[Attribute]) -> Option<(Option<String>, Option<String>, Option<u32>)> {
+    let attr = attrs.iter().find(|attr| is_contract_attribute(attr, "spec_locked"))?;
+    let syn::Meta::List(list) = &attr.meta else {
+        return Some((None, None, None));
+    };
+    let args = list
+        .parse_args_with(syn::punctuated::Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated)
+        .ok()?;
+    
+    let mut section = None;
+    let mut function = None;
+    let mut unwind = None;
+    let mut positional = 0;
+    for arg in args {
+        match arg {
+            syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(lit), .. }) => {
+                let value = lit.value();
+                if positional == 0 {
+                    if let Some((sec, func)) = value.split_once("::") {
+                        section = Some(sec.trim().to_string());
+                        function = Some(func.trim().to_string());
+                    } else {
+                        section = Some(value);
                     }
+                } else if positional == 1 {
+                    function = Some(value);
+                }
+                positional += 1;
+            }
+            syn::Expr::Assign(assign) => {
+                let (syn::Expr::Path(key), syn::Expr::Lit(syn::ExprLit { lit, .. })) =
+                    (&*assign.left, &*assign.right) else {
+                    continue;
+                };
+                match lit {
+                    syn::Lit::Str(lit) if key.path.is_ident("section") => section = Some(lit.value()),
+                    syn::Lit::Str(lit) if key.path.is_ident("function") => function = Some(lit.value()),
+                    syn::Lit::Int(lit) if key.path.is_ident("unwind") => unwind = lit.base10_parse().ok(),
+                    _ => {}
                 }
             }
+            _ => {}
         }
     }
-    None
+    Some((section, function, unwind))
+}
+
+/// Verify functions on `options.jobs` worker threads, returning results in input order
+///
+/// Syntax trees can't be sent between threads, so each worker re-parses the
+/// files it needs (keeping spans intact), and every Z3 context is created and
+/// used on a single worker.
+pub fn verify_functions(functions: &[FunctionToVerify], options: &VerifyOptions) -> Vec<VerificationResult> {
+    let jobs = options.jobs;
+    if jobs <= 1 || functions.len() <= 1 {
+        return functions.iter().map(|f| verify_with_progress(f, options)).collect();
+    }
+    
+    // Functions are found again by name and line: impls and modules can reuse a name
+    let work: Vec<(PathBuf, String, usize)> = functions.iter()
+        .map(|f| (f.file_path.clone(), f.function_name.clone(), f.line))
+        .collect();
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.min(work.len()) {
+            let sender = sender.clone();
+            let (work, next) = (&work, &next);
+            scope.spawn(move || {
+                let mut parsed: HashMap<PathBuf, Result<Vec<FunctionToVerify>, String>> = HashMap::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some((path, name, line)) = work.get(index) else {
+                        break;
+                    };
+                    let file_functions = parsed.entry(path.clone())
+                        .or_insert_with(|| parse_file_for_functions(path));
+                    let function = file_functions.as_ref()
+                        .map_err(|e| e.clone())
+                        .and_then(|fs| {
+                            fs.iter()
+                                .find(|f| &f.function_name == name && f.line == *line)
+                                .ok_or_else(|| format!("{} not found at line {} of {}", name, line, path.display()))
+                        });
+                    let result = match function {
+                        Ok(function) => verify_with_progress(function, options),
+                        Err(reason) => VerificationResult::Failed {
+                            contract: "-".to_string(),
+                            reason,
+                            counterexample: None,
+                            condition: None,
+                        },
+                    };
+                    if sender.send((index, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    drop(sender);
+    
+    let mut results: Vec<Option<VerificationResult>> = vec![None; functions.len()];
+    for (index, result) in receiver {
+        results[index] = Some(result);
+    }
+    results.into_iter()
+        .map(|r| r.expect("every function is verified by a worker"))
+        .collect()
+}
+
+/// Verify a single function, reporting it on stderr when `options.verbose` is set
+///
+/// Workers finish in any order, so the lines are progress, not the report.
+fn verify_with_progress(function: &FunctionToVerify, options: &VerifyOptions) -> VerificationResult {
+    let start = std::time::Instant::now();
+    let result = verify_function(function, options);
+    if options.verbose {
+        let status = match &result {
+            VerificationResult::Passed => "✅",
+            VerificationResult::Partial { .. } => "⚠️ ",
+            VerificationResult::Unknown { .. } => "❓",
+            VerificationResult::NotImplemented => "⏳",
+            VerificationResult::Failed { .. } | VerificationResult::Overflow { .. } => "❌",
+        };
+        eprintln!(
+            "{} {}::{} ({:.2?})",
+            status,
+            function.file_path.display(),
+            function.function_name,
+            start.elapsed(),
+        );
+    }
+    result
 }
 
 /// Verify a single function
-pub fn verify_function(function: &FunctionToVerify) -> VerificationResult {
+///
+/// Checks the loop invariants first, since the contracts are proven assuming
+/// them; once the contracts hold, every call must satisfy its callee's
+/// requires, every index must be in bounds, and every arithmetic operation
+/// in the body must be proven free of overflow.
+pub fn verify_function(function: &FunctionToVerify, options: &VerifyOptions) -> VerificationResult {
+    if let Some(failure) = check_loops(function, options) {
+        return failure;
+    }
+    let result = verify_contracts(function, options);
+    if !matches!(result, VerificationResult::Passed | VerificationResult::Partial { .. }) {
+        return result;
+    }
+    if let Some(failure) = check_call_preconditions(function, options) {
+        return failure;
+    }
+    if let Some(failure) = check_bounds(function, options) {
+        return failure;
+    }
+    if options.no_overflow_checks {
+        return result;
+    }
+    
+    let (failures, unknown) = check_overflow(function, options);
+    if !failures.is_empty() {
+        VerificationResult::Overflow { failures }
+    } else if let Some(reason) = unknown {
+        VerificationResult::Unknown {
+            contract: "Overflow".to_string(),
+            reason,
+        }
+    } else {
+        result
+    }
+}
+
+/// Verify the requires/ensures contracts of a function
+fn verify_contracts(function: &FunctionToVerify, options: &VerifyOptions) -> VerificationResult {
     if function.contracts.is_empty() {
         // No contracts to verify - this is valid (function might only have #[spec_locked])
         return VerificationResult::Passed;
@@ -213,6 +569,9 @@ pub fn verify_function(function: &FunctionToVerify) -> VerificationResult {
     
     let mut verified_count = 0;
     let mut failed_contracts = Vec::new();
+    // Contracts Z3 could neither prove nor refute (e.g. timeout)
+    #[cfg_attr(not(feature = "z3"), allow(unused_mut))]
+    let mut unknown_contracts: Vec<(String, String)> = Vec::new();
     let mut requires_z3_count = 0;
     
     // Separate requires and ensures contracts
@@ -230,6 +589,8 @@ pub fn verify_function(function: &FunctionToVerify) -> VerificationResult {
             failed_contracts.push((
                 format!("{:?}", contract.contract_type),
                 "Empty contract condition".to_string(),
+                None,
+                Some(contract.condition.clone()),
             ));
             continue;
         }
@@ -244,6 +605,8 @@ pub fn verify_function(function: &FunctionToVerify) -> VerificationResult {
                     failed_contracts.push((
                         format!("{:?}", contract.contract_type),
                         reason,
+                        None,
+                        Some(contract.condition.clone()),
                     ));
                 }
                 StaticCheck::RequiresZ3 => {
@@ -251,10 +614,16 @@ pub fn verify_function(function: &FunctionToVerify) -> VerificationResult {
                     // Try Z3 if available
                     #[cfg(feature = "z3")]
                     {
-                        if let Err(e) = verify_with_z3(contract, function.function_sig.as_ref(), &[]) {
+                        if let Err(e) = verify_with_z3(contract, function.function_sig.as_ref(), &[], options) {
+                            if e.unknown {
+                                unknown_contracts.push((format!("{:?}", contract.contract_type), e.message));
+                                continue;
+                            }
                             failed_contracts.push((
                                 format!("{:?}", contract.contract_type),
-                                format!("Z3 verification failed: {}", e),
+                                format!("Z3 verification failed: {}", e.message),
+                                e.counterexample,
+                                Some(contract.condition.clone()),
                             ));
                         } else {
                             verified_count += 1;
@@ -279,16 +648,20 @@ pub fn verify_function(function: &FunctionToVerify) -> VerificationResult {
             failed_contracts.push((
                 format!("{:?}", contract.contract_type),
                 "Cannot verify: contract condition could not be parsed as expression".to_string(),
+                None,
+                Some(contract.condition.clone()),
             ));
         }
     }
     
     // Early return if requires contracts failed
     if !failed_contracts.is_empty() {
-        let (contract_type, reason) = &failed_contracts[0];
+        let (contract_type, reason, counterexample, condition) = &failed_contracts[0];
         return VerificationResult::Failed {
             contract: contract_type.clone(),
             reason: format!("{} ({} total failures)", reason, failed_contracts.len()),
+            counterexample: counterexample.clone(),
+            condition: condition.clone(),
         };
     }
     
@@ -300,6 +673,8 @@ pub fn verify_function(function: &FunctionToVerify) -> VerificationResult {
             failed_contracts.push((
                 format!("{:?}", contract.contract_type),
                 "Empty contract condition".to_string(),
+                None,
+                Some(contract.condition.clone()),
             ));
             continue;
         }
@@ -313,6 +688,8 @@ pub fn verify_function(function: &FunctionToVerify) -> VerificationResult {
                     failed_contracts.push((
                         format!("{:?}", contract.contract_type),
                         reason,
+                        None,
+                        Some(contract.condition.clone()),
                     ));
                 }
                 StaticCheck::RequiresZ3 => {
@@ -321,10 +698,16 @@ pub fn verify_function(function: &FunctionToVerify) -> VerificationResult {
                     {
                         // For ensures, pass the requires contracts as context
                         // This allows verifier to prove: requires && impl => ensures
-                        if let Err(e) = verify_with_z3(contract, function.function_sig.as_ref(), &requires_contracts) {
+                        if let Err(e) = verify_with_z3(contract, function.function_sig.as_ref(), &requires_contracts, options) {
+                            if e.unknown {
+                                unknown_contracts.push((format!("{:?}", contract.contract_type), e.message));
+                                continue;
+                            }
                             failed_contracts.push((
                                 format!("{:?}", contract.contract_type),
-                                format!("Z3: {}", e),
+                                format!("Z3: {}", e.message),
+                                e.counterexample,
+                                Some(contract.condition.clone()),
                             ));
                         } else {
                             verified_count += 1;
@@ -341,16 +724,27 @@ pub fn verify_function(function: &FunctionToVerify) -> VerificationResult {
             failed_contracts.push((
                 format!("{:?}", contract.contract_type),
                 "Cannot verify: contract condition could not be parsed".to_string(),
+                None,
+                Some(contract.condition.clone()),
             ));
         }
     }
     
     // Report results
     if !failed_contracts.is_empty() {
-        let (contract_type, reason) = &failed_contracts[0];
+        let (contract_type, reason, counterexample, condition) = &failed_contracts[0];
         return VerificationResult::Failed {
             contract: contract_type.clone(),
             reason: format!("{} ({} total failures)", reason, failed_contracts.len()),
+            counterexample: counterexample.clone(),
+            condition: condition.clone(),
+        };
+    }
+    
+    if let Some((contract_type, reason)) = unknown_contracts.first() {
+        return VerificationResult::Unknown {
+            contract: contract_type.clone(),
+            reason: reason.clone(),
         };
     }
     
@@ -375,6 +769,13 @@ enum StaticCheck {
 
 /// Check a contract statically (simplified version for CLI)
 fn check_contract_statically(expr: &syn::Expr, contract_type: ContractType) -> StaticCheck {
+    // `old(..)` is the state on entry, which only an ensures can compare against
+    if mentions_old(expr) {
+        return match contract_type {
+            ContractType::Requires => StaticCheck::Failed("`old(..)` can only be used in ensures".to_string()),
+            ContractType::Ensures => StaticCheck::RequiresZ3,
+        };
+    }
     // Simple pattern matching for common cases
     match expr {
         // Non-negative checks: x >= 0 or 0 <= x
@@ -431,13 +832,13 @@ fn is_literal(expr: &syn::Expr) -> bool {
 
 /// Verify contract with Z3 (if feature enabled)
 #[cfg(feature = "z3")]
-fn verify_with_z3(contract: &Contract, func_sig: Option<&syn::ItemFn>, requires_contracts: &[&Contract]) -> Result<(), String> {
+fn verify_with_z3(contract: &Contract, func_sig: Option<&syn::ItemFn>, requires_contracts: &[&Contract], options: &VerifyOptions) -> Result<(), Z3Failure> {
     use crate::parser::contracts::{Contract as LibraryContract, ContractType as LibraryContractType};
-    use crate::translator::z3_verifier::{Z3Verifier, VerificationResult};
+    use crate::translator::z3_verifier::VerificationResult;
     
     // Convert CLI Contract to library Contract
     let expr = contract.expr.as_ref().ok_or_else(|| {
-        "Cannot verify: missing parsed expression".to_string()
+        Z3Failure::from("Cannot verify: missing parsed expression".to_string())
     })?;
     
     let library_contract = LibraryContract {
@@ -450,7 +851,7 @@ fn verify_with_z3(contract: &Contract, func_sig: Option<&syn::ItemFn>, requires_
     };
     
     // Use Z3 verifier with function signature and requires contracts for context
-    let mut verifier = Z3Verifier::new();
+    let mut verifier = z3_verifier(options, func_sig);
     
     // Convert requires contracts to library format
     let requires_library: Vec<_> = requires_contracts.iter()
@@ -470,39 +871,364 @@ fn verify_with_z3(contract: &Contract, func_sig: Option<&syn::ItemFn>, requires_
             Ok(())
         }
         VerificationResult::Failed { counterexample } => {
-            let msg = if let Some(ce) = counterexample {
-                format!("Contract violated. Counterexample: {:?}", ce.assignments)
+            let msg = if counterexample.is_some() {
+                "Contract violated".to_string()
             } else {
                 "Contract violated (no counterexample available)".to_string()
             };
-            Err(msg)
+            Err(Z3Failure {
+                message: msg,
+                counterexample: counterexample.map(|ce| ce.assignments),
+                unknown: false,
+            })
         }
         VerificationResult::Unknown { reason } => {
-            Err(format!("Z3 verification unknown: {}", reason))
+            Err(Z3Failure {
+                message: format!("Z3 verification unknown: {}", reason),
+                counterexample: None,
+                unknown: true,
+            })
         }
         VerificationResult::Error { error } => {
-            Err(format!("Z3 verification error: {}", error))
+            Err(format!("Z3 verification error: {}", error).into())
+        }
+    }
+}
+
+/// Z3 verifier configured by the options, knowing the contracts of `func`'s callees and the types it uses
+#[cfg(feature = "z3")]
+pub fn z3_verifier(options: &VerifyOptions, func: Option<&ItemFn>) -> crate::translator::z3_verifier::Z3Verifier {
+    use crate::translator::calls::FunctionSummary;
+    use crate::translator::z3_translator::{ArithmeticMode, IntType};
+    use crate::translator::z3_verifier::Z3Verifier;
+    
+    let mode = if options.bit_precise {
+        ArithmeticMode::BitPrecise
+    } else {
+        ArithmeticMode::Integer
+    };
+    let mut verifier = Z3Verifier::with_mode(mode);
+    if let Some(timeout) = options.timeout {
+        verifier = verifier.with_timeout(timeout);
+    }
+    
+    let called = func.map(called_functions).unwrap_or_default();
+    let summaries = options.summaries.iter()
+        .filter(|summary| called.contains(&summary.name))
+        .filter_map(|summary| syn::parse_str::<ItemFn>(&summary.source).ok())
+        .map(|callee| FunctionSummary::from_fn(&callee));
+    let types: Vec<syn::Item> = func.map(|func| referenced_types(func, options)).unwrap_or_default().into_iter()
+        .filter_map(|ty| syn::parse_str::<syn::Item>(&ty.source).ok())
+        .collect();
+    let enums = types.iter().filter_map(|item| match item {
+        syn::Item::Enum(item_enum) => Some(item_enum.clone()),
+        _ => None,
+    });
+    let structs = types.iter().filter_map(|item| match item {
+        syn::Item::Struct(item_struct) => Some(item_struct.clone()),
+        _ => None,
+    });
+    // All of them, since theorems name constants the function itself may not
+    let constants = options.constants.iter()
+        .filter_map(|constant| Some((constant.name.clone(), IntType::from_name(&constant.type_name)?, constant.value)));
+    verifier.with_summaries(summaries).with_enums(enums).with_structs(structs).with_constants(constants)
+}
+
+/// A function's requires contracts, converted to the library's contract type
+#[cfg(feature = "z3")]
+pub fn library_requires(function: &FunctionToVerify) -> Vec<crate::parser::contracts::Contract> {
+    use crate::parser::contracts::{Contract as LibraryContract, ContractType as LibraryContractType};
+    
+    function.contracts.iter()
+        .filter(|c| c.contract_type == ContractType::Requires)
+        .filter_map(|c| {
+            c.expr.as_ref().map(|expr| {
+                LibraryContract {
+                    contract_type: LibraryContractType::Requires,
+                    condition: expr.clone(),
+                    comment: None,
+                }
+            })
+        })
+        .collect()
+}
+
+/// Check that every call to a spec-locked function establishes the callee's requires
+///
+/// Returns the first call that may violate them, or, if none does, the first
+/// call Z3 could neither prove nor refute.
+#[cfg(feature = "z3")]
+fn check_call_preconditions(function: &FunctionToVerify, options: &VerifyOptions) -> Option<VerificationResult> {
+    let func = function.function_sig.as_ref()?;
+    let mut verifier = z3_verifier(options, Some(func));
+    let obligations = verifier.verify_call_preconditions(func, &library_requires(function));
+    first_failed_obligation(obligations, |obligation| {
+        format!("Precondition may not hold for `{}`", obligation.expression)
+    })
+}
+
+/// Check that every loop's invariants hold on entry and are preserved, and
+/// that its `decreases` measure decreases
+///
+/// Returns the first obligation that may fail, or, if none does, the first
+/// one Z3 could neither prove nor refute.
+#[cfg(feature = "z3")]
+fn check_loops(function: &FunctionToVerify, options: &VerifyOptions) -> Option<VerificationResult> {
+    let func = function.function_sig.as_ref()?;
+    let mut verifier = z3_verifier(options, Some(func));
+    let obligations = verifier.verify_loop_obligations(func, &library_requires(function));
+    first_failed_obligation(obligations, |obligation| {
+        if obligation.operation == "decreases" {
+            format!("Loop measure `{}` may not decrease, or may be negative", obligation.expression)
+        } else {
+            format!("Loop invariant may not hold for `{}`", obligation.expression)
         }
+    })
+}
+
+/// Check that every index `v[i]` is below the length of `v`
+///
+/// Returns the first index that may be out of bounds, or, if none is, the
+/// first one Z3 could neither prove nor refute.
+#[cfg(feature = "z3")]
+fn check_bounds(function: &FunctionToVerify, options: &VerifyOptions) -> Option<VerificationResult> {
+    let func = function.function_sig.as_ref()?;
+    let mut verifier = z3_verifier(options, Some(func));
+    let obligations = verifier.verify_bounds_obligations(func, &library_requires(function));
+    first_failed_obligation(obligations, |obligation| {
+        format!("Index may be out of bounds for `{}`", obligation.expression)
+    })
+}
+
+/// The first failed obligation, or else the first inconclusive one
+#[cfg(feature = "z3")]
+fn first_failed_obligation(obligations: Vec<crate::translator::z3_verifier::ObligationResult>, failure_reason: impl Fn(&crate::translator::z3_verifier::ObligationResult) -> String) -> Option<VerificationResult> {
+    use crate::translator::z3_verifier::VerificationResult as Z3Result;
+    
+    let mut unknown = None;
+    for obligation in obligations {
+        let reason = failure_reason(&obligation);
+        let (reason, counterexample) = match obligation.result {
+            Z3Result::Verified => continue,
+            Z3Result::Failed { counterexample } => (reason, counterexample.map(|ce| ce.assignments)),
+            Z3Result::Unknown { reason } => {
+                unknown.get_or_insert(VerificationResult::Unknown {
+                    contract: obligation.operation,
+                    reason: format!("Z3 verification unknown for `{}`: {}", obligation.expression, reason),
+                });
+                continue;
+            }
+            Z3Result::Error { error } => (format!("Z3 verification error: {}", error), None),
+        };
+        return Some(VerificationResult::Failed {
+            contract: obligation.operation,
+            reason,
+            counterexample,
+            condition: None,
+        });
+    }
+    unknown
+}
+
+#[cfg(not(feature = "z3"))]
+fn check_call_preconditions(_function: &FunctionToVerify, _options: &VerifyOptions) -> Option<VerificationResult> {
+    // Callee preconditions need Z3 to discharge
+    None
+}
+
+#[cfg(not(feature = "z3"))]
+fn check_loops(_function: &FunctionToVerify, _options: &VerifyOptions) -> Option<VerificationResult> {
+    // Loop invariants need Z3 to discharge
+    None
+}
+
+#[cfg(not(feature = "z3"))]
+fn check_bounds(_function: &FunctionToVerify, _options: &VerifyOptions) -> Option<VerificationResult> {
+    // Index bounds need Z3 to discharge
+    None
+}
+
+/// Check that no arithmetic operation in the function body can overflow
+///
+/// Returns the operations that may overflow, and the reason if any operation
+/// could be neither proven nor refuted.
+#[cfg(feature = "z3")]
+fn check_overflow(function: &FunctionToVerify, options: &VerifyOptions) -> (Vec<OverflowFailure>, Option<String>) {
+    use crate::translator::z3_verifier::VerificationResult;
+    
+    let Some(func) = function.function_sig.as_ref() else {
+        return (Vec::new(), None);
+    };
+    
+    let mut verifier = z3_verifier(options, Some(func));
+    let requires_library = library_requires(function);
+    
+    let mut failures = Vec::new();
+    let mut unknown = None;
+    for obligation in verifier.verify_overflow_obligations(func, &requires_library) {
+        let (reason, counterexample) = match obligation.result {
+            VerificationResult::Verified => continue,
+            VerificationResult::Failed { counterexample } => {
+                let reason = "Overflow possible".to_string();
+                (reason, counterexample.map(|ce| ce.assignments))
+            }
+            VerificationResult::Unknown { reason } => {
+                unknown.get_or_insert(format!("Z3 verification unknown for `{}`: {}", obligation.expression, reason));
+                continue;
+            }
+            VerificationResult::Error { error } => {
+                (format!("Z3 verification error: {}", error), None)
+            }
+        };
+        failures.push(OverflowFailure {
+            operation: obligation.operation,
+            expression: obligation.expression,
+            line: obligation.line,
+            column: obligation.column,
+            reason,
+            counterexample,
+        });
     }
+    (failures, unknown)
 }
 
 #[cfg(not(feature = "z3"))]
-fn verify_with_z3(_contract: &Contract, _func_sig: Option<&syn::ItemFn>, _requires: &[&Contract]) -> Result<(), String> {
+fn check_overflow(_function: &FunctionToVerify, _options: &VerifyOptions) -> (Vec<OverflowFailure>, Option<String>) {
+    // Overflow obligations need Z3 to discharge
+    (Vec::new(), None)
+}
+
+#[cfg(not(feature = "z3"))]
+fn verify_with_z3(_contract: &Contract, _func_sig: Option<&syn::ItemFn>, _requires: &[&Contract], _options: &VerifyOptions) -> Result<(), String> {
     Err("Z3 feature not enabled. Build with --features z3 to enable Z3 verification.".to_string())
 }
 
+/// Why Z3 could not verify a contract
+#[cfg(feature = "z3")]
+struct Z3Failure {
+    message: String,
+    counterexample: Option<Counterexample>,
+    /// Z3 neither proved nor refuted the contract (e.g. timeout)
+    unknown: bool,
+}
+
+#[cfg(feature = "z3")]
+impl From<String> for Z3Failure {
+    fn from(message: String) -> Self {
+        Z3Failure { message, counterexample: None, unknown: false }
+    }
+}
+
 /// Result of function verification
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum VerificationResult {
     Passed,
     Failed {
         contract: String,
         reason: String,
+        counterexample: Option<Counterexample>,
+        /// Condition of the contract that failed
+        condition: Option<String>,
     },
     Partial {
         verified: usize,
         total: usize,
     },
+    /// Z3 could neither prove nor refute a contract (e.g. it timed out)
+    Unknown {
+        contract: String,
+        reason: String,
+    },
+    /// Contracts hold, but some arithmetic operations may overflow
+    Overflow {
+        failures: Vec<OverflowFailure>,
+    },
     NotImplemented,
 }
 
+/// An arithmetic operation that could not be proven free of overflow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverflowFailure {
+    /// Operator or cast, e.g. `+`, `<<`, `as u32`
+    pub operation: String,
+    /// Source text of the operation
+    pub expression: String,
+    /// Line of the operation (1-based, 0 if unknown)
+    pub line: usize,
+    /// Column of the operation (1-based)
+    pub column: usize,
+    pub reason: String,
+    pub counterexample: Option<Counterexample>,
+}
+
+/// Concrete values that violate a contract, as `(name, Rust literal)` pairs
+///
+/// Parameters come first (in signature order), then `let`-bound locals, then `result`.
+pub type Counterexample = Vec<(String, String)>;
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover_types_drops_conflicting_names() {
+        let root = std::env::temp_dir().join(format!("spec-lock-types-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src").join("lib.rs"), "\
+pub struct OutPoint { pub index: u32 }
+pub mod script { pub struct Header { pub version: u32 } }
+pub mod block { pub struct Header { pub height: u64 } }
+").unwrap();
+        // An identical copy of a definition isn't a conflict
+        std::fs::write(root.join("src").join("copy.rs"), "pub struct OutPoint { pub index: u32 }\n").unwrap();
+        let (types, conflicting) = discover_types(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let names: Vec<_> = types.iter().map(|ty| ty.name.as_str()).collect();
+        assert_eq!(names, vec!["OutPoint"]);
+        assert_eq!(conflicting, vec!["Header".to_string()]);
+    }
+
+    #[test]
+    fn test_verify_functions_in_parallel() {
+        let root = std::env::temp_dir().join(format!("spec-lock-parallel-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        // Function i has i + 1 contracts, and its ensures fails differently from the others'
+        for (file, range) in [("a.rs", 0..3), ("b.rs", 3..6)] {
+            let source: String = range.map(|i| format!(
+                "#[spec_locked(\"1.1\")]\n{}#[ensures(result == x + {})]\npub fn f{}(x: u64) -> u64 {{ x + {} }}\n",
+                "#[requires(x < 1000)]\n".repeat(i),
+                i + 1,
+                i,
+                i,
+            )).collect();
+            std::fs::write(root.join("src").join(file), source).unwrap();
+        }
+        // Platform variants share a name; each is verified against its own contract
+        let variants: String = ["unix", "not(unix)"].iter().enumerate().map(|(i, cfg)| format!(
+            "#[cfg({})]\n#[spec_locked(\"1.1\")]\n{}#[ensures(result == x + {})]\npub fn g(x: u64) -> u64 {{ x }}\n",
+            cfg,
+            "#[requires(x < 1000)]\n".repeat(i + 6),
+            i + 10,
+        )).collect();
+        std::fs::write(root.join("src").join("c.rs"), variants).unwrap();
+        let functions = discover_functions(&root).unwrap();
+        assert_eq!(functions.len(), 8);
+
+        let serial: Vec<String> = verify_functions(&functions, &VerifyOptions { jobs: 1, ..VerifyOptions::default() })
+            .iter()
+            .map(|result| format!("{:?}", result))
+            .collect();
+        let parallel: Vec<String> = verify_functions(&functions, &VerifyOptions { jobs: 4, ..VerifyOptions::default() })
+            .iter()
+            .map(|result| format!("{:?}", result))
+            .collect();
+        // Workers re-parse the files, so they're removed only now
+        std::fs::remove_dir_all(&root).unwrap();
+        // Every result is distinct, so equal lists mean the parallel results are in input order
+        assert_eq!(serial.iter().collect::<BTreeSet<_>>().len(), functions.len(), "{:#?}", serial);
+        assert_eq!(parallel, serial);
+    }
+}
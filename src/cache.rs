@@ -1,6 +1,7 @@
 //! Specification caching to avoid re-parsing on every compile
 //!
-//! Caches parsed specifications in `target/.spec-cache/` keyed by file hash.
+//! Caches parsed specifications and verification results in
+//! `target/.spec-cache/`, keyed by content hash.
 
 use sha2::{Digest, Sha256};
 use std::fs;
@@ -24,11 +25,16 @@ impl SpecCache {
         SpecCache { cache_dir }
     }
 
-    /// Get cache key for a file (based on content hash)
-    pub fn cache_key(file_path: &Path) -> String {
-        // In a real implementation, we'd hash the file content
-        // For now, use a simple approach based on file path and mtime
-        format!("{:x}", Sha256::digest(file_path.to_string_lossy().as_bytes()))
+    /// Get cache key for a list of inputs
+    ///
+    /// Each part is length-prefixed, so `["ab", "c"]` and `["a", "bc"]` differ.
+    pub fn key_for(parts: &[&str]) -> String {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        format!("{:x}", hasher.finalize())
     }
 
     /// Load cached specification if available
@@ -42,19 +48,6 @@ impl SpecCache {
         let cache_file = self.cache_dir.join(format!("{}.cache", key));
         fs::write(cache_file, content).ok();
     }
-
-    /// Check if cache entry is valid (not expired)
-    pub fn is_valid(&self, key: &str, _source_mtime: u64) -> bool {
-        let cache_file = self.cache_dir.join(format!("{}.cache", key));
-        if let Ok(metadata) = fs::metadata(&cache_file) {
-            if let Ok(mtime) = metadata.modified() {
-                // In a real implementation, compare with source file mtime
-                // For now, just check if cache file exists
-                return true;
-            }
-        }
-        false
-    }
 }
 
 #[cfg(test)]
@@ -64,9 +57,8 @@ mod tests {
 
     #[test]
     fn test_cache_key() {
-        let path = Path::new("/tmp/test.md");
-        let key = SpecCache::cache_key(path);
-        assert!(!key.is_empty());
+        assert_ne!(SpecCache::key_for(&["ab", "c"]), SpecCache::key_for(&["a", "bc"]));
+        assert_eq!(SpecCache::key_for(&["ab", "c"]), SpecCache::key_for(&["ab", "c"]));
     }
 
     #[test]
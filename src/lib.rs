@@ -27,11 +27,11 @@
 //! 3. Links function to spec (contracts come from manual annotations or Orange Paper)
 
 mod parser;
-mod cache;
 mod macro_impl;
+mod runtime_checks;
 mod translator;
 mod report;
-// CLI module is only used by the binary, not the library
+// CLI and cache modules are only used by the binary, not the library
 
 // Note: Proc-macro crates cannot export regular items.
 // The binary accesses modules directly via path manipulation.
@@ -51,6 +51,7 @@ use proc_macro::TokenStream;
 /// - `section`: Section ID in Orange Paper (e.g., "6.1")
 /// - `function`: Function name in specification (e.g., "GetBlockSubsidy")
 /// - `spec_path`: Optional path to Orange Paper (defaults to workspace-relative)
+/// - `unwind`: Optional loop bound for the Kani harness written by `cargo spec-lock gen-kani`
 ///
 /// # Examples
 ///
@@ -89,6 +90,8 @@ pub fn spec_locked(args: TokenStream, input: TokenStream) -> TokenStream {
 ///
 /// This is a placeholder that allows code to compile.
 /// The actual verification will be done by the `cargo spec-lock` tool.
+/// With the `runtime-checks` feature, `#[spec_locked]` also asserts it on
+/// entry to the function.
 #[proc_macro_attribute]
 pub fn requires(_args: TokenStream, input: TokenStream) -> TokenStream {
     // Pass through unchanged - verification tool will process these
@@ -99,8 +102,33 @@ pub fn requires(_args: TokenStream, input: TokenStream) -> TokenStream {
 ///
 /// This is a placeholder that allows code to compile.
 /// The actual verification will be done by the `cargo spec-lock` tool.
+/// With the `runtime-checks` feature, `#[spec_locked]` also asserts it on
+/// the function's result.
 #[proc_macro_attribute]
 pub fn ensures(_args: TokenStream, input: TokenStream) -> TokenStream {
     // Pass through unchanged - verification tool will process these
     input
 }
+
+/// Pass-through macro for #[invariant] attributes on loops
+///
+/// Attach it to a `for` or `while` loop inside a `#[spec_locked]` function,
+/// which removes it before compilation (attributes on statements aren't stable).
+/// The `cargo spec-lock` tool checks that the invariant holds on entry and is
+/// preserved by every iteration, and assumes it after the loop.
+#[proc_macro_attribute]
+pub fn invariant(_args: TokenStream, input: TokenStream) -> TokenStream {
+    // Pass through unchanged - verification tool will process these
+    input
+}
+
+/// Pass-through macro for #[decreases] attributes on loops
+///
+/// Attach it to a loop like `#[invariant]`. The `cargo spec-lock` tool checks
+/// that the measure is non-negative and strictly decreases with every
+/// iteration, so the loop terminates.
+#[proc_macro_attribute]
+pub fn decreases(_args: TokenStream, input: TokenStream) -> TokenStream {
+    // Pass through unchanged - verification tool will process these
+    input
+}
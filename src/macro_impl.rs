@@ -4,11 +4,13 @@
 //! Contracts are provided via manual #[requires] and #[ensures] attributes,
 //! which will be verified by the BLVM Spec Lock verification tool.
 
-use proc_macro2::{TokenStream, Span};
+use proc_macro2::{Delimiter, Group, TokenStream, TokenTree, Span};
 use quote::quote;
 use syn::{parse::Parse, parse_macro_input, ItemFn, LitStr, Token, Ident};
 use crate::parser::{SpecParser, FunctionSpec, SpecSection};
-use regex::Regex;
+use crate::parser::contracts::is_contract_path;
+use crate::parser::math::{self, MathExpr};
+use crate::parser::orange_paper::{rust_to_pascal_case, generate_name_variations};
 
 /// Arguments for #[spec_locked] attribute
 ///
@@ -19,6 +21,7 @@ use regex::Regex;
 /// - `#[spec_locked("6.1", "GetBlockSubsidy")]` - Simple positional
 /// - `#[spec_locked(section = "6.1", function = "GetBlockSubsidy")]` - Named parameters
 /// - `#[spec_locked("6.1::GetBlockSubsidy")]` - Single string with separator
+/// - `#[spec_locked("6.1", unwind = 65)]` - Loop bound for the generated Kani harness
 struct SpecLockedArgs {
     section: Option<LitStr>,  // Optional - can be auto-inferred
     function: Option<LitStr>,  // Optional - can be inferred from function name
@@ -39,57 +42,23 @@ impl Parse for SpecLockedArgs {
                     let section = LitStr::new(parts[0].trim(), first.span());
                     let function = LitStr::new(parts[1].trim(), first.span());
                     
-                    // Check for optional spec_path
-                    let spec_path = if input.peek(Token![,]) {
-                        input.parse::<Token![,]>()?;
-                        if input.peek(Ident) {
-                            let key: Ident = input.parse()?;
-                            if key == "spec_path" {
-                                input.parse::<Token![=]>()?;
-                                Some(input.parse()?)
-                            } else {
-                                return Err(input.error("Expected 'spec_path' after section::function"));
-                            }
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    };
+                    // Check for optional spec_path / unwind
+                    let spec_path = parse_trailing_options(input)?;
                     
                     return Ok(SpecLockedArgs { section: Some(section), function: Some(function), spec_path });
                 }
             }
             
             // It's positional: first is section, second is optional function name
-            let function = if input.peek(Token![,]) {
+            let function = if input.peek(Token![,]) && input.peek2(LitStr) {
                 input.parse::<Token![,]>()?;
-                if input.peek(LitStr) {
-                    Some(input.parse()?)
-                } else {
-                    None  // Function name will be inferred
-                }
+                Some(input.parse()?)
             } else {
                 None  // Function name will be inferred
             };
             
-            // Check for optional spec_path
-            let spec_path = if input.peek(Token![,]) {
-                input.parse::<Token![,]>()?;
-                if input.peek(Ident) {
-                    let key: Ident = input.parse()?;
-                    if key == "spec_path" {
-                        input.parse::<Token![=]>()?;
-                        Some(input.parse()?)
-                    } else {
-                        return Err(input.error("Expected 'spec_path'"));
-                    }
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
+            // Check for optional spec_path / unwind
+            let spec_path = parse_trailing_options(input)?;
             
             return Ok(SpecLockedArgs {
                 section: Some(first),
@@ -106,16 +75,17 @@ impl Parse for SpecLockedArgs {
         while !input.is_empty() {
             let key: Ident = input.parse()?;
             input.parse::<Token![=]>()?;
-            let value: LitStr = input.parse()?;
 
             if key == "section" {
-                section = Some(value);
+                section = Some(input.parse()?);
             } else if key == "function" {
-                function = Some(value);
+                function = Some(input.parse()?);
             } else if key == "spec_path" {
-                spec_path = Some(value);
+                spec_path = Some(input.parse()?);
+            } else if key == "unwind" {
+                parse_unwind(input)?;
             } else {
-                return Err(input.error(format!("Unknown parameter: {}. Expected 'section', 'function', or 'spec_path'", key)));
+                return Err(input.error(format!("Unknown parameter: {}. Expected 'section', 'function', 'spec_path', or 'unwind'", key)));
             }
 
             if !input.is_empty() {
@@ -131,6 +101,36 @@ impl Parse for SpecLockedArgs {
     }
 }
 
+/// Parse the `, spec_path = "..."` and `, unwind = N` options after positional arguments
+fn parse_trailing_options(input: syn::parse::ParseStream) -> syn::Result<Option<LitStr>> {
+    let mut spec_path = None;
+    while input.peek(Token![,]) {
+        input.parse::<Token![,]>()?;
+        if input.is_empty() {
+            break;
+        }
+        let key: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        if key == "spec_path" {
+            spec_path = Some(input.parse()?);
+        } else if key == "unwind" {
+            parse_unwind(input)?;
+        } else {
+            return Err(input.error(format!("Unknown parameter: {}. Expected 'spec_path' or 'unwind'", key)));
+        }
+    }
+    Ok(spec_path)
+}
+
+/// Parse the value of `unwind = N`
+///
+/// The loop bound is only read by `cargo spec-lock gen-kani` (for the
+/// function's `#[kani::unwind(N)]`); the macro just checks it.
+fn parse_unwind(input: syn::parse::ParseStream) -> syn::Result<()> {
+    input.parse::<syn::LitInt>()?.base10_parse::<u32>()?;
+    Ok(())
+}
+
 /// Generate contract annotations from parsed Orange Paper specification
 ///
 /// Tailored for Bitcoin consensus functions:
@@ -229,35 +229,7 @@ fn generate_ensures(spec: &FunctionSpec, func: &syn::ItemFn) -> TokenStream {
     let mut ensures = Vec::<TokenStream2>::new();
     
     // Check if function returns a tuple (which doesn't have View)
-    let returns_tuple = if let syn::ReturnType::Type(_, return_type) = &func.sig.output {
-        match return_type.as_ref() {
-            syn::Type::Tuple(_) => true,
-            syn::Type::Path(type_path) => {
-                // Check for Result<Tuple, E>
-                if let Some(segment) = type_path.path.segments.last() {
-                    if segment.ident == "Result" {
-                        // Check if Result contains a tuple
-                        if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
-                            if let Some(syn::GenericArgument::Type(inner_type)) = args.args.first() {
-                                matches!(inner_type, syn::Type::Tuple(_))
-                            } else {
-                                false
-                            }
-                        } else {
-                            false
-                        }
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
-            }
-            _ => false
-        }
-    } else {
-        false
-    };
+    let returns_tuple = returns_tuple(func);
     
     // First, try to generate contracts from Orange Paper mathematical properties
     if !spec.contracts.is_empty() && !returns_tuple {
@@ -268,7 +240,7 @@ fn generate_ensures(spec: &FunctionSpec, func: &syn::ItemFn) -> TokenStream {
                 crate::parser::ContractType::Property | 
                 crate::parser::ContractType::EdgeCase => {
                     // Translate mathematical notation to Rust contract
-                    let rust_expr = translate_math_to_rust_contract(&contract.condition, &spec.name, func);
+                    let rust_expr = translate_math_to_rust_contract(&contract.condition, contract.math.as_ref(), &spec.name, func);
                     
                     let comment_str = contract.comment.as_ref()
                         .map(|c| format!(" // {}", c))
@@ -302,7 +274,7 @@ fn generate_ensures(spec: &FunctionSpec, func: &syn::ItemFn) -> TokenStream {
         for property in &spec.properties {
             if matches!(property.property_type, crate::parser::PropertyType::Ensures | crate::parser::PropertyType::Invariant) {
                 // Translate mathematical notation to Rust contract
-                let rust_expr = translate_math_to_rust_contract(&property.statement, &spec.name, func);
+                let rust_expr = translate_math_to_rust_contract(&property.statement, None, &spec.name, func);
                 
                 let comment_str = format!(" // {}", property.name);
                 let comment_tokens: TokenStream = comment_str.parse().unwrap_or_default();
@@ -324,7 +296,7 @@ fn generate_ensures(spec: &FunctionSpec, func: &syn::ItemFn) -> TokenStream {
     // Try extracting from theorems
     for theorem in &spec.theorems {
         // Translate theorem statement to Rust contract
-        let rust_expr = translate_math_to_rust_contract(&theorem.statement, &spec.name, func);
+        let rust_expr = translate_math_to_rust_contract(&theorem.statement, None, &spec.name, func);
         
         let comment_str = format!(" // Theorem {}: {}", theorem.number, theorem.name);
         let comment_tokens: TokenStream = comment_str.parse().unwrap_or_default();
@@ -336,7 +308,7 @@ fn generate_ensures(spec: &FunctionSpec, func: &syn::ItemFn) -> TokenStream {
     
     // Try extracting from formula
     if let Some(formula) = &spec.formula {
-        let rust_expr = translate_math_to_rust_contract(formula, &spec.name, func);
+        let rust_expr = translate_math_to_rust_contract(formula, None, &spec.name, func);
         ensures.push(quote! {
             #[blvm_spec_lock::ensures(#rust_expr)] // From formula
         });
@@ -351,72 +323,12 @@ fn generate_ensures(spec: &FunctionSpec, func: &syn::ItemFn) -> TokenStream {
     }
 }
 
-/// Convert Rust function name (snake_case) to Orange Paper function name (PascalCase)
-fn rust_to_pascal_case(rust_name: &str) -> String {
-    rust_name
-        .split('_')
-        .map(|word| {
-            let mut chars = word.chars();
-            match chars.next() {
-                None => String::new(),
-                Some(first) => first.to_uppercase().collect::<String>() + &chars.as_str(),
-            }
-        })
-        .collect()
-}
-
-/// Generate name variations for improved matching
-/// e.g., "check_bip30" -> ["CheckBip30", "BIP30", "CheckBIP30", "Bip30"]
-fn generate_name_variations(func_name: &str) -> Vec<String> {
-    let mut variations = Vec::new();
-    
-    // Original PascalCase
-    variations.push(rust_to_pascal_case(func_name));
-    
-    // Remove common prefixes
-    let without_check = func_name.strip_prefix("check_").unwrap_or(func_name);
-    let without_verify = without_check.strip_prefix("verify_").unwrap_or(without_check);
-    let without_calculate = without_verify.strip_prefix("calculate_").unwrap_or(without_verify);
-    let without_get = without_calculate.strip_prefix("get_").unwrap_or(without_calculate);
-    
-    if without_check != func_name {
-        variations.push(rust_to_pascal_case(without_check));
-    }
-    if without_verify != without_check {
-        variations.push(rust_to_pascal_case(without_verify));
-    }
-    if without_calculate != without_verify {
-        variations.push(rust_to_pascal_case(without_calculate));
-    }
-    if without_get != without_calculate {
-        variations.push(rust_to_pascal_case(without_get));
-    }
-    
-    // Handle BIP/script variations
-    if func_name.contains("bip") {
-        let bip_upper = func_name.replace("bip", "BIP").replace("_", "");
-        variations.push(bip_upper);
-        let bip_pascal = rust_to_pascal_case(func_name).replace("Bip", "BIP");
-        variations.push(bip_pascal);
-    }
-    
-    // Remove "With" suffixes
-    if func_name.contains("_with_") {
-        let without_with: Vec<&str> = func_name.split("_with_").collect();
-        if !without_with.is_empty() {
-            variations.push(rust_to_pascal_case(without_with[0]));
-        }
-    }
-    
-    variations
-}
-
 /// Process #[spec_locked] attribute
 pub fn process_spec_locked(args: proc_macro::TokenStream, input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     use syn::{parse_macro_input, ItemFn};
     
     // Parse the function
-    let func = parse_macro_input!(input as ItemFn);
+    let func = strip_loop_contracts(parse_macro_input!(input as ItemFn));
     // Parse arguments
     let args = parse_macro_input!(args as SpecLockedArgs);
     
@@ -725,6 +637,13 @@ pub fn process_spec_locked(args: proc_macro::TokenStream, input: proc_macro::Tok
         func_spec.description.as_deref().unwrap_or("")
     );
 
+    // With the `runtime-checks` feature, the contracts are also checked whenever the function runs
+    let func = if cfg!(feature = "runtime-checks") {
+        crate::runtime_checks::instrument(func, &section_id_display)
+    } else {
+        func
+    };
+
     // Return function with documentation and generated contracts
     let doc_str_lit = LitStr::new(&spec_doc, Span::call_site());
     
@@ -740,10 +659,21 @@ pub fn process_spec_locked(args: proc_macro::TokenStream, input: proc_macro::Tok
 ///
 /// Converts LaTeX math expressions like:
 /// - `$\text{GetBlockSubsidy}(h) \geq 0$` → `*result >= 0`
-/// - `$h = 0 \implies \text{GetBlockSubsidy}(h) = 50 \times C$` → `*height == 0 ==> *result == INITIAL_SUBSIDY`
-fn translate_math_to_rust_contract(math_expr: &str, func_name: &str, func: &syn::ItemFn) -> TokenStream {
-    let mut translated = math_expr.to_string();
-    
+/// - `$h = 0 \implies \text{GetBlockSubsidy}(h) = 50 \times C$` → `!(*height == 0) || (*result == (50 * SATOSHIS_PER_BTC))`
+///
+/// `parsed` is the already-parsed math, if the caller has it. Arguments of the
+/// spec function's application are mapped positionally onto the Rust parameters.
+/// For a function returning `Result` or `Option`, the spec function's value is
+/// the `Ok`/`Some` payload: `result.is_ok() ==> (result.unwrap() >= 0)`.
+fn translate_math_to_rust_contract(math_expr: &str, parsed: Option<&MathExpr>, func_name: &str, func: &syn::ItemFn) -> TokenStream {
+    let expr = match parsed {
+        Some(expr) => expr.clone(),
+        None => match math::parse_math(math::math_content(math_expr)) {
+            Ok(expr) => expr,
+            Err(_) => return quote! { /* Math translation failed: #math_expr */ },
+        },
+    };
+
     // Get parameter names from function signature
     let param_names: Vec<String> = func.sig.inputs.iter()
         .filter_map(|input| {
@@ -758,112 +688,126 @@ fn translate_math_to_rust_contract(math_expr: &str, func_name: &str, func: &syn:
             }
         })
         .collect();
-    
-    // Translate LaTeX operators to Rust contract syntax
-    translated = translated.replace(r"\geq", ">=");
-    translated = translated.replace(r"\leq", "<=");
-    translated = translated.replace(r"\implies", "==>");
-    translated = translated.replace(r"\iff", "==");
-    translated = translated.replace(r"\land", "&&");
-    translated = translated.replace(r"\lor", "||");
-    translated = translated.replace(r"\times", "*");
-    translated = translated.replace(r"\text{", "");
-    translated = translated.replace("}", "");
-    
-    // Replace function calls with *result
-    // Pattern: FunctionName(args) → *result
-    // BUT: Skip this for tuple return types (they don't support dereferencing)
-    let func_name_pattern = Regex::new(&format!(r"\b{}", regex::escape(func_name))).unwrap();
-    // Check if function returns a tuple - if so, don't replace with *result
-    let returns_tuple = if let syn::ReturnType::Type(_, return_type) = &func.sig.output {
-        match return_type.as_ref() {
-            syn::Type::Tuple(_) => true,
-            syn::Type::Path(type_path) => {
-                if let Some(segment) = type_path.path.segments.last() {
-                    if segment.ident == "Result" {
-                        if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
-                            if let Some(syn::GenericArgument::Type(inner_type)) = args.args.first() {
-                                matches!(inner_type, syn::Type::Tuple(_))
-                            } else {
-                                false
-                            }
-                        } else {
-                            false
-                        }
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
-            }
-            _ => false
-        }
-    } else {
-        false
+
+    // Tuple results (and Result<tuple, _>) can't be compared to a spec value
+    if returns_tuple(func) {
+        return quote! { /* Math translation failed (tuple result): #math_expr */ };
+    }
+    let success = success_test(func);
+    let mut ctx = math::RustContext::for_function(func_name)
+        .with_vars(math::CONSENSUS_CONSTANTS.iter().copied());
+    ctx.result = match success {
+        Some(_) => "result.unwrap()".to_string(),
+        None => "*result".to_string(),
     };
-    
-    if !returns_tuple {
-        translated = func_name_pattern.replace_all(&translated, "*result").to_string();
-    } else {
-        // For tuple return types, replace with match expression instead
-        translated = func_name_pattern.replace_all(&translated, "match result { Ok(_) => true, Err(_) => true }").to_string();
+
+    // Conventional names (h → height) for parameters the function has, then
+    // the spec call's arguments positionally: GetBlockSubsidy(h) with fn(height) → h ↦ *height
+    for (math_name, rust_name) in math::CONVENTIONAL_PARAMS {
+        if param_names.iter().any(|p| p == rust_name) {
+            ctx.vars.insert(math_name.to_string(), format!("*{}", rust_name));
+        }
     }
-    
-    // Replace common variable names with parameter names + *
-    // Common patterns: h → *height, tx → *tx, etc.
-    if param_names.len() == 1 {
-        let param_name = &param_names[0];
-        // Simple heuristic: if math uses single letter, map to first parameter
-        translated = translated.replace("h", &format!("*{}", param_name));
-    } else {
-        // Multi-parameter: try to match common patterns
-        translated = translated.replace("h", "*height");
-        translated = translated.replace("tx", "*tx");
-        translated = translated.replace("us", "*utxo_set");
+    if let Some(args) = expr.find_call(func_name) {
+        for (arg, param) in args.iter().zip(&param_names) {
+            if let MathExpr::Var(name) = arg {
+                ctx.vars.insert(name.clone(), format!("*{}", param));
+            }
+        }
     }
-    
-    // Replace mathematical constants
-    translated = translated.replace("50 \\times C", "INITIAL_SUBSIDY");
-    translated = translated.replace("25 \\times C", "INITIAL_SUBSIDY / 2");
-    translated = translated.replace("12.5 \\times C", "INITIAL_SUBSIDY / 4");
-    translated = translated.replace("MAX\\_MONEY", "MAX_MONEY");
-    translated = translated.replace("H", "HALVING_INTERVAL");
-    
-    // Replace set notation
-    translated = translated.replace(r"\mathbb{N}", "Natural");
-    translated = translated.replace(r"\mathbb{Z}", "Integer");
-    
-    // Replace array/list access notation
-    translated = translated.replace(r"\[", "[");
-    translated = translated.replace(r"\]", "]");
-    translated = translated.replace(".", ".");
-    
-    // Replace cardinality notation |x| with .len()
-    let cardinality_pattern = Regex::new(r"\|([^|]+)\|").unwrap();
-    translated = cardinality_pattern.replace_all(&translated, "$1.len()").to_string();
-    
-    // Replace superscript notation (e.g., 0^{32} → [0u8; 32])
-    translated = translated.replace("0^{32}", "[0u8; 32]");
-    translated = translated.replace("2^{32} - 1", "0xffffffff");
-    
-    // Clean up: remove $ delimiters
-    translated = translated.replace("$", "");
-    
-    // Replace @ syntax with * for result dereference
-    translated = translated.replace("@", "*");
-    
-    // Replace seq@.len() with seq.len() (direct access for slices/vecs)
-    let seq_len_pattern = Regex::new(r"(\w+)@\.len\(\)").unwrap();
-    translated = seq_len_pattern.replace_all(&translated, "$1.len()").to_string();
-    
-    // Replace old(value@) with old(*value) in postconditions
-    let old_pattern = Regex::new(r"old\((\w+)@\)").unwrap();
-    translated = old_pattern.replace_all(&translated, "old(*$1)").to_string();
-    
+
+    let translated = match (expr.to_rust(&ctx), success) {
+        (Ok(translated), Some(test)) => format!("result.{}() ==> ({})", test, translated),
+        (Ok(translated), None) => translated,
+        (Err(_), _) => return quote! { /* Math translation failed: #math_expr */ },
+    };
+
     // Try to parse as valid Rust contract expression
     translated.parse().unwrap_or_else(|_| {
         // If parsing fails, create a comment with the original math
         quote! { /* Math translation failed: #math_expr -> #translated */ }
     })
 }
+
+/// Remove `#[invariant]` and `#[decreases]` from the loops in a function body
+///
+/// The verification tool reads them from the source; custom attributes on
+/// statements don't compile on stable Rust.
+fn strip_loop_contracts(mut func: ItemFn) -> ItemFn {
+    let block = &func.block;
+    if let Ok(block) = syn::parse2(strip_loop_contract_tokens(quote!(#block))) {
+        *func.block = block;
+    }
+    func
+}
+
+fn strip_loop_contract_tokens(tokens: TokenStream) -> TokenStream {
+    let mut stripped = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                if let Some(TokenTree::Group(group)) = tokens.peek() {
+                    if group.delimiter() == Delimiter::Bracket && is_loop_contract(group.stream()) {
+                        tokens.next();
+                        continue;
+                    }
+                }
+                stripped.push(TokenTree::Punct(punct));
+            }
+            TokenTree::Group(group) => {
+                let mut inner = Group::new(group.delimiter(), strip_loop_contract_tokens(group.stream()));
+                inner.set_span(group.span());
+                stripped.push(TokenTree::Group(inner));
+            }
+            other => stripped.push(other),
+        }
+    }
+    stripped.into_iter().collect()
+}
+
+/// Whether the contents of `#[...]` are a loop contract
+fn is_loop_contract(attribute: TokenStream) -> bool {
+    syn::parse2::<syn::Meta>(attribute)
+        .is_ok_and(|meta| is_contract_path(meta.path(), "invariant") || is_contract_path(meta.path(), "decreases"))
+}
+
+/// Whether a function returns a tuple or `Result<tuple, _>`
+fn returns_tuple(func: &syn::ItemFn) -> bool {
+    let syn::ReturnType::Type(_, return_type) = &func.sig.output else {
+        return false;
+    };
+    match return_type.as_ref() {
+        syn::Type::Tuple(_) => true,
+        syn::Type::Path(type_path) => {
+            let Some(segment) = type_path.path.segments.last() else {
+                return false;
+            };
+            if segment.ident != "Result" {
+                return false;
+            }
+            match &segment.arguments {
+                syn::PathArguments::AngleBracketed(args) => {
+                    matches!(args.args.first(), Some(syn::GenericArgument::Type(syn::Type::Tuple(_))))
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// The test for a successful result (`is_ok`, `is_some`) if the function returns `Result` or `Option`
+fn success_test(func: &syn::ItemFn) -> Option<&'static str> {
+    let syn::ReturnType::Type(_, return_type) = &func.sig.output else {
+        return None;
+    };
+    let syn::Type::Path(type_path) = return_type.as_ref() else {
+        return None;
+    };
+    match type_path.path.segments.last()?.ident.to_string().as_str() {
+        "Result" => Some("is_ok"),
+        "Option" => Some("is_some"),
+        _ => None,
+    }
+}
@@ -1,7 +1,14 @@
 //! Contract AST and parsing
 //!
-//! Defines the structure for verification contracts (requires/ensures)
+//! Defines the structure for verification contracts (requires/ensures) and
+//! loop contracts (invariant/decreases)
+//!
+//! Conditions are Rust expressions, plus implication: `a ==> b` holds when `a`
+//! is false or `b` holds. It binds more loosely than `||`, groups to the right,
+//! and is parsed as `!(a) || (b)`.
 
+use proc_macro2::{Delimiter, Group, Punct, Spacing, Span, TokenStream, TokenTree};
+use quote::format_ident;
 use syn::{Attribute, Expr, ItemFn};
 
 /// A verification contract (precondition or postcondition)
@@ -24,6 +31,18 @@ pub enum ContractType {
     Ensures,
 }
 
+/// Contracts of a loop, from the attributes on the loop itself
+///
+/// Only read by the Z3 loop translation (`translator::loops`).
+#[cfg(feature = "z3")]
+#[derive(Debug, Clone, Default)]
+pub struct LoopContracts {
+    /// #[invariant(condition)]: holds before every iteration and after the loop
+    pub invariants: Vec<Expr>,
+    /// #[decreases(measure)]: non-negative, and strictly smaller after each iteration
+    pub decreases: Option<Expr>,
+}
+
 /// Extract contracts from a function's attributes
 pub fn extract_contracts(func: &ItemFn) -> Vec<Contract> {
     let mut contracts = Vec::new();
@@ -37,42 +56,181 @@ pub fn extract_contracts(func: &ItemFn) -> Vec<Contract> {
     contracts
 }
 
-/// Parse a single attribute to see if it's a contract
-fn parse_contract_attribute(attr: &Attribute) -> Option<Contract> {
-    let path = attr.path();
-    
-    // Check if it's #[requires(...)] or #[ensures(...)]
-    // Handle both bare attributes and crate-prefixed: #[blvm_spec_lock::requires]
-    let is_requires = path.is_ident("requires") || 
-        (path.segments.len() == 2 && 
-         path.segments[0].ident == "blvm_spec_lock" && 
-         path.segments[1].ident == "requires");
-    
-    let is_ensures = path.is_ident("ensures") || 
-        (path.segments.len() == 2 && 
-         path.segments[0].ident == "blvm_spec_lock" && 
-         path.segments[1].ident == "ensures");
-    
-    if is_requires {
-        // Parse the condition from the attribute
-        if let Ok(expr) = attr.parse_args::<Expr>() {
-            return Some(Contract {
-                contract_type: ContractType::Requires,
-                condition: expr,
-                comment: extract_comment(attr),
-            });
+/// Extract the contracts of a loop from its attributes
+///
+/// A later `#[decreases]` replaces an earlier one.
+#[cfg(feature = "z3")]
+pub fn extract_loop_contracts(attrs: &[Attribute]) -> LoopContracts {
+    let mut contracts = LoopContracts::default();
+
+    for attr in attrs {
+        let is_invariant = is_contract_attribute(attr, "invariant");
+        if !is_invariant && !is_contract_attribute(attr, "decreases") {
+            continue;
         }
-    } else if is_ensures {
-        if let Ok(expr) = attr.parse_args::<Expr>() {
-            return Some(Contract {
-                contract_type: ContractType::Ensures,
-                condition: expr,
-                comment: extract_comment(attr),
-            });
+        let Ok(tokens) = attr.parse_args::<TokenStream>() else {
+            continue;
+        };
+        if let Ok(expr) = syn::parse2(rewrite_implications(tokens)) {
+            if is_invariant {
+                contracts.invariants.push(expr);
+            } else {
+                contracts.decreases = Some(expr);
+            }
         }
     }
 
-    None
+    contracts
+}
+
+/// Check for `#[name(...)]`, bare or crate-prefixed (`#[blvm_spec_lock::name(...)]`)
+pub fn is_contract_attribute(attr: &Attribute, name: &str) -> bool {
+    is_contract_path(attr.path(), name)
+}
+
+/// Check whether an attribute path names the contract `name`
+pub fn is_contract_path(path: &syn::Path, name: &str) -> bool {
+    path.is_ident(name) ||
+        (path.segments.len() == 2 &&
+         path.segments[0].ident == "blvm_spec_lock" &&
+         path.segments[1].ident == name)
+}
+
+/// Rewrite each `a ==> b` to `!(a) || (b)`, including inside brackets
+///
+/// Commas and semicolons end an implication, and the parameters of a closure
+/// (`|x| a ==> b`) or the pattern of a match arm (`p => a ==> b`) stay in front.
+pub fn rewrite_implications(tokens: TokenStream) -> TokenStream {
+    let mut rewritten = Vec::new();
+    let mut segment = Vec::new();
+    for token in tokens {
+        match token {
+            TokenTree::Punct(punct) if matches!(punct.as_char(), ',' | ';') => {
+                rewritten.extend(rewrite_segment(std::mem::take(&mut segment)));
+                rewritten.push(TokenTree::Punct(punct));
+            }
+            TokenTree::Group(group) => {
+                let mut inner = Group::new(group.delimiter(), rewrite_implications(group.stream()));
+                inner.set_span(group.span());
+                segment.push(TokenTree::Group(inner));
+            }
+            other => segment.push(other),
+        }
+    }
+    rewritten.extend(rewrite_segment(segment));
+    rewritten.into_iter().collect()
+}
+
+/// Replace each `old(e)` with a variable holding `e` on entry, added to `old_values`
+///
+/// The variables are named `__spec_lock_old_N`. Returns `None` for a condition
+/// that can't be evaluated by running code (`forall`/`exists`).
+pub fn capture_old(tokens: TokenStream, old_values: &mut Vec<(syn::Ident, TokenStream)>) -> Option<TokenStream> {
+    let mut captured = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    let mut after_dot = false;
+    while let Some(token) = tokens.next() {
+        let is_dot = matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '.');
+        match token {
+            TokenTree::Ident(ident) if !after_dot && matches!(tokens.peek(), Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis) => {
+                if ident == "forall" || ident == "exists" {
+                    return None;
+                }
+                if ident == "old" {
+                    let Some(TokenTree::Group(argument)) = tokens.next() else {
+                        unreachable!("peeked a group");
+                    };
+                    let name = format_ident!("__spec_lock_old_{}", old_values.len(), span = Span::call_site());
+                    old_values.push((name.clone(), argument.stream()));
+                    captured.push(TokenTree::Ident(name));
+                } else {
+                    captured.push(TokenTree::Ident(ident));
+                }
+            }
+            TokenTree::Group(group) => {
+                let mut inner = Group::new(group.delimiter(), capture_old(group.stream(), old_values)?);
+                inner.set_span(group.span());
+                captured.push(TokenTree::Group(inner));
+            }
+            other => captured.push(other),
+        }
+        after_dot = is_dot;
+    }
+    Some(captured.into_iter().collect())
+}
+
+/// Rewrite the implications of a segment without top-level separators
+fn rewrite_segment(tokens: Vec<TokenTree>) -> Vec<TokenTree> {
+    let start = condition_start(&tokens);
+    let Some(arrow) = (start..tokens.len()).find(|&i| is_implication(&tokens[i..])) else {
+        return tokens;
+    };
+    let mut rewritten = tokens[..start].to_vec();
+    let premise: TokenStream = tokens[start..arrow].iter().cloned().collect();
+    let conclusion: TokenStream = rewrite_segment(tokens[arrow + 3..].to_vec()).into_iter().collect();
+    rewritten.push(TokenTree::Punct(Punct::new('!', Spacing::Alone)));
+    rewritten.push(TokenTree::Group(Group::new(Delimiter::Parenthesis, premise)));
+    rewritten.push(TokenTree::Punct(Punct::new('|', Spacing::Joint)));
+    rewritten.push(TokenTree::Punct(Punct::new('|', Spacing::Alone)));
+    rewritten.push(TokenTree::Group(Group::new(Delimiter::Parenthesis, conclusion)));
+    rewritten
+}
+
+/// Where the condition of a segment starts: after closure parameters or a match arm's `=>`
+fn condition_start(tokens: &[TokenTree]) -> usize {
+    if let Some(arm) = (0..tokens.len()).find(|&i| is_fat_arrow(&tokens[i..]) && (i == 0 || !is_implication(&tokens[i - 1..]))) {
+        return arm + 2;
+    }
+    let offset = usize::from(matches!(tokens.first(), Some(TokenTree::Ident(ident)) if ident == "move"));
+    match tokens.get(offset) {
+        Some(TokenTree::Punct(punct)) if punct.as_char() == '|' => {
+            if punct.spacing() == Spacing::Joint && is_punct(tokens.get(offset + 1), '|') {
+                return offset + 2;
+            }
+            (offset + 1..tokens.len())
+                .find(|&i| is_punct(tokens.get(i), '|'))
+                .map_or(0, |close| close + 1)
+        }
+        _ => 0,
+    }
+}
+
+/// Whether the tokens start with `==>`
+fn is_implication(tokens: &[TokenTree]) -> bool {
+    matches!(tokens, [TokenTree::Punct(a), TokenTree::Punct(b), TokenTree::Punct(c), ..]
+        if a.as_char() == '=' && a.spacing() == Spacing::Joint &&
+           b.as_char() == '=' && c.as_char() == '>')
+}
+
+/// Whether the tokens start with `=>` (and not `==>`)
+fn is_fat_arrow(tokens: &[TokenTree]) -> bool {
+    matches!(tokens, [TokenTree::Punct(a), TokenTree::Punct(b), ..]
+        if a.as_char() == '=' && a.spacing() == Spacing::Joint && b.as_char() == '>')
+}
+
+fn is_punct(token: Option<&TokenTree>, c: char) -> bool {
+    matches!(token, Some(TokenTree::Punct(punct)) if punct.as_char() == c)
+}
+
+/// Parse a single attribute to see if it's a contract
+fn parse_contract_attribute(attr: &Attribute) -> Option<Contract> {
+    // Check if it's #[requires(...)] or #[ensures(...)]
+    let contract_type = if is_contract_attribute(attr, "requires") {
+        ContractType::Requires
+    } else if is_contract_attribute(attr, "ensures") {
+        ContractType::Ensures
+    } else {
+        return None;
+    };
+
+    // Parse the condition from the attribute, allowing `==>`
+    let tokens = attr.parse_args::<TokenStream>().ok()?;
+    let condition = syn::parse2(rewrite_implications(tokens)).ok()?;
+    Some(Contract {
+        contract_type,
+        condition,
+        comment: extract_comment(attr),
+    })
 }
 
 /// Extract comment from attribute if present
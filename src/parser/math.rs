@@ -0,0 +1,1363 @@
+//! Math expression parser for Orange Paper formulas
+//!
+//! Parses the LaTeX/Unicode subset used by the Orange Paper (`\text{}`,
+//! `\times`, `\geq`, `\implies`, `\sum`, `\lfloor \rfloor`, `2^{...}`,
+//! `\begin{cases}`, set membership and quantifiers) into a typed AST, and
+//! renders that AST as Rust.
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Orange Paper constants and their names in consensus code
+pub const CONSENSUS_CONSTANTS: &[(&str, &str)] = &[
+    ("H", "HALVING_INTERVAL"),
+    ("C", "SATOSHIS_PER_BTC"),
+    ("M_max", "MAX_MONEY"),
+];
+
+/// Conventional Orange Paper variable names and the Rust parameters they denote
+pub const CONVENTIONAL_PARAMS: &[(&str, &str)] = &[
+    ("h", "height"),
+    ("tx", "tx"),
+    ("b", "block"),
+    ("us", "utxo_set"),
+];
+
+/// A parsed math expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum MathExpr {
+    /// Integer or decimal literal
+    Number(String),
+    /// Variable or constant, with subscripts folded in (`M_{max}` → `M_max`)
+    Var(String),
+    /// Function application, e.g. `\text{GetBlockSubsidy}(h)`
+    Call { name: String, args: Vec<MathExpr> },
+    /// Field access, e.g. `tx.inputs`
+    Field { base: Box<MathExpr>, field: String },
+    /// Indexing, e.g. `tx.inputs[i]`
+    Index { base: Box<MathExpr>, index: Box<MathExpr> },
+    Unary { op: UnaryOp, expr: Box<MathExpr> },
+    Binary { op: BinaryOp, left: Box<MathExpr>, right: Box<MathExpr> },
+    /// `\lfloor x \rfloor`
+    Floor(Box<MathExpr>),
+    /// `\lceil x \rceil`
+    Ceil(Box<MathExpr>),
+    /// `|x|`: size of a set or sequence
+    Cardinality(Box<MathExpr>),
+    /// `\sum_{var=from}^{to} body`
+    Sum { var: String, from: Box<MathExpr>, to: Box<MathExpr>, body: Box<MathExpr> },
+    /// `\begin{cases} value & \text{if } cond \\ ... \end{cases}` (`None` = otherwise)
+    Cases(Vec<(MathExpr, Option<MathExpr>)>),
+    /// `x \in S` or `x \notin S`
+    Member { element: Box<MathExpr>, set: MathSet, negated: bool },
+    /// `\forall x, y \in S: body` or `\exists x \in S: body`
+    Quantified { quantifier: Quantifier, vars: Vec<String>, domain: Option<MathSet>, body: Box<MathExpr> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Implies,
+    Iff,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantifier {
+    Forall,
+    Exists,
+}
+
+/// A set used in membership tests and quantifier domains
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MathSet {
+    /// ℕ
+    Naturals,
+    /// ℤ
+    Integers,
+    /// ℝ
+    Reals,
+    /// 𝔹 / {0, 1}
+    Booleans,
+    /// Any other named set (e.g. `\mathcal{TX}`, `UTXO`)
+    Named(String),
+}
+
+/// Parse a math expression (without `$` delimiters; see [`math_content`])
+pub fn parse_math(input: &str) -> Result<MathExpr, String> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.parse_expr()?;
+    // Tolerate sentence punctuation after the formula
+    while matches!(parser.peek(), Some(Token::Dot | Token::Comma)) {
+        parser.pos += 1;
+    }
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(format!("Unexpected {:?} after expression", token)),
+    }
+}
+
+/// The math inside the first `$$...$$` or `$...$` span, or the whole text if there is none
+pub fn math_content(text: &str) -> &str {
+    for delimiter in ["$$", "$"] {
+        if let Some(start) = text.find(delimiter) {
+            let rest = &text[start + delimiter.len()..];
+            if let Some(end) = rest.find(delimiter) {
+                return rest[..end].trim();
+            }
+        }
+    }
+    text.trim()
+}
+
+// ---------------------------------------------------------------------------
+// Tokenizer
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(String),
+    Ident(String),
+    /// Contents of `\text{...}` and friends
+    Text(String),
+    Set(MathSet),
+    Plus,
+    Minus,
+    Times,
+    Div,
+    Mod,
+    Caret,
+    Underscore,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Not,
+    Implies,
+    Iff,
+    Forall,
+    Exists,
+    In,
+    NotIn,
+    Sum,
+    Frac,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    LFloor,
+    RFloor,
+    LCeil,
+    RCeil,
+    Bar,
+    Comma,
+    Colon,
+    Dot,
+    Amp,
+    RowSep,
+    BeginCases,
+    EndCases,
+}
+
+/// End of a number written with thousands separators (`210,000`) starting at `start`
+///
+/// The whole run must have the `d{1,3}(,ddd)+` form; anything else is left to
+/// the plain digit scan so that `1,2345` stays two numbers.
+fn thousands_separated_end(chars: &[char], start: usize) -> Option<usize> {
+    let digits = |from: usize| chars[from..].iter().take_while(|c| c.is_ascii_digit()).count();
+    let lead = digits(start);
+    if !(1..=3).contains(&lead) {
+        return None;
+    }
+    let mut end = start + lead;
+    let mut groups = 0;
+    while chars.get(end) == Some(&',') && chars.get(end + 1).is_some_and(|c| c.is_ascii_digit()) {
+        if digits(end + 1) != 3 {
+            return None;
+        }
+        end += 4;
+        groups += 1;
+    }
+    (groups > 0).then_some(end)
+}
+
+/// Whether the next token sits inside the parentheses of a call such as `\max(0,100)`,
+/// where a comma separates arguments rather than digit groups
+fn in_argument_list(tokens: &[Token]) -> bool {
+    let mut depth = 0usize;
+    for (index, token) in tokens.iter().enumerate().rev() {
+        match token {
+            Token::RParen => depth += 1,
+            Token::LParen if depth > 0 => depth -= 1,
+            Token::LParen if index > 0 && matches!(tokens[index - 1], Token::Ident(_) | Token::Text(_)) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let input = input.replace(r"\_", "_");
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            ' ' | '\t' | '\n' | '\r' | '$' => i += 1,
+            '\\' => {
+                let start = i + 1;
+                let mut end = start;
+                while end < chars.len() && chars[end].is_ascii_alphabetic() {
+                    end += 1;
+                }
+                if end == start {
+                    // Single-character commands: \\ (row separator), \{ \}, spacing
+                    match next {
+                        Some('\\') => tokens.push(Token::RowSep),
+                        Some('{') => tokens.push(Token::LBrace),
+                        Some('}') => tokens.push(Token::RBrace),
+                        Some('|') => tokens.push(Token::Bar),
+                        Some(',' | ';' | ':' | '!' | ' ') => {}
+                        Some(other) => return Err(format!("Unsupported command \\{}", other)),
+                        None => return Err("Trailing backslash".to_string()),
+                    }
+                    i = start + 1;
+                    continue;
+                }
+                let command: String = chars[start..end].iter().collect();
+                i = end;
+                let token = match command.as_str() {
+                    "text" | "textit" | "textbf" | "mathrm" | "mathit" | "mathsf" | "texttt" | "operatorname" | "mathcal" => {
+                        let (content, after) = read_group(&chars, i)?;
+                        i = after;
+                        Token::Text(content.trim().to_string())
+                    }
+                    "mathbb" => {
+                        let (content, after) = read_group(&chars, i)?;
+                        i = after;
+                        Token::Set(match content.trim() {
+                            "N" => MathSet::Naturals,
+                            "Z" => MathSet::Integers,
+                            "R" => MathSet::Reals,
+                            "B" => MathSet::Booleans,
+                            other => MathSet::Named(other.to_string()),
+                        })
+                    }
+                    "begin" | "end" => {
+                        let (content, after) = read_group(&chars, i)?;
+                        i = after;
+                        match (command.as_str(), content.trim()) {
+                            ("begin", "cases") => Token::BeginCases,
+                            ("end", "cases") => Token::EndCases,
+                            (_, env) => return Err(format!("Unsupported environment {}", env)),
+                        }
+                    }
+                    "times" | "cdot" => Token::Times,
+                    "div" => Token::Div,
+                    "bmod" | "mod" => Token::Mod,
+                    "frac" => Token::Frac,
+                    "geq" | "ge" => Token::Ge,
+                    "leq" | "le" => Token::Le,
+                    "neq" | "ne" => Token::Ne,
+                    "gt" => Token::Gt,
+                    "lt" => Token::Lt,
+                    "land" | "wedge" => Token::And,
+                    "lor" | "vee" => Token::Or,
+                    "lnot" | "neg" => Token::Not,
+                    "implies" | "Rightarrow" | "Longrightarrow" | "rightarrow" | "to" => Token::Implies,
+                    "iff" | "Leftrightarrow" | "Longleftrightarrow" => Token::Iff,
+                    "forall" => Token::Forall,
+                    "exists" => Token::Exists,
+                    "in" => Token::In,
+                    "notin" => Token::NotIn,
+                    "sum" => Token::Sum,
+                    "lfloor" => Token::LFloor,
+                    "rfloor" => Token::RFloor,
+                    "lceil" => Token::LCeil,
+                    "rceil" => Token::RCeil,
+                    "left" | "right" | "big" | "Big" | "bigl" | "bigr" | "Bigl" | "Bigr" | "quad" | "qquad" => continue,
+                    "dots" | "ldots" | "cdots" => return Err("Ellipses are not supported".to_string()),
+                    // Greek letters and named operators such as \max are plain identifiers
+                    _ => Token::Ident(command),
+                };
+                tokens.push(token);
+            }
+            '0'..='9' => {
+                let start = i;
+                match thousands_separated_end(&chars, start) {
+                    Some(end) if !in_argument_list(&tokens) => i = end,
+                    _ => {
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                if i < chars.len() && chars[i] == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit()) {
+                    i += 1;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+                // Thousands separators ("210,000") are dropped
+                let number: String = chars[start..i].iter().filter(|&&d| d != ',').collect();
+                tokens.push(Token::Num(number));
+            }
+            c if c.is_ascii_alphabetic() => {
+                let mut name = String::new();
+                while i < chars.len() {
+                    let c = chars[i];
+                    if c.is_ascii_alphanumeric() {
+                        name.push(c);
+                        i += 1;
+                    } else if c == '_' && chars.get(i + 1) == Some(&'{') {
+                        // Subscript: M_{max} → M_max
+                        let (content, after) = read_group(&chars, i + 1)?;
+                        let subscript: String = content.chars()
+                            .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+                            .collect::<String>()
+                            .replace("text", "");
+                        name.push('_');
+                        name.push_str(&subscript);
+                        i = after;
+                    } else if c == '_' && chars.get(i + 1).is_some_and(|d| d.is_ascii_alphanumeric()) {
+                        name.push(c);
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(name));
+            }
+            _ => {
+                let (token, width) = match (c, next) {
+                    ('>', Some('=')) => (Token::Ge, 2),
+                    ('<', Some('=')) => (Token::Le, 2),
+                    ('!', Some('=')) => (Token::Ne, 2),
+                    ('=', Some('=')) if chars.get(i + 2) == Some(&'>') => (Token::Implies, 3),
+                    ('=', Some('=')) => (Token::Eq, 2),
+                    ('=', Some('>')) => (Token::Implies, 2),
+                    ('&', Some('&')) => (Token::And, 2),
+                    ('|', Some('|')) => (Token::Or, 2),
+                    ('-', Some('>')) => (Token::Implies, 2),
+                    ('=', _) => (Token::Eq, 1),
+                    ('<', _) => (Token::Lt, 1),
+                    ('>', _) => (Token::Gt, 1),
+                    ('!', _) | ('¬', _) => (Token::Not, 1),
+                    ('+', _) => (Token::Plus, 1),
+                    ('-', _) | ('−', _) => (Token::Minus, 1),
+                    ('*', _) | ('×', _) | ('·', _) => (Token::Times, 1),
+                    ('/', _) | ('÷', _) => (Token::Div, 1),
+                    ('%', _) => (Token::Mod, 1),
+                    ('^', _) => (Token::Caret, 1),
+                    ('_', _) => (Token::Underscore, 1),
+                    ('(', _) => (Token::LParen, 1),
+                    (')', _) => (Token::RParen, 1),
+                    ('[', _) => (Token::LBracket, 1),
+                    (']', _) => (Token::RBracket, 1),
+                    ('{', _) => (Token::LBrace, 1),
+                    ('}', _) => (Token::RBrace, 1),
+                    ('|', _) => (Token::Bar, 1),
+                    (',', _) => (Token::Comma, 1),
+                    (':', _) => (Token::Colon, 1),
+                    ('.', _) => (Token::Dot, 1),
+                    ('&', _) => (Token::Amp, 1),
+                    ('≥', _) => (Token::Ge, 1),
+                    ('≤', _) => (Token::Le, 1),
+                    ('≠', _) => (Token::Ne, 1),
+                    ('∧', _) => (Token::And, 1),
+                    ('∨', _) => (Token::Or, 1),
+                    ('⟹' | '⇒' | '→', _) => (Token::Implies, 1),
+                    ('⟺' | '⇔', _) => (Token::Iff, 1),
+                    ('∀', _) => (Token::Forall, 1),
+                    ('∃', _) => (Token::Exists, 1),
+                    ('∈', _) => (Token::In, 1),
+                    ('∉', _) => (Token::NotIn, 1),
+                    ('∑' | 'Σ', _) => (Token::Sum, 1),
+                    ('⌊', _) => (Token::LFloor, 1),
+                    ('⌋', _) => (Token::RFloor, 1),
+                    ('⌈', _) => (Token::LCeil, 1),
+                    ('⌉', _) => (Token::RCeil, 1),
+                    ('ℕ', _) => (Token::Set(MathSet::Naturals), 1),
+                    ('ℤ', _) => (Token::Set(MathSet::Integers), 1),
+                    ('ℝ', _) => (Token::Set(MathSet::Reals), 1),
+                    _ => return Err(format!("Unexpected character '{}'", c)),
+                };
+                tokens.push(token);
+                i += width;
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Read a `{...}` group starting at `start` (after optional spaces), returning its raw content
+fn read_group(chars: &[char], start: usize) -> Result<(String, usize), String> {
+    let mut i = start;
+    while i < chars.len() && chars[i] == ' ' {
+        i += 1;
+    }
+    if chars.get(i) != Some(&'{') {
+        return Err("Expected '{'".to_string());
+    }
+    let mut depth = 0;
+    let content_start = i + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok((chars[content_start..i].iter().collect(), i + 1));
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    Err("Unclosed '{'".to_string())
+}
+
+// ---------------------------------------------------------------------------
+// Parser
+// ---------------------------------------------------------------------------
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), String> {
+        if self.eat(&token) {
+            Ok(())
+        } else {
+            Err(format!("Expected {:?}, found {:?}", token, self.peek()))
+        }
+    }
+
+    /// Lowest precedence: quantifiers extend as far right as possible
+    fn parse_expr(&mut self) -> Result<MathExpr, String> {
+        let quantifier = match self.peek() {
+            Some(Token::Forall) => Quantifier::Forall,
+            Some(Token::Exists) => Quantifier::Exists,
+            _ => return self.parse_iff(),
+        };
+        self.pos += 1;
+
+        let mut vars = Vec::new();
+        loop {
+            match self.peek().cloned() {
+                Some(Token::Ident(name) | Token::Text(name)) => {
+                    self.pos += 1;
+                    vars.push(name);
+                }
+                other => return Err(format!("Expected quantified variable, found {:?}", other)),
+            }
+            // `\forall x, y \in S` lists variables; a comma before the body ends the list
+            if self.peek() == Some(&Token::Comma) && matches!(self.peek_at(1), Some(Token::Ident(_))) &&
+               matches!(self.peek_at(2), Some(Token::Comma | Token::In)) {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+
+        let domain = if self.eat(&Token::In) {
+            Some(self.parse_set()?)
+        } else {
+            None
+        };
+
+        // Separator between binder and body
+        if matches!(self.peek(), Some(Token::Colon | Token::Comma | Token::Dot)) {
+            self.pos += 1;
+        }
+
+        let body = self.parse_expr()?;
+        Ok(MathExpr::Quantified { quantifier, vars, domain, body: Box::new(body) })
+    }
+
+    fn parse_set(&mut self) -> Result<MathSet, String> {
+        match self.peek().cloned() {
+            Some(Token::Set(set)) => {
+                self.pos += 1;
+                Ok(set)
+            }
+            Some(Token::Ident(name) | Token::Text(name)) => {
+                self.pos += 1;
+                Ok(MathSet::Named(name))
+            }
+            other => Err(format!("Expected a set, found {:?}", other)),
+        }
+    }
+
+    fn parse_iff(&mut self) -> Result<MathExpr, String> {
+        let mut left = self.parse_implies()?;
+        while self.eat(&Token::Iff) {
+            let right = self.parse_implies()?;
+            left = binary(BinaryOp::Iff, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_implies(&mut self) -> Result<MathExpr, String> {
+        let left = self.parse_or()?;
+        if self.eat(&Token::Implies) {
+            // Right-associative, and the consequent may be quantified
+            let right = self.parse_expr()?;
+            return Ok(binary(BinaryOp::Implies, left, right));
+        }
+        Ok(left)
+    }
+
+    fn parse_or(&mut self) -> Result<MathExpr, String> {
+        let mut left = self.parse_and()?;
+        while self.eat(&Token::Or) {
+            let right = self.parse_and()?;
+            left = binary(BinaryOp::Or, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<MathExpr, String> {
+        let mut left = self.parse_not()?;
+        while self.eat(&Token::And) {
+            let right = self.parse_not()?;
+            left = binary(BinaryOp::And, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<MathExpr, String> {
+        if self.eat(&Token::Not) {
+            let expr = self.parse_not()?;
+            return Ok(MathExpr::Unary { op: UnaryOp::Not, expr: Box::new(expr) });
+        }
+        if matches!(self.peek(), Some(Token::Forall | Token::Exists)) {
+            return self.parse_expr();
+        }
+        self.parse_comparison()
+    }
+
+    /// Comparisons and membership; chains like `a ≤ b < c` become conjunctions
+    fn parse_comparison(&mut self) -> Result<MathExpr, String> {
+        let first = self.parse_additive()?;
+
+        if matches!(self.peek(), Some(Token::In | Token::NotIn)) {
+            let negated = self.peek() == Some(&Token::NotIn);
+            self.pos += 1;
+            let set = self.parse_set()?;
+            return Ok(MathExpr::Member { element: Box::new(first), set, negated });
+        }
+
+        let mut result: Option<MathExpr> = None;
+        let mut left = first.clone();
+        while let Some(op) = self.peek().and_then(comparison_op) {
+            self.pos += 1;
+            let right = self.parse_additive()?;
+            let comparison = binary(op, left, right.clone());
+            result = Some(match result {
+                Some(previous) => binary(BinaryOp::And, previous, comparison),
+                None => comparison,
+            });
+            left = right;
+        }
+        Ok(result.unwrap_or(first))
+    }
+
+    fn parse_additive(&mut self) -> Result<MathExpr, String> {
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => BinaryOp::Add,
+                Some(Token::Minus) => BinaryOp::Sub,
+                _ => break,
+            };
+            self.pos += 1;
+            let right = self.parse_multiplicative()?;
+            left = binary(op, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<MathExpr, String> {
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Times) => BinaryOp::Mul,
+                Some(Token::Div) => BinaryOp::Div,
+                Some(Token::Mod) => BinaryOp::Mod,
+                // Juxtaposition after a number or bracketed term: `2h`, `50 C`, `\lfloor x \rfloor y`
+                Some(Token::Ident(_) | Token::Text(_) | Token::LParen | Token::LFloor | Token::LCeil)
+                    if implicit_multiplicand(&left) => {
+                    let right = self.parse_unary()?;
+                    left = binary(BinaryOp::Mul, left, right);
+                    continue;
+                }
+                _ => break,
+            };
+            self.pos += 1;
+            let right = self.parse_unary()?;
+            left = binary(op, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<MathExpr, String> {
+        if self.eat(&Token::Minus) {
+            let expr = self.parse_unary()?;
+            return Ok(MathExpr::Unary { op: UnaryOp::Neg, expr: Box::new(expr) });
+        }
+        if self.eat(&Token::Sum) {
+            return self.parse_sum();
+        }
+        self.parse_power()
+    }
+
+    fn parse_power(&mut self) -> Result<MathExpr, String> {
+        let base = self.parse_postfix()?;
+        if self.eat(&Token::Caret) {
+            let exponent = if self.eat(&Token::LBrace) {
+                let exponent = self.parse_expr()?;
+                self.expect(Token::RBrace)?;
+                exponent
+            } else {
+                self.parse_unary()?
+            };
+            return Ok(binary(BinaryOp::Pow, base, exponent));
+        }
+        Ok(base)
+    }
+
+    fn parse_postfix(&mut self) -> Result<MathExpr, String> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            match (self.peek(), self.peek_at(1).cloned()) {
+                (Some(Token::Dot), Some(Token::Ident(field))) => {
+                    self.pos += 2;
+                    // Rust-style `.len()`, `.pow(n)` as emitted for contracts
+                    if self.peek() == Some(&Token::LParen) {
+                        let args = self.parse_args()?;
+                        expr = match (field.as_str(), args.len()) {
+                            ("len", 0) => MathExpr::Cardinality(Box::new(expr)),
+                            ("pow", 1) => binary(BinaryOp::Pow, expr, args.into_iter().next().unwrap()),
+                            _ => return Err(format!("Unsupported method call .{}()", field)),
+                        };
+                    } else {
+                        expr = MathExpr::Field { base: Box::new(expr), field };
+                    }
+                }
+                (Some(Token::LBracket), _) => {
+                    self.pos += 1;
+                    let index = self.parse_expr()?;
+                    self.expect(Token::RBracket)?;
+                    expr = MathExpr::Index { base: Box::new(expr), index: Box::new(index) };
+                }
+                _ => break,
+            }
+        }
+        Ok(expr)
+    }
+
+    fn parse_args(&mut self) -> Result<Vec<MathExpr>, String> {
+        self.expect(Token::LParen)?;
+        let mut args = Vec::new();
+        if !self.eat(&Token::RParen) {
+            loop {
+                args.push(self.parse_expr()?);
+                if self.eat(&Token::RParen) {
+                    break;
+                }
+                self.expect(Token::Comma)?;
+            }
+        }
+        Ok(args)
+    }
+
+    fn parse_primary(&mut self) -> Result<MathExpr, String> {
+        let token = self.peek().cloned().ok_or("Unexpected end of expression")?;
+        self.pos += 1;
+        match token {
+            Token::Num(n) => Ok(MathExpr::Number(n)),
+            Token::Ident(name) | Token::Text(name) => {
+                if self.peek() == Some(&Token::LParen) {
+                    let args = self.parse_args()?;
+                    Ok(MathExpr::Call { name, args })
+                } else {
+                    Ok(MathExpr::Var(name))
+                }
+            }
+            Token::LParen => {
+                let expr = self.parse_expr()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Token::LBrace => {
+                let expr = self.parse_expr()?;
+                self.expect(Token::RBrace)?;
+                Ok(expr)
+            }
+            Token::LFloor => {
+                let expr = self.parse_expr()?;
+                self.expect(Token::RFloor)?;
+                Ok(MathExpr::Floor(Box::new(expr)))
+            }
+            Token::LCeil => {
+                let expr = self.parse_expr()?;
+                self.expect(Token::RCeil)?;
+                Ok(MathExpr::Ceil(Box::new(expr)))
+            }
+            Token::Bar => {
+                let expr = self.parse_additive()?;
+                self.expect(Token::Bar)?;
+                Ok(MathExpr::Cardinality(Box::new(expr)))
+            }
+            Token::Frac => {
+                self.expect(Token::LBrace)?;
+                let numerator = self.parse_expr()?;
+                self.expect(Token::RBrace)?;
+                self.expect(Token::LBrace)?;
+                let denominator = self.parse_expr()?;
+                self.expect(Token::RBrace)?;
+                Ok(binary(BinaryOp::Div, numerator, denominator))
+            }
+            Token::BeginCases => self.parse_cases(),
+            other => Err(format!("Unexpected {:?}", other)),
+        }
+    }
+
+    /// `\sum_{i=from}^{to} body` (after `\sum`)
+    fn parse_sum(&mut self) -> Result<MathExpr, String> {
+        self.expect(Token::Underscore)?;
+        self.expect(Token::LBrace)?;
+        let var = match self.peek().cloned() {
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                name
+            }
+            other => return Err(format!("Expected summation variable, found {:?}", other)),
+        };
+        self.expect(Token::Eq)?;
+        let from = self.parse_expr()?;
+        self.expect(Token::RBrace)?;
+        self.expect(Token::Caret)?;
+        let to = if self.eat(&Token::LBrace) {
+            let to = self.parse_expr()?;
+            self.expect(Token::RBrace)?;
+            to
+        } else {
+            self.parse_postfix()?
+        };
+        let body = self.parse_multiplicative()?;
+        Ok(MathExpr::Sum { var, from: Box::new(from), to: Box::new(to), body: Box::new(body) })
+    }
+
+    /// Rows of `value & \text{if } cond \\` up to `\end{cases}` (after `\begin{cases}`)
+    fn parse_cases(&mut self) -> Result<MathExpr, String> {
+        let mut rows = Vec::new();
+        while !self.eat(&Token::EndCases) {
+            let value = self.parse_expr()?;
+            let mut condition = None;
+            if self.eat(&Token::Amp) {
+                match self.peek().cloned() {
+                    Some(Token::Text(word) | Token::Ident(word)) if is_otherwise(&word) => {
+                        self.pos += 1;
+                    }
+                    Some(Token::Text(word) | Token::Ident(word)) if word == "if" => {
+                        self.pos += 1;
+                        condition = Some(self.parse_expr()?);
+                    }
+                    _ => condition = Some(self.parse_expr()?),
+                }
+                self.eat(&Token::Comma);
+            }
+            rows.push((value, condition));
+            if !self.eat(&Token::RowSep) && self.peek() != Some(&Token::EndCases) {
+                return Err(format!("Expected \\\\ or \\end{{cases}}, found {:?}", self.peek()));
+            }
+        }
+        if rows.is_empty() {
+            return Err("Empty cases".to_string());
+        }
+        Ok(MathExpr::Cases(rows))
+    }
+}
+
+fn binary(op: BinaryOp, left: MathExpr, right: MathExpr) -> MathExpr {
+    MathExpr::Binary { op, left: Box::new(left), right: Box::new(right) }
+}
+
+fn comparison_op(token: &Token) -> Option<BinaryOp> {
+    match token {
+        Token::Eq => Some(BinaryOp::Eq),
+        Token::Ne => Some(BinaryOp::Ne),
+        Token::Lt => Some(BinaryOp::Lt),
+        Token::Le => Some(BinaryOp::Le),
+        Token::Gt => Some(BinaryOp::Gt),
+        Token::Ge => Some(BinaryOp::Ge),
+        _ => None,
+    }
+}
+
+/// Whether a term can be followed by an implicit multiplication
+///
+/// Only numbers and bracketed terms qualify, so prose such as "the subsidy"
+/// is rejected instead of being read as a product.
+fn implicit_multiplicand(expr: &MathExpr) -> bool {
+    match expr {
+        MathExpr::Number(_) | MathExpr::Floor(_) | MathExpr::Ceil(_) => true,
+        MathExpr::Binary { op: BinaryOp::Mul | BinaryOp::Pow, left, .. } => implicit_multiplicand(left),
+        _ => false,
+    }
+}
+
+fn is_otherwise(word: &str) -> bool {
+    matches!(word, "otherwise" | "else")
+}
+
+// ---------------------------------------------------------------------------
+// Queries and display
+// ---------------------------------------------------------------------------
+
+impl MathExpr {
+    /// Arguments of the first application of `function` (case-insensitive)
+    pub fn find_call(&self, function: &str) -> Option<&[MathExpr]> {
+        let mut found = None;
+        self.visit(&mut |expr| {
+            if found.is_none() {
+                if let MathExpr::Call { name, args } = expr {
+                    if name.eq_ignore_ascii_case(function) {
+                        found = Some(args.as_slice());
+                    }
+                }
+            }
+        });
+        found
+    }
+
+    /// Call `f` on this expression and every subexpression
+    pub fn visit<'a>(&'a self, f: &mut impl FnMut(&'a MathExpr)) {
+        f(self);
+        match self {
+            MathExpr::Number(_) | MathExpr::Var(_) => {}
+            MathExpr::Call { args, .. } => args.iter().for_each(|arg| arg.visit(f)),
+            MathExpr::Field { base, .. } => base.visit(f),
+            MathExpr::Index { base, index } => {
+                base.visit(f);
+                index.visit(f);
+            }
+            MathExpr::Unary { expr, .. } | MathExpr::Floor(expr) | MathExpr::Ceil(expr) | MathExpr::Cardinality(expr) => expr.visit(f),
+            MathExpr::Binary { left, right, .. } => {
+                left.visit(f);
+                right.visit(f);
+            }
+            MathExpr::Sum { from, to, body, .. } => {
+                from.visit(f);
+                to.visit(f);
+                body.visit(f);
+            }
+            MathExpr::Cases(rows) => {
+                for (value, condition) in rows {
+                    value.visit(f);
+                    if let Some(condition) = condition {
+                        condition.visit(f);
+                    }
+                }
+            }
+            MathExpr::Member { element, .. } => element.visit(f),
+            MathExpr::Quantified { body, .. } => body.visit(f),
+        }
+    }
+}
+
+impl BinaryOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "×",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "mod",
+            BinaryOp::Pow => "^",
+            BinaryOp::Eq => "=",
+            BinaryOp::Ne => "≠",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "≤",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => "≥",
+            BinaryOp::And => "∧",
+            BinaryOp::Or => "∨",
+            BinaryOp::Implies => "⟹",
+            BinaryOp::Iff => "⟺",
+        }
+    }
+}
+
+impl fmt::Display for MathSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MathSet::Naturals => write!(f, "ℕ"),
+            MathSet::Integers => write!(f, "ℤ"),
+            MathSet::Reals => write!(f, "ℝ"),
+            MathSet::Booleans => write!(f, "𝔹"),
+            MathSet::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Unicode rendering, fully parenthesized below the top level
+impl fmt::Display for MathExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MathExpr::Number(n) => write!(f, "{}", n),
+            MathExpr::Var(name) => write!(f, "{}", name),
+            MathExpr::Call { name, args } => {
+                let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+            MathExpr::Field { base, field } => write!(f, "{}.{}", Grouped(base), field),
+            MathExpr::Index { base, index } => write!(f, "{}[{}]", Grouped(base), index),
+            MathExpr::Unary { op: UnaryOp::Neg, expr } => write!(f, "-{}", Grouped(expr)),
+            MathExpr::Unary { op: UnaryOp::Not, expr } => write!(f, "¬{}", Grouped(expr)),
+            MathExpr::Binary { op, left, right } => {
+                write!(f, "{} {} {}", Grouped(left), op.symbol(), Grouped(right))
+            }
+            MathExpr::Floor(expr) => write!(f, "⌊{}⌋", expr),
+            MathExpr::Ceil(expr) => write!(f, "⌈{}⌉", expr),
+            MathExpr::Cardinality(expr) => write!(f, "|{}|", expr),
+            MathExpr::Sum { var, from, to, body } => {
+                write!(f, "∑_{{{}={}}}^{{{}}} {}", var, from, to, Grouped(body))
+            }
+            MathExpr::Cases(rows) => {
+                let rows: Vec<String> = rows.iter()
+                    .map(|(value, condition)| match condition {
+                        Some(condition) => format!("{} if {}", value, condition),
+                        None => format!("{} otherwise", value),
+                    })
+                    .collect();
+                write!(f, "{{ {} }}", rows.join("; "))
+            }
+            MathExpr::Member { element, set, negated } => {
+                write!(f, "{} {} {}", Grouped(element), if *negated { "∉" } else { "∈" }, set)
+            }
+            MathExpr::Quantified { quantifier, vars, domain, body } => {
+                let symbol = match quantifier {
+                    Quantifier::Forall => "∀",
+                    Quantifier::Exists => "∃",
+                };
+                write!(f, "{}{}", symbol, vars.join(", "))?;
+                if let Some(domain) = domain {
+                    write!(f, " ∈ {}", domain)?;
+                }
+                write!(f, ": {}", body)
+            }
+        }
+    }
+}
+
+/// Displays an expression, parenthesized unless it is atomic
+struct Grouped<'a>(&'a MathExpr);
+
+impl fmt::Display for Grouped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            MathExpr::Binary { .. } | MathExpr::Unary { .. } | MathExpr::Member { .. } | MathExpr::Quantified { .. } | MathExpr::Sum { .. } => {
+                write!(f, "({})", self.0)
+            }
+            expr => write!(f, "{}", expr),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Rust rendering
+// ---------------------------------------------------------------------------
+
+/// How math names map onto Rust when rendering
+#[derive(Debug, Clone, Default)]
+pub struct RustContext {
+    /// Spec function whose application stands for the function's result
+    pub result_function: Option<String>,
+    /// Rust expression for the result (default `result`)
+    pub result: String,
+    /// Math variable or constant → Rust expression
+    pub vars: HashMap<String, String>,
+    /// Spec function → Rust function to call (default: its snake_case name)
+    pub functions: HashMap<String, String>,
+}
+
+impl RustContext {
+    /// Context for a contract on `function`: `Function(...)` renders as `result`
+    pub fn for_function(function: &str) -> Self {
+        RustContext {
+            result_function: Some(function.to_string()),
+            result: "result".to_string(),
+            vars: HashMap::new(),
+            functions: HashMap::new(),
+        }
+    }
+
+    /// Add math → Rust name mappings
+    pub fn with_vars<'a>(mut self, vars: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        for (math, rust) in vars {
+            self.vars.insert(math.to_string(), rust.to_string());
+        }
+        self
+    }
+}
+
+impl MathExpr {
+    /// Render as a Rust expression
+    ///
+    /// Floors of divisions become integer division and `x × 2^{-n}` becomes
+    /// `x >> n`. A universal quantifier over the function's inputs (variables
+    /// mapped to Rust names) is just its body; other quantifiers become the
+    /// `forall(|v: u64| ..)`/`exists(|v: u64| ..)` contract pseudo-functions.
+    pub fn to_rust(&self, ctx: &RustContext) -> Result<String, String> {
+        Ok(match self {
+            MathExpr::Number(n) => n.clone(),
+            MathExpr::Var(name) => ctx.vars.get(name).cloned().unwrap_or_else(|| name.clone()),
+            MathExpr::Call { name, args } => {
+                if ctx.result_function.as_deref().is_some_and(|f| f.eq_ignore_ascii_case(name)) {
+                    return Ok(ctx.result.clone());
+                }
+                let args = args.iter().map(|a| a.to_rust(ctx)).collect::<Result<Vec<_>, _>>()?;
+                match (name.as_str(), args.as_slice()) {
+                    ("max" | "min", [a, b]) => format!("{}.{}({})", rust_receiver(a), name, b),
+                    _ => {
+                        let function = ctx.functions.get(name).cloned().unwrap_or_else(|| pascal_to_snake(name));
+                        format!("{}({})", function, args.join(", "))
+                    }
+                }
+            }
+            MathExpr::Field { base, field } => format!("{}.{}", rust_receiver(&base.to_rust(ctx)?), field),
+            MathExpr::Index { base, index } => format!("{}[{}]", rust_receiver(&base.to_rust(ctx)?), index.to_rust(ctx)?),
+            MathExpr::Unary { op, expr } => {
+                let symbol = if *op == UnaryOp::Neg { "-" } else { "!" };
+                format!("{}{}", symbol, rust_operand(&expr.to_rust(ctx)?))
+            }
+            MathExpr::Binary { op, left, right } => return render_binary(*op, left, right, ctx),
+            MathExpr::Floor(expr) => expr.to_rust(ctx)?,
+            MathExpr::Ceil(expr) => match &**expr {
+                MathExpr::Binary { op: BinaryOp::Div, left, right } => {
+                    format!("{}.div_ceil({})", rust_receiver(&left.to_rust(ctx)?), right.to_rust(ctx)?)
+                }
+                expr => expr.to_rust(ctx)?,
+            },
+            MathExpr::Cardinality(expr) => format!("{}.len()", rust_receiver(&expr.to_rust(ctx)?)),
+            MathExpr::Sum { var, from, to, body } => {
+                let mut inner = ctx.clone();
+                inner.vars.remove(var);
+                format!(
+                    "({}..={}).map(|{}| {}).sum()",
+                    rust_operand(&from.to_rust(ctx)?),
+                    rust_operand(&to.to_rust(ctx)?),
+                    var,
+                    body.to_rust(&inner)?
+                )
+            }
+            MathExpr::Cases(rows) => {
+                let mut rendered = String::new();
+                for (i, (value, condition)) in rows.iter().enumerate() {
+                    let value = value.to_rust(ctx)?;
+                    match condition {
+                        // The last row is the fallback even if it states its condition
+                        Some(condition) if i + 1 < rows.len() => {
+                            if i > 0 {
+                                rendered.push_str(" else ");
+                            }
+                            rendered.push_str(&format!("if {} {{ {} }}", condition.to_rust(ctx)?, value));
+                        }
+                        _ if i == 0 => return Ok(value),
+                        _ => {
+                            rendered.push_str(&format!(" else {{ {} }}", value));
+                            break;
+                        }
+                    }
+                }
+                rendered
+            }
+            MathExpr::Member { element, set, negated } => {
+                let element = rust_operand(&element.to_rust(ctx)?);
+                let holds = match set {
+                    MathSet::Naturals => format!("{} >= 0", element),
+                    MathSet::Integers | MathSet::Reals => "true".to_string(),
+                    MathSet::Booleans => format!("({} == 0 || {} == 1)", element, element),
+                    MathSet::Named(name) => return Err(format!("Membership in {} has no Rust form", name)),
+                };
+                if *negated {
+                    format!("!({})", holds)
+                } else {
+                    holds
+                }
+            }
+            MathExpr::Quantified { quantifier, vars, domain, body } => {
+                // A function's inputs are already universally quantified by its contract
+                let bound: Vec<&String> = vars.iter()
+                    .filter(|v| *quantifier == Quantifier::Exists || !ctx.vars.contains_key(*v))
+                    .collect();
+                if bound.is_empty() {
+                    return body.to_rust(ctx);
+                }
+                let ty = match domain {
+                    Some(MathSet::Naturals) => "u64",
+                    Some(MathSet::Integers) | None => "i64",
+                    Some(set) => return Err(format!("Quantification over {} has no Rust form", set)),
+                };
+                // The spec function applied to a bound variable isn't this call's result
+                let mut inner = RustContext { result_function: None, ..ctx.clone() };
+                for var in &bound {
+                    inner.vars.remove(*var);
+                }
+                let params: Vec<String> = bound.iter().map(|v| format!("{}: {}", v, ty)).collect();
+                let name = if *quantifier == Quantifier::Forall { "forall" } else { "exists" };
+                format!("{}(|{}| {})", name, params.join(", "), body.to_rust(&inner)?)
+            }
+        })
+    }
+}
+
+fn render_binary(op: BinaryOp, left: &MathExpr, right: &MathExpr, ctx: &RustContext) -> Result<String, String> {
+    // x × 2^{-n} → x >> n
+    if op == BinaryOp::Mul {
+        for (value, factor) in [(left, right), (right, left)] {
+            if let Some(shift) = negative_power_of_two(factor) {
+                return Ok(format!("{} >> {}", rust_operand(&value.to_rust(ctx)?), rust_operand(&shift.to_rust(ctx)?)));
+            }
+        }
+    }
+    if op == BinaryOp::Pow {
+        let exponent = right.to_rust(ctx)?;
+        return Ok(match left {
+            MathExpr::Number(two) if two == "2" => format!("(1 << {})", rust_operand(&exponent)),
+            _ if matches!(right, MathExpr::Number(_)) => format!("{}.pow({})", rust_receiver(&left.to_rust(ctx)?), exponent),
+            _ => format!("{}.pow({} as u32)", rust_receiver(&left.to_rust(ctx)?), rust_operand(&exponent)),
+        });
+    }
+
+    let l = rust_operand(&left.to_rust(ctx)?);
+    let r = rust_operand(&right.to_rust(ctx)?);
+    Ok(match op {
+        BinaryOp::Implies => format!("!{} || {}", l, r),
+        BinaryOp::Iff => format!("{} == {}", l, r),
+        _ => format!("{} {} {}", l, rust_symbol(op), r),
+    })
+}
+
+/// `n` if `expr` is `2^{-n}`
+fn negative_power_of_two(expr: &MathExpr) -> Option<&MathExpr> {
+    match expr {
+        MathExpr::Binary { op: BinaryOp::Pow, left, right } if **left == MathExpr::Number("2".to_string()) => {
+            match &**right {
+                MathExpr::Unary { op: UnaryOp::Neg, expr } => Some(expr),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn rust_symbol(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Mod => "%",
+        BinaryOp::Eq => "==",
+        BinaryOp::Ne => "!=",
+        BinaryOp::Lt => "<",
+        BinaryOp::Le => "<=",
+        BinaryOp::Gt => ">",
+        BinaryOp::Ge => ">=",
+        BinaryOp::And => "&&",
+        BinaryOp::Or => "||",
+        BinaryOp::Pow | BinaryOp::Implies | BinaryOp::Iff => unreachable!("rendered specially"),
+    }
+}
+
+/// Parenthesize a rendered operand unless it is a single token or call
+fn rust_operand(rendered: &str) -> String {
+    let atomic = rendered.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '*'))
+        || (rendered.ends_with(')') && rendered.starts_with(|c: char| c.is_alphanumeric() || c == '(') && balanced_call(rendered));
+    if atomic {
+        rendered.to_string()
+    } else {
+        format!("({})", rendered)
+    }
+}
+
+/// Parenthesize a method/field receiver, including dereferences (`(*tx).inputs`)
+fn rust_receiver(rendered: &str) -> String {
+    if rendered.starts_with('*') {
+        format!("({})", rendered)
+    } else {
+        rust_operand(rendered)
+    }
+}
+
+/// Whether `rendered` is a single call or parenthesized group, e.g. `f(a, b)` or `(a + b)`
+fn balanced_call(rendered: &str) -> bool {
+    let Some(open) = rendered.find('(') else {
+        return false;
+    };
+    if !rendered[..open].chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | ':')) {
+        return false;
+    }
+    let mut depth = 0;
+    for (i, c) in rendered.char_indices().skip(open) {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i == rendered.len() - 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// `GetBlockSubsidy` → `get_block_subsidy`
+fn pascal_to_snake(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 && !snake.ends_with('_') {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subsidy_formula() {
+        let formula = r"\text{GetBlockSubsidy}(h) = \begin{cases} 0 & \text{if } \lfloor h / H \rfloor \geq 64 \\ 50 \times C \times 2^{-\lfloor h / H \rfloor} & \text{otherwise} \end{cases}";
+        let expr = parse_math(formula).unwrap();
+        let MathExpr::Binary { op: BinaryOp::Eq, left, right } = &expr else {
+            panic!("expected equation, got {:?}", expr);
+        };
+        assert_eq!(**left, MathExpr::Call { name: "GetBlockSubsidy".to_string(), args: vec![MathExpr::Var("h".to_string())] });
+
+        let ctx = RustContext::default().with_vars([("h", "height")]);
+        assert_eq!(
+            right.to_rust(&ctx).unwrap(),
+            "if (height / H) >= 64 { 0 } else { (50 * C) >> (height / H) }"
+        );
+    }
+
+    #[test]
+    fn test_parse_quantified_theorem() {
+        let expr = parse_math("∀h ∈ ℕ: GetBlockSubsidy(h) ≥ 0 ∧ GetBlockSubsidy(h) ≤ 50 × C").unwrap();
+        let MathExpr::Quantified { quantifier: Quantifier::Forall, vars, domain, body } = &expr else {
+            panic!("expected quantifier, got {:?}", expr);
+        };
+        assert_eq!(vars, &vec!["h".to_string()]);
+        assert_eq!(domain, &Some(MathSet::Naturals));
+        assert!(matches!(**body, MathExpr::Binary { op: BinaryOp::And, .. }));
+        assert_eq!(expr.find_call("getblocksubsidy"), Some(&[MathExpr::Var("h".to_string())][..]));
+
+        // Quantifiers over function inputs are implicit; others become `forall(..)`
+        let ctx = RustContext::for_function("GetBlockSubsidy").with_vars(CONSENSUS_CONSTANTS.iter().copied());
+        assert_eq!(
+            expr.to_rust(&ctx).unwrap(),
+            "forall(|h: u64| (get_block_subsidy(h) >= 0) && (get_block_subsidy(h) <= (50 * SATOSHIS_PER_BTC)))"
+        );
+        let exists = parse_math("∃i ∈ ℕ: i < |tx.inputs| ∧ IsCoinbase(tx.inputs[i])").unwrap();
+        assert_eq!(
+            exists.to_rust(&RustContext::default()).unwrap(),
+            "exists(|i: u64| (i < tx.inputs.len()) && is_coinbase(tx.inputs[i]))"
+        );
+        let ctx = ctx.with_vars([("h", "height")]);
+        assert_eq!(expr.to_rust(&ctx).unwrap(), "(result >= 0) && (result <= (50 * SATOSHIS_PER_BTC))");
+    }
+
+    #[test]
+    fn test_parse_operators() {
+        let sum = parse_math(r"\text{TotalSupply}(h) = \sum_{i=0}^{h} \text{GetBlockSubsidy}(i)").unwrap();
+        let ctx = RustContext::for_function("TotalSupply").with_vars([("h", "height")]);
+        assert_eq!(sum.to_rust(&ctx).unwrap(), "result == ((0..=height).map(|i| get_block_subsidy(i)).sum())");
+
+        let implication = parse_math(r"x \geq 1 \implies |tx.inputs| > 0").unwrap();
+        assert_eq!(implication.to_rust(&RustContext::default()).unwrap(), "!(x >= 1) || (tx.inputs.len() > 0)");
+
+        let chain = parse_math("0 ≤ fee ≤ M_{max}").unwrap();
+        assert_eq!(chain.to_string(), "(0 ≤ fee) ∧ (fee ≤ M_max)");
+
+        assert_eq!(parse_math("210,000").unwrap(), MathExpr::Number("210000".to_string()));
+        assert_eq!(parse_math("2,100,000").unwrap(), MathExpr::Number("2100000".to_string()));
+        // Commas between call arguments aren't digit groups
+        let max = parse_math(r"\max(0,100)").unwrap();
+        assert_eq!(max, MathExpr::Call {
+            name: "max".to_string(),
+            args: vec![MathExpr::Number("0".to_string()), MathExpr::Number("100".to_string())],
+        });
+        let call = parse_math("f(1,234)").unwrap();
+        assert!(matches!(call, MathExpr::Call { ref args, .. } if args.len() == 2));
+        assert_eq!(math_content("Subsidy: $h \\in \\mathbb{N}$ always"), "h \\in \\mathbb{N}");
+    }
+
+    #[test]
+    fn test_prose_is_not_math() {
+        // Letters inside words are never rewritten or multiplied together
+        assert!(parse_math("the subsidy halves every H blocks").is_err());
+        let expr = parse_math(r"\text{the} = h").unwrap();
+        assert_eq!(expr.to_rust(&RustContext::default().with_vars([("h", "height")])).unwrap(), "the == height");
+    }
+}
@@ -1,10 +1,13 @@
 //! Parser module for BLVM Spec Lock
 //!
 //! This module contains:
-//! - `contracts`: Parses #[requires] and #[ensures] attributes from Rust functions
+//! - `contracts`: Parses #[requires] and #[ensures] attributes from Rust functions, and
+//!   #[invariant] and #[decreases] attributes from loops
 //! - `orange_paper`: Parses Orange Paper markdown to extract function specifications
+//! - `math`: Parses Orange Paper LaTeX/Unicode formulas and renders them as Rust
 
 pub mod contracts;
+pub mod math;
 pub mod orange_paper;
 
 // Re-export Orange Paper types (used by macro_impl)
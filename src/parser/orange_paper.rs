@@ -3,6 +3,7 @@
 //! Parses Orange Paper markdown to extract function specifications, theorems, and properties
 //! and links them to Rust implementations.
 
+use crate::parser::math::{self, BinaryOp, MathExpr, RustContext};
 use regex::Regex;
 use std::collections::HashMap;
 
@@ -21,7 +22,7 @@ pub struct FunctionSpec {
     pub theorems: Vec<Theorem>,
     /// Contracts extracted from properties
     pub contracts: Vec<Contract>,
-    /// Raw markdown content for this section
+    /// Raw markdown block for this function
     pub content: String,
     /// Conditions (for backward compatibility with macro_impl)
     pub conditions: Vec<String>,
@@ -72,6 +73,11 @@ pub struct Contract {
     pub condition: String,
     /// Comment/description
     pub comment: Option<String>,
+    /// Parsed math the condition was rendered from (None if it didn't parse)
+    ///
+    /// Only the macro reads it; the CLI compiles this module too.
+    #[allow(dead_code)]
+    pub math: Option<MathExpr>,
 }
 
 /// Contract type
@@ -195,6 +201,10 @@ impl SpecParser {
         
         for cap in function_re.captures_iter(content) {
             let name = cap.get(1).unwrap().as_str().to_string();
+            // "**Properties**:" introduces a function's property list
+            if name == "Properties" {
+                continue;
+            }
             let signature = cap.get(2).map(|m| m.as_str().to_string());
             
             let mut func_spec = FunctionSpec {
@@ -204,7 +214,7 @@ impl SpecParser {
                 properties: Vec::new(),
                 theorems: Vec::new(),
                 contracts: Vec::new(),
-                content: String::new(),
+                content: function_block(content, &name).unwrap_or_default().to_string(),
                 conditions: Vec::new(),
                 formula: None,
                 description: None,
@@ -239,10 +249,13 @@ impl SpecParser {
             constants = self.extract_constants_from_section(section_id, content)?;
         }
         
-        // Update section with parsed functions and constants
+        let theorems = self.parse_theorems(content, None)?;
+        
+        // Update section with parsed functions, theorems and constants
         if let Some(section) = self.sections.get_mut(section_id) {
             section.content = section_content;
             section.functions = functions;
+            section.theorems = theorems;
             section.constants = constants;
         }
         
@@ -409,21 +422,7 @@ impl SpecParser {
         // Simple pattern without look-ahead
         let property_re = Regex::new(r"(?m)^\s*-\s*\*\*([^:]+)\*\*:\s*(.+)$").map_err(|e| format!("Regex error: {}", e))?;
         
-        // Find the Properties section for this function using string search (avoid regex issues)
-        // Look for function name in bold: **FunctionName**
-        let func_marker = format!("**{}**", func_name);
-        
-        if let Some(func_pos) = content.find(&func_marker) {
-            // Extract properties from the section content after the function name
-            let start_pos = func_pos + func_marker.len();
-            let remaining = &content[start_pos..];
-            
-            // Find the end of this function's section (next function or section header)
-            let next_func = remaining.find("**").unwrap_or(remaining.len());
-            let next_section = remaining.find("\n###").unwrap_or(remaining.len());
-            let block_end = next_func.min(next_section);
-            let block_content = &remaining[..block_end];
-            
+        if let Some(block_content) = function_block(content, func_name) {
             // Look for "**Properties**:" header and extract properties after it
             if let Some(props_start) = block_content.find("**Properties**:") {
                 let props_section = &block_content[props_start..];
@@ -462,6 +461,17 @@ impl SpecParser {
 
     /// Extract theorems
     fn extract_theorems(&self, func: &mut FunctionSpec, content: &str) -> Result<(), String> {
+        let theorems = self.parse_theorems(content, Some(&func.name))?;
+        func.theorems.extend(theorems);
+        Ok(())
+    }
+
+    /// Parse all theorems in section content
+    ///
+    /// `func_name` lets a theorem without math be recognized by mentioning the function.
+    fn parse_theorems(&self, content: &str, func_name: Option<&str>) -> Result<Vec<Theorem>, String> {
+        let mut theorems = Vec::new();
+        
         // Match: **Theorem X.Y.Z** (Name)
         // Use simple, reliable regex
         let theorem_re = Regex::new(r"\*\*Theorem\s+([\d.]+)\*\*[^(]*\(([^)]+)\)").map_err(|e| format!("Regex error: {}", e))?;
@@ -505,7 +515,7 @@ impl SpecParser {
                    potential_statement.contains("≥") ||
                    potential_statement.contains("≤") ||
                    potential_statement.contains("=") ||
-                   potential_statement.contains(&func.name) {
+                   func_name.is_some_and(|name| potential_statement.contains(name)) {
                     statement = potential_statement;
                 }
             }
@@ -522,7 +532,7 @@ impl SpecParser {
                 None
             };
             
-            func.theorems.push(Theorem {
+            theorems.push(Theorem {
                 number,
                 name,
                 statement,
@@ -530,7 +540,7 @@ impl SpecParser {
             });
         }
         
-        Ok(())
+        Ok(theorems)
     }
 
     /// Extract mathematical formula
@@ -553,26 +563,30 @@ impl SpecParser {
                     in_formula = true;
                     formula_content = line.to_string();
                     lines_in_formula = 1;
+                    // A formula opened and closed on the same line is already complete
+                    if line.matches("$$").count() < 2 {
+                        continue;
+                    }
                 } else {
                     // End of formula
                     formula_content.push_str("\n");
                     formula_content.push_str(line);
                     lines_in_formula += 1;
-                    
-                    // Check if this formula matches our function
-                    if formula_content.contains(&latex_func) || 
-                       formula_content.contains(&latex_func_alt) ||
-                       (formula_content.contains("GetBlockSubsidy") && func_name.contains("Subsidy")) ||
-                       (formula_content.contains("TotalSupply") && func_name.contains("Supply")) {
-                        // Clean up formula - remove extra content after closing $$
-                        let cleaned = self.clean_formula(&formula_content);
-                        func.formula = Some(cleaned);
-                        break;
-                    }
-                    in_formula = false;
-                    formula_content.clear();
-                    lines_in_formula = 0;
                 }
+                
+                // Check if this formula matches our function
+                if formula_content.contains(&latex_func) || 
+                   formula_content.contains(&latex_func_alt) ||
+                   (formula_content.contains("GetBlockSubsidy") && func_name.contains("Subsidy")) ||
+                   (formula_content.contains("TotalSupply") && func_name.contains("Supply")) {
+                    // Clean up formula - remove extra content after closing $$
+                    let cleaned = self.clean_formula(&formula_content);
+                    func.formula = Some(cleaned);
+                    break;
+                }
+                in_formula = false;
+                formula_content.clear();
+                lines_in_formula = 0;
             } else if in_formula {
                 if lines_in_formula < MAX_FORMULA_LINES {
                     formula_content.push_str("\n");
@@ -626,6 +640,7 @@ impl SpecParser {
                 contract_type,
                 condition,
                 comment: Some(property.name.clone()),
+                math: math::parse_math(math::math_content(&property.statement)).ok(),
             });
         }
         
@@ -636,90 +651,53 @@ impl SpecParser {
     /// Extracts properties directly from theorem statements
     fn generate_contracts_from_theorems(&self, func: &mut FunctionSpec) -> Result<(), String> {
         for theorem in &func.theorems {
-            // Parse theorem statement to extract properties
-            // Theorem format: "∀h ∈ ℕ: get_block_subsidy(h) ≥ 0 ∧ get_block_subsidy(h) ≤ INITIAL_SUBSIDY"
-            let statement = &theorem.statement;
-            
-            // Split by logical operators (∧, ∨, ⟹, etc.)
-            // For now, handle simple conjunctions (∧)
-            let parts: Vec<&str> = statement.split('∧').collect();
-            
-            for part in parts {
-                let part = part.trim();
-                
-                // Check if this part mentions the function
-                let func_name_lower = func.name.to_lowercase();
-                if part.to_lowercase().contains(&func_name_lower) || 
-                   part.contains(&format!(r"\text{{{}}}", func.name)) {
-                    
-                    // Translate to Rust contract
-                    let condition = self.translate_theorem_statement_to_rust(part, &func.name)?;
-                    
-                    // Determine contract type based on statement
-                    let contract_type = if part.contains("≥") || part.contains("≤") || 
-                                         part.contains(">") || part.contains("<") ||
-                                         part.contains("=") {
-                        ContractType::Ensures
-                    } else {
-                        ContractType::Ensures  // Default to ensures for theorems
-                    };
-                    
-                    func.contracts.push(Contract {
-                        contract_type,
-                        condition,
-                        comment: Some(format!("From Theorem {}", theorem.number)),
-                    });
+            // Theorem format: "∀h ∈ ℕ: GetBlockSubsidy(h) ≥ 0 ∧ GetBlockSubsidy(h) ≤ 50 × C"
+            // Each conjunct that mentions the function becomes a contract
+            let Ok(parsed) = math::parse_math(math::math_content(&theorem.statement)) else {
+                continue;
+            };
+            let body = match parsed {
+                MathExpr::Quantified { body, .. } => *body,
+                expr => expr,
+            };
+
+            for part in conjuncts(body) {
+                if part.find_call(&func.name).is_none() {
+                    continue;
                 }
+                let condition = part.to_rust(&Self::rust_context(&func.name))
+                    .unwrap_or_else(|_| part.to_string());
+                func.contracts.push(Contract {
+                    contract_type: ContractType::Ensures,
+                    condition,
+                    comment: Some(format!("From Theorem {}", theorem.number)),
+                    math: Some(part),
+                });
             }
         }
         
         Ok(())
     }
 
-    /// Translate theorem statement to Rust-like expression
-    fn translate_theorem_statement_to_rust(&self, statement: &str, func_name: &str) -> Result<String, String> {
-        // Use the same translation logic as properties
-        self.translate_property_to_rust(statement, func_name)
-    }
-
     /// Translate mathematical property to Rust-like expression
+    ///
+    /// Statements that don't parse as math are returned with `$` delimiters stripped.
     fn translate_property_to_rust(&self, statement: &str, func_name: &str) -> Result<String, String> {
-        // Simple translation - replace common patterns
-        let mut rust_expr = statement.to_string();
-        
-        // Replace LaTeX function calls - handle escaped backslashes properly
-        // First replace LaTeX \text{FunctionName} format (literal backslash + text)
-        let latex_pattern = format!(r"\text{{{}}}", func_name);
-        rust_expr = rust_expr.replace(&latex_pattern, "result");
-        // Also handle escaped version
-        let latex_pattern_escaped = format!(r"\\text{{{}}}", func_name);
-        rust_expr = rust_expr.replace(&latex_pattern_escaped, "result");
-        // Then replace plain function name (but not if it's part of a larger word)
-        rust_expr = rust_expr.replace(&format!("{}", func_name), "result");
-        
-        // Replace mathematical operators
-        rust_expr = rust_expr.replace("≥", ">=");
-        rust_expr = rust_expr.replace("≤", "<=");
-        rust_expr = rust_expr.replace("≠", "!=");
-        rust_expr = rust_expr.replace("⟹", "=>");
-        
-        // Replace common variables (be careful not to replace in the middle of words)
-        rust_expr = rust_expr.replace(" h ", " height ");
-        rust_expr = rust_expr.replace("(h)", "(height)");
-        rust_expr = rust_expr.replace(" h,", " height,");
-        rust_expr = rust_expr.replace(" h)", " height)");
-        rust_expr = rust_expr.replace(" H ", " HALVING_INTERVAL ");
-        rust_expr = rust_expr.replace("(H)", "(HALVING_INTERVAL)");
-        rust_expr = rust_expr.replace(" C ", " SATOSHIS_PER_BTC ");
-        rust_expr = rust_expr.replace("(C)", "(SATOSHIS_PER_BTC)");
-        
-        // Remove LaTeX formatting (do this last)
-        rust_expr = rust_expr.replace("$", "");
-        rust_expr = rust_expr.replace(r"\text{", "");
-        rust_expr = rust_expr.replace(r"\{", "{");
-        rust_expr = rust_expr.replace(r"\}", "}");
-        
-        Ok(rust_expr)
+        let rendered = math::parse_math(math::math_content(statement))
+            .and_then(|expr| expr.to_rust(&Self::rust_context(func_name)));
+        Ok(rendered.unwrap_or_else(|_| statement.replace('$', "").trim().to_string()))
+    }
+
+    /// Rendering context for contracts on `func_name`: conventional parameter names and constants
+    fn rust_context(func_name: &str) -> RustContext {
+        RustContext::for_function(func_name)
+            .with_vars(math::CONVENTIONAL_PARAMS.iter().copied())
+            .with_vars(math::CONSENSUS_CONSTANTS.iter().copied())
+    }
+
+    /// All parsed sections, in no particular order
+    pub fn sections(&self) -> impl Iterator<Item = &SpecSection> {
+        self.sections.values()
     }
 
     /// Find a section by ID
@@ -876,4 +854,124 @@ impl SpecParser {
         }
         None
     }
+
+    /// Resolve the spec function a Rust function is locked to
+    ///
+    /// `name` is the explicit spec function name or the Rust function name
+    /// (converted to PascalCase). With a section (or granular ID such as
+    /// "6.1.1"), only that section is searched; otherwise all sections are.
+    /// Returns the function spec and its section ID.
+    pub fn resolve_function(&self, section: Option<&str>, name: &str) -> Option<(&FunctionSpec, String)> {
+        let mut candidates = vec![name.to_string(), rust_to_pascal_case(name)];
+        candidates.extend(generate_name_variations(name));
+
+        match section {
+            Some(section_id) => {
+                let spec_section = self.find_section(section_id)
+                    .or_else(|| self.find_subsection(section_id).map(|(s, _)| s))?;
+                candidates.iter()
+                    .find_map(|candidate| spec_section.functions.iter()
+                        .find(|f| f.name.eq_ignore_ascii_case(candidate)))
+                    .map(|f| (f, spec_section.id.clone()))
+            }
+            None => candidates.iter()
+                .find_map(|candidate| self.find_function_anywhere(candidate))
+                .map(|(f, section_id)| (f, section_id.to_string())),
+        }
+    }
+
+    /// Check whether a section or granular ID (e.g. "6.1.1") exists in the spec
+    pub fn section_exists(&self, section_id: &str) -> bool {
+        self.find_section(section_id).is_some() || self.find_subsection(section_id).is_some()
+    }
+}
+
+/// Split a conjunction into its conjuncts
+fn conjuncts(expr: MathExpr) -> Vec<MathExpr> {
+    match expr {
+        MathExpr::Binary { op: BinaryOp::And, left, right } => {
+            let mut parts = conjuncts(*left);
+            parts.extend(conjuncts(*right));
+            parts
+        }
+        expr => vec![expr],
+    }
+}
+
+/// Markdown block of a function: from its "**Name**" marker to the next
+/// function, theorem or section header
+///
+/// Property items ("- **Name**:") and the "**Properties**:" header belong to the block.
+pub fn function_block<'c>(content: &'c str, func_name: &str) -> Option<&'c str> {
+    let func_marker = format!("**{}**", func_name);
+    let func_pos = content.find(&func_marker)?;
+    let remaining = &content[func_pos + func_marker.len()..];
+    
+    let next_func = remaining.match_indices("\n**")
+        .find(|(pos, _)| !remaining[pos + 1..].starts_with("**Properties**"))
+        .map(|(pos, _)| pos)
+        .unwrap_or(remaining.len());
+    let next_section = remaining.find("\n###").unwrap_or(remaining.len());
+    let block_end = func_pos + func_marker.len() + next_func.min(next_section);
+    Some(&content[func_pos..block_end])
+}
+
+/// Convert Rust function name (snake_case) to Orange Paper function name (PascalCase)
+pub fn rust_to_pascal_case(rust_name: &str) -> String {
+    rust_name
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                None => String::new(),
+                Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+            }
+        })
+        .collect()
+}
+
+/// Generate name variations for improved matching
+/// e.g., "check_bip30" -> ["CheckBip30", "BIP30", "CheckBIP30", "Bip30"]
+pub fn generate_name_variations(func_name: &str) -> Vec<String> {
+    let mut variations = Vec::new();
+    
+    // Original PascalCase
+    variations.push(rust_to_pascal_case(func_name));
+    
+    // Remove common prefixes
+    let without_check = func_name.strip_prefix("check_").unwrap_or(func_name);
+    let without_verify = without_check.strip_prefix("verify_").unwrap_or(without_check);
+    let without_calculate = without_verify.strip_prefix("calculate_").unwrap_or(without_verify);
+    let without_get = without_calculate.strip_prefix("get_").unwrap_or(without_calculate);
+    
+    if without_check != func_name {
+        variations.push(rust_to_pascal_case(without_check));
+    }
+    if without_verify != without_check {
+        variations.push(rust_to_pascal_case(without_verify));
+    }
+    if without_calculate != without_verify {
+        variations.push(rust_to_pascal_case(without_calculate));
+    }
+    if without_get != without_calculate {
+        variations.push(rust_to_pascal_case(without_get));
+    }
+    
+    // Handle BIP/script variations
+    if func_name.contains("bip") {
+        let bip_upper = func_name.replace("bip", "BIP").replace("_", "");
+        variations.push(bip_upper);
+        let bip_pascal = rust_to_pascal_case(func_name).replace("Bip", "BIP");
+        variations.push(bip_pascal);
+    }
+    
+    // Remove "With" suffixes
+    if func_name.contains("_with_") {
+        let without_with: Vec<&str> = func_name.split("_with_").collect();
+        if !without_with.is_empty() {
+            variations.push(rust_to_pascal_case(without_with[0]));
+        }
+    }
+    
+    variations
 }
@@ -0,0 +1,235 @@
+//! Runtime contract checks (the `runtime-checks` feature)
+//!
+//! With the feature enabled, `#[spec_locked]` rewrites the function so its
+//! contracts are checked whenever it runs: each `#[requires]` becomes a
+//! `debug_assert!` on entry, the body runs in a closure (so `return` and `?`
+//! still produce the result), and each `#[ensures]` is asserted against the
+//! value it returned. `old(e)` is evaluated and cloned on entry, and so is
+//! every by-value parameter an `#[ensures]` reads (unless its type is a
+//! primitive), since the body may consume it.
+//!
+//! Only the contracts written below `#[spec_locked]` are seen by the macro.
+//! Contracts using `forall`/`exists` range over unbounded values and are left
+//! to the verifier, as are requires misusing `old(..)`.
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::{format_ident, quote};
+use syn::{Expr, Ident, ItemFn};
+use crate::parser::contracts::{capture_old, is_contract_attribute, rewrite_implications};
+use crate::translator::static_checker::mentions_old;
+
+/// A contract ready to be asserted at run time
+struct RuntimeContract {
+    condition: Expr,
+    /// Panic message naming the function, the contract and the Orange Paper section
+    message: String,
+}
+
+/// Add runtime checks of `func`'s contracts to its body
+///
+/// `section` is the Orange Paper section reported when a contract fails.
+/// `const` and `async` functions are returned unchanged.
+pub fn instrument(func: ItemFn, section: &str) -> ItemFn {
+    if func.sig.constness.is_some() || func.sig.asyncness.is_some() {
+        return func;
+    }
+    let name = func.sig.ident.to_string();
+    let mut requires = Vec::new();
+    let mut ensures = Vec::new();
+    let mut old_values = Vec::new();
+    let consumable = consumable_params(&func.sig);
+    let mut param_copies = Vec::new();
+    for attr in &func.attrs {
+        let kind = if is_contract_attribute(attr, "requires") {
+            "requires"
+        } else if is_contract_attribute(attr, "ensures") {
+            "ensures"
+        } else {
+            continue;
+        };
+        let Ok(tokens) = attr.parse_args::<TokenStream>() else {
+            continue;
+        };
+        // A requires has no earlier state for `old(..)` to refer to; the verifier reports it
+        if kind == "requires" && syn::parse2::<Expr>(rewrite_implications(tokens.clone())).is_ok_and(|expr| mentions_old(&expr)) {
+            continue;
+        }
+        let message = format!("`{}`: {} `{}` violated (Orange Paper Section {})", name, kind, tokens, section);
+        let Some(mut tokens) = capture_old(rewrite_implications(tokens), &mut old_values) else {
+            continue;
+        };
+        if kind == "ensures" {
+            tokens = capture_params(tokens, &consumable, &mut param_copies);
+        }
+        let Ok(condition) = syn::parse2(tokens) else {
+            continue;
+        };
+        let contract = RuntimeContract { condition, message };
+        if kind == "requires" {
+            requires.push(contract);
+        } else {
+            ensures.push(contract);
+        }
+    }
+    if requires.is_empty() && ensures.is_empty() {
+        return func;
+    }
+
+    let entry_checks = requires.iter().map(|RuntimeContract { condition, message }| {
+        quote! { debug_assert!(#condition, "{}", #message); }
+    });
+    let block = &func.block;
+    let body = if ensures.is_empty() {
+        quote! {{
+            #(#entry_checks)*
+            #block
+        }}
+    } else {
+        let return_type = match &func.sig.output {
+            syn::ReturnType::Default => quote!(()),
+            syn::ReturnType::Type(_, ty) => quote!(#ty),
+        };
+        let old_captures = old_values.iter().map(|(name, expr)| {
+            quote! {
+                #[allow(clippy::clone_on_copy, clippy::redundant_clone)]
+                let #name = (#expr).clone();
+            }
+        });
+        let param_captures = param_copies.iter().map(|(param, copy)| {
+            quote! {
+                #[allow(clippy::clone_on_copy, clippy::redundant_clone)]
+                let #copy = #param.clone();
+            }
+        });
+        let exit_checks = ensures.iter().map(|RuntimeContract { condition, message }| {
+            quote! { debug_assert!(#condition, "{}", #message); }
+        });
+        quote! {{
+            #(#entry_checks)*
+            #(#old_captures)*
+            #(#param_captures)*
+            #[allow(clippy::redundant_closure_call)]
+            let result: #return_type = (|| -> #return_type #block)();
+            #(#exit_checks)*
+            result
+        }}
+    };
+
+    let mut func = func;
+    match syn::parse2(body) {
+        Ok(block) => {
+            *func.block = block;
+            func
+        }
+        Err(_) => func,
+    }
+}
+
+/// The by-value parameters whose values the body may consume (those of non-primitive types)
+fn consumable_params(sig: &syn::Signature) -> Vec<Ident> {
+    sig.inputs.iter()
+        .filter_map(|input| match input {
+            syn::FnArg::Typed(pat_type) => match (&*pat_type.pat, &*pat_type.ty) {
+                (_, syn::Type::Reference(_)) => None,
+                (syn::Pat::Ident(ident), ty) if !is_primitive(ty) => Some(ident.ident.clone()),
+                _ => None,
+            },
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+/// Whether a type is a primitive (and so `Copy`)
+fn is_primitive(ty: &syn::Type) -> bool {
+    const PRIMITIVES: &[&str] = &[
+        "bool", "char", "u8", "u16", "u32", "u64", "u128", "usize",
+        "i8", "i16", "i32", "i64", "i128", "isize", "f32", "f64",
+    ];
+    matches!(ty, syn::Type::Path(path) if path.path.get_ident().is_some_and(|ident| PRIMITIVES.iter().any(|p| ident == p)))
+}
+
+/// Replace the `params` an ensures reads with copies taken on entry, recording them in `copies`
+fn capture_params(tokens: TokenStream, params: &[Ident], copies: &mut Vec<(Ident, Ident)>) -> TokenStream {
+    let mut captured = Vec::new();
+    let mut after_dot = false;
+    for token in tokens {
+        let is_dot = matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '.');
+        match token {
+            // `x.v` is a field, not the parameter `v`
+            TokenTree::Ident(ident) if !after_dot && params.contains(&ident) => {
+                let copy = match copies.iter().find(|(param, _)| *param == ident) {
+                    Some((_, copy)) => copy.clone(),
+                    None => {
+                        let copy = format_ident!("__spec_lock_param_{}", ident);
+                        copies.push((ident.clone(), copy.clone()));
+                        copy
+                    }
+                };
+                captured.push(TokenTree::Ident(copy));
+            }
+            TokenTree::Group(group) => {
+                let mut inner = proc_macro2::Group::new(group.delimiter(), capture_params(group.stream(), params, copies));
+                inner.set_span(group.span());
+                captured.push(TokenTree::Group(inner));
+            }
+            other => captured.push(other),
+        }
+        after_dot = is_dot;
+    }
+    captured.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_instrument() {
+        let func: ItemFn = parse_quote! {
+            #[requires(*x < 1000)]
+            #[ensures(*x == old(*x) + 1)]
+            #[ensures(forall(|i: u64| i < *x ==> i < 1001))]
+            fn bump(x: &mut u64) -> bool {
+                if *x == 0 {
+                    return false;
+                }
+                *x += 1;
+                true
+            }
+        };
+        let instrumented = instrument(func, "5.3");
+        let body = quote!(#instrumented).to_string();
+        assert!(body.contains("let __spec_lock_old_0 = (* x) . clone ()"), "{}", body);
+        assert!(body.contains("debug_assert ! (* x == __spec_lock_old_0 + 1"), "{}", body);
+        assert!(body.contains("requires `* x < 1000` violated (Orange Paper Section 5.3)"), "{}", body);
+        // The quantified ensures isn't checked
+        assert_eq!(body.matches("debug_assert !").count(), 2, "{}", body);
+
+        // Nor is a requires using `old(..)`, which has no earlier state
+        let func: ItemFn = parse_quote! {
+            #[requires(old(*x) < 1000)]
+            #[ensures(*x > 0)]
+            fn set(x: &mut u64) { *x = 1; }
+        };
+        let instrumented = instrument(func, "5.3");
+        let body = quote!(#instrumented).to_string();
+        assert_eq!(body.matches("debug_assert !").count(), 1, "{}", body);
+        assert!(!body.contains("__spec_lock_old"), "{}", body);
+
+        // A parameter the body may consume is read from a copy taken on entry
+        let func: ItemFn = parse_quote! {
+            #[ensures(result.len() == v.len() && n > 0)]
+            fn keep(v: Vec<u64>, n: u8) -> Vec<u64> { v }
+        };
+        let instrumented = instrument(func, "6.2");
+        let body = quote!(#instrumented).to_string();
+        assert!(body.contains("let __spec_lock_param_v = v . clone ()"), "{}", body);
+        assert!(body.contains("debug_assert ! (result . len () == __spec_lock_param_v . len () && n > 0"), "{}", body);
+        assert!(!body.contains("__spec_lock_param_n"), "{}", body);
+
+        // Without contracts the function is unchanged
+        let plain: ItemFn = parse_quote! { fn f() -> u8 { 1 } };
+        assert_eq!(instrument(plain.clone(), "1.1"), plain);
+    }
+}
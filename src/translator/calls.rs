@@ -0,0 +1,341 @@
+//! Function calls: modular verification via contract summaries
+//!
+//! A call is translated to an uninterpreted function applied to its
+//! arguments, so a caller is never verified against its callee's body. When
+//! the callee has a registered [`FunctionSummary`] (e.g. it's another
+//! `#[spec_locked]` function):
+//! - its `requires` is a proof obligation at each call site, collected with the
+//!   other implicit obligations (see `translator::overflow`), and
+//! - its `ensures` is assumed about the call's result whenever its `requires`
+//!   holds, as an axiom over the uninterpreted function (see
+//!   [`Z3Translator::call_axioms`]).
+//!
+//! Calls to unknown functions stay uninterpreted: the result is an arbitrary
+//! value, the same for equal arguments. A place passed by `&mut` is updated
+//! by the call (see [`Z3Translator::call_assignment`]).
+
+use std::collections::{BTreeSet, HashMap};
+use syn::{Expr, ItemFn};
+use z3::ast::{forall_const, Ast, Bool, Dynamic};
+use z3::{FuncDecl, Pattern, Sort};
+use crate::parser::contracts::{extract_contracts, Contract, ContractType};
+use crate::translator::datatypes::ValueType;
+use crate::translator::loops::place_root;
+use crate::translator::two_state::{is_mut_reference, post_state_key, pre_state_key};
+use crate::translator::z3_translator::{IntType, TranslationError, Z3Translator};
+
+/// What a caller may rely on about a function: its signature and contracts
+#[derive(Debug, Clone)]
+pub struct FunctionSummary {
+    /// Function name, matched against the last segment of a call's path
+    pub name: String,
+    /// Parameters in signature order
+    pub params: Vec<(String, syn::Type)>,
+    pub return_type: Option<syn::Type>,
+    pub requires: Vec<Expr>,
+    pub ensures: Vec<Expr>,
+}
+
+impl FunctionSummary {
+    /// Summarize a function from its signature and `#[requires]`/`#[ensures]` attributes
+    pub fn from_fn(func: &ItemFn) -> Self {
+        Self::with_contracts(func, &extract_contracts(func))
+    }
+
+    /// Summarize a function from its signature and the given contracts
+    pub fn with_contracts(func: &ItemFn, contracts: &[Contract]) -> Self {
+        let params = func.sig.inputs.iter()
+            .filter_map(|input| match input {
+                syn::FnArg::Typed(pat_type) => match &*pat_type.pat {
+                    syn::Pat::Ident(ident) => Some((ident.ident.to_string(), (*pat_type.ty).clone())),
+                    _ => None,
+                },
+                syn::FnArg::Receiver(_) => None,
+            })
+            .collect();
+        let return_type = match &func.sig.output {
+            syn::ReturnType::Type(_, ty) => Some((**ty).clone()),
+            syn::ReturnType::Default => None,
+        };
+        let conditions = |contract_type: ContractType| -> Vec<Expr> {
+            contracts.iter()
+                .filter(|c| c.contract_type == contract_type)
+                .map(|c| c.condition.clone())
+                .collect()
+        };
+
+        FunctionSummary {
+            name: func.sig.ident.to_string(),
+            params,
+            return_type,
+            requires: conditions(ContractType::Requires),
+            ensures: conditions(ContractType::Ensures),
+        }
+    }
+}
+
+impl Z3Translator {
+    /// Translate a call to an application of the callee's uninterpreted function
+    pub(crate) fn translate_call<'a>(&'a self, call: &syn::ExprCall, vars: &mut HashMap<String, Dynamic<'a>>, expected: Option<IntType>) -> Result<Dynamic<'a>, TranslationError> {
+        let name = callee_name(call)?;
+        let (decl, args) = match self.summary(&name) {
+            Some(summary) => (self.summary_decl(summary), self.call_args(summary, call, vars)?),
+            None => {
+                let args = call.args.iter()
+                    .map(|arg| {
+                        let ty = self.infer_int_type(arg);
+                        self.translate_typed(arg, vars, ty)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let domain: Vec<Sort> = args.iter().map(|arg| arg.get_sort()).collect();
+                let domain: Vec<&Sort> = domain.iter().collect();
+                let range = self.value_sort(None, expected);
+                (FuncDecl::new(self.context(), name.as_str(), &domain, &range), args)
+            }
+        };
+        self.record_call(&name);
+        let args: Vec<&dyn Ast> = args.iter().map(|arg| arg as &dyn Ast).collect();
+        Ok(decl.apply(&args))
+    }
+
+    /// The effect of a call on the place it passes by `&mut`, e.g. `x` in `f(&mut x)`
+    ///
+    /// The place becomes the callee's post-state function for that parameter
+    /// applied to the call's arguments (see `summary_axiom`), or an unknown
+    /// value if the callee has no summary. Only the first `&mut` argument is
+    /// modelled.
+    pub(crate) fn call_assignment<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>) -> Option<(String, Dynamic<'a>)> {
+        let Expr::Call(call) = expr else {
+            return None;
+        };
+        let name = callee_name(call).ok();
+        let summary = name.as_deref().and_then(|name| self.summary(name));
+        let (index, target) = call.args.iter().enumerate().find_map(|(index, arg)| match arg {
+            Expr::Reference(reference) if reference.mutability.is_some() => Some((index, &*reference.expr)),
+            _ if summary.is_some_and(|s| s.params.get(index).is_some_and(|(_, ty)| is_mut_reference(ty))) => Some((index, arg)),
+            _ => None,
+        })?;
+        let old = self.place_value(target, vars)?;
+        let post = summary.and_then(|summary| {
+            let (param, ty) = summary.params.get(index)?;
+            let args = self.call_args(summary, call, vars).ok()?;
+            let args: Vec<&dyn Ast> = args.iter().map(|arg| arg as &dyn Ast).collect();
+            self.record_call(&summary.name);
+            Some(self.post_state_decl(summary, param, ty).apply(&args))
+        });
+        let value = match post {
+            Some(post) if post.get_sort() == old.get_sort() => post,
+            _ => self.fresh_value(&place_root(target)?, &old),
+        };
+        self.assign_place(target, value, vars)
+    }
+
+    /// Integer type returned by a call to a summarized function
+    pub(crate) fn call_return_type(&self, call: &syn::ExprCall) -> Option<IntType> {
+        let summary = self.summary(&callee_name(call).ok()?)?;
+        IntType::from_type(summary.return_type.as_ref()?)
+    }
+
+    /// Type of the value returned by a call to a summarized function
+    pub(crate) fn call_value_type(&self, call: &syn::ExprCall) -> Option<ValueType> {
+        let summary = self.summary(&callee_name(call).ok()?)?;
+        Some(self.value_type(summary.return_type.as_ref()?))
+    }
+
+    /// The callee's `requires`, instantiated with a call's arguments
+    ///
+    /// Returns the callee's name and its conjoined preconditions, or `None` if
+    /// the callee has no summary or no preconditions, or they can't be translated.
+    pub(crate) fn call_precondition<'a>(&'a self, call: &syn::ExprCall, vars: &mut HashMap<String, Dynamic<'a>>) -> Option<(String, Bool<'a>)> {
+        let name = callee_name(call).ok()?;
+        let summary = self.summary(&name)?;
+        if summary.requires.is_empty() {
+            return None;
+        }
+        let args = self.call_args(summary, call, vars).ok()?;
+        let mut callee_vars: HashMap<String, Dynamic<'a>> = summary.params.iter()
+            .map(|(param, _)| param.clone())
+            .zip(args)
+            .collect();
+        let requires = self.with_callee_types(summary, || {
+            summary.requires.iter()
+                .map(|condition| self.translate_typed(condition, &mut callee_vars, None).ok()?.as_bool())
+                .collect::<Option<Vec<_>>>()
+        })?;
+        let requires: Vec<&Bool> = requires.iter().collect();
+        Some((name, Bool::and(self.context(), &requires)))
+    }
+
+    /// Axioms giving the contracts of every summarized function called so far
+    ///
+    /// For a callee `f(x)` these state `∀x. requires(x) ⟹ ensures(x, f(x))`,
+    /// plus the parameter and result ranges in integer mode. A `&mut`
+    /// parameter `x` is `f!x'(x)` on return, and `old(x)` in the ensures is
+    /// `x`. Callees whose preconditions can't be translated get no axiom
+    /// (nothing is assumed).
+    pub fn call_axioms(&self) -> Vec<Bool<'_>> {
+        let mut axioms = Vec::new();
+        let mut done = BTreeSet::new();
+        // Translating a callee's ensures may record further calls
+        loop {
+            let pending: Vec<String> = self.called_functions().into_iter()
+                .filter(|name| !done.contains(name))
+                .collect();
+            if pending.is_empty() {
+                return axioms;
+            }
+            for name in pending {
+                if let Some(summary) = self.summary(&name) {
+                    axioms.extend(self.summary_axiom(summary));
+                }
+                done.insert(name);
+            }
+        }
+    }
+
+    fn summary_axiom(&self, summary: &FunctionSummary) -> Option<Bool<'_>> {
+        let ctx = self.context();
+        let bound: Vec<Dynamic<'_>> = summary.params.iter()
+            .map(|(param, ty)| self.typed_const(&format!("{}!{}", summary.name, param), ty))
+            .collect();
+        let bound_refs: Vec<&dyn Ast> = bound.iter().map(|b| b as &dyn Ast).collect();
+        let application = self.summary_decl(summary).apply(&bound_refs);
+
+        let mut callee_vars: HashMap<String, Dynamic<'_>> = summary.params.iter()
+            .map(|(param, _)| param.clone())
+            .zip(bound.iter().cloned())
+            .collect();
+        callee_vars.insert("result".to_string(), application.clone());
+
+        // The ensures sees the `&mut` parameters' post-states, and their pre-states through `old`
+        let post_states: Vec<(String, Dynamic<'_>)> = summary.params.iter()
+            .filter(|(_, ty)| is_mut_reference(ty))
+            .map(|(param, ty)| (param.clone(), self.post_state_decl(summary, param, ty).apply(&bound_refs)))
+            .collect();
+
+        let (mut premise, mut conclusion) = self.with_callee_types(summary, || {
+            let requires = summary.requires.iter()
+                .map(|condition| self.translate_typed(condition, &mut callee_vars, None).ok()?.as_bool())
+                .collect::<Option<Vec<_>>>()?;
+            for (param, post) in &post_states {
+                if let Some(pre) = callee_vars.insert(param.clone(), post.clone()) {
+                    callee_vars.insert(pre_state_key(param), pre);
+                }
+            }
+            // Dropping an ensures only weakens what callers may assume
+            let ensures: Vec<Bool<'_>> = summary.ensures.iter()
+                .filter_map(|condition| self.translate_typed(condition, &mut callee_vars, None).ok()?.as_bool())
+                .collect();
+            Some((requires, ensures))
+        })?;
+        // Integers (also inside `Option` and `Result`) are within their types' ranges
+        for ((_, ty), value) in summary.params.iter().zip(&bound) {
+            premise.extend(self.value_in_range(value, &self.value_type(ty)));
+        }
+        if let Some(return_ty) = &summary.return_type {
+            conclusion.extend(self.value_in_range(&application, &self.value_type(return_ty)));
+        }
+        if conclusion.is_empty() {
+            return None;
+        }
+
+        let premise: Vec<&Bool> = premise.iter().collect();
+        let conclusion: Vec<&Bool> = conclusion.iter().collect();
+        let axiom = Bool::and(ctx, &premise).implies(&Bool::and(ctx, &conclusion));
+        if bound.is_empty() {
+            return Some(axiom);
+        }
+        // A call may be used only for its effect on a `&mut` argument
+        let patterns: Vec<Pattern<'_>> = std::iter::once(&application)
+            .chain(post_states.iter().map(|(_, post)| post))
+            .map(|term| Pattern::new(ctx, &[term as &dyn Ast]))
+            .collect();
+        let patterns: Vec<&Pattern<'_>> = patterns.iter().collect();
+        Some(forall_const(ctx, &bound_refs, &patterns, &axiom))
+    }
+
+    /// Translate a call's arguments, each sized to its parameter's type
+    fn call_args<'a>(&'a self, summary: &FunctionSummary, call: &syn::ExprCall, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Vec<Dynamic<'a>>, TranslationError> {
+        if call.args.len() != summary.params.len() {
+            return Err(TranslationError::TypeError(format!(
+                "{} takes {} arguments but is called with {}",
+                summary.name, summary.params.len(), call.args.len()
+            )));
+        }
+        summary.params.iter()
+            .zip(&call.args)
+            .map(|((param, ty), arg)| {
+                let value = self.translate_value(arg, vars, &self.value_type(ty))?;
+                if value.get_sort() != self.value_sort(Some(ty), IntType::from_type(ty)) {
+                    return Err(TranslationError::TypeError(format!(
+                        "Argument `{}` of {} has the wrong sort", param, summary.name
+                    )));
+                }
+                Ok(value)
+            })
+            .collect()
+    }
+
+    /// The uninterpreted function standing for a summarized callee
+    fn summary_decl(&self, summary: &FunctionSummary) -> FuncDecl<'_> {
+        let domain: Vec<Sort> = summary.params.iter()
+            .map(|(_, ty)| self.value_sort(Some(ty), IntType::from_type(ty)))
+            .collect();
+        let domain: Vec<&Sort> = domain.iter().collect();
+        let return_ty = summary.return_type.as_ref();
+        let range = self.value_sort(return_ty, return_ty.and_then(IntType::from_type));
+        FuncDecl::new(self.context(), summary.name.as_str(), &domain, &range)
+    }
+
+    /// The uninterpreted function giving a summarized callee's `&mut` parameter on return
+    fn post_state_decl(&self, summary: &FunctionSummary, param: &str, ty: &syn::Type) -> FuncDecl<'_> {
+        let domain: Vec<Sort> = summary.params.iter()
+            .map(|(_, ty)| self.value_sort(Some(ty), IntType::from_type(ty)))
+            .collect();
+        let domain: Vec<&Sort> = domain.iter().collect();
+        let range = self.value_sort(Some(ty), IntType::from_type(ty));
+        FuncDecl::new(self.context(), post_state_key(&format!("{}!{}", summary.name, param)), &domain, &range)
+    }
+
+    /// Z3 sort of a value of the given Rust type (or integer type) in the current mode
+    fn value_sort(&self, ty: Option<&syn::Type>, int_ty: Option<IntType>) -> Sort<'_> {
+        match ty {
+            Some(ty) => self.sort_of(&self.value_type(ty)),
+            None => self.sort_of(&ValueType::Int(int_ty)),
+        }
+    }
+
+    /// A constant of the given Rust type in the current mode
+    fn typed_const(&self, name: &str, ty: &syn::Type) -> Dynamic<'_> {
+        self.value_const(name, &self.value_type(ty))
+    }
+
+    /// Run `f` with the callee's parameter and result types declared
+    ///
+    /// Variable types are tracked by name, so the caller's types for the same
+    /// names are restored afterwards.
+    fn with_callee_types<T>(&self, summary: &FunctionSummary, f: impl FnOnce() -> T) -> T {
+        let types: Vec<(&str, Option<IntType>)> = summary.params.iter()
+            .map(|(param, ty)| (param.as_str(), IntType::from_type(ty)))
+            .chain(std::iter::once(("result", summary.return_type.as_ref().and_then(IntType::from_type))))
+            .collect();
+        let saved: Vec<(&str, Option<IntType>)> = types.iter()
+            .map(|&(name, ty)| (name, self.set_var_type(name, ty)))
+            .collect();
+        let value = f();
+        for (name, ty) in saved {
+            self.set_var_type(name, ty);
+        }
+        value
+    }
+}
+
+/// Name of the called function: the last segment of its path
+fn callee_name(call: &syn::ExprCall) -> Result<String, TranslationError> {
+    match &*call.func {
+        Expr::Path(path) => path.path.segments.last()
+            .map(|segment| segment.ident.to_string())
+            .ok_or_else(|| TranslationError::UnsupportedExpression("Call to an empty path".to_string())),
+        _ => Err(TranslationError::UnsupportedExpression("Call through a computed function value".to_string())),
+    }
+}
@@ -0,0 +1,723 @@
+//! Branching in function bodies: `match`, `if let`, `?` and early returns
+//!
+//! A pattern becomes a condition on the matched value (see
+//! [`Z3Translator::pattern_condition`]): a variant test for `Some(..)`,
+//! `Err(..)` or `Enum::Variant`, an equality or range check for literals, and
+//! nothing for a binding, which is bound to the value (or the variant's field)
+//! in the arm's variables. In value position an `if` or `match` becomes an
+//! if-then-else over its arms. Arms that return from the function (or panic)
+//! are left out; a `return` contributes an exit instead.
+//!
+//! The ways a block can return from the function are collected as [`Exits`]:
+//! for each `return`, `?` or branch that returns, the condition under which it
+//! is taken and what it returns. Code after an exit only runs when no exit
+//! before it was taken. Patterns that can't be modelled (tuples, slices,
+//! bindings inside `|`) are translation errors rather than being skipped,
+//! since skipping an arm would silently weaken the proof.
+
+use std::collections::HashMap;
+use quote::ToTokens;
+use syn::parse::{Parse, ParseStream};
+use syn::{BinOp, Block, Expr, Pat, Stmt, Token};
+use z3::ast::{Ast, Bool, Dynamic};
+use z3::Context;
+use crate::translator::datatypes::ValueType;
+use crate::translator::loops::{exits_function, is_loop};
+use crate::translator::z3_translator::{IntType, TranslationError, Z3Translator};
+
+/// The ways a block can return from the function
+pub(crate) struct Exits<'a> {
+    ctx: &'a Context,
+    /// For each exit: when it's taken (and no earlier exit was), and how
+    /// `result` relates to the value returned and the `&mut` parameters to
+    /// their post-states, if known
+    exits: Vec<(Bool<'a>, Option<Bool<'a>>)>,
+    /// Facts established along the way, each holding unless an earlier exit was taken
+    facts: Vec<Bool<'a>>,
+}
+
+impl<'a> Exits<'a> {
+    pub(crate) fn new(ctx: &'a Context) -> Self {
+        Exits { ctx, exits: Vec::new(), facts: Vec::new() }
+    }
+
+    /// Holds when no exit has been taken
+    pub(crate) fn none_taken(&self) -> Bool<'a> {
+        let negated: Vec<Bool<'a>> = self.exits.iter().map(|(taken, _)| taken.not()).collect();
+        let refs: Vec<&Bool<'a>> = negated.iter().collect();
+        Bool::and(self.ctx, &refs)
+    }
+
+    /// Record an exit taken when `condition` holds (and no earlier exit was)
+    fn exit(&mut self, condition: &Bool<'a>, returns: Option<Bool<'a>>) {
+        let taken = Bool::and(self.ctx, &[condition, &self.none_taken()]);
+        self.exits.push((taken, returns));
+    }
+
+    /// Record a fact that holds unless an earlier exit was taken
+    pub(crate) fn fact(&mut self, fact: &Bool<'a>) {
+        if fact.as_bool() != Some(true) {
+            self.facts.push(self.none_taken().implies(fact));
+        }
+    }
+
+    /// Add the exits and facts of a nested block that runs when `guard` holds
+    pub(crate) fn nest(&mut self, guard: &Bool<'a>, inner: Exits<'a>) {
+        let reached = Bool::and(self.ctx, &[guard, &self.none_taken()]);
+        for fact in inner.facts {
+            self.facts.push(reached.implies(&fact));
+        }
+        for (taken, returns) in inner.exits {
+            self.exit(&Bool::and(self.ctx, &[guard, &taken]), returns);
+        }
+    }
+
+    /// `(taken ⟹ returns)` for every exit, and the facts; `None` if there are neither
+    pub(crate) fn formula(&self) -> Option<Bool<'a>> {
+        let mut parts: Vec<Bool<'a>> = self.facts.clone();
+        parts.extend(self.exits.iter()
+            .filter_map(|(taken, returns)| Some(taken.implies(returns.as_ref()?))));
+        match parts.len() {
+            0 => None,
+            1 => parts.pop(),
+            _ => {
+                let refs: Vec<&Bool<'a>> = parts.iter().collect();
+                Some(Bool::and(self.ctx, &refs))
+            }
+        }
+    }
+}
+
+/// An arm of a `match`, with its bindings
+pub(crate) struct MatchArm<'a, 'e> {
+    /// Holds when this arm is the one taken
+    pub(crate) taken: Bool<'a>,
+    /// The variables in the arm, including its pattern's bindings
+    pub(crate) vars: HashMap<String, Dynamic<'a>>,
+    pub(crate) body: &'e Expr,
+}
+
+impl Z3Translator {
+    /// Collect the exits of a block, running its statements on `vars`
+    ///
+    /// In tail position the block's final expression is the function's result.
+    pub(crate) fn block_exits<'a>(&'a self, block: &Block, vars: &mut HashMap<String, Dynamic<'a>>, tail: bool) -> Result<Exits<'a>, TranslationError> {
+        let mut exits = Exits::new(self.context());
+        for (index, stmt) in block.stmts.iter().enumerate() {
+            match stmt {
+                Stmt::Local(local) => {
+                    if let Some(init) = &local.init {
+                        self.expr_exits(&init.expr, vars, &mut exits)?;
+                    }
+                    if let Some(else_exits) = self.let_else_exits(local, vars)? {
+                        exits.nest(&Bool::from_bool(self.context(), true), else_exits);
+                    }
+                    self.execute_local(local, vars);
+                }
+                Stmt::Expr(Expr::Return(ret), _) => {
+                    // Nothing after a `return` runs
+                    self.return_exit(ret.expr.as_deref(), vars, &mut exits)?;
+                    return Ok(exits);
+                }
+                Stmt::Expr(expr, None) if tail && index + 1 == block.stmts.len() && !is_loop(expr) => {
+                    self.tail_exits(expr, vars, &mut exits)?;
+                }
+                Stmt::Expr(expr, _) if panics(expr) => {
+                    exits.exit(&Bool::from_bool(self.context(), true), Some(Bool::from_bool(self.context(), false)));
+                    return Ok(exits);
+                }
+                Stmt::Expr(expr, _) => {
+                    if is_loop(expr) && exits_function(expr) {
+                        return Err(TranslationError::UnsupportedExpression(
+                            "return or `?` inside a loop".to_string(),
+                        ));
+                    }
+                    self.expr_exits(expr, vars, &mut exits)?;
+                    let established = self.execute_statement(expr, vars);
+                    exits.fact(&established);
+                }
+                Stmt::Macro(mac) if is_panic_macro(&mac.mac) => {
+                    exits.exit(&Bool::from_bool(self.context(), true), Some(Bool::from_bool(self.context(), false)));
+                    return Ok(exits);
+                }
+                // Items and other macros (debug_assert!, ...) are skipped
+                Stmt::Item(_) | Stmt::Macro(_) => {}
+            }
+        }
+        Ok(exits)
+    }
+
+    /// Collect the exits of the expression that produces the function's result
+    fn tail_exits<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>, exits: &mut Exits<'a>) -> Result<(), TranslationError> {
+        match expr {
+            Expr::Return(ret) => self.return_exit(ret.expr.as_deref(), vars, exits),
+            Expr::If(if_expr) => self.if_exits(if_expr, vars, exits, true),
+            Expr::Match(match_expr) => self.match_exits(match_expr, vars, exits, true),
+            Expr::Block(block) => {
+                let inner = self.block_exits(&block.block, &mut vars.clone(), true)?;
+                exits.nest(&Bool::from_bool(self.context(), true), inner);
+                Ok(())
+            }
+            Expr::Paren(paren) => self.tail_exits(&paren.expr, vars, exits),
+            // A panicking path produces no result
+            _ if panics(expr) => {
+                exits.exit(&Bool::from_bool(self.context(), true), Some(Bool::from_bool(self.context(), false)));
+                Ok(())
+            }
+            _ => self.return_exit(Some(expr), vars, exits),
+        }
+    }
+
+    /// Record returning `value` (after any exits inside it)
+    ///
+    /// A value that can't be translated leaves the result unconstrained on this
+    /// path. The value may itself update a variable (`*x += 1`, `v.pop()`),
+    /// which the state of the `&mut` parameters on return includes.
+    fn return_exit<'a>(&'a self, value: Option<&Expr>, vars: &mut HashMap<String, Dynamic<'a>>, exits: &mut Exits<'a>) -> Result<(), TranslationError> {
+        let returns = match value {
+            Some(value) => {
+                self.expr_exits(value, vars, exits)?;
+                let returns = self.translate_result_value(value, vars).ok()
+                    .and_then(|value| self.result_equals(&value, vars));
+                if let Some((name, updated)) = self.assignment(value, &mut vars.clone()) {
+                    vars.insert(name, updated);
+                }
+                returns
+            }
+            None => None,
+        };
+        exits.exit(&Bool::from_bool(self.context(), true), self.with_exit_state(returns, vars));
+        Ok(())
+    }
+
+    /// Collect the exits inside an expression evaluated as a statement or operand
+    ///
+    /// Expressions without `return` or `?` have none. An exit inside an
+    /// expression that isn't modelled (e.g. a macro) is an error, since the
+    /// code after it would be assumed to always run.
+    pub(crate) fn expr_exits<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>, exits: &mut Exits<'a>) -> Result<(), TranslationError> {
+        if !exits_function(expr) {
+            return Ok(());
+        }
+        match expr {
+            Expr::Return(ret) => self.return_exit(ret.expr.as_deref(), vars, exits),
+            Expr::Try(try_expr) => {
+                self.expr_exits(&try_expr.expr, vars, exits)?;
+                let value = self.translate_typed(&try_expr.expr, vars, None)?;
+                let (taken, returns) = self.try_exit(&value, vars)?;
+                exits.exit(&taken, self.with_exit_state(returns, vars));
+                Ok(())
+            }
+            Expr::If(if_expr) => self.if_exits(if_expr, vars, exits, false),
+            Expr::Match(match_expr) => self.match_exits(match_expr, vars, exits, false),
+            Expr::Block(block) => {
+                let inner = self.block_exits(&block.block, &mut vars.clone(), false)?;
+                exits.nest(&Bool::from_bool(self.context(), true), inner);
+                Ok(())
+            }
+            // A closure's `return` and `?` leave the closure, not the function
+            Expr::Closure(_) => Ok(()),
+            Expr::Binary(bin) => {
+                self.expr_exits(&bin.left, vars, exits)?;
+                // The right side of && / || only runs when the left side didn't short-circuit
+                let guard = match bin.op {
+                    BinOp::And(_) | BinOp::Or(_) if exits_function(&bin.right) => {
+                        let left = self.translate_typed(&bin.left, vars, None)?.as_bool().ok_or_else(|| {
+                            TranslationError::TypeError("Operand of && or || must be boolean".to_string())
+                        })?;
+                        if matches!(bin.op, BinOp::And(_)) { left } else { left.not() }
+                    }
+                    _ => Bool::from_bool(self.context(), true),
+                };
+                let mut inner = Exits::new(self.context());
+                self.expr_exits(&bin.right, vars, &mut inner)?;
+                exits.nest(&guard, inner);
+                Ok(())
+            }
+            Expr::Paren(paren) => self.expr_exits(&paren.expr, vars, exits),
+            Expr::Group(group) => self.expr_exits(&group.expr, vars, exits),
+            Expr::Unary(unary) => self.expr_exits(&unary.expr, vars, exits),
+            Expr::Cast(cast) => self.expr_exits(&cast.expr, vars, exits),
+            Expr::Reference(reference) => self.expr_exits(&reference.expr, vars, exits),
+            Expr::Field(field) => self.expr_exits(&field.base, vars, exits),
+            Expr::Let(let_expr) => self.expr_exits(&let_expr.expr, vars, exits),
+            Expr::Assign(assign) => self.expr_exits(&assign.right, vars, exits),
+            Expr::Index(index) => {
+                self.expr_exits(&index.expr, vars, exits)?;
+                self.expr_exits(&index.index, vars, exits)
+            }
+            Expr::Call(call) => call.args.iter().try_for_each(|arg| self.expr_exits(arg, vars, exits)),
+            Expr::MethodCall(call) => {
+                self.expr_exits(&call.receiver, vars, exits)?;
+                call.args.iter().try_for_each(|arg| self.expr_exits(arg, vars, exits))
+            }
+            Expr::Tuple(tuple) => tuple.elems.iter().try_for_each(|elem| self.expr_exits(elem, vars, exits)),
+            Expr::Array(array) => array.elems.iter().try_for_each(|elem| self.expr_exits(elem, vars, exits)),
+            Expr::Struct(item) => item.fields.iter().try_for_each(|field| self.expr_exits(&field.expr, vars, exits)),
+            _ if is_loop(expr) => Err(TranslationError::UnsupportedExpression(
+                "return or `?` inside a loop".to_string(),
+            )),
+            _ => Err(TranslationError::UnsupportedExpression(format!(
+                "return or `?` inside `{}`", expr.to_token_stream()
+            ))),
+        }
+    }
+
+    /// When `value?` returns early, and what it returns
+    ///
+    /// `?` on an `Err` returns it (converted with `From`, so only its variant
+    /// is known if the error types differ); on `None` it returns `None`.
+    fn try_exit<'a>(&'a self, value: &Dynamic<'a>, vars: &HashMap<String, Dynamic<'a>>) -> Result<(Bool<'a>, Option<Bool<'a>>), TranslationError> {
+        let result = vars.get("result");
+        match self.type_of_value(value) {
+            Some(ValueType::Result(_, _)) => {
+                let taken = self.is_variant(value, "Err")?;
+                let returns = result.and_then(|result| {
+                    let is_err = self.is_variant(result, "Err").ok()?;
+                    let (_, error, _) = self.variant_field_values(value, "Err").ok()?.pop()?;
+                    let (_, returned, _) = self.variant_field_values(result, "Err").ok()?.pop()?;
+                    match returned._safe_eq(&error) {
+                        Ok(same) => Some(Bool::and(self.context(), &[&is_err, &same])),
+                        Err(_) => Some(is_err),
+                    }
+                });
+                Ok((taken, returns))
+            }
+            Some(ValueType::Option(_)) => {
+                let taken = self.is_variant(value, "None")?;
+                let returns = result.and_then(|result| self.is_variant(result, "None").ok());
+                Ok((taken, returns))
+            }
+            _ => Err(TranslationError::TypeError("`?` on a value that isn't a Result or Option".to_string())),
+        }
+    }
+
+    /// The value of `value?` when it doesn't return early
+    pub(crate) fn translate_try_value<'a>(&'a self, try_expr: &syn::ExprTry, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let value = self.translate_typed(&try_expr.expr, vars, None)?;
+        let variant = match self.type_of_value(&value) {
+            Some(ValueType::Result(_, _)) => "Ok",
+            Some(ValueType::Option(_)) => "Some",
+            _ => return Err(TranslationError::TypeError("`?` on a value that isn't a Result or Option".to_string())),
+        };
+        let (_, field, _) = self.variant_field_values(&value, variant)?.pop()
+            .expect("Ok and Some have one field");
+        Ok(field)
+    }
+
+    /// Collect the exits of an `if`, in tail position or as a statement
+    fn if_exits<'a>(&'a self, if_expr: &syn::ExprIf, vars: &mut HashMap<String, Dynamic<'a>>, exits: &mut Exits<'a>, tail: bool) -> Result<(), TranslationError> {
+        if !tail && !exits_function(&Expr::If(if_expr.clone())) {
+            return Ok(());
+        }
+        self.expr_exits(&if_expr.cond, vars, exits)?;
+        let mut then_vars = vars.clone();
+        let cond = self.branch_condition(&if_expr.cond, vars, &mut then_vars)?;
+        let then_exits = self.block_exits(&if_expr.then_branch, &mut then_vars, tail)?;
+        exits.nest(&cond, then_exits);
+        if let Some((_, else_expr)) = &if_expr.else_branch {
+            let mut else_exits = Exits::new(self.context());
+            let else_vars = &mut vars.clone();
+            match &**else_expr {
+                Expr::Block(block) => else_exits = self.block_exits(&block.block, else_vars, tail)?,
+                Expr::If(nested) => self.if_exits(nested, else_vars, &mut else_exits, tail)?,
+                other if tail => self.tail_exits(other, else_vars, &mut else_exits)?,
+                other => self.expr_exits(other, else_vars, &mut else_exits)?,
+            }
+            exits.nest(&cond.not(), else_exits);
+        }
+        Ok(())
+    }
+
+    /// Collect the exits of a `match`, in tail position or as a statement
+    fn match_exits<'a>(&'a self, match_expr: &syn::ExprMatch, vars: &mut HashMap<String, Dynamic<'a>>, exits: &mut Exits<'a>, tail: bool) -> Result<(), TranslationError> {
+        if !tail && !exits_function(&Expr::Match(match_expr.clone())) {
+            return Ok(());
+        }
+        self.expr_exits(&match_expr.expr, vars, exits)?;
+        for mut arm in self.match_arms(match_expr, vars)? {
+            let mut arm_exits = Exits::new(self.context());
+            if tail {
+                self.tail_exits(arm.body, &mut arm.vars, &mut arm_exits)?;
+            } else {
+                self.expr_exits(arm.body, &mut arm.vars, &mut arm_exits)?;
+            }
+            exits.nest(&arm.taken, arm_exits);
+        }
+        Ok(())
+    }
+
+    /// The exits of a `let ... else`: its else block runs when the pattern doesn't match
+    pub(crate) fn let_else_exits<'a>(&'a self, local: &syn::Local, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Option<Exits<'a>>, TranslationError> {
+        let Some(init) = &local.init else {
+            return Ok(None);
+        };
+        let Some((_, else_expr)) = &init.diverge else {
+            return Ok(None);
+        };
+        let value = self.translate_typed(&init.expr, vars, self.infer_int_type(&init.expr))?;
+        let matches = self.pattern_condition(&local.pat, &value, self.infer_int_type(&init.expr), &mut vars.clone())?;
+        let mut else_exits = Exits::new(self.context());
+        self.tail_exits(else_expr, &mut vars.clone(), &mut else_exits)?;
+        let mut exits = Exits::new(self.context());
+        exits.nest(&matches.not(), else_exits);
+        Ok(Some(exits))
+    }
+
+    /// Translate the condition of an `if`, including `if let`
+    ///
+    /// The bindings of `let` patterns (also in `&&` chains) are added to
+    /// `branch_vars`, the variables of the branch taken when it holds.
+    pub(crate) fn branch_condition<'a>(&'a self, cond: &Expr, vars: &mut HashMap<String, Dynamic<'a>>, branch_vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Bool<'a>, TranslationError> {
+        match cond {
+            Expr::Let(let_expr) => {
+                let int_ty = self.infer_int_type(&let_expr.expr);
+                let value = self.translate_typed(&let_expr.expr, vars, int_ty)?;
+                self.pattern_condition(&let_expr.pat, &value, int_ty, branch_vars)
+            }
+            Expr::Binary(bin) if matches!(bin.op, BinOp::And(_)) && has_let(cond) => {
+                let left = self.branch_condition(&bin.left, vars, branch_vars)?;
+                // Later conditions see the bindings of earlier ones
+                let mut scope = branch_vars.clone();
+                let right = self.branch_condition(&bin.right, &mut scope, branch_vars)?;
+                Ok(Bool::and(self.context(), &[&left, &right]))
+            }
+            Expr::Paren(paren) => self.branch_condition(&paren.expr, vars, branch_vars),
+            _ => self.translate_typed(cond, vars, None)?.as_bool().ok_or_else(|| {
+                TranslationError::TypeError("If condition must be boolean".to_string())
+            }),
+        }
+    }
+
+    /// The arms of a `match`, each with the condition under which it's taken
+    ///
+    /// An arm is taken when its pattern and guard hold and no earlier arm's do.
+    pub(crate) fn match_arms<'a, 'e>(&'a self, match_expr: &'e syn::ExprMatch, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Vec<MatchArm<'a, 'e>>, TranslationError> {
+        let ctx = self.context();
+        let int_ty = self.infer_int_type(&match_expr.expr);
+        let scrutinee = self.translate_typed(&match_expr.expr, vars, int_ty)?;
+        let mut no_earlier_arm = Bool::from_bool(ctx, true);
+        let mut arms = Vec::new();
+        for arm in &match_expr.arms {
+            let mut arm_vars = vars.clone();
+            let mut matches = self.pattern_condition(&arm.pat, &scrutinee, int_ty, &mut arm_vars)?;
+            if let Some((_, guard)) = &arm.guard {
+                let guard = self.translate_typed(guard, &mut arm_vars, None)?.as_bool().ok_or_else(|| {
+                    TranslationError::TypeError("Match guard must be boolean".to_string())
+                })?;
+                matches = Bool::and(ctx, &[&matches, &guard]);
+            }
+            arms.push(MatchArm {
+                taken: Bool::and(ctx, &[&no_earlier_arm, &matches]),
+                vars: arm_vars,
+                body: &arm.body,
+            });
+            no_earlier_arm = Bool::and(ctx, &[&no_earlier_arm, &matches.not()]);
+        }
+        Ok(arms)
+    }
+
+    /// The condition under which `value` matches `pat`, binding its names in `vars`
+    ///
+    /// `int_ty` is the integer type of `value`, for literal patterns.
+    pub(crate) fn pattern_condition<'a>(&'a self, pat: &Pat, value: &Dynamic<'a>, int_ty: Option<IntType>, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Bool<'a>, TranslationError> {
+        let ctx = self.context();
+        let truth = Bool::from_bool(ctx, true);
+        match pat {
+            Pat::Wild(_) | Pat::Rest(_) => Ok(truth),
+            Pat::Ident(ident) => {
+                let name = ident.ident.to_string();
+                if ident.subpat.is_none() && name == "None" {
+                    return self.is_variant(value, "None");
+                }
+                let matches = match &ident.subpat {
+                    Some((_, subpat)) => self.pattern_condition(subpat, value, int_ty, vars)?,
+                    None => truth,
+                };
+                self.set_var_type(&name, int_ty);
+                vars.insert(name, value.clone());
+                Ok(matches)
+            }
+            Pat::Lit(lit) => {
+                let literal = self.translate_typed(&Expr::Lit(lit.clone()), vars, int_ty)?;
+                literal_eq(value, &literal)
+            }
+            Pat::Range(range) => {
+                let mut bounds = Vec::new();
+                if let Some(start) = &range.start {
+                    let start = self.translate_typed(start, vars, int_ty)?;
+                    bounds.push(self.translate_binary_op(BinOp::Ge(Default::default()), value.clone(), start, int_ty)?);
+                }
+                if let Some(end) = &range.end {
+                    let op = match range.limits {
+                        syn::RangeLimits::HalfOpen(_) => BinOp::Lt(Default::default()),
+                        syn::RangeLimits::Closed(_) => BinOp::Le(Default::default()),
+                    };
+                    let end = self.translate_typed(end, vars, int_ty)?;
+                    bounds.push(self.translate_binary_op(op, value.clone(), end, int_ty)?);
+                }
+                let bounds: Vec<Bool<'a>> = bounds.iter().filter_map(|bound| bound.as_bool()).collect();
+                let refs: Vec<&Bool<'a>> = bounds.iter().collect();
+                Ok(Bool::and(ctx, &refs))
+            }
+            Pat::Or(or) => {
+                if or.cases.iter().any(binds_names) {
+                    return Err(TranslationError::UnsupportedExpression(format!(
+                        "bindings inside the pattern `{}`", pat.to_token_stream()
+                    )));
+                }
+                let cases = or.cases.iter()
+                    .map(|case| self.pattern_condition(case, value, int_ty, vars))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let refs: Vec<&Bool<'a>> = cases.iter().collect();
+                Ok(Bool::or(ctx, &refs))
+            }
+            Pat::Paren(paren) => self.pattern_condition(&paren.pat, value, int_ty, vars),
+            Pat::Reference(reference) => self.pattern_condition(&reference.pat, value, int_ty, vars),
+            Pat::Type(pat_type) => {
+                let int_ty = IntType::from_type(&pat_type.ty).or(int_ty);
+                self.pattern_condition(&pat_type.pat, value, int_ty, vars)
+            }
+            Pat::Path(path) => {
+                let variant = path.path.segments.last().map(|s| s.ident.to_string()).unwrap_or_default();
+                if self.type_of_value(value).is_some_and(|ty| self.variant_names(&ty).contains(&variant)) {
+                    return self.is_variant(value, &variant);
+                }
+                // A constant
+                let constant = self.translate_typed(&Expr::Path(path.clone()), vars, int_ty)?;
+                literal_eq(value, &constant)
+            }
+            Pat::TupleStruct(tuple_struct) => {
+                let variant = variant_name(&tuple_struct.path);
+                let matches = self.is_variant(value, &variant)?;
+                let fields = self.variant_field_values(value, &variant)?;
+                let elems: Vec<&Pat> = tuple_struct.elems.iter().collect();
+                // `..` stands for the fields between those before and after it
+                let rest = elems.iter().position(|elem| matches!(elem, Pat::Rest(_)));
+                let positioned: Vec<(usize, &Pat)> = match rest {
+                    Some(_) if elems.len() - 1 > fields.len() => return Err(TranslationError::TypeError(format!(
+                        "`{}` has {} fields but the pattern has {}", variant, fields.len(), elems.len() - 1
+                    ))),
+                    Some(rest) => elems[..rest].iter().copied().enumerate()
+                        .chain(elems[rest + 1..].iter().enumerate().map(|(i, elem)| (fields.len() + i + rest + 1 - elems.len(), *elem)))
+                        .collect(),
+                    None if elems.len() == fields.len() => elems.into_iter().enumerate().collect(),
+                    None => return Err(TranslationError::TypeError(format!(
+                        "`{}` has {} fields but the pattern has {}", variant, fields.len(), elems.len()
+                    ))),
+                };
+                let mut conditions = vec![matches];
+                for (index, elem) in positioned {
+                    let (_, field, field_ty) = &fields[index];
+                    conditions.push(self.pattern_condition(elem, field, field_ty.int_type(), vars)?);
+                }
+                let refs: Vec<&Bool<'a>> = conditions.iter().collect();
+                Ok(Bool::and(ctx, &refs))
+            }
+            Pat::Struct(pat_struct) => {
+                let variant = variant_name(&pat_struct.path);
+                let matches = self.is_variant(value, &variant)?;
+                let fields = self.variant_field_values(value, &variant)?;
+                let mut conditions = vec![matches];
+                for field_pat in &pat_struct.fields {
+                    let member = match &field_pat.member {
+                        syn::Member::Named(ident) => ident.to_string(),
+                        syn::Member::Unnamed(index) => index.index.to_string(),
+                    };
+                    let (_, field, field_ty) = fields.iter()
+                        .find(|(name, _, _)| *name == member)
+                        .ok_or_else(|| TranslationError::TypeError(format!("`{}` has no field `{}`", variant, member)))?;
+                    conditions.push(self.pattern_condition(&field_pat.pat, field, field_ty.int_type(), vars)?);
+                }
+                let refs: Vec<&Bool<'a>> = conditions.iter().collect();
+                Ok(Bool::and(ctx, &refs))
+            }
+            _ => Err(TranslationError::UnsupportedExpression(format!(
+                "the pattern `{}`", pat.to_token_stream()
+            ))),
+        }
+    }
+
+    /// Translate an `if` used as a value: `ite(cond, then, else)`
+    pub(crate) fn translate_if_value<'a>(&'a self, if_expr: &syn::ExprIf, vars: &mut HashMap<String, Dynamic<'a>>, ty: Option<&ValueType>) -> Result<Dynamic<'a>, TranslationError> {
+        let Some((_, else_expr)) = &if_expr.else_branch else {
+            return Err(TranslationError::UnsupportedExpression("`if` without `else` used as a value".to_string()));
+        };
+        let mut then_vars = vars.clone();
+        let cond = self.branch_condition(&if_expr.cond, vars, &mut then_vars)?;
+        let then_block = Expr::Block(syn::ExprBlock {
+            attrs: Vec::new(),
+            label: None,
+            block: if_expr.then_branch.clone(),
+        });
+        let arms = [(cond.clone(), &then_block, then_vars), (cond.not(), &**else_expr, vars.clone())];
+        self.select_value(arms, ty)
+    }
+
+    /// Translate a `match` used as a value: an if-then-else over its arms
+    pub(crate) fn translate_match_value<'a>(&'a self, match_expr: &syn::ExprMatch, vars: &mut HashMap<String, Dynamic<'a>>, ty: Option<&ValueType>) -> Result<Dynamic<'a>, TranslationError> {
+        let arms = self.match_arms(match_expr, vars)?;
+        self.select_value(arms.into_iter().map(|arm| (arm.taken, arm.body, arm.vars)), ty)
+    }
+
+    /// Translate `matches!(value, pattern)`, optionally with an `if` guard
+    pub(crate) fn translate_matches<'a>(&'a self, mac: &syn::Macro, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let args: MatchesArgs = mac.parse_body()
+            .map_err(|e| TranslationError::ParseError(format!("matches!: {}", e)))?;
+        let int_ty = self.infer_int_type(&args.value);
+        let value = self.translate_typed(&args.value, vars, int_ty)?;
+        let mut pattern_vars = vars.clone();
+        let matches = self.pattern_condition(&args.pat, &value, int_ty, &mut pattern_vars)?;
+        let Some(guard) = &args.guard else {
+            return Ok(matches.into());
+        };
+        let guard = self.translate_typed(guard, &mut pattern_vars, None)?.as_bool()
+            .ok_or_else(|| TranslationError::TypeError("Match guard must be boolean".to_string()))?;
+        Ok(Bool::and(self.context(), &[&matches, &guard]).into())
+    }
+
+    /// The value of the branch taken, from branches and the conditions under which they're taken
+    ///
+    /// Branches that return or panic produce no value and are left out; the
+    /// last remaining branch is the default.
+    fn select_value<'a, 'e>(&'a self, branches: impl IntoIterator<Item = (Bool<'a>, &'e Expr, HashMap<String, Dynamic<'a>>)>, ty: Option<&ValueType>) -> Result<Dynamic<'a>, TranslationError> {
+        let mut values = Vec::new();
+        for (taken, body, mut branch_vars) in branches {
+            if diverges(body) {
+                continue;
+            }
+            values.push((taken, self.translate_hinted(body, &mut branch_vars, ty)?));
+        }
+        let (_, mut value) = values.pop().ok_or_else(|| {
+            TranslationError::UnsupportedExpression("a branch used as a value that never produces one".to_string())
+        })?;
+        for (taken, branch_value) in values.into_iter().rev() {
+            if branch_value.get_sort() != value.get_sort() {
+                return Err(TranslationError::TypeError("Branches have values of different sorts".to_string()));
+            }
+            value = taken.ite(&branch_value, &value);
+        }
+        Ok(value)
+    }
+
+    /// Translate a block used as a value: its statements, then its final expression
+    pub(crate) fn translate_block_value<'a>(&'a self, block: &Block, vars: &mut HashMap<String, Dynamic<'a>>, ty: Option<&ValueType>) -> Result<Dynamic<'a>, TranslationError> {
+        let mut inner = vars.clone();
+        for (index, stmt) in block.stmts.iter().enumerate() {
+            match stmt {
+                Stmt::Local(local) => self.execute_local(local, &mut inner),
+                Stmt::Expr(expr, None) if index + 1 == block.stmts.len() => {
+                    return self.translate_hinted(expr, &mut inner, ty);
+                }
+                Stmt::Expr(expr, _) => {
+                    self.execute_statement(expr, &mut inner);
+                }
+                _ => {}
+            }
+        }
+        self.construct(&ValueType::Unit, "()", Vec::new())
+    }
+
+    /// The type of the function's result, from the `result` variable
+    pub(crate) fn result_type<'a>(&'a self, vars: &HashMap<String, Dynamic<'a>>) -> Option<ValueType> {
+        match self.type_of_value(vars.get("result")?)? {
+            ValueType::Int(_) => Some(ValueType::Int(self.var_type("result"))),
+            ty => Some(ty),
+        }
+    }
+
+    /// Translate a value computed for the function result, typed as the result
+    pub(crate) fn translate_result_value<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let result_ty = self.result_type(vars);
+        self.translate_hinted(expr, vars, result_ty.as_ref())
+    }
+
+    /// Build `result == value`, or `None` if there's no result or the sorts differ
+    pub(crate) fn result_equals<'a>(&'a self, value: &Dynamic<'a>, vars: &HashMap<String, Dynamic<'a>>) -> Option<Bool<'a>> {
+        vars.get("result")?._safe_eq(value).ok()
+    }
+}
+
+/// `value == literal`, reporting a type error if the sorts differ
+fn literal_eq<'a>(value: &Dynamic<'a>, literal: &Dynamic<'a>) -> Result<Bool<'a>, TranslationError> {
+    value._safe_eq(literal)
+        .map_err(|_| TranslationError::TypeError("Pattern doesn't match the type of the value".to_string()))
+}
+
+/// The variant a pattern path names: its last segment
+fn variant_name(path: &syn::Path) -> String {
+    path.segments.last().map(|s| s.ident.to_string()).unwrap_or_default()
+}
+
+/// Check whether a pattern binds any names
+fn binds_names(pat: &Pat) -> bool {
+    match pat {
+        Pat::Ident(ident) => ident.ident != "None" || ident.subpat.is_some(),
+        Pat::Or(or) => or.cases.iter().any(binds_names),
+        Pat::Paren(paren) => binds_names(&paren.pat),
+        Pat::Reference(reference) => binds_names(&reference.pat),
+        Pat::Type(pat_type) => binds_names(&pat_type.pat),
+        Pat::TupleStruct(tuple_struct) => tuple_struct.elems.iter().any(binds_names),
+        Pat::Struct(pat_struct) => pat_struct.fields.iter().any(|field| binds_names(&field.pat)),
+        Pat::Tuple(tuple) => tuple.elems.iter().any(binds_names),
+        Pat::Slice(slice) => slice.elems.iter().any(binds_names),
+        _ => false,
+    }
+}
+
+/// The arguments of `matches!`
+struct MatchesArgs {
+    value: Expr,
+    pat: Pat,
+    guard: Option<Expr>,
+}
+
+impl Parse for MatchesArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let value = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let pat = Pat::parse_multi_with_leading_vert(input)?;
+        let guard = if input.parse::<Option<Token![if]>>()?.is_some() {
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        input.parse::<Option<Token![,]>>()?;
+        Ok(MatchesArgs { value, pat, guard })
+    }
+}
+
+/// Check whether a condition contains a `let` (outside nested expressions)
+fn has_let(cond: &Expr) -> bool {
+    match cond {
+        Expr::Let(_) => true,
+        Expr::Binary(bin) if matches!(bin.op, BinOp::And(_)) => has_let(&bin.left) || has_let(&bin.right),
+        Expr::Paren(paren) => has_let(&paren.expr),
+        _ => false,
+    }
+}
+
+/// Check whether an expression never produces a value: it returns or panics
+pub(crate) fn diverges(expr: &Expr) -> bool {
+    match expr {
+        Expr::Return(_) => true,
+        Expr::Block(block) => match block.block.stmts.last() {
+            Some(Stmt::Expr(expr, _)) => diverges(expr),
+            Some(Stmt::Macro(mac)) => is_panic_macro(&mac.mac),
+            _ => false,
+        },
+        Expr::Paren(paren) => diverges(&paren.expr),
+        _ => panics(expr),
+    }
+}
+
+/// Check whether an expression is a `panic!`, `unreachable!`, `todo!` or `unimplemented!`
+fn panics(expr: &Expr) -> bool {
+    matches!(expr, Expr::Macro(mac) if is_panic_macro(&mac.mac))
+}
+
+fn is_panic_macro(mac: &syn::Macro) -> bool {
+    mac.path.segments.last().is_some_and(|segment| {
+        matches!(segment.ident.to_string().as_str(), "panic" | "unreachable" | "todo" | "unimplemented")
+    })
+}
@@ -0,0 +1,663 @@
+//! Option, Result, enums and structs as Z3 algebraic datatypes
+//!
+//! Values of type `Option<T>`, `Result<T, E>`, `()` and of the enums
+//! registered with [`Z3Translator::add_enum`] are Z3 datatype values, with one
+//! constructor per variant and one field per variant field. A struct registered
+//! with [`Z3Translator::add_struct`] is a datatype with a single constructor,
+//! and `x.field` reads its field. Sequences are datatype values too (see
+//! `translator::sequences`). A datatype sort
+//! is declared when first needed and named after its shape (e.g.
+//! `Option<Int>`); declaring the same name again yields the same sort, so only
+//! the shape of each sort is remembered (see [`Z3Translator::type_of_value`]).
+//!
+//! Fields of types that aren't modelled are integers, like other unknown
+//! types. A field whose type contains its own enum or struct (a recursive
+//! type) is an integer too.
+
+use std::collections::HashMap;
+use syn::Expr;
+use z3::ast::{Ast, Bool, Datatype, Dynamic};
+use z3::{DatatypeAccessor, DatatypeBuilder, DatatypeSort, Sort};
+use crate::translator::two_state::old_argument;
+use crate::translator::z3_translator::{is_bool_type, ArithmeticMode, IntType, TranslationError, Z3Translator};
+
+/// A datatype variant to declare: its constructor name and named field sorts
+type Variant<'ctx> = (String, Vec<(String, Sort<'ctx>)>);
+
+/// How values of a Rust type are modelled in Z3
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    Bool,
+    /// An integer, with its Rust type if known; also any type that isn't modelled
+    Int(Option<IntType>),
+    /// `()`
+    Unit,
+    Option(Box<ValueType>),
+    Result(Box<ValueType>, Box<ValueType>),
+    /// A registered enum, by name
+    Enum(String),
+    /// A registered struct, by name
+    Struct(String),
+    /// `Vec<T>`, `[T]` or `[T; N]`
+    Seq(Box<ValueType>),
+}
+
+impl ValueType {
+    /// The integer type of an integer value
+    pub fn int_type(&self) -> Option<IntType> {
+        match self {
+            ValueType::Int(ty) => *ty,
+            _ => None,
+        }
+    }
+}
+
+impl Z3Translator {
+    /// How values of a Rust type are modelled
+    ///
+    /// References and boxes are modelled as the value they point to.
+    pub(crate) fn value_type(&self, ty: &syn::Type) -> ValueType {
+        if is_bool_type(ty) {
+            return ValueType::Bool;
+        }
+        if let Some(int_ty) = IntType::from_type(ty) {
+            return ValueType::Int(Some(int_ty));
+        }
+        match ty {
+            syn::Type::Reference(reference) => self.value_type(&reference.elem),
+            syn::Type::Paren(paren) => self.value_type(&paren.elem),
+            syn::Type::Group(group) => self.value_type(&group.elem),
+            syn::Type::Tuple(tuple) if tuple.elems.is_empty() => ValueType::Unit,
+            syn::Type::Slice(slice) => ValueType::Seq(Box::new(self.value_type(&slice.elem))),
+            syn::Type::Array(array) => ValueType::Seq(Box::new(self.value_type(&array.elem))),
+            syn::Type::Path(type_path) => {
+                let Some(segment) = type_path.path.segments.last() else {
+                    return ValueType::Int(None);
+                };
+                let args: Vec<&syn::Type> = match &segment.arguments {
+                    syn::PathArguments::AngleBracketed(args) => args.args.iter()
+                        .filter_map(|arg| match arg {
+                            syn::GenericArgument::Type(ty) => Some(ty),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                let name = segment.ident.to_string();
+                match (name.as_str(), args.as_slice()) {
+                    ("Option", [inner]) => ValueType::Option(Box::new(self.value_type(inner))),
+                    ("Result", [ok, err]) => ValueType::Result(Box::new(self.value_type(ok)), Box::new(self.value_type(err))),
+                    // e.g. `type Result<T> = std::result::Result<T, Error>`, with an unknown error type
+                    ("Result", [ok]) => ValueType::Result(Box::new(self.value_type(ok)), Box::new(ValueType::Int(None))),
+                    ("Box", [inner]) => self.value_type(inner),
+                    ("Vec", [elem]) => ValueType::Seq(Box::new(self.value_type(elem))),
+                    (_, []) if self.enum_definition(&name).is_some() => ValueType::Enum(name),
+                    (_, []) if self.struct_definition(&name).is_some() => ValueType::Struct(name),
+                    _ => ValueType::Int(None),
+                }
+            }
+            _ => ValueType::Int(None),
+        }
+    }
+
+    /// The Z3 sort of values of a type, in the current arithmetic mode
+    pub(crate) fn sort_of(&self, ty: &ValueType) -> Sort<'_> {
+        match ty {
+            ValueType::Bool => Sort::bool(self.context()),
+            ValueType::Int(int_ty) => match self.mode() {
+                ArithmeticMode::Integer => Sort::int(self.context()),
+                ArithmeticMode::BitPrecise => Sort::bitvector(self.context(), int_ty.map_or(64, |t| t.bits)),
+            },
+            _ => self.datatype(ty).expect("datatype for a non-primitive type").sort,
+        }
+    }
+
+    /// Declare the datatype of an `Option`, `Result`, `()`, enum, struct or sequence type
+    ///
+    /// Returns `None` for booleans and integers.
+    pub(crate) fn datatype(&self, ty: &ValueType) -> Option<DatatypeSort<'_>> {
+        let ctx = self.context();
+        let field = |name: String, ty: &ValueType| (name, self.sort_of(ty));
+        let (name, variants): (String, Vec<Variant<'_>>) = match ty {
+            ValueType::Bool | ValueType::Int(_) => return None,
+            ValueType::Unit => ("Unit".to_string(), vec![("()".to_string(), Vec::new())]),
+            ValueType::Option(inner) => {
+                let value = field("Some.0".to_string(), inner);
+                let name = format!("Option<{}>", value.1);
+                (name, vec![("None".to_string(), Vec::new()), ("Some".to_string(), vec![value])])
+            }
+            ValueType::Result(ok, err) => {
+                let ok = field("Ok.0".to_string(), ok);
+                let err = field("Err.0".to_string(), err);
+                let name = format!("Result<{}, {}>", ok.1, err.1);
+                (name, vec![("Ok".to_string(), vec![ok]), ("Err".to_string(), vec![err])])
+            }
+            ValueType::Enum(enum_name) => {
+                let variants = self.variant_names(ty).into_iter()
+                    .enumerate()
+                    .map(|(index, variant)| {
+                        let fields = self.variant_fields(ty, index).iter()
+                            .map(|(field_name, field_ty)| field(format!("{}::{}.{}", enum_name, variant, field_name), field_ty))
+                            .collect();
+                        (format!("{}::{}", enum_name, variant), fields)
+                    })
+                    .collect();
+                (enum_name.clone(), variants)
+            }
+            ValueType::Struct(struct_name) => {
+                let fields = self.variant_fields(ty, 0).iter()
+                    .map(|(field_name, field_ty)| field(format!("{}.{}", struct_name, field_name), field_ty))
+                    .collect();
+                (struct_name.clone(), vec![(struct_name.clone(), fields)])
+            }
+            ValueType::Seq(elem) => {
+                let index = self.index_sort();
+                let elems = Sort::array(ctx, &index, &self.sort_of(elem));
+                let name = format!("Seq<{}>", self.sort_of(elem));
+                (name, vec![("Seq".to_string(), vec![("Seq.len".to_string(), index), ("Seq.elems".to_string(), elems)])])
+            }
+        };
+        let mut builder = DatatypeBuilder::new(ctx, name);
+        for (variant, fields) in &variants {
+            let fields = fields.iter()
+                .map(|(field_name, sort)| (field_name.as_str(), DatatypeAccessor::Sort(sort.clone())))
+                .collect();
+            builder = builder.variant(variant, fields);
+        }
+        let datatype = builder.finish();
+        self.remember_datatype(&datatype.sort, ty);
+        Some(datatype)
+    }
+
+    /// How a translated value is modelled, from its sort
+    pub(crate) fn type_of_value(&self, value: &Dynamic<'_>) -> Option<ValueType> {
+        if value.as_bool().is_some() {
+            Some(ValueType::Bool)
+        } else if value.as_int().is_some() {
+            Some(ValueType::Int(None))
+        } else if let Some(bv) = value.as_bv() {
+            Some(ValueType::Int(Some(IntType { bits: bv.get_size(), signed: false })))
+        } else {
+            self.datatype_type(&value.get_sort())
+        }
+    }
+
+    /// A constant of the given type
+    pub(crate) fn value_const(&self, name: &str, ty: &ValueType) -> Dynamic<'_> {
+        match ty {
+            ValueType::Bool => Bool::new_const(self.context(), name).into(),
+            ValueType::Int(int_ty) => self.int_const(name, *int_ty),
+            _ => Datatype::new_const(self.context(), name, &self.sort_of(ty)).into(),
+        }
+    }
+
+    /// Names of a datatype's variants, in constructor order
+    pub(crate) fn variant_names(&self, ty: &ValueType) -> Vec<String> {
+        match ty {
+            ValueType::Unit => vec!["()".to_string()],
+            ValueType::Option(_) => vec!["None".to_string(), "Some".to_string()],
+            ValueType::Result(_, _) => vec!["Ok".to_string(), "Err".to_string()],
+            ValueType::Enum(name) => self.enum_definition(name)
+                .map(|item| item.variants.iter().map(|v| v.ident.to_string()).collect())
+                .unwrap_or_default(),
+            ValueType::Struct(name) => vec![name.clone()],
+            ValueType::Bool | ValueType::Int(_) | ValueType::Seq(_) => Vec::new(),
+        }
+    }
+
+    /// Names and types of the fields of a datatype's variant
+    ///
+    /// Tuple fields are named by position (`0`, `1`, ...).
+    pub(crate) fn variant_fields(&self, ty: &ValueType, variant: usize) -> Vec<(String, ValueType)> {
+        let fields = match (ty, variant) {
+            (ValueType::Option(inner), 1) => return vec![("0".to_string(), (**inner).clone())],
+            (ValueType::Result(ok, _), 0) => return vec![("0".to_string(), (**ok).clone())],
+            (ValueType::Result(_, err), 1) => return vec![("0".to_string(), (**err).clone())],
+            (ValueType::Enum(name), _) => self.enum_definition(name)
+                .and_then(|item| item.variants.iter().nth(variant))
+                .map(|variant| (name, &variant.fields)),
+            (ValueType::Struct(name), 0) => self.struct_definition(name).map(|item| (name, &item.fields)),
+            _ => None,
+        };
+        let Some((name, fields)) = fields else {
+            return Vec::new();
+        };
+        fields.iter()
+            .enumerate()
+            .map(|(index, field)| {
+                let field_name = field.ident.as_ref().map_or_else(|| index.to_string(), |ident| ident.to_string());
+                let mut field_ty = self.value_type(&field.ty);
+                if self.contains_type(&field_ty, name, &mut Vec::new()) {
+                    field_ty = ValueType::Int(None);
+                }
+                (field_name, field_ty)
+            })
+            .collect()
+    }
+
+    /// Check whether values of a type can contain a value of the enum or struct `target`
+    fn contains_type(&self, ty: &ValueType, target: &str, seen: &mut Vec<String>) -> bool {
+        match ty {
+            ValueType::Option(inner) => self.contains_type(inner, target, seen),
+            ValueType::Result(ok, err) => self.contains_type(ok, target, seen) || self.contains_type(err, target, seen),
+            ValueType::Seq(elem) => self.contains_type(elem, target, seen),
+            ValueType::Enum(name) | ValueType::Struct(name) if name == target => true,
+            ValueType::Enum(name) | ValueType::Struct(name) if !seen.contains(name) => {
+                seen.push(name.clone());
+                let fields: Vec<&syn::Field> = match ty {
+                    ValueType::Enum(_) => self.enum_definition(name)
+                        .map(|item| item.variants.iter().flat_map(|variant| variant.fields.iter()).collect())
+                        .unwrap_or_default(),
+                    _ => self.struct_definition(name)
+                        .map(|item| item.fields.iter().collect())
+                        .unwrap_or_default(),
+                };
+                fields.iter().any(|field| self.contains_type(&self.value_type(&field.ty), target, seen))
+            }
+            _ => false,
+        }
+    }
+
+    /// The datatype of a value, with the index of the named variant
+    fn variant_of<'a>(&'a self, value: &Dynamic<'a>, variant: &str) -> Result<(ValueType, DatatypeSort<'a>, usize), TranslationError> {
+        let ty = self.type_of_value(value)
+            .filter(|ty| !matches!(ty, ValueType::Bool | ValueType::Int(_)))
+            .ok_or_else(|| TranslationError::TypeError(format!("`{}` is matched against a value that isn't an enum, struct, Option or Result", variant)))?;
+        let index = self.variant_names(&ty).iter()
+            .position(|name| name == variant)
+            .ok_or_else(|| TranslationError::TypeError(format!("`{}` isn't a variant of {:?}", variant, ty)))?;
+        let datatype = self.datatype(&ty).expect("datatype for a non-primitive type");
+        Ok((ty, datatype, index))
+    }
+
+    /// Holds when `value` is the named variant
+    pub(crate) fn is_variant<'a>(&'a self, value: &Dynamic<'a>, variant: &str) -> Result<Bool<'a>, TranslationError> {
+        let (_, datatype, index) = self.variant_of(value, variant)?;
+        Ok(datatype.variants[index].tester.apply(&[value]).as_bool().expect("testers return Bool"))
+    }
+
+    /// The fields of `value` as the named variant, with their types
+    ///
+    /// Only meaningful when `value` is that variant.
+    pub(crate) fn variant_field_values<'a>(&'a self, value: &Dynamic<'a>, variant: &str) -> Result<Vec<(String, Dynamic<'a>, ValueType)>, TranslationError> {
+        let (ty, datatype, index) = self.variant_of(value, variant)?;
+        Ok(self.variant_fields(&ty, index).into_iter()
+            .zip(&datatype.variants[index].accessors)
+            .map(|((name, field_ty), accessor)| (name, accessor.apply(&[value]), field_ty))
+            .collect())
+    }
+
+    /// Construct a variant of a datatype from its field values
+    ///
+    /// A field whose value has the wrong sort (e.g. an error type that isn't
+    /// modelled) gets an unknown value instead.
+    pub(crate) fn construct<'a>(&'a self, ty: &ValueType, variant: &str, fields: Vec<Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let datatype = self.datatype(ty)
+            .ok_or_else(|| TranslationError::TypeError(format!("`{}` of a type that isn't an enum, struct, Option or Result", variant)))?;
+        let index = self.variant_names(ty).iter()
+            .position(|name| name == variant)
+            .ok_or_else(|| TranslationError::TypeError(format!("`{}` isn't a variant of {:?}", variant, ty)))?;
+        let field_types = self.variant_fields(ty, index);
+        if fields.len() != field_types.len() {
+            return Err(TranslationError::TypeError(format!(
+                "`{}` has {} fields but is given {}", variant, field_types.len(), fields.len()
+            )));
+        }
+        let fields: Vec<Dynamic<'a>> = fields.into_iter()
+            .zip(&field_types)
+            .map(|(value, (name, field_ty))| {
+                if value.get_sort() == self.sort_of(field_ty) {
+                    value
+                } else {
+                    self.fresh_value(name, &self.value_const(name, field_ty))
+                }
+            })
+            .collect();
+        let fields: Vec<&dyn Ast<'a>> = fields.iter().map(|f| f as &dyn Ast<'a>).collect();
+        Ok(datatype.variants[index].constructor.apply(&fields))
+    }
+
+    /// The range constraints of a value's integers, e.g. `is_Some(x) ⟹ 0 <= x.0 <= u64::MAX`
+    ///
+    /// `None` if the value has no constrained integers (bit-vectors already
+    /// encode their range). A sequence's length and elements are constrained
+    /// like integers.
+    pub(crate) fn value_in_range<'a>(&'a self, value: &Dynamic<'a>, ty: &ValueType) -> Option<Bool<'a>> {
+        match ty {
+            ValueType::Int(Some(int_ty)) => Some(self.int_in_range(&value.as_int()?, *int_ty)),
+            ValueType::Option(_) | ValueType::Result(_, _) | ValueType::Enum(_) | ValueType::Struct(_) => {
+                let mut constraints = Vec::new();
+                for variant in self.variant_names(ty) {
+                    let fields: Vec<Bool<'a>> = self.variant_field_values(value, &variant).ok()?.iter()
+                        .filter_map(|(_, field, field_ty)| self.value_in_range(field, field_ty))
+                        .collect();
+                    if !fields.is_empty() {
+                        let fields: Vec<&Bool<'a>> = fields.iter().collect();
+                        constraints.push(self.is_variant(value, &variant).ok()?.implies(&Bool::and(self.context(), &fields)));
+                    }
+                }
+                let constraints: Vec<&Bool<'a>> = constraints.iter().collect();
+                (!constraints.is_empty()).then(|| Bool::and(self.context(), &constraints))
+            }
+            ValueType::Seq(_) => self.seq_in_range(value),
+            _ => None,
+        }
+    }
+
+    /// The type of an expression's value, if known without translating it
+    pub(crate) fn infer_value_type(&self, expr: &Expr) -> Option<ValueType> {
+        match expr {
+            Expr::Path(path) => path.path.get_ident()
+                .and_then(|ident| self.var_value_type(&ident.to_string()))
+                .or_else(|| self.infer_int_type(expr).map(|ty| ValueType::Int(Some(ty)))),
+            Expr::Call(call) => match old_argument(call) {
+                Some(arg) => self.infer_value_type(arg),
+                None => self.call_value_type(call),
+            },
+            Expr::MethodCall(method) => self.method_value_type(method),
+            Expr::Index(index) => self.seq_elem_type(&index.expr),
+            Expr::Field(field) => self.field_value_type(field),
+            Expr::Paren(paren) => self.infer_value_type(&paren.expr),
+            Expr::Group(group) => self.infer_value_type(&group.expr),
+            Expr::Reference(reference) => self.infer_value_type(&reference.expr),
+            Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Deref(_)) => self.infer_value_type(&unary.expr),
+            _ => self.infer_int_type(expr).map(|ty| ValueType::Int(Some(ty))),
+        }
+    }
+
+    /// The type of a method's result, if known
+    ///
+    /// e.g. `u64` for `amount.unwrap()` with `amount: Option<u64>`; see
+    /// [`Z3Translator::seq_method_value_type`] for sequence methods.
+    pub(crate) fn method_value_type(&self, method: &syn::ExprMethodCall) -> Option<ValueType> {
+        if let Some(ty) = self.seq_method_value_type(method) {
+            return Some(ty);
+        }
+        let receiver = self.infer_value_type(&method.receiver)?;
+        match (method.method.to_string().as_str(), receiver) {
+            ("unwrap" | "expect" | "unwrap_or" | "unwrap_or_default", ValueType::Option(value) | ValueType::Result(value, _)) => Some(*value),
+            ("unwrap_err" | "expect_err", ValueType::Result(_, error)) => Some(*error),
+            _ => None,
+        }
+    }
+
+    /// The type of a struct's field, if known, e.g. `u64` for `out.value` with `out: TxOut`
+    pub(crate) fn field_value_type(&self, field: &syn::ExprField) -> Option<ValueType> {
+        let ty = self.infer_value_type(&field.base)?;
+        if !matches!(ty, ValueType::Struct(_)) {
+            return None;
+        }
+        let name = member_name(&field.member);
+        self.variant_fields(&ty, 0).into_iter()
+            .find(|(field_name, _)| *field_name == name)
+            .map(|(_, field_ty)| field_ty)
+    }
+
+    /// Translate `x.field` (or `x.0`) on a struct
+    pub(crate) fn translate_field<'a>(&'a self, field: &syn::ExprField, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let base = self.translate_typed(&field.base, vars, None)?;
+        self.field_value(&base, &field.member)
+    }
+
+    /// The value of a struct's field
+    fn field_value<'a>(&'a self, value: &Dynamic<'a>, member: &syn::Member) -> Result<Dynamic<'a>, TranslationError> {
+        let name = member_name(member);
+        let Some(ValueType::Struct(struct_name)) = self.type_of_value(value) else {
+            return Err(TranslationError::TypeError(format!("Field `{}` of a value that isn't a struct", name)));
+        };
+        self.variant_field_values(value, &struct_name)?.into_iter()
+            .find(|(field_name, _, _)| *field_name == name)
+            .map(|(_, field, _)| field)
+            .ok_or_else(|| TranslationError::TypeError(format!("`{}` has no field `{}`", struct_name, name)))
+    }
+
+    /// A struct with one field replaced
+    fn with_field<'a>(&'a self, value: &Dynamic<'a>, member: &syn::Member, field: Dynamic<'a>) -> Result<Dynamic<'a>, TranslationError> {
+        let name = member_name(member);
+        let ty = self.type_of_value(value);
+        let Some(ValueType::Struct(struct_name)) = &ty else {
+            return Err(TranslationError::TypeError(format!("Field `{}` of a value that isn't a struct", name)));
+        };
+        let old_fields = self.variant_field_values(value, struct_name)?;
+        if !old_fields.iter().any(|(field_name, _, _)| *field_name == name) {
+            return Err(TranslationError::TypeError(format!("`{}` has no field `{}`", struct_name, name)));
+        }
+        let fields = old_fields.into_iter()
+            .map(|(field_name, old, _)| if field_name == name { field.clone() } else { old })
+            .collect();
+        self.construct(ty.as_ref().expect("checked above"), struct_name, fields)
+    }
+
+    /// The current value of a place: a variable, or a field of a place (e.g. `tx.outputs`)
+    pub(crate) fn place_value<'a>(&'a self, place: &Expr, vars: &HashMap<String, Dynamic<'a>>) -> Option<Dynamic<'a>> {
+        match place {
+            Expr::Path(path) => vars.get(&path.path.get_ident()?.to_string()).cloned(),
+            Expr::Paren(paren) => self.place_value(&paren.expr, vars),
+            Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Deref(_)) => self.place_value(&unary.expr, vars),
+            Expr::Field(field) => self.field_value(&self.place_value(&field.base, vars)?, &field.member).ok(),
+            _ => None,
+        }
+    }
+
+    /// The variable a place belongs to, and its value once the place is set to `value`
+    ///
+    /// Setting a field rebuilds the structs around it: after `tx.out.value = v`,
+    /// `tx` is `tx` with `out` replaced by `tx.out` with `value` replaced by `v`.
+    pub(crate) fn assign_place<'a>(&'a self, place: &Expr, value: Dynamic<'a>, vars: &HashMap<String, Dynamic<'a>>) -> Option<(String, Dynamic<'a>)> {
+        match place {
+            Expr::Path(path) => Some((path.path.get_ident()?.to_string(), value)),
+            Expr::Paren(paren) => self.assign_place(&paren.expr, value, vars),
+            Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Deref(_)) => self.assign_place(&unary.expr, value, vars),
+            Expr::Field(field) => {
+                let base = self.place_value(&field.base, vars)?;
+                let base = self.with_field(&base, &field.member, value).ok()?;
+                self.assign_place(&field.base, base, vars)
+            }
+            _ => None,
+        }
+    }
+
+    /// Translate a method of `Option` or `Result`
+    ///
+    /// `is_some`, `is_none`, `is_ok` and `is_err` test the variant. `unwrap`,
+    /// `expect`, `unwrap_err` and `expect_err` give the payload, which is
+    /// unknown for the other variant; `unwrap_or(d)` and `unwrap_or_default()`
+    /// give `d` or `0`/`false` instead. `ok()` and `err()` give an `Option`.
+    /// Returns `None` for other methods.
+    pub(crate) fn translate_datatype_method<'a>(&'a self, method: &syn::ExprMethodCall, vars: &mut HashMap<String, Dynamic<'a>>) -> Option<Result<Dynamic<'a>, TranslationError>> {
+        let name = method.method.to_string();
+        let known = matches!(name.as_str(),
+            "is_some" | "is_none" | "is_ok" | "is_err" | "unwrap" | "expect" | "unwrap_err" |
+            "expect_err" | "unwrap_or" | "unwrap_or_default" | "ok" | "err");
+        known.then(|| self.datatype_method(&name, method, vars))
+    }
+
+    fn datatype_method<'a>(&'a self, name: &str, method: &syn::ExprMethodCall, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let receiver = self.translate_typed(&method.receiver, vars, None)?;
+        let (success, failure) = match self.type_of_value(&receiver) {
+            Some(ValueType::Option(_)) => ("Some", "None"),
+            Some(ValueType::Result(_, _)) => ("Ok", "Err"),
+            _ => return Err(TranslationError::TypeError(format!("`{}` on a value that isn't an Option or Result", name))),
+        };
+        let payload = |variant: &str| -> Result<(Dynamic<'a>, ValueType), TranslationError> {
+            let (_, value, ty) = self.variant_field_values(&receiver, variant)?.into_iter().next()
+                .ok_or_else(|| TranslationError::TypeError(format!("`{}` has no value", variant)))?;
+            Ok((value, ty))
+        };
+        match name {
+            "is_some" | "is_ok" => Ok(self.is_variant(&receiver, success)?.into()),
+            "is_none" | "is_err" => Ok(self.is_variant(&receiver, failure)?.into()),
+            "unwrap" | "expect" => Ok(payload(success)?.0),
+            "unwrap_err" | "expect_err" => Ok(payload("Err")?.0),
+            "unwrap_or" | "unwrap_or_default" => {
+                let (value, ty) = payload(success)?;
+                let default = match (name, method.args.first()) {
+                    ("unwrap_or", Some(default)) => self.translate_value(default, vars, &ty)?,
+                    ("unwrap_or_default", None) => self.default_value(&ty)?,
+                    _ => return Err(TranslationError::UnsupportedExpression(format!("`{}` with {} arguments", name, method.args.len()))),
+                };
+                if default.get_sort() != value.get_sort() {
+                    return Err(TranslationError::TypeError(format!("The default of `{}` has the wrong type", name)));
+                }
+                Ok(self.is_variant(&receiver, success)?.ite(&value, &default))
+            }
+            _ => {
+                let variant = if name == "ok" { "Ok" } else { "Err" };
+                let (value, ty) = payload(variant)?;
+                let option = ValueType::Option(Box::new(ty));
+                let some = self.construct(&option, "Some", vec![value])?;
+                let none = self.construct(&option, "None", Vec::new())?;
+                Ok(self.is_variant(&receiver, variant)?.ite(&some, &none))
+            }
+        }
+    }
+
+    /// `Default::default()` of an integer or `bool`
+    fn default_value(&self, ty: &ValueType) -> Result<Dynamic<'_>, TranslationError> {
+        match ty {
+            ValueType::Bool => Ok(Bool::from_bool(self.context(), false).into()),
+            ValueType::Int(int_ty) => self.int_literal(0, *int_ty),
+            _ => Err(TranslationError::UnsupportedExpression(format!("The default value of {:?}", ty))),
+        }
+    }
+
+    /// Translate `Some(e)`, `None`, `Ok(e)`, `Err(e)`, `()`, an enum variant or a struct
+    ///
+    /// `expected` gives the type of `None`, `Ok` and `Err` (e.g. the function's
+    /// return type); `Some(e)` takes its type from `e` otherwise. Fields missing
+    /// from `S { a, ..base }` are taken from `base`. Returns `None` if `expr`
+    /// isn't a constructor.
+    pub(crate) fn translate_constructor<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>, expected: Option<&ValueType>) -> Option<Result<Dynamic<'a>, TranslationError>> {
+        let mut rest = None;
+        let (path, args): (&syn::Path, Vec<(String, &Expr)>) = match expr {
+            Expr::Tuple(tuple) if tuple.elems.is_empty() => return Some(self.construct(&ValueType::Unit, "()", Vec::new())),
+            Expr::Path(path) => (&path.path, Vec::new()),
+            Expr::Call(call) => match &*call.func {
+                Expr::Path(path) => (&path.path, call.args.iter().enumerate().map(|(i, arg)| (i.to_string(), arg)).collect()),
+                _ => return None,
+            },
+            Expr::Struct(item) => {
+                rest = item.rest.as_deref();
+                (&item.path, item.fields.iter().map(|field| (member_name(&field.member), &field.expr)).collect())
+            }
+            _ => return None,
+        };
+        let (ty, variant) = match self.constructor_type(path, expected) {
+            Some(Ok(found)) => found,
+            Some(Err(e)) => return Some(Err(e)),
+            None => {
+                // `Some(e)` without an expected type: an option of whatever `e` is
+                let is_some = path.segments.last().is_some_and(|s| s.ident == "Some") && args.len() == 1;
+                if !is_some || !is_prelude_path(path, "Option") {
+                    return None;
+                }
+                let value = match self.translate_typed(args[0].1, vars, None) {
+                    Ok(value) => value,
+                    Err(e) => return Some(Err(e)),
+                };
+                let ty = ValueType::Option(Box::new(self.type_of_value(&value)?));
+                return Some(self.construct(&ty, "Some", vec![value]));
+            }
+        };
+        let index = self.variant_names(&ty).iter().position(|name| *name == variant)?;
+        let field_types = self.variant_fields(&ty, index);
+        let base_fields = match rest {
+            Some(base) => match self.translate_value(base, vars, &ty).and_then(|base| self.variant_field_values(&base, &variant)) {
+                Ok(fields) => fields,
+                Err(e) => return Some(Err(e)),
+            },
+            None => Vec::new(),
+        };
+        let mut fields = Vec::new();
+        for (name, field_ty) in &field_types {
+            if let Some((_, arg)) = args.iter().find(|(arg_name, _)| arg_name == name) {
+                match self.translate_value(arg, vars, field_ty) {
+                    Ok(value) => fields.push(value),
+                    Err(e) => return Some(Err(e)),
+                }
+            } else if let Some((_, value, _)) = base_fields.iter().find(|(base_name, _, _)| base_name == name) {
+                fields.push(value.clone());
+            } else {
+                return Some(Err(TranslationError::TypeError(format!("Missing field `{}` of `{}`", name, variant))));
+            }
+        }
+        Some(self.construct(&ty, &variant, fields))
+    }
+
+    /// The datatype and variant a constructor path names
+    ///
+    /// A struct's only variant is named after the struct. `None` if the path
+    /// isn't a constructor, or is `Some` with no expected type; an error for
+    /// `None`, `Ok` or `Err` with no expected type.
+    fn constructor_type(&self, path: &syn::Path, expected: Option<&ValueType>) -> Option<Result<(ValueType, String), TranslationError>> {
+        let segments: Vec<String> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+        let variant = segments.last()?.clone();
+        if let [.., enum_name, _] = segments.as_slice() {
+            if self.enum_definition(enum_name).is_some() {
+                return Some(Ok((ValueType::Enum(enum_name.clone()), variant)));
+            }
+        }
+        if self.struct_definition(&variant).is_some() {
+            return Some(Ok((ValueType::Struct(variant.clone()), variant)));
+        }
+        let (family, variant) = match variant.as_str() {
+            "Some" | "None" if is_prelude_path(path, "Option") => ("Option", variant),
+            "Ok" | "Err" if is_prelude_path(path, "Result") => ("Result", variant),
+            _ => return None,
+        };
+        match (family, expected) {
+            ("Option", Some(ty @ ValueType::Option(_))) | ("Result", Some(ty @ ValueType::Result(_, _))) => Some(Ok((ty.clone(), variant))),
+            ("Option", _) if variant == "Some" => None,
+            _ => Some(Err(TranslationError::TypeError(format!("Can't infer the type of `{}`", variant)))),
+        }
+    }
+
+    /// Translate an expression whose value is expected to have the given type
+    ///
+    /// Like [`Z3Translator::translate_typed`], but the type also fixes the sort
+    /// of constructors such as `None` and `Err(e)`, including in the arms of
+    /// an `if` or `match`.
+    pub(crate) fn translate_value<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>, ty: &ValueType) -> Result<Dynamic<'a>, TranslationError> {
+        if let ValueType::Seq(elem) = ty {
+            if let Some(value) = self.translate_seq_literal(expr, vars, Some(elem)) {
+                return value;
+            }
+        }
+        match expr {
+            Expr::Paren(paren) => self.translate_value(&paren.expr, vars, ty),
+            Expr::Group(group) => self.translate_value(&group.expr, vars, ty),
+            Expr::Reference(reference) => self.translate_value(&reference.expr, vars, ty),
+            Expr::If(if_expr) => self.translate_if_value(if_expr, vars, Some(ty)),
+            Expr::Match(match_expr) => self.translate_match_value(match_expr, vars, Some(ty)),
+            Expr::Block(block) => self.translate_block_value(&block.block, vars, Some(ty)),
+            _ => match self.translate_constructor(expr, vars, Some(ty)) {
+                Some(value) => value,
+                None => self.translate_typed(expr, vars, ty.int_type()),
+            },
+        }
+    }
+
+    /// Translate an expression with an optional expected type
+    pub(crate) fn translate_hinted<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>, ty: Option<&ValueType>) -> Result<Dynamic<'a>, TranslationError> {
+        match ty {
+            Some(ty) => self.translate_value(expr, vars, ty),
+            None => self.translate_typed(expr, vars, None),
+        }
+    }
+}
+
+/// Name of a field: its identifier, or its position in a tuple struct
+fn member_name(member: &syn::Member) -> String {
+    match member {
+        syn::Member::Named(ident) => ident.to_string(),
+        syn::Member::Unnamed(index) => index.index.to_string(),
+    }
+}
+
+/// Check whether a path is `Some`/`Ok`-like: a bare variant name, or qualified by its type
+fn is_prelude_path(path: &syn::Path, family: &str) -> bool {
+    match path.segments.len() {
+        1 => true,
+        n => path.segments[n - 2].ident == family,
+    }
+}
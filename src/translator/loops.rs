@@ -0,0 +1,421 @@
+//! Loops: invariants, termination measures and their obligations
+//!
+//! A loop is summarized by its `#[invariant(...)]` and `#[decreases(...)]`
+//! attributes instead of being unrolled. The variables it assigns are
+//! havocked (replaced by fresh constants standing for their values at the
+//! start of an arbitrary iteration), and these obligations are collected with
+//! the other implicit obligations (see `translator::overflow`):
+//! - each invariant holds when the loop is entered,
+//! - one iteration, starting from a state where the invariants and the loop
+//!   condition hold, preserves each invariant, and
+//! - the `decreases` measure is non-negative and strictly smaller after that
+//!   iteration, so the loop terminates.
+//!
+//! After the loop, the invariants are assumed along with the negated loop
+//! condition.
+//!
+//! A `for` loop over an integer range (`a..b`, `a..=b`) runs while its
+//! counter is within the range. In an invariant the loop variable is the
+//! counter at the start of the next iteration, so after `for i in 0..n` it
+//! equals `n`. Over any other iterable the loop variable is unconstrained and
+//! the loop may stop after any iteration. Loops containing `break` or
+//! `continue` only havoc their variables: their invariants are neither checked
+//! nor assumed. An invariant or measure that isn't checked, there or because
+//! it can't be translated, gets an obligation that is never proven.
+
+use std::collections::{BTreeSet, HashMap};
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{Block, Expr, Stmt};
+use z3::ast::{Ast, Bool, Dynamic, Int};
+use crate::parser::contracts::{extract_loop_contracts, LoopContracts};
+use crate::translator::overflow::{leave_scope, to_int, Obligation, ObligationKind};
+use crate::translator::sequences::mutates_receiver;
+use crate::translator::z3_translator::{compound_operator, ArithmeticMode, IntType, Z3Translator};
+
+/// How a loop decides whether to run another iteration
+enum Iteration<'e> {
+    /// `while cond { ... }`
+    While(&'e Expr),
+    /// `for var in start..end` or `for var in start..=end`
+    Range {
+        var: Option<String>,
+        start: &'e Expr,
+        end: &'e Expr,
+        inclusive: bool,
+    },
+    /// `for var in iterable`, `while let` or `loop`: may stop after any iteration
+    Other {
+        var: Option<String>,
+    },
+}
+
+/// The counter of a `for` loop over a range
+struct Counter<'a> {
+    name: String,
+    ty: Option<IntType>,
+    start: Dynamic<'a>,
+    /// Value at the start of an arbitrary iteration
+    value: Dynamic<'a>,
+}
+
+impl Z3Translator {
+    /// Collect the obligations of a loop and havoc the variables it assigns
+    ///
+    /// Returns the facts established once the loop has finished.
+    pub(crate) fn collect_loop_obligations<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>, guard: &Bool<'a>, out: &mut Vec<Obligation<'a>>) -> Bool<'a> {
+        let ctx = self.context();
+        let truth = Bool::from_bool(ctx, true);
+        let Some((attrs, iteration, body)) = loop_parts(expr) else {
+            return truth;
+        };
+        let contracts = extract_loop_contracts(attrs);
+        // Where a loop with `break` or `continue` stops isn't modelled
+        let opaque = breaks_out(body);
+
+        // Values at the start of an arbitrary iteration
+        let mut assigned = BTreeSet::new();
+        assigned_in_block(body, &mut assigned);
+        let mut head = vars.clone();
+        for name in &assigned {
+            if let Some(value) = vars.get(name) {
+                head.insert(name.clone(), self.fresh_value(name, value));
+            }
+        }
+        let mut entry = vars.clone();
+
+        // Facts at the start of every iteration that hold by construction
+        let mut bounds = Vec::new();
+        let mut condition = None;
+        let mut counter = None;
+        let mut element = None;
+        match iteration {
+            Iteration::While(cond) => condition = self.translate_condition(cond, &mut head.clone()),
+            Iteration::Range { var, start, end, inclusive } => {
+                match self.range_counter(var.as_deref(), start, end, vars) {
+                    Some((new_counter, end)) => {
+                        let (Some(in_range), Some(counter_bounds)) = (
+                            self.counter_in_range(&new_counter, &end, inclusive),
+                            self.counter_bounds(&new_counter, &end, inclusive),
+                        ) else {
+                            return self.havoc_loop(body, &contracts, head, vars, guard, out);
+                        };
+                        condition = Some(in_range);
+                        bounds.push(counter_bounds);
+                        entry.insert(new_counter.name.clone(), new_counter.start.clone());
+                        head.insert(new_counter.name.clone(), new_counter.value.clone());
+                        counter = Some(new_counter);
+                    }
+                    None => element = var,
+                }
+            }
+            Iteration::Other { var } => element = var,
+        }
+        if opaque {
+            return self.havoc_loop(body, &contracts, head, vars, guard, out);
+        }
+        let previous_type = counter.as_ref().map(|c| self.set_var_type(&c.name, c.ty));
+
+        // Invariants on entry; only those that translate are checked and assumed
+        let mut invariants = Vec::new();
+        for invariant in &contracts.invariants {
+            let on_entry = self.translate_condition(invariant, &mut entry.clone());
+            let at_head = self.translate_condition(invariant, &mut head.clone());
+            match (on_entry, at_head) {
+                (Some(on_entry), Some(at_head)) => {
+                    out.push(self.obligation(ObligationKind::Loop, "invariant on entry".to_string(), invariant, guard, &on_entry));
+                    invariants.push((invariant, at_head));
+                }
+                _ => out.push(self.unencoded_obligation(ObligationKind::Loop, "invariant".to_string(), invariant)),
+            }
+        }
+
+        // One arbitrary iteration
+        let mut assumed: Vec<&Bool<'a>> = vec![guard];
+        assumed.extend(&bounds);
+        assumed.extend(&condition);
+        assumed.extend(invariants.iter().map(|(_, at_head)| at_head));
+        let iteration_guard = Bool::and(ctx, &assumed);
+        let mut body_vars = head.clone();
+        if let Some(name) = &element {
+            let unknown = self.int_const(name, None);
+            body_vars.insert(name.clone(), self.fresh_value(name, &unknown));
+        }
+        let end_guard = self.collect_block_obligations(body, &mut body_vars, &iteration_guard, out);
+
+        // Values at the start of the next iteration
+        let mut next = head.clone();
+        leave_scope(&mut next, &body_vars);
+        if let Some(counter) = &counter {
+            match self.increment(&counter.value, counter.ty) {
+                Some(incremented) => {
+                    next.insert(counter.name.clone(), incremented);
+                }
+                None => {
+                    next.insert(counter.name.clone(), self.fresh_value(&counter.name, &counter.value));
+                }
+            }
+        }
+        for (invariant, _) in &invariants {
+            let preserved = self.translate_condition(invariant, &mut next.clone())
+                .unwrap_or_else(|| Bool::from_bool(ctx, false));
+            out.push(self.obligation(ObligationKind::Loop, "invariant preserved".to_string(), *invariant, &end_guard, &preserved));
+        }
+        if let Some(measure) = &contracts.decreases {
+            out.push(match self.measure_decreases(measure, &head, &next) {
+                Some(decreases) => self.obligation(ObligationKind::Loop, "decreases".to_string(), measure, &end_guard, &decreases),
+                None => self.unencoded_obligation(ObligationKind::Loop, "decreases".to_string(), measure),
+            });
+        }
+
+        // After the loop: the invariants hold and the condition doesn't
+        let mut established: Vec<Bool<'a>> = bounds;
+        established.extend(condition.map(|c| c.not()));
+        established.extend(invariants.into_iter().map(|(_, at_head)| at_head));
+        let refs: Vec<&Bool<'a>> = established.iter().collect();
+        let established = Bool::and(ctx, &refs);
+
+        // The loop variable goes out of scope
+        if let (Some(counter), Some(previous_type)) = (&counter, previous_type) {
+            head.remove(&counter.name);
+            if let Some(outer) = vars.get(&counter.name) {
+                head.insert(counter.name.clone(), outer.clone());
+            }
+            self.set_var_type(&counter.name, previous_type);
+        }
+        *vars = head;
+        established
+    }
+
+    /// Havoc a loop's variables without assuming anything about where it stops
+    ///
+    /// The body's obligations are still collected, from an arbitrary state.
+    /// The loop's invariants and measure aren't checked, so each is left unproven.
+    fn havoc_loop<'a>(&'a self, body: &Block, contracts: &LoopContracts, head: HashMap<String, Dynamic<'a>>, vars: &mut HashMap<String, Dynamic<'a>>, guard: &Bool<'a>, out: &mut Vec<Obligation<'a>>) -> Bool<'a> {
+        for invariant in &contracts.invariants {
+            out.push(self.unencoded_obligation(ObligationKind::Loop, "invariant".to_string(), invariant));
+        }
+        if let Some(measure) = &contracts.decreases {
+            out.push(self.unencoded_obligation(ObligationKind::Loop, "decreases".to_string(), measure));
+        }
+        self.collect_block_obligations(body, &mut head.clone(), guard, out);
+        let mut after = vars.clone();
+        leave_scope(&mut after, &head);
+        *vars = after;
+        Bool::from_bool(self.context(), true)
+    }
+
+    /// Translate a range's bounds and declare its counter
+    ///
+    /// Returns the counter and the translated end of the range.
+    fn range_counter<'a>(&'a self, var: Option<&str>, start: &Expr, end: &Expr, vars: &mut HashMap<String, Dynamic<'a>>) -> Option<(Counter<'a>, Dynamic<'a>)> {
+        let ty = self.infer_int_type(start).or_else(|| self.infer_int_type(end));
+        let start = self.translate_typed(start, vars, ty).ok()?;
+        let end = self.translate_typed(end, vars, ty).ok()?;
+        if start.get_sort() != end.get_sort() || start.as_bool().is_some() {
+            return None;
+        }
+        let name = var.unwrap_or("_").to_string();
+        let value = self.fresh_value(&name, &start);
+        Some((Counter { name, ty, start, value }, end))
+    }
+
+    /// The loop condition of a range: the counter hasn't reached the end
+    fn counter_in_range<'a>(&'a self, counter: &Counter<'a>, end: &Dynamic<'a>, inclusive: bool) -> Option<Bool<'a>> {
+        let op = if inclusive {
+            syn::BinOp::Le(Default::default())
+        } else {
+            syn::BinOp::Lt(Default::default())
+        };
+        self.translate_binary_op(op, counter.value.clone(), end.clone(), counter.ty).ok()?.as_bool()
+    }
+
+    /// Where the counter can be at the start of an iteration or after the loop
+    ///
+    /// From `start` up to the end of the range (one past it for `..=`), or
+    /// exactly `start` if the range is empty. In bit-precise mode an inclusive
+    /// range ending at the type's maximum never stops by this model, so
+    /// nothing is assumed about its counter.
+    fn counter_bounds<'a>(&'a self, counter: &Counter<'a>, end: &Dynamic<'a>, inclusive: bool) -> Option<Bool<'a>> {
+        let ctx = self.context();
+        let signed = counter.ty.is_some_and(|t| t.signed);
+        let value = to_int(&counter.value, signed)?;
+        let start = to_int(&counter.start, signed)?;
+        let mut end = to_int(end, signed)?;
+        if inclusive {
+            if self.mode() == ArithmeticMode::BitPrecise {
+                let max = Int::from_str(ctx, &counter.ty.map_or(u64::MAX as u128, |t| t.max_value()).to_string())?;
+                if counter.value.as_bv().is_some() {
+                    let fits = end.lt(&max);
+                    let end_past = Int::add(ctx, &[&end, &Int::from_u64(ctx, 1)]);
+                    let bounded = Bool::and(ctx, &[&start.le(&value), &Bool::or(ctx, &[&value._eq(&start), &value.le(&end_past)])]);
+                    return Some(fits.implies(&bounded));
+                }
+            }
+            end = Int::add(ctx, &[&end, &Int::from_u64(ctx, 1)]);
+        }
+        Some(Bool::and(ctx, &[&start.le(&value), &Bool::or(ctx, &[&value._eq(&start), &value.le(&end)])]))
+    }
+
+    /// `value + 1`, in the counter's sort
+    fn increment<'a>(&'a self, value: &Dynamic<'a>, ty: Option<IntType>) -> Option<Dynamic<'a>> {
+        let one = self.int_literal(1, ty.or_else(|| value.as_bv().map(|bv| IntType { bits: bv.get_size(), signed: false }))).ok()?;
+        self.translate_binary_op(syn::BinOp::Add(Default::default()), value.clone(), one, ty).ok()
+    }
+
+    /// The measure is non-negative before the iteration and smaller after it
+    fn measure_decreases<'a>(&'a self, measure: &Expr, head: &HashMap<String, Dynamic<'a>>, next: &HashMap<String, Dynamic<'a>>) -> Option<Bool<'a>> {
+        let ctx = self.context();
+        let ty = self.infer_int_type(measure);
+        let signed = ty.is_some_and(|t| t.signed);
+        let before = to_int(&self.translate_typed(measure, &mut head.clone(), ty).ok()?, signed)?;
+        let after = to_int(&self.translate_typed(measure, &mut next.clone(), ty).ok()?, signed)?;
+        Some(Bool::and(ctx, &[&before.ge(&Int::from_u64(ctx, 0)), &after.lt(&before)]))
+    }
+}
+
+/// Check whether an expression is a `for`, `while` or `loop` loop
+pub(crate) fn is_loop(expr: &Expr) -> bool {
+    matches!(expr, Expr::ForLoop(_) | Expr::While(_) | Expr::Loop(_))
+}
+
+/// Check whether an expression may return from the enclosing function (`return` or `?`)
+pub(crate) fn exits_function(expr: &Expr) -> bool {
+    any_token(expr.to_token_stream(), &|token| match token {
+        TokenTree::Ident(ident) => ident == "return",
+        TokenTree::Punct(punct) => punct.as_char() == '?',
+        _ => false,
+    })
+}
+
+/// Check whether a loop body contains `break` or `continue` (at any depth)
+fn breaks_out(body: &Block) -> bool {
+    any_token(body.to_token_stream(), &|token| {
+        matches!(token, TokenTree::Ident(ident) if ident == "break" || ident == "continue")
+    })
+}
+
+fn any_token(tokens: TokenStream, matches: &impl Fn(&TokenTree) -> bool) -> bool {
+    tokens.into_iter().any(|token| match &token {
+        TokenTree::Group(group) => any_token(group.stream(), matches),
+        _ => matches(&token),
+    })
+}
+
+/// A loop's attributes, how it iterates, and its body
+fn loop_parts(expr: &Expr) -> Option<(&[syn::Attribute], Iteration<'_>, &Block)> {
+    match expr {
+        Expr::While(while_loop) => Some((&while_loop.attrs, Iteration::While(&while_loop.cond), &while_loop.body)),
+        Expr::ForLoop(for_loop) => {
+            let var = match &*for_loop.pat {
+                syn::Pat::Ident(ident) => Some(ident.ident.to_string()),
+                _ => None,
+            };
+            let mut iterable = &*for_loop.expr;
+            while let Expr::Paren(paren) = iterable {
+                iterable = &paren.expr;
+            }
+            let iteration = match iterable {
+                Expr::Range(syn::ExprRange { start: Some(start), end: Some(end), limits, .. }) => Iteration::Range {
+                    var,
+                    start,
+                    end,
+                    inclusive: matches!(limits, syn::RangeLimits::Closed(_)),
+                },
+                _ => Iteration::Other { var },
+            };
+            Some((&for_loop.attrs, iteration, &for_loop.body))
+        }
+        Expr::Loop(infinite) => Some((&infinite.attrs, Iteration::Other { var: None }, &infinite.body)),
+        _ => None,
+    }
+}
+
+/// Names of the variables a block may assign, including through `&mut` and
+/// methods changing a sequence
+fn assigned_in_block(block: &Block, out: &mut BTreeSet<String>) {
+    for stmt in &block.stmts {
+        match stmt {
+            Stmt::Local(local) => {
+                if let Some(init) = &local.init {
+                    assigned_in_expr(&init.expr, out);
+                }
+            }
+            Stmt::Expr(expr, _) => assigned_in_expr(expr, out),
+            _ => {}
+        }
+    }
+}
+
+fn assigned_in_expr(expr: &Expr, out: &mut BTreeSet<String>) {
+    match expr {
+        Expr::Assign(assign) => {
+            out.extend(place_root(&assign.left));
+            assigned_in_expr(&assign.right, out);
+        }
+        Expr::Binary(bin) => {
+            if compound_operator(bin.op).is_some() {
+                out.extend(place_root(&bin.left));
+            }
+            assigned_in_expr(&bin.left, out);
+            assigned_in_expr(&bin.right, out);
+        }
+        Expr::Reference(reference) => {
+            if reference.mutability.is_some() {
+                out.extend(place_root(&reference.expr));
+            }
+            assigned_in_expr(&reference.expr, out);
+        }
+        Expr::Block(block) => assigned_in_block(&block.block, out),
+        Expr::If(if_expr) => {
+            assigned_in_expr(&if_expr.cond, out);
+            assigned_in_block(&if_expr.then_branch, out);
+            if let Some((_, else_expr)) = &if_expr.else_branch {
+                assigned_in_expr(else_expr, out);
+            }
+        }
+        Expr::ForLoop(for_loop) => {
+            assigned_in_expr(&for_loop.expr, out);
+            assigned_in_block(&for_loop.body, out);
+        }
+        Expr::While(while_loop) => {
+            assigned_in_expr(&while_loop.cond, out);
+            assigned_in_block(&while_loop.body, out);
+        }
+        Expr::Loop(infinite) => assigned_in_block(&infinite.body, out),
+        Expr::Match(match_expr) => {
+            assigned_in_expr(&match_expr.expr, out);
+            for arm in &match_expr.arms {
+                assigned_in_expr(&arm.body, out);
+            }
+        }
+        Expr::Call(call) => call.args.iter().for_each(|arg| assigned_in_expr(arg, out)),
+        Expr::MethodCall(call) => {
+            if mutates_receiver(&call.method.to_string()) {
+                out.extend(place_root(&call.receiver));
+            }
+            assigned_in_expr(&call.receiver, out);
+            call.args.iter().for_each(|arg| assigned_in_expr(arg, out));
+        }
+        Expr::Paren(paren) => assigned_in_expr(&paren.expr, out),
+        Expr::Unary(unary) => assigned_in_expr(&unary.expr, out),
+        Expr::Return(ret) => {
+            if let Some(value) = &ret.expr {
+                assigned_in_expr(value, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The variable a place expression (`x`, `*x`, `x.field`, `x[i]`) belongs to
+pub(crate) fn place_root(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Path(path) => path.path.get_ident().map(|ident| ident.to_string()),
+        Expr::Paren(paren) => place_root(&paren.expr),
+        Expr::Unary(unary) => place_root(&unary.expr),
+        Expr::Field(field) => place_root(&field.base),
+        Expr::Index(index) => place_root(&index.expr),
+        _ => None,
+    }
+}
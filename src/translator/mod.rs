@@ -4,6 +4,15 @@
 //! - `static`: Fast Rust-based static checks (Tier 1)
 //! - `z3_translator`: Rust AST → Z3 AST translation (Tier 2)
 //! - `z3_verifier`: Z3 solving and counterexample extraction
+//! - `overflow`: Implicit obligations (arithmetic overflow, callee preconditions)
+//! - `calls`: Function calls modelled by the callee's contracts
+//! - `loops`: Loops summarized by their `#[invariant]` and `#[decreases]` contracts
+//! - `datatypes`: `Option`, `Result` and enums as Z3 datatypes
+//! - `control_flow`: `match`, `if let`, `?` and early returns
+//! - `sequences`: `Vec`, slices and arrays as a length plus a Z3 array
+//! - `theorem`: Orange Paper theorem statements → Z3, checked against the implementation
+//! - `two_state`: `&mut` parameters with pre- and post-states, and `old(..)` in ensures
+//! - `quantifiers`: `forall(|i| ..)` and `exists(|i| ..)` in contracts, with triggers
 
 pub mod static_checker;
 
@@ -13,6 +22,33 @@ pub mod z3_translator;
 #[cfg(feature = "z3")]
 pub mod z3_verifier;
 
+#[cfg(feature = "z3")]
+pub mod overflow;
+
+#[cfg(feature = "z3")]
+pub mod calls;
+
+#[cfg(feature = "z3")]
+pub mod loops;
+
+#[cfg(feature = "z3")]
+pub mod datatypes;
+
+#[cfg(feature = "z3")]
+pub mod control_flow;
+
+#[cfg(feature = "z3")]
+pub mod sequences;
+
+#[cfg(feature = "z3")]
+pub mod theorem;
+
+#[cfg(feature = "z3")]
+pub mod two_state;
+
+#[cfg(feature = "z3")]
+pub mod quantifiers;
+
 pub use static_checker::*;
 
 #[cfg(feature = "z3")]
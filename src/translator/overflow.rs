@@ -0,0 +1,512 @@
+//! Implicit obligations: arithmetic overflow, callee preconditions, loops and indexing
+//!
+//! Every `+`, `-`, `*`, `/`, `%`, unary `-`, shift and integer `as` cast in a
+//! spec-locked body yields an obligation that the operation cannot overflow
+//! (for shifts, that the shift amount is in range; for `/` and `%`, that the
+//! divisor isn't zero), so code that panics in a debug build or silently
+//! truncates is caught without extra annotations. An operation whose type or
+//! operands can't be translated still yields an obligation, one that is never
+//! proven, so it can't pass unchecked. Every call to a
+//! summarized function yields an obligation that the callee's `requires` hold
+//! (see `translator::calls`), every loop yields obligations for its
+//! invariants and termination measure (see `translator::loops`), and every
+//! `v[i]` yields an obligation that `i` is in bounds (see
+//! `translator::sequences`).
+//!
+//! Collecting the obligations walks the body statement by statement, tracking
+//! assignments, so the same walk also gives the effect of a statement on the
+//! variables (see [`Z3Translator::execute_statement`]).
+
+use crate::translator::control_flow::Exits;
+use crate::translator::datatypes::ValueType;
+use crate::translator::loops::is_loop;
+use crate::translator::z3_translator::{IntType, Z3Translator};
+use quote::ToTokens;
+use std::collections::HashMap;
+use syn::spanned::Spanned;
+use syn::{BinOp, Block, Expr, ItemFn, Stmt};
+use z3::ast::{Ast, Bool, Dynamic, Int};
+
+/// What an obligation guards against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObligationKind {
+    /// An arithmetic operation or cast overflowing
+    Overflow,
+    /// A call violating the callee's `requires`
+    Precondition,
+    /// A loop invariant failing on entry or after an iteration, or a loop
+    /// measure failing to decrease
+    Loop,
+    /// An index out of a sequence's bounds
+    Bounds,
+}
+
+/// A single operation that must be safe
+#[derive(Debug, Clone)]
+pub struct Obligation<'a> {
+    pub kind: ObligationKind,
+    /// Operator or cast, e.g. `+`, `<<`, `as u32`, `requires of f` for a call to `f`,
+    /// `invariant on entry`, `invariant preserved` or `decreases` for a loop, or
+    /// `[]` for an index
+    pub operation: String,
+    /// Source text of the whole operation
+    pub expression: String,
+    /// Line of the operation (1-based, 0 if unknown)
+    pub line: usize,
+    /// Column of the operation (1-based)
+    pub column: usize,
+    /// Holds when the operation is safe, guarded by the path leading to it;
+    /// `None` if the operation couldn't be encoded (it is then never proven)
+    pub condition: Option<Bool<'a>>,
+}
+
+impl Z3Translator {
+    /// Collect the implicit obligations of a function body
+    ///
+    /// `vars` must already contain the function parameters. Operations whose
+    /// integer type can't be inferred, or whose operands can't be translated,
+    /// produce an obligation without a condition.
+    pub fn collect_obligations<'a>(&'a self, func: &ItemFn, vars: &mut HashMap<String, Dynamic<'a>>) -> Vec<Obligation<'a>> {
+        let mut obligations = Vec::new();
+        let guard = Bool::from_bool(self.context(), true);
+        self.collect_block_obligations(&func.block, vars, &guard, &mut obligations);
+        obligations
+    }
+
+    /// Run a statement for its effect on `vars`, ignoring its obligations
+    ///
+    /// Returns the facts the statement establishes (e.g. a loop's invariant on
+    /// exit), `true` if none.
+    pub(crate) fn execute_statement<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>) -> Bool<'a> {
+        let truth = Bool::from_bool(self.context(), true);
+        self.collect_stmt_obligations(expr, vars, &truth, &mut Vec::new())
+    }
+
+    /// Run a `let` statement for its effect on `vars`, ignoring its obligations
+    pub(crate) fn execute_local<'a>(&'a self, local: &syn::Local, vars: &mut HashMap<String, Dynamic<'a>>) {
+        let truth = Bool::from_bool(self.context(), true);
+        self.collect_local_obligations(local, vars, &truth, &mut Vec::new());
+    }
+
+    /// Collect the obligations of a block, returning the guard at its end
+    pub(crate) fn collect_block_obligations<'a>(&'a self, block: &Block, vars: &mut HashMap<String, Dynamic<'a>>, guard: &Bool<'a>, out: &mut Vec<Obligation<'a>>) -> Bool<'a> {
+        let mut guard = guard.clone();
+        for stmt in &block.stmts {
+            // Code after a statement that may return (`if c { return x; }`,
+            // `f(x)?`) only runs when it doesn't
+            let exits = match stmt {
+                Stmt::Local(local) => self.local_exits(local, vars),
+                Stmt::Expr(expr, _) => {
+                    let mut exits = Exits::new(self.context());
+                    self.expr_exits(expr, &mut vars.clone(), &mut exits).ok().map(|_| exits)
+                }
+                _ => None,
+            };
+            match stmt {
+                Stmt::Local(local) => self.collect_local_obligations(local, vars, &guard, out),
+                Stmt::Expr(expr, _) => guard = self.collect_stmt_obligations(expr, vars, &guard, out),
+                _ => {}
+            }
+            if let Some(exits) = exits {
+                guard = Bool::and(self.context(), &[&guard, &exits.none_taken()]);
+            }
+        }
+        guard
+    }
+
+    /// The exits of a `let` statement's initializer and `else` block
+    fn local_exits<'a>(&'a self, local: &syn::Local, vars: &HashMap<String, Dynamic<'a>>) -> Option<Exits<'a>> {
+        let mut vars = vars.clone();
+        let mut exits = Exits::new(self.context());
+        self.expr_exits(&local.init.as_ref()?.expr, &mut vars, &mut exits).ok()?;
+        if let Some(else_exits) = self.let_else_exits(local, &mut vars).ok()? {
+            exits.nest(&Bool::from_bool(self.context(), true), else_exits);
+        }
+        Some(exits)
+    }
+
+    /// Collect the obligations of a `let` statement and bind its variables
+    ///
+    /// The bound value is computed from the variables before any assignments
+    /// inside the initializer (e.g. `let x = v.pop();`), which the walk applies.
+    fn collect_local_obligations<'a>(&'a self, local: &syn::Local, vars: &mut HashMap<String, Dynamic<'a>>, guard: &Bool<'a>, out: &mut Vec<Obligation<'a>>) {
+        let Some(init) = &local.init else {
+            return;
+        };
+        let binding = self.translate_local(local, &mut vars.clone());
+        let assignment = self.assignment(&init.expr, &mut vars.clone());
+        self.collect_expr_obligations(&init.expr, vars, guard, out);
+        if let Some((name, value)) = assignment {
+            vars.insert(name, value);
+        }
+        if let Some((_, else_expr)) = &init.diverge {
+            // `let pattern = value else { ... }` binds the pattern's names
+            let int_ty = self.infer_int_type(&init.expr);
+            if let Ok(value) = self.translate_typed(&init.expr, &mut vars.clone(), int_ty) {
+                let mut else_vars = vars.clone();
+                if let Ok(matches) = self.pattern_condition(&local.pat, &value, int_ty, vars) {
+                    let else_guard = Bool::and(self.context(), &[guard, &matches.not()]);
+                    self.collect_expr_obligations(else_expr, &mut else_vars, &else_guard, out);
+                }
+            }
+        }
+        if let Some((name, value)) = binding {
+            vars.insert(name, value);
+        }
+    }
+
+    /// Collect the obligations of an expression statement and apply its effect to `vars`
+    ///
+    /// Returns the guard for the code after it, including any facts it establishes.
+    fn collect_stmt_obligations<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>, guard: &Bool<'a>, out: &mut Vec<Obligation<'a>>) -> Bool<'a> {
+        if is_loop(expr) {
+            let established = self.collect_loop_obligations(expr, vars, guard, out);
+            return Bool::and(self.context(), &[guard, &established]);
+        }
+        // The assigned value is computed before any assignments inside it
+        let assignment = self.assignment(expr, &mut vars.clone());
+        self.collect_expr_obligations(expr, vars, guard, out);
+        if let Some((name, value)) = assignment {
+            vars.insert(name, value);
+        }
+        guard.clone()
+    }
+
+    fn collect_expr_obligations<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>, guard: &Bool<'a>, out: &mut Vec<Obligation<'a>>) {
+        match expr {
+            Expr::Binary(bin) => {
+                self.collect_expr_obligations(&bin.left, vars, guard, out);
+                // The right side of && / || only runs when the left side didn't short-circuit
+                let right_guard = match bin.op {
+                    BinOp::And(_) => self.translate_condition(&bin.left, vars)
+                        .map(|left| Bool::and(self.context(), &[guard, &left])),
+                    BinOp::Or(_) => self.translate_condition(&bin.left, vars)
+                        .map(|left| Bool::and(self.context(), &[guard, &left.not()])),
+                    _ => None,
+                };
+                self.collect_expr_obligations(&bin.right, vars, right_guard.as_ref().unwrap_or(guard), out);
+                if let Some(obligation) = self.binary_obligation(bin, vars, guard) {
+                    out.push(obligation);
+                }
+            }
+            Expr::Cast(cast) => {
+                self.collect_expr_obligations(&cast.expr, vars, guard, out);
+                if let Some(obligation) = self.cast_obligation(cast, vars, guard) {
+                    out.push(obligation);
+                }
+            }
+            Expr::If(if_expr) => {
+                self.collect_expr_obligations(&if_expr.cond, vars, guard, out);
+                let mut then_vars = vars.clone();
+                let cond = self.branch_condition(&if_expr.cond, &mut vars.clone(), &mut then_vars).ok();
+                let then_guard = match &cond {
+                    Some(c) => Bool::and(self.context(), &[guard, c]),
+                    None => guard.clone(),
+                };
+                self.collect_block_obligations(&if_expr.then_branch, &mut then_vars, &then_guard, out);
+                let mut else_vars = vars.clone();
+                if let Some((_, else_expr)) = &if_expr.else_branch {
+                    let else_guard = match &cond {
+                        Some(c) => Bool::and(self.context(), &[guard, &c.not()]),
+                        None => guard.clone(),
+                    };
+                    self.collect_expr_obligations(else_expr, &mut else_vars, &else_guard, out);
+                }
+                self.merge_branches(vec![(cond, then_vars), (None, else_vars)], vars);
+            }
+            Expr::Match(match_expr) => {
+                self.collect_expr_obligations(&match_expr.expr, vars, guard, out);
+                let branches = match self.match_arms(match_expr, &mut vars.clone()) {
+                    Ok(arms) => arms.into_iter()
+                        .map(|mut arm| {
+                            let arm_guard = Bool::and(self.context(), &[guard, &arm.taken]);
+                            self.collect_stmt_obligations(arm.body, &mut arm.vars, &arm_guard, out);
+                            (Some(arm.taken), arm.vars)
+                        })
+                        .collect(),
+                    // Arms whose patterns can't be translated run under the outer guard
+                    Err(_) => match_expr.arms.iter()
+                        .map(|arm| {
+                            let mut arm_vars = vars.clone();
+                            self.collect_stmt_obligations(&arm.body, &mut arm_vars, guard, out);
+                            (None, arm_vars)
+                        })
+                        .collect(),
+                };
+                self.merge_branches(branches, vars);
+            }
+            Expr::Try(try_expr) => self.collect_expr_obligations(&try_expr.expr, vars, guard, out),
+            Expr::Let(let_expr) => self.collect_expr_obligations(&let_expr.expr, vars, guard, out),
+            Expr::Block(block) => {
+                let mut inner = vars.clone();
+                self.collect_block_obligations(&block.block, &mut inner, guard, out);
+                leave_scope(vars, &inner);
+            }
+            Expr::Paren(paren) => self.collect_expr_obligations(&paren.expr, vars, guard, out),
+            Expr::Group(group) => self.collect_expr_obligations(&group.expr, vars, guard, out),
+            Expr::Unary(unary) => {
+                self.collect_expr_obligations(&unary.expr, vars, guard, out);
+                if let Some(obligation) = self.negation_obligation(unary, vars, guard) {
+                    out.push(obligation);
+                }
+            }
+            Expr::Return(ret) => {
+                if let Some(value) = &ret.expr {
+                    self.collect_expr_obligations(value, vars, guard, out);
+                }
+            }
+            Expr::Assign(assign) => {
+                self.collect_expr_obligations(&assign.right, vars, guard, out);
+                self.collect_expr_obligations(&assign.left, vars, guard, out);
+            }
+            Expr::MethodCall(call) => {
+                self.collect_expr_obligations(&call.receiver, vars, guard, out);
+                for arg in &call.args {
+                    self.collect_expr_obligations(arg, vars, guard, out);
+                }
+            }
+            Expr::Call(call) => {
+                for arg in &call.args {
+                    self.collect_expr_obligations(arg, vars, guard, out);
+                }
+                if let Some(obligation) = self.call_obligation(call, vars, guard) {
+                    out.push(obligation);
+                }
+            }
+            Expr::Index(index) => {
+                self.collect_expr_obligations(&index.expr, vars, guard, out);
+                self.collect_expr_obligations(&index.index, vars, guard, out);
+                if let Some(obligation) = self.index_obligation(index, vars, guard) {
+                    out.push(obligation);
+                }
+            }
+            Expr::Field(field) => self.collect_expr_obligations(&field.base, vars, guard, out),
+            _ => {}
+        }
+    }
+
+    /// Obligation for `a + b`, `a - b`, `a * b`, `a / b`, `a % b`, `a << n`, `a >> n` (and their compound assignments)
+    ///
+    /// Operations on two literals are left to the compiler, which rejects
+    /// those that overflow, and those on a float literal aren't integer arithmetic.
+    fn binary_obligation<'a>(&'a self, bin: &syn::ExprBinary, vars: &mut HashMap<String, Dynamic<'a>>, guard: &Bool<'a>) -> Option<Obligation<'a>> {
+        if !is_checked_op(bin.op) || (is_int_literal(&bin.left) && is_int_literal(&bin.right))
+            || is_float_literal(&bin.left) || is_float_literal(&bin.right) {
+            return None;
+        }
+        let operation = bin.op.to_token_stream().to_string();
+        Some(match self.binary_safety(bin, vars) {
+            Some(safe) => self.obligation(ObligationKind::Overflow, operation, bin, guard, &safe),
+            None => self.unencoded_obligation(ObligationKind::Overflow, operation, bin),
+        })
+    }
+
+    /// When a checked binary operation is safe, if its type and operands can be translated
+    fn binary_safety<'a>(&'a self, bin: &syn::ExprBinary, vars: &mut HashMap<String, Dynamic<'a>>) -> Option<Bool<'a>> {
+        let ctx = self.context();
+        if let BinOp::Shl(_) | BinOp::ShlAssign(_) | BinOp::Shr(_) | BinOp::ShrAssign(_) = bin.op {
+            // A literal's type is at least as wide as its value needs, so a
+            // shift amount below that width is below the actual one too
+            let bits = match self.infer_int_type(&bin.left) {
+                Some(ty) => ty.bits,
+                None => literal_bits(&bin.left)?,
+            };
+            // Shifting by the type's width or more panics in debug builds
+            let amount_ty = self.infer_int_type(&bin.right);
+            let amount = self.translate_typed(&bin.right, vars, amount_ty).ok()?;
+            let amount = to_int(&amount, amount_ty.is_some_and(|t| t.signed))?;
+            return Some(Bool::and(ctx, &[
+                &amount.ge(&Int::from_u64(ctx, 0)),
+                &amount.lt(&Int::from_u64(ctx, u64::from(bits))),
+            ]));
+        }
+
+        let ty = self.infer_int_type(&bin.left).or_else(|| self.infer_int_type(&bin.right))?;
+        let left = to_int(&self.translate_typed(&bin.left, vars, Some(ty)).ok()?, ty.signed)?;
+        let right = to_int(&self.translate_typed(&bin.right, vars, Some(ty)).ok()?, ty.signed)?;
+        match bin.op {
+            BinOp::Div(_) | BinOp::DivAssign(_) | BinOp::Rem(_) | BinOp::RemAssign(_) => {
+                // Dividing by zero panics, and so does `MIN / -1` (or `MIN % -1`)
+                let nonzero = right._eq(&Int::from_i64(ctx, 0)).not();
+                if !ty.signed {
+                    return Some(nonzero);
+                }
+                let min = Int::from_str(ctx, &ty.min_value().to_string()).expect("valid integer");
+                let overflows = Bool::and(ctx, &[&left._eq(&min), &right._eq(&Int::from_i64(ctx, -1))]);
+                Some(Bool::and(ctx, &[&nonzero, &overflows.not()]))
+            }
+            _ => {
+                // Compute the mathematically exact result and require it to fit
+                let exact = match bin.op {
+                    BinOp::Add(_) | BinOp::AddAssign(_) => Int::add(ctx, &[&left, &right]),
+                    BinOp::Sub(_) | BinOp::SubAssign(_) => Int::sub(ctx, &[&left, &right]),
+                    _ => Int::mul(ctx, &[&left, &right]),
+                };
+                Some(self.int_in_range(&exact, ty))
+            }
+        }
+    }
+
+    /// Obligation for `-x`: the negation must fit (`-i64::MIN` doesn't)
+    fn negation_obligation<'a>(&'a self, unary: &syn::ExprUnary, vars: &mut HashMap<String, Dynamic<'a>>, guard: &Bool<'a>) -> Option<Obligation<'a>> {
+        if !matches!(unary.op, syn::UnOp::Neg(_)) || is_int_literal(&unary.expr) || is_float_literal(&unary.expr) {
+            return None;
+        }
+        let operation = "-".to_string();
+        let safe = self.infer_int_type(&unary.expr).and_then(|ty| {
+            let value = to_int(&self.translate_typed(&unary.expr, vars, Some(ty)).ok()?, ty.signed)?;
+            Some(self.int_in_range(&value.unary_minus(), ty))
+        });
+        Some(match safe {
+            Some(safe) => self.obligation(ObligationKind::Overflow, operation, unary, guard, &safe),
+            None => self.unencoded_obligation(ObligationKind::Overflow, operation, unary),
+        })
+    }
+
+    /// Obligation for `expr as T`: the value must be representable in `T`
+    fn cast_obligation<'a>(&'a self, cast: &syn::ExprCast, vars: &mut HashMap<String, Dynamic<'a>>, guard: &Bool<'a>) -> Option<Obligation<'a>> {
+        let target = IntType::from_type(&cast.ty)?;
+        // Literals are checked by the compiler, and booleans fit any integer type
+        if is_int_literal(&cast.expr) || matches!(self.infer_value_type(&cast.expr), Some(ValueType::Bool)) {
+            return None;
+        }
+        let operation = format!("as {}", cast.ty.to_token_stream());
+        let Some(source) = self.infer_int_type(&cast.expr) else {
+            return Some(self.unencoded_obligation(ObligationKind::Overflow, operation, cast));
+        };
+        if target.contains(&source) {
+            return None;
+        }
+        let Some(value) = self.translate_typed(&cast.expr, vars, Some(source)).ok().and_then(|value| to_int(&value, source.signed)) else {
+            return Some(self.unencoded_obligation(ObligationKind::Overflow, operation, cast));
+        };
+        let safe = self.int_in_range(&value, target);
+        Some(self.obligation(ObligationKind::Overflow, operation, cast, guard, &safe))
+    }
+
+    /// Obligation for a call to a summarized function: its preconditions must hold
+    fn call_obligation<'a>(&'a self, call: &syn::ExprCall, vars: &mut HashMap<String, Dynamic<'a>>, guard: &Bool<'a>) -> Option<Obligation<'a>> {
+        let (callee, precondition) = self.call_precondition(call, vars)?;
+        Some(self.obligation(ObligationKind::Precondition, format!("requires of {}", callee), call, guard, &precondition))
+    }
+
+    /// Obligation for `v[i]`: `i` must be below `v`'s length
+    fn index_obligation<'a>(&'a self, index: &syn::ExprIndex, vars: &mut HashMap<String, Dynamic<'a>>, guard: &Bool<'a>) -> Option<Obligation<'a>> {
+        let in_bounds = self.index_in_bounds(index, vars)?;
+        Some(self.obligation(ObligationKind::Bounds, "[]".to_string(), index, guard, &in_bounds))
+    }
+
+    pub(crate) fn obligation<'a>(&'a self, kind: ObligationKind, operation: String, node: &impl ToTokens, guard: &Bool<'a>, safe: &Bool<'a>) -> Obligation<'a> {
+        let start = node.span().start();
+        Obligation {
+            kind,
+            operation,
+            expression: node.to_token_stream().to_string(),
+            line: start.line,
+            column: start.column + 1,
+            condition: Some(guard.implies(safe)),
+        }
+    }
+
+    /// An obligation for an operation that couldn't be encoded, which is never proven
+    pub(crate) fn unencoded_obligation<'a>(&'a self, kind: ObligationKind, operation: String, node: &impl ToTokens) -> Obligation<'a> {
+        let start = node.span().start();
+        Obligation {
+            kind,
+            operation,
+            expression: node.to_token_stream().to_string(),
+            line: start.line,
+            column: start.column + 1,
+            condition: None,
+        }
+    }
+
+    /// Set each variable to its value after a branch: the value from the branch taken
+    ///
+    /// Each branch comes with the condition under which it's taken; the last
+    /// one is taken otherwise, and branches that return are left out by
+    /// their callers' guards. Variables assigned in a branch become
+    /// unconstrained if its condition couldn't be translated.
+    pub(crate) fn merge_branches<'a>(&'a self, branches: Vec<(Option<Bool<'a>>, HashMap<String, Dynamic<'a>>)>, vars: &mut HashMap<String, Dynamic<'a>>) {
+        for (name, value) in vars.iter_mut() {
+            let Some(values) = branches.iter().map(|(_, branch_vars)| branch_vars.get(name)).collect::<Option<Vec<_>>>() else {
+                continue;
+            };
+            let Some((last, earlier)) = values.split_last() else {
+                continue;
+            };
+            if earlier.iter().all(|v| v == last) {
+                *value = (*last).clone();
+                continue;
+            }
+            let mut merged = Some((*last).clone());
+            for ((cond, _), branch_value) in branches.iter().zip(earlier).rev() {
+                merged = match (cond, merged) {
+                    (Some(cond), Some(merged)) if branch_value.get_sort() == merged.get_sort() => Some(cond.ite(*branch_value, &merged)),
+                    _ => None,
+                };
+            }
+            *value = merged.unwrap_or_else(|| self.fresh_value(name, value));
+        }
+    }
+
+    /// Translate a branch condition, if it translates to a boolean
+    pub(crate) fn translate_condition<'a>(&'a self, cond: &Expr, vars: &mut HashMap<String, Dynamic<'a>>) -> Option<Bool<'a>> {
+        self.translate_typed(cond, vars, None).ok()?.as_bool()
+    }
+}
+
+/// Copy the values of the variables of an enclosing scope back from an inner one
+///
+/// Variables declared in the inner scope are dropped.
+pub(crate) fn leave_scope<'a>(vars: &mut HashMap<String, Dynamic<'a>>, inner: &HashMap<String, Dynamic<'a>>) {
+    for (name, value) in vars.iter_mut() {
+        if let Some(inner_value) = inner.get(name) {
+            *value = inner_value.clone();
+        }
+    }
+}
+
+/// Whether a binary operator gets an overflow obligation
+fn is_checked_op(op: BinOp) -> bool {
+    matches!(op,
+        BinOp::Add(_) | BinOp::AddAssign(_) | BinOp::Sub(_) | BinOp::SubAssign(_) |
+        BinOp::Mul(_) | BinOp::MulAssign(_) | BinOp::Div(_) | BinOp::DivAssign(_) |
+        BinOp::Rem(_) | BinOp::RemAssign(_) | BinOp::Shl(_) | BinOp::ShlAssign(_) |
+        BinOp::Shr(_) | BinOp::ShrAssign(_))
+}
+
+/// Whether an expression is an integer literal, possibly negated or parenthesized
+fn is_int_literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(lit) => matches!(lit.lit, syn::Lit::Int(_)),
+        Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Neg(_)) => is_int_literal(&unary.expr),
+        Expr::Paren(paren) => is_int_literal(&paren.expr),
+        Expr::Group(group) => is_int_literal(&group.expr),
+        _ => false,
+    }
+}
+
+/// Whether an expression is a float literal (`-1.5` isn't integer arithmetic)
+fn is_float_literal(expr: &Expr) -> bool {
+    matches!(expr, Expr::Lit(lit) if matches!(lit.lit, syn::Lit::Float(_)))
+}
+
+/// Fewest bits of a standard integer type that can hold an unsuffixed integer literal
+fn literal_bits(expr: &Expr) -> Option<u32> {
+    let Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(lit), .. }) = expr else {
+        return None;
+    };
+    let value: u128 = lit.base10_parse().ok()?;
+    [8, 16, 32, 64].into_iter()
+        .find(|bits| value < 1u128 << bits)
+        .or(Some(128))
+}
+
+/// Interpret an integer or bit-vector value as a mathematical integer
+pub(crate) fn to_int<'a>(value: &Dynamic<'a>, signed: bool) -> Option<Int<'a>> {
+    value
+        .as_int()
+        .or_else(|| value.as_bv().map(|bv| Int::from_bv(&bv, signed)))
+}
+
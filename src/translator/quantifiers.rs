@@ -0,0 +1,149 @@
+//! `forall` and `exists` in contracts
+//!
+//! `forall(|i: usize| i < outs.len() ==> outs[i].value <= MAX_MONEY)` holds
+//! when the closure's body holds for every value of its parameters, and
+//! `exists(|i: usize| ..)` when it holds for some. Each parameter ranges over
+//! its Rust type (a parameter without a type annotation is an unbounded
+//! integer). The Orange Paper's `∀`/`∃` over variables that aren't function
+//! inputs are rendered as these pseudo-functions (see `MathExpr::to_rust`).
+//!
+//! Quantifiers get triggers (see [`triggers`]) so Z3 instantiates them with
+//! the elements and calls that occur elsewhere in the query, instead of
+//! relying on its own heuristics.
+
+use std::collections::HashMap;
+use syn::Expr;
+use z3::ast::{exists_const, forall_const, Ast, Bool, Dynamic};
+use z3::{DeclKind, Pattern};
+use crate::parser::math::Quantifier;
+use crate::translator::datatypes::ValueType;
+use crate::translator::z3_translator::{TranslationError, Z3Translator};
+
+/// The quantifier and closure of `forall(|..| ..)` or `exists(|..| ..)`, if `call` is one
+pub(crate) fn quantified_closure(call: &syn::ExprCall) -> Option<(Quantifier, &syn::ExprClosure)> {
+    let Expr::Path(path) = &*call.func else {
+        return None;
+    };
+    let quantifier = if path.path.is_ident("forall") {
+        Quantifier::Forall
+    } else if path.path.is_ident("exists") {
+        Quantifier::Exists
+    } else {
+        return None;
+    };
+    match call.args.iter().collect::<Vec<_>>().as_slice() {
+        [Expr::Closure(closure)] => Some((quantifier, closure)),
+        _ => None,
+    }
+}
+
+/// The name and type annotation of a quantified closure parameter
+fn bound_param(pat: &syn::Pat) -> Result<(String, Option<&syn::Type>), TranslationError> {
+    match pat {
+        syn::Pat::Ident(ident) => Ok((ident.ident.to_string(), None)),
+        syn::Pat::Type(pat_type) => match &*pat_type.pat {
+            syn::Pat::Ident(ident) => Ok((ident.ident.to_string(), Some(&*pat_type.ty))),
+            _ => Err(TranslationError::UnsupportedExpression("Quantified parameter must be a name".to_string())),
+        },
+        _ => Err(TranslationError::UnsupportedExpression("Quantified parameter must be a name".to_string())),
+    }
+}
+
+impl Z3Translator {
+    /// Translate `forall(closure)` or `exists(closure)` to a Z3 quantifier
+    pub(crate) fn translate_quantified<'a>(&'a self, quantifier: Quantifier, closure: &syn::ExprClosure, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let ctx = self.context();
+        let mut scope = vars.clone();
+        let mut bound = Vec::new();
+        let mut in_range = Vec::new();
+        let mut shadowed = Vec::new();
+        for input in &closure.inputs {
+            let (name, ty) = bound_param(input)?;
+            let value_ty = ty.map_or(ValueType::Int(None), |ty| self.value_type(ty));
+            let var = self.fresh_value(&name, &self.value_const(&name, &value_ty));
+            in_range.extend(self.value_in_range(&var, &value_ty));
+            shadowed.push((name.clone(), self.set_var_type(&name, value_ty.int_type()), self.var_value_type(&name)));
+            self.remember_value_type(&name, &var);
+            scope.insert(name, var.clone());
+            bound.push(var);
+        }
+
+        let body = self.translate_typed(&closure.body, &mut scope, None);
+
+        for (name, var_type, value_type) in shadowed.into_iter().rev() {
+            self.set_var_type(&name, var_type);
+            self.set_value_type(&name, value_type);
+        }
+
+        let name = if quantifier == Quantifier::Forall { "forall" } else { "exists" };
+        let body = body?.as_bool()
+            .ok_or_else(|| TranslationError::TypeError(format!("The closure passed to `{}` must return a bool", name)))?;
+        let in_range = Bool::and(ctx, &in_range.iter().collect::<Vec<_>>());
+        let bound_refs: Vec<&dyn Ast<'a>> = bound.iter().map(|var| var as &dyn Ast<'a>).collect();
+        let patterns = triggers(ctx, &bound, &body);
+        let pattern_refs: Vec<&Pattern<'a>> = patterns.iter().collect();
+        Ok(match quantifier {
+            Quantifier::Forall => forall_const(ctx, &bound_refs, &pattern_refs, &in_range.implies(&body)),
+            Quantifier::Exists => exists_const(ctx, &bound_refs, &pattern_refs, &Bool::and(ctx, &[&in_range, &body])),
+        }.into())
+    }
+}
+
+/// Triggers for a quantifier over `bound` with the given body
+///
+/// Each trigger is a smallest term of `body` that mentions every bound
+/// variable and is built from array reads, datatype field accesses and
+/// uninterpreted functions (e.g. `outs[i].value` gives `elems(outs)[i]`).
+/// Arithmetic on a bound variable can't appear in a trigger, so `a[i + 1]`
+/// isn't one. Returns no triggers (leaving the choice to Z3) if no term
+/// qualifies.
+pub(crate) fn triggers<'a>(ctx: &'a z3::Context, bound: &[Dynamic<'a>], body: &Bool<'a>) -> Vec<Pattern<'a>> {
+    if bound.is_empty() || bound.len() > 64 {
+        return Vec::new();
+    }
+    let all = u64::MAX >> (64 - bound.len());
+    let mut terms = Vec::new();
+    visit(&Dynamic::from_ast(body), bound, all, &mut terms);
+    terms.iter()
+        .map(|term| Pattern::new(ctx, &[term as &dyn Ast<'a>]))
+        .collect()
+}
+
+/// What [`visit`] learned about a term
+struct TermInfo {
+    /// The bound variables it mentions, as a bitmask over `bound`
+    mentions: u64,
+    /// Whether it can be part of a trigger
+    triggerable: bool,
+    /// Whether it contains a trigger candidate
+    has_candidate: bool,
+}
+
+/// Collect the trigger candidates in `term` into `terms`
+fn visit<'a>(term: &Dynamic<'a>, bound: &[Dynamic<'a>], all: u64, terms: &mut Vec<Dynamic<'a>>) -> TermInfo {
+    if let Some(index) = bound.iter().position(|var| var == term) {
+        return TermInfo { mentions: 1 << index, triggerable: true, has_candidate: false };
+    }
+    // Nested quantifiers bind their own variables and can't be looked into
+    if !term.is_app() {
+        return TermInfo { mentions: 0, triggerable: false, has_candidate: false };
+    }
+    let children = term.children();
+    let mut info = TermInfo { mentions: 0, triggerable: true, has_candidate: false };
+    for child in &children {
+        let child = visit(child, bound, all, terms);
+        info.mentions |= child.mentions;
+        // Ground subterms are fine anywhere in a trigger
+        info.triggerable &= child.triggerable || child.mentions == 0;
+        info.has_candidate |= child.has_candidate;
+    }
+    let trigger_op = matches!(term.decl().kind(), DeclKind::SELECT | DeclKind::UNINTERPRETED | DeclKind::DT_ACCESSOR);
+    info.triggerable &= trigger_op && !children.is_empty();
+    if info.triggerable && info.mentions == all && !info.has_candidate {
+        if !terms.contains(term) {
+            terms.push(term.clone());
+        }
+        info.has_candidate = true;
+    }
+    info
+}
@@ -0,0 +1,633 @@
+//! `Vec`, slices and arrays as a length plus a Z3 array
+//!
+//! A value of type `Vec<T>`, `[T]` or `[T; N]` is a `Seq` datatype value with
+//! two fields: its length, a `usize`, and its elements, a Z3 array from
+//! `usize` indices to `T` of which only those below the length are
+//! meaningful. Indexing reads the array, and every `v[i]` in a body yields an
+//! obligation that `i` is below the length (see `translator::overflow`).
+//!
+//! Iterator chains over a sequence (`v.iter()` with `copied`, `cloned`, `map`
+//! and `filter` adapters) can be consumed by `sum`, `count`, `all` and `any`.
+//! A sum or count is a Z3 recursive function of the array and the length, the
+//! same function wherever the same chain appears, so the sums in a contract
+//! and in the body agree. `all` and `any` are quantifiers over the indices
+//! below the length.
+//!
+//! `v.push(x)`, `v.pop()`, `v.clear()`, `v.truncate(n)`, `v.swap(i, j)` and
+//! `v[i] = x` update a local sequence; other methods taking `&mut self` leave
+//! it unknown.
+
+use std::collections::{BTreeSet, HashMap};
+use proc_macro2::{TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{BinOp, Expr};
+use z3::ast::{exists_const, forall_const, Array, Ast, Bool, Dynamic};
+use z3::{Pattern, RecFuncDecl, Sort};
+use crate::translator::datatypes::ValueType;
+use crate::translator::loops::place_root;
+use crate::translator::z3_translator::{compound_operator, IntType, TranslationError, Z3Translator};
+
+/// Type of lengths and indices (`usize`)
+pub(crate) const INDEX: IntType = IntType::U64;
+
+/// The length and elements of a sequence value
+pub(crate) struct SeqParts<'a> {
+    pub len: Dynamic<'a>,
+    pub elems: Array<'a>,
+    pub elem: ValueType,
+}
+
+/// An iterator adapter between a sequence and the method consuming it
+enum Adapter<'e> {
+    Map(&'e syn::ExprClosure),
+    Filter(&'e syn::ExprClosure),
+}
+
+impl Z3Translator {
+    /// The length and elements of a sequence value; `None` for other values
+    pub(crate) fn seq_parts<'a>(&'a self, value: &Dynamic<'a>) -> Option<SeqParts<'a>> {
+        let ValueType::Seq(elem) = self.type_of_value(value)? else {
+            return None;
+        };
+        let datatype = self.datatype(&ValueType::Seq(elem.clone()))?;
+        let accessors = &datatype.variants[0].accessors;
+        Some(SeqParts {
+            len: accessors[0].apply(&[value]),
+            elems: accessors[1].apply(&[value]).as_array()?,
+            elem: *elem,
+        })
+    }
+
+    /// A sequence with the given length and elements
+    pub(crate) fn make_seq<'a>(&'a self, elem: &ValueType, len: &Dynamic<'a>, elems: &Array<'a>) -> Dynamic<'a> {
+        let datatype = self.datatype(&ValueType::Seq(Box::new(elem.clone()))).expect("datatype for a sequence");
+        datatype.variants[0].constructor.apply(&[len, elems])
+    }
+
+    /// The Z3 sort of lengths and indices
+    pub(crate) fn index_sort(&self) -> Sort<'_> {
+        self.sort_of(&ValueType::Int(Some(INDEX)))
+    }
+
+    /// `len == N` for a parameter of array type `[T; N]`
+    pub(crate) fn fixed_length<'a>(&'a self, value: &Dynamic<'a>, ty: &syn::Type) -> Option<Bool<'a>> {
+        let syn::Type::Array(array) = strip_references(ty) else {
+            return None;
+        };
+        let len = self.translate_typed(&array.len, &mut HashMap::new(), Some(INDEX)).ok()?;
+        self.seq_parts(value)?.len._safe_eq(&len).ok()
+    }
+
+    /// The range constraints of a sequence: its length is a `usize`, and its
+    /// elements (below the length) are within their types' ranges
+    pub(crate) fn seq_in_range<'a>(&'a self, value: &Dynamic<'a>) -> Option<Bool<'a>> {
+        let parts = self.seq_parts(value)?;
+        let mut constraints: Vec<Bool<'a>> = self.value_in_range(&parts.len, &ValueType::Int(Some(INDEX))).into_iter().collect();
+        let i = self.fresh_value("i", &parts.len);
+        let element = parts.elems.select(&i);
+        if let Some(elem_in_range) = self.value_in_range(&element, &parts.elem) {
+            let in_bounds = self.in_bounds(&i, &parts.len).ok()?;
+            let pattern = Pattern::new(self.context(), &[&element as &dyn Ast]);
+            constraints.push(forall_const(self.context(), &[&i], &[&pattern], &in_bounds.implies(&elem_in_range)));
+        }
+        let constraints: Vec<&Bool<'a>> = constraints.iter().collect();
+        (!constraints.is_empty()).then(|| Bool::and(self.context(), &constraints))
+    }
+
+    /// Holds when two sequences have the same length and the same elements below it
+    pub(crate) fn seq_eq<'a>(&'a self, left: &SeqParts<'a>, right: &SeqParts<'a>) -> Result<Bool<'a>, TranslationError> {
+        let i = self.fresh_value("i", &left.len);
+        let same_elem = left.elems.select(&i)._safe_eq(&right.elems.select(&i))
+            .map_err(|_| TranslationError::TypeError("Cannot compare sequences of different element types".to_string()))?;
+        let same_elems = forall_const(self.context(), &[&i], &[], &self.in_bounds(&i, &left.len)?.implies(&same_elem));
+        let same_len = left.len._safe_eq(&right.len)
+            .map_err(|_| TranslationError::TypeError("Cannot compare sequence lengths".to_string()))?;
+        Ok(Bool::and(self.context(), &[&same_len, &same_elems]))
+    }
+
+    /// Translate `v[i]`: the element at index `i`, unknown if `i` is out of bounds
+    pub(crate) fn translate_index<'a>(&'a self, index: &syn::ExprIndex, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let (parts, i) = self.index_parts(index, vars)?;
+        Ok(parts.elems.select(&i))
+    }
+
+    /// Holds when the index of `v[i]` is below `v`'s length
+    pub(crate) fn index_in_bounds<'a>(&'a self, index: &syn::ExprIndex, vars: &mut HashMap<String, Dynamic<'a>>) -> Option<Bool<'a>> {
+        let (parts, i) = self.index_parts(index, vars).ok()?;
+        self.in_bounds(&i, &parts.len).ok()
+    }
+
+    fn index_parts<'a>(&'a self, index: &syn::ExprIndex, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<(SeqParts<'a>, Dynamic<'a>), TranslationError> {
+        if matches!(&*index.index, Expr::Range(_)) {
+            return Err(TranslationError::UnsupportedExpression("Slicing with a range".to_string()));
+        }
+        let seq = self.translate_typed(&index.expr, vars, None)?;
+        let parts = self.seq_parts(&seq)
+            .ok_or_else(|| TranslationError::TypeError("Indexing a value that isn't a Vec, slice or array".to_string()))?;
+        let i = self.translate_index_value(&index.index, vars)?;
+        Ok((parts, i))
+    }
+
+    /// Translate an index or length, which must be a `usize`
+    fn translate_index_value<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let value = self.translate_typed(expr, vars, Some(INDEX))?;
+        if value.get_sort() != self.index_sort() {
+            return Err(TranslationError::TypeError(format!("`{}` isn't a usize", expr.to_token_stream())));
+        }
+        Ok(value)
+    }
+
+    /// `0 <= i < len`
+    fn in_bounds<'a>(&'a self, i: &Dynamic<'a>, len: &Dynamic<'a>) -> Result<Bool<'a>, TranslationError> {
+        let zero = self.int_literal(0, Some(INDEX))?;
+        let above = self.index_compare(BinOp::Le(Default::default()), &zero, i)?;
+        let below = self.index_compare(BinOp::Lt(Default::default()), i, len)?;
+        Ok(Bool::and(self.context(), &[&above, &below]))
+    }
+
+    fn index_compare<'a>(&'a self, op: BinOp, left: &Dynamic<'a>, right: &Dynamic<'a>) -> Result<Bool<'a>, TranslationError> {
+        self.translate_binary_op(op, left.clone(), right.clone(), Some(INDEX))?.as_bool()
+            .ok_or_else(|| TranslationError::TypeError("Expected Bool".to_string()))
+    }
+
+    fn index_arith<'a>(&'a self, op: BinOp, left: &Dynamic<'a>, right: &Dynamic<'a>) -> Result<Dynamic<'a>, TranslationError> {
+        self.translate_binary_op(op, left.clone(), right.clone(), Some(INDEX))
+    }
+
+    /// Translate a sequence literal: `[a, b]`, `[x; n]`, `vec![...]` or `Vec::new()`
+    ///
+    /// `elem` is the expected element type, e.g. for `Vec::new()` or `[]`;
+    /// otherwise elements are integers unless the first one is known to be
+    /// something else. Returns `None` if `expr` isn't a sequence literal.
+    pub(crate) fn translate_seq_literal<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>, elem: Option<&ValueType>) -> Option<Result<Dynamic<'a>, TranslationError>> {
+        match expr {
+            Expr::Array(array) => Some(self.seq_of_elements(&array.elems.iter().collect::<Vec<_>>(), vars, elem)),
+            Expr::Repeat(repeat) => Some(self.seq_of_copies(&repeat.expr, &repeat.len, vars, elem)),
+            Expr::Macro(mac) if mac.mac.path.is_ident("vec") => {
+                let tokens = &mac.mac.tokens;
+                match syn::parse2::<Expr>(quote!([#tokens])) {
+                    Ok(literal) => self.translate_seq_literal(&literal, vars, elem),
+                    Err(e) => Some(Err(TranslationError::ParseError(e.to_string()))),
+                }
+            }
+            Expr::Call(call) if is_empty_vec(&call.func) => {
+                let elem = elem.cloned().unwrap_or(ValueType::Int(None));
+                Some(self.int_literal(0, Some(INDEX)).map(|len| {
+                    let elems = Array::fresh_const(self.context(), "elems", &self.index_sort(), &self.sort_of(&elem));
+                    self.make_seq(&elem, &len, &elems)
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    fn seq_of_elements<'a>(&'a self, elements: &[&Expr], vars: &mut HashMap<String, Dynamic<'a>>, elem: Option<&ValueType>) -> Result<Dynamic<'a>, TranslationError> {
+        let hint = elem.cloned().or_else(|| elements.first().and_then(|first| self.infer_value_type(first)));
+        let values = elements.iter()
+            .map(|element| self.translate_hinted(element, vars, hint.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let elem = hint
+            .or_else(|| values.first().and_then(|first| self.type_of_value(first)))
+            .unwrap_or(ValueType::Int(None));
+        let elem_sort = self.sort_of(&elem);
+        if values.iter().any(|value| value.get_sort() != elem_sort) {
+            return Err(TranslationError::TypeError("Elements of an array literal have different types".to_string()));
+        }
+        let mut elems = Array::fresh_const(self.context(), "elems", &self.index_sort(), &elem_sort);
+        for (index, value) in values.iter().enumerate() {
+            elems = elems.store(&self.int_literal(index as u128, Some(INDEX))?, value);
+        }
+        Ok(self.make_seq(&elem, &self.int_literal(values.len() as u128, Some(INDEX))?, &elems))
+    }
+
+    fn seq_of_copies<'a>(&'a self, value: &Expr, len: &Expr, vars: &mut HashMap<String, Dynamic<'a>>, elem: Option<&ValueType>) -> Result<Dynamic<'a>, TranslationError> {
+        let value = self.translate_hinted(value, vars, elem)?;
+        let elem = match elem {
+            Some(elem) => elem.clone(),
+            None => self.type_of_value(&value).unwrap_or(ValueType::Int(None)),
+        };
+        let len = self.translate_index_value(len, vars)?;
+        let elems = Array::const_array(self.context(), &self.index_sort(), &value);
+        Ok(self.make_seq(&elem, &len, &elems))
+    }
+
+    /// The type of a sequence or iterator method's result, if known
+    ///
+    /// e.g. `usize` for `v.len()` and `Option<u64>` for `v.first()` with `v: Vec<u64>`.
+    pub(crate) fn seq_method_value_type(&self, method: &syn::ExprMethodCall) -> Option<ValueType> {
+        match method.method.to_string().as_str() {
+            "len" | "count" => Some(ValueType::Int(Some(INDEX))),
+            "sum" => {
+                let turbofish = method.turbofish.as_ref().and_then(|turbofish| match turbofish.args.first()? {
+                    syn::GenericArgument::Type(ty) => IntType::from_type(ty),
+                    _ => None,
+                });
+                match turbofish {
+                    Some(ty) => Some(ValueType::Int(Some(ty))),
+                    None => match iterator_chain(&method.receiver)? {
+                        (source, adapters) if adapters.is_empty() => self.seq_elem_type(source),
+                        _ => None,
+                    },
+                }
+            }
+            "first" | "last" | "get" | "pop" => Some(ValueType::Option(Box::new(self.seq_elem_type(&method.receiver)?))),
+            "iter" | "into_iter" | "copied" | "cloned" | "to_vec" | "as_slice" | "clone" | "to_owned" => self.infer_value_type(&method.receiver),
+            _ => None,
+        }
+    }
+
+    /// The element type of a sequence expression, if known
+    pub(crate) fn seq_elem_type(&self, expr: &Expr) -> Option<ValueType> {
+        match self.infer_value_type(expr)? {
+            ValueType::Seq(elem) => Some(*elem),
+            _ => None,
+        }
+    }
+
+    /// Translate a method of a sequence, or one consuming an iterator over one
+    ///
+    /// `len`, `is_empty`, `contains`, `first`, `last`, `get` and `pop` (its
+    /// value; see [`Z3Translator::seq_assignment`] for its effect) read the
+    /// sequence; `iter`, `copied`, `clone` and the like return it unchanged.
+    /// Returns `None` for other methods.
+    pub(crate) fn translate_seq_method<'a>(&'a self, method: &syn::ExprMethodCall, vars: &mut HashMap<String, Dynamic<'a>>) -> Option<Result<Dynamic<'a>, TranslationError>> {
+        let name = method.method.to_string();
+        match name.as_str() {
+            "sum" | "count" | "all" | "any" => {
+                let (source, adapters) = iterator_chain(&method.receiver)?;
+                Some(self.consume_iterator(&name, method, source, &adapters, vars))
+            }
+            "len" | "is_empty" | "contains" | "first" | "last" | "get" | "pop" => Some(self.seq_method(&name, method, vars)),
+            "iter" | "into_iter" | "copied" | "cloned" | "to_vec" | "as_slice" | "clone" | "to_owned" if method.args.is_empty() => {
+                Some(self.translate_typed(&method.receiver, vars, None))
+            }
+            _ => None,
+        }
+    }
+
+    fn seq_method<'a>(&'a self, name: &str, method: &syn::ExprMethodCall, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let receiver = self.translate_typed(&method.receiver, vars, None)?;
+        let parts = self.seq_parts(&receiver)
+            .ok_or_else(|| TranslationError::TypeError(format!("`{}` on a value that isn't a Vec, slice or array", name)))?;
+        let arg = || method.args.first()
+            .ok_or_else(|| TranslationError::UnsupportedExpression(format!("`{}` without an argument", name)));
+        let zero = self.int_literal(0, Some(INDEX))?;
+        match name {
+            "len" => Ok(parts.len),
+            "is_empty" => Ok(parts.len._eq(&zero).into()),
+            "contains" => {
+                let value = self.translate_value(arg()?, vars, &parts.elem)?;
+                let i = self.fresh_value("i", &parts.len);
+                let found = parts.elems.select(&i)._safe_eq(&value)
+                    .map_err(|_| TranslationError::TypeError("`contains` of a value of the wrong type".to_string()))?;
+                let body = Bool::and(self.context(), &[&self.in_bounds(&i, &parts.len)?, &found]);
+                Ok(exists_const(self.context(), &[&i], &[], &body).into())
+            }
+            _ => {
+                let index = match name {
+                    "first" => zero,
+                    "get" => self.translate_index_value(arg()?, vars)?,
+                    _ => self.index_arith(BinOp::Sub(Default::default()), &parts.len, &self.int_literal(1, Some(INDEX))?)?,
+                };
+                let option = ValueType::Option(Box::new(parts.elem.clone()));
+                let some = self.construct(&option, "Some", vec![parts.elems.select(&index)])?;
+                let none = self.construct(&option, "None", Vec::new())?;
+                Ok(self.in_bounds(&index, &parts.len)?.ite(&some, &none))
+            }
+        }
+    }
+
+    fn consume_iterator<'a>(&'a self, name: &str, method: &syn::ExprMethodCall, source: &Expr, adapters: &[Adapter<'_>], vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let seq = self.translate_typed(source, vars, None)?;
+        let parts = self.seq_parts(&seq)
+            .ok_or_else(|| TranslationError::TypeError(format!("`{}` of an iterator that isn't over a Vec, slice or array", name)))?;
+        if name == "sum" || name == "count" {
+            return self.iterator_fold(name, &parts, adapters, vars);
+        }
+        let Some(Expr::Closure(predicate)) = method.args.first() else {
+            return Err(TranslationError::UnsupportedExpression(format!("`{}` without a closure", name)));
+        };
+        let ctx = self.context();
+        let i = self.fresh_value("i", &parts.len);
+        let (value, value_ty, selected) = self.apply_adapters(adapters, parts.elems.select(&i), &parts.elem, vars)?;
+        let holds = self.apply_closure(predicate, &value, value_ty, vars)?.as_bool()
+            .ok_or_else(|| TranslationError::TypeError(format!("The closure of `{}` isn't a condition", name)))?;
+        let in_bounds = self.in_bounds(&i, &parts.len)?;
+        let quantified = if name == "all" {
+            forall_const(ctx, &[&i], &[], &Bool::and(ctx, &[&in_bounds, &selected]).implies(&holds))
+        } else {
+            exists_const(ctx, &[&i], &[], &Bool::and(ctx, &[&in_bounds, &selected, &holds]))
+        };
+        Ok(quantified.into())
+    }
+
+    /// `sum()` or `count()` of an iterator over a sequence
+    ///
+    /// A recursive function of the elements `a`, the length `n` and the
+    /// variables the adapters capture: `f(a, 0) = 0` and
+    /// `f(a, n) = f(a, n - 1) + term(a[n - 1])`, where `term` is the mapped
+    /// element (or `1` for `count`), or `0` if the element is filtered out.
+    /// It is named after the chain, so equal chains give the same function.
+    fn iterator_fold<'a>(&'a self, name: &str, parts: &SeqParts<'a>, adapters: &[Adapter<'_>], vars: &HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let ctx = self.context();
+        let captured: Vec<(String, Dynamic<'a>)> = captured_vars(adapters).into_iter()
+            .filter_map(|name| Some((name.clone(), vars.get(&name)?.clone())))
+            .collect();
+
+        // The definition's bound variables
+        let elems = Array::fresh_const(ctx, "elems", &self.index_sort(), &self.sort_of(&parts.elem));
+        let n = self.fresh_value("n", &parts.len);
+        let bound_captures: Vec<Dynamic<'a>> = captured.iter().map(|(name, value)| self.fresh_value(name, value)).collect();
+        let mut scope: HashMap<String, Dynamic<'a>> = captured.iter()
+            .map(|(name, _)| name.clone())
+            .zip(bound_captures.iter().cloned())
+            .collect();
+
+        let zero = self.int_literal(0, Some(INDEX))?;
+        let one = self.int_literal(1, Some(INDEX))?;
+        let last = self.index_arith(BinOp::Sub(Default::default()), &n, &one)?;
+        let (value, _, selected) = self.apply_adapters(adapters, elems.select(&last), &parts.elem, &mut scope)?;
+        let (term, empty) = if name == "count" {
+            (selected.ite(&one, &zero), zero.clone())
+        } else {
+            let empty = match value.as_bv() {
+                Some(bv) => self.int_literal(0, Some(IntType { bits: bv.get_size(), signed: false }))?,
+                None if value.as_int().is_some() => self.int_literal(0, None)?,
+                None => return Err(TranslationError::TypeError("`sum` of values that aren't integers".to_string())),
+            };
+            (selected.ite(&value, &empty), empty)
+        };
+
+        let mut bound: Vec<Dynamic<'a>> = vec![elems.clone().into(), n.clone()];
+        bound.extend(bound_captures);
+        let domain: Vec<Sort<'a>> = bound.iter().map(|b| b.get_sort()).collect();
+        let domain: Vec<&Sort<'a>> = domain.iter().collect();
+        let signature: Vec<String> = domain.iter().map(|sort| sort.to_string()).collect();
+        let chain: Vec<String> = adapters.iter().map(|adapter| adapter.to_string()).collect();
+        let function_name = format!("{}[{}]({})", name, chain.join("."), signature.join(", "));
+        let function = RecFuncDecl::new(ctx, function_name, &domain, &term.get_sort());
+
+        let mut previous: Vec<&dyn Ast<'a>> = vec![&elems, &last];
+        previous.extend(bound[2..].iter().map(|b| b as &dyn Ast<'a>));
+        let rest = function.apply(&previous);
+        let total = self.translate_binary_op(BinOp::Add(Default::default()), rest, term, None)?;
+        let no_elements = self.index_compare(BinOp::Le(Default::default()), &n, &zero)?;
+        let bound_refs: Vec<&dyn Ast<'a>> = bound.iter().map(|b| b as &dyn Ast<'a>).collect();
+        function.add_def(&bound_refs, &no_elements.ite(&empty, &total));
+
+        let mut args: Vec<&dyn Ast<'a>> = vec![&parts.elems, &parts.len];
+        args.extend(captured.iter().map(|(_, value)| value as &dyn Ast<'a>));
+        Ok(function.apply(&args))
+    }
+
+    /// Apply a chain of `map` and `filter` adapters to an element
+    ///
+    /// Returns the mapped value, its integer type if known, and the condition
+    /// under which the element passes the filters.
+    fn apply_adapters<'a>(&'a self, adapters: &[Adapter<'_>], element: Dynamic<'a>, elem: &ValueType, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<(Dynamic<'a>, Option<IntType>, Bool<'a>), TranslationError> {
+        let mut value = element;
+        let mut value_ty = elem.int_type();
+        let mut selected = Vec::new();
+        for adapter in adapters {
+            match adapter {
+                Adapter::Map(closure) => {
+                    value_ty = self.closure_int_type(closure, value_ty);
+                    value = self.apply_closure(closure, &value, value_ty, vars)?;
+                }
+                Adapter::Filter(closure) => {
+                    let keep = self.apply_closure(closure, &value, value_ty, vars)?.as_bool()
+                        .ok_or_else(|| TranslationError::TypeError("The closure of `filter` isn't a condition".to_string()))?;
+                    selected.push(keep);
+                }
+            }
+        }
+        let selected: Vec<&Bool<'a>> = selected.iter().collect();
+        Ok((value, value_ty, Bool::and(self.context(), &selected)))
+    }
+
+    /// Translate a one-parameter closure's body with its parameter bound to `arg`
+    fn apply_closure<'a>(&'a self, closure: &syn::ExprClosure, arg: &Dynamic<'a>, arg_ty: Option<IntType>, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let param = closure_param(closure)?;
+        let mut scope = vars.clone();
+        scope.insert(param.clone(), arg.clone());
+        let previous = self.set_var_type(&param, arg_ty);
+        let value = self.translate_typed(&closure.body, &mut scope, None);
+        self.set_var_type(&param, previous);
+        value
+    }
+
+    /// The integer type of a `map` closure's result, given its parameter's
+    fn closure_int_type(&self, closure: &syn::ExprClosure, param_ty: Option<IntType>) -> Option<IntType> {
+        let param = closure_param(closure).ok()?;
+        let previous = self.set_var_type(&param, param_ty);
+        let ty = self.infer_int_type(&closure.body);
+        self.set_var_type(&param, previous);
+        ty
+    }
+
+    /// The effect of a statement that changes a sequence in place
+    ///
+    /// The sequence is a local variable or a field of one (e.g. `tx.outputs`).
+    /// Returns the variable and its new value, or `None` if `expr` doesn't
+    /// change a sequence.
+    pub(crate) fn seq_assignment<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>) -> Option<(String, Dynamic<'a>)> {
+        let target = match expr {
+            Expr::MethodCall(call) if mutates_receiver(&call.method.to_string()) => &*call.receiver,
+            Expr::Assign(assign) => match &*assign.left {
+                Expr::Index(index) => &*index.expr,
+                _ => return None,
+            },
+            Expr::Binary(bin) => match &*bin.left {
+                Expr::Index(index) if compound_operator(bin.op).is_some() => &*index.expr,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let old = self.place_value(target, vars)?;
+        let parts = self.seq_parts(&old)?;
+        let name = place_root(target)?;
+        let new = self.updated_seq(expr, &parts, vars).unwrap_or_else(|_| self.fresh_value(&name, &old));
+        self.assign_place(target, new, vars)
+    }
+
+    /// A sequence after a statement changing it; an error for changes that aren't modelled
+    fn updated_seq<'a>(&'a self, expr: &Expr, parts: &SeqParts<'a>, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let (index, value) = match expr {
+            Expr::MethodCall(call) => return self.mutate_seq(call, parts, vars),
+            Expr::Assign(assign) => match &*assign.left {
+                Expr::Index(index) => (index, self.translate_value(&assign.right, vars, &parts.elem)?),
+                _ => return Err(TranslationError::UnsupportedExpression(format!("{:?}", expr))),
+            },
+            Expr::Binary(bin) => match (&*bin.left, compound_operator(bin.op)) {
+                (Expr::Index(index), Some(op)) => {
+                    let value = Expr::Binary(syn::ExprBinary {
+                        attrs: Vec::new(),
+                        left: bin.left.clone(),
+                        op,
+                        right: bin.right.clone(),
+                    });
+                    (index, self.translate_typed(&value, vars, parts.elem.int_type())?)
+                }
+                _ => return Err(TranslationError::UnsupportedExpression(format!("{:?}", expr))),
+            },
+            _ => return Err(TranslationError::UnsupportedExpression(format!("{:?}", expr))),
+        };
+        let i = self.translate_index_value(&index.index, vars)?;
+        self.store_element(parts, &i, &value)
+    }
+
+    fn store_element<'a>(&'a self, parts: &SeqParts<'a>, i: &Dynamic<'a>, value: &Dynamic<'a>) -> Result<Dynamic<'a>, TranslationError> {
+        if value.get_sort() != self.sort_of(&parts.elem) {
+            return Err(TranslationError::TypeError("Storing an element of the wrong type".to_string()));
+        }
+        Ok(self.make_seq(&parts.elem, &parts.len, &parts.elems.store(i, value)))
+    }
+
+    /// A sequence after a method taking `&mut self`; an error for methods that aren't modelled
+    fn mutate_seq<'a>(&'a self, call: &syn::ExprMethodCall, parts: &SeqParts<'a>, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let name = call.method.to_string();
+        let zero = self.int_literal(0, Some(INDEX))?;
+        let one = self.int_literal(1, Some(INDEX))?;
+        let arg = |index: usize| call.args.iter().nth(index)
+            .ok_or_else(|| TranslationError::UnsupportedExpression(format!("`{}` with {} arguments", name, call.args.len())));
+        let (len, elems) = match name.as_str() {
+            "push" => {
+                let value = self.translate_value(arg(0)?, vars, &parts.elem)?;
+                if value.get_sort() != self.sort_of(&parts.elem) {
+                    return Err(TranslationError::TypeError("Pushing an element of the wrong type".to_string()));
+                }
+                (self.index_arith(BinOp::Add(Default::default()), &parts.len, &one)?, parts.elems.store(&parts.len, &value))
+            }
+            "pop" => {
+                let shorter = self.index_arith(BinOp::Sub(Default::default()), &parts.len, &one)?;
+                let non_empty = self.index_compare(BinOp::Lt(Default::default()), &zero, &parts.len)?;
+                (non_empty.ite(&shorter, &parts.len), parts.elems.clone())
+            }
+            "clear" => (zero, parts.elems.clone()),
+            "truncate" => {
+                let n = self.translate_index_value(arg(0)?, vars)?;
+                let shorter = self.index_compare(BinOp::Lt(Default::default()), &n, &parts.len)?;
+                (shorter.ite(&n, &parts.len), parts.elems.clone())
+            }
+            "swap" => {
+                let i = self.translate_index_value(arg(0)?, vars)?;
+                let j = self.translate_index_value(arg(1)?, vars)?;
+                let elems = parts.elems.store(&i, &parts.elems.select(&j)).store(&j, &parts.elems.select(&i));
+                (parts.len.clone(), elems)
+            }
+            _ => return Err(TranslationError::UnsupportedExpression(format!("Method call: {}", name))),
+        };
+        Ok(self.make_seq(&parts.elem, &len, &elems))
+    }
+}
+
+impl std::fmt::Display for Adapter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Adapter::Map(closure) => write!(f, "map({})", closure.to_token_stream()),
+            Adapter::Filter(closure) => write!(f, "filter({})", closure.to_token_stream()),
+        }
+    }
+}
+
+/// Check whether a method changes the sequence it's called on
+///
+/// Also used to find the variables a loop assigns (see `translator::loops`).
+pub(crate) fn mutates_receiver(method: &str) -> bool {
+    matches!(method,
+        "push" | "pop" | "clear" | "truncate" | "swap" | "insert" | "remove" | "swap_remove" |
+        "extend" | "extend_from_slice" | "append" | "resize" | "retain" | "dedup" | "drain" |
+        "split_off" | "fill" | "reverse" | "rotate_left" | "rotate_right" | "sort" |
+        "sort_unstable" | "sort_by" | "sort_by_key" | "sort_unstable_by" | "sort_unstable_by_key")
+}
+
+/// The sequence an iterator chain runs over, and its adapters in the order they apply
+///
+/// e.g. `v` and `[map(...)]` for `v.iter().map(...)`. `None` if `expr` isn't
+/// such a chain.
+fn iterator_chain(expr: &Expr) -> Option<(&Expr, Vec<Adapter<'_>>)> {
+    let mut adapters = Vec::new();
+    let mut current = expr;
+    loop {
+        let call = match current {
+            Expr::Paren(paren) => {
+                current = &paren.expr;
+                continue;
+            }
+            Expr::MethodCall(call) => call,
+            _ => return None,
+        };
+        match (call.method.to_string().as_str(), call.args.first()) {
+            ("iter" | "into_iter", None) => {
+                adapters.reverse();
+                return Some((&call.receiver, adapters));
+            }
+            ("copied" | "cloned", None) => {}
+            ("map", Some(Expr::Closure(closure))) => adapters.push(Adapter::Map(closure)),
+            ("filter", Some(Expr::Closure(closure))) => adapters.push(Adapter::Filter(closure)),
+            _ => return None,
+        }
+        current = &call.receiver;
+    }
+}
+
+/// Names used in the adapters' closures, other than their parameters
+fn captured_vars(adapters: &[Adapter<'_>]) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    for adapter in adapters {
+        let (Adapter::Map(closure) | Adapter::Filter(closure)) = adapter;
+        let mut used = BTreeSet::new();
+        collect_idents(closure.body.to_token_stream(), &mut used);
+        if let Ok(param) = closure_param(closure) {
+            used.remove(&param);
+        }
+        names.extend(used);
+    }
+    names
+}
+
+fn collect_idents(tokens: TokenStream, out: &mut BTreeSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Group(group) => collect_idents(group.stream(), out),
+            TokenTree::Ident(ident) => {
+                out.insert(ident.to_string());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The name a one-parameter closure binds, looking through `&x` and `x: T`
+fn closure_param(closure: &syn::ExprClosure) -> Result<String, TranslationError> {
+    fn name(pat: &syn::Pat) -> Option<String> {
+        match pat {
+            syn::Pat::Ident(ident) => Some(ident.ident.to_string()),
+            syn::Pat::Reference(reference) => name(&reference.pat),
+            syn::Pat::Type(pat_type) => name(&pat_type.pat),
+            _ => None,
+        }
+    }
+    match closure.inputs.iter().collect::<Vec<_>>().as_slice() {
+        [param] => name(param).ok_or_else(|| TranslationError::UnsupportedExpression(format!("Closure parameter `{}`", param.to_token_stream()))),
+        _ => Err(TranslationError::UnsupportedExpression("Closure without exactly one parameter".to_string())),
+    }
+}
+
+/// Check whether a call is `Vec::new()`, `Vec::with_capacity(n)` or `Vec::default()`
+fn is_empty_vec(func: &Expr) -> bool {
+    let Expr::Path(path) = func else {
+        return false;
+    };
+    let segments: Vec<String> = path.path.segments.iter().map(|s| s.ident.to_string()).collect();
+    matches!(segments.as_slice(), [.., vec, constructor] if vec == "Vec" && matches!(constructor.as_str(), "new" | "with_capacity" | "default"))
+}
+
+fn strip_references(ty: &syn::Type) -> &syn::Type {
+    match ty {
+        syn::Type::Reference(reference) => strip_references(&reference.elem),
+        syn::Type::Paren(paren) => strip_references(&paren.elem),
+        syn::Type::Group(group) => strip_references(&group.elem),
+        _ => ty,
+    }
+}
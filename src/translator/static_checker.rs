@@ -5,7 +5,10 @@
 //! - Overflow checks: `a + b` → `a.checked_add(b).is_some()`
 //! - Option checks: `opt.is_some()`
 //! - Constant equality: `value == CONSTANT`
+//! - Two-state postconditions: `*x == old(*x) + 1` needs the body, so Z3 decides it (`mentions_old`)
 
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use quote::ToTokens;
 use syn::Expr;
 use crate::parser::contracts::{Contract, ContractType};
 
@@ -25,12 +28,42 @@ pub enum StaticCheckResult {
 /// Returns `Some(result)` if the check can be done statically,
 /// or `None` if Z3 is required.
 pub fn check_contract_statically(contract: &Contract) -> Option<StaticCheckResult> {
+    if mentions_old(&contract.condition) {
+        // A precondition has no earlier state for `old` to refer to
+        return Some(match contract.contract_type {
+            ContractType::Requires => StaticCheckResult::Failed,
+            ContractType::Ensures => StaticCheckResult::RequiresZ3,
+        });
+    }
     match contract.contract_type {
         ContractType::Requires => check_requires_statically(&contract.condition),
         ContractType::Ensures => check_ensures_statically(&contract.condition),
     }
 }
 
+/// Check if an expression refers to a pre-state with `old(..)`
+pub fn mentions_old(expr: &Expr) -> bool {
+    fn search(tokens: TokenStream) -> bool {
+        let mut after_dot = false;
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            match &token {
+                // `old(..)`, but not a method `x.old(..)`
+                TokenTree::Ident(ident) if ident == "old" && !after_dot => {
+                    if matches!(tokens.peek(), Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis) {
+                        return true;
+                    }
+                }
+                TokenTree::Group(group) if search(group.stream()) => return true,
+                _ => {}
+            }
+            after_dot = matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '.');
+        }
+        false
+    }
+    search(expr.to_token_stream())
+}
+
 /// Check a requires (precondition) contract statically
 fn check_requires_statically(expr: &Expr) -> Option<StaticCheckResult> {
     // Pattern match on common precondition patterns
@@ -167,5 +200,24 @@ mod tests {
         // Should require Z3 (needs type information)
         assert_eq!(result, Some(StaticCheckResult::RequiresZ3));
     }
+
+    #[test]
+    fn test_old() {
+        let ensures = Contract {
+            contract_type: ContractType::Ensures,
+            condition: parse_quote! { *x == old(*x) + 1 },
+            comment: None,
+        };
+        assert_eq!(check_contract_statically(&ensures), Some(StaticCheckResult::RequiresZ3));
+
+        let requires = Contract {
+            contract_type: ContractType::Requires,
+            condition: parse_quote! { old(*x) > 0 },
+            comment: None,
+        };
+        assert_eq!(check_contract_statically(&requires), Some(StaticCheckResult::Failed));
+
+        assert!(!mentions_old(&parse_quote! { v.old(1) == bold(2) }));
+    }
 }
 
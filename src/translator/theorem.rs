@@ -0,0 +1,424 @@
+//! Theorem compiler: Orange Paper math → Z3
+//!
+//! Compiles a parsed theorem statement (see `parser::math`) straight into Z3
+//! terms, without going through a Rust contract. Leading universal quantifiers
+//! become free variables, and each application of the spec function (e.g.
+//! `GetBlockSubsidy(h)`) is bound to its own copy of the translated function
+//! body, so the theorem is proven against the implementation itself.
+
+use std::collections::HashMap;
+use z3::ast::{exists_const, forall_const, Ast, Bool, Dynamic, Int};
+use z3::Pattern;
+use syn::ItemFn;
+use crate::parser::contracts::Contract;
+use crate::parser::math::{self, BinaryOp, MathExpr, MathSet, Quantifier, UnaryOp};
+use crate::translator::quantifiers::triggers;
+use crate::translator::z3_translator::{IntType, TranslationError, Z3Translator};
+
+/// A theorem compiled to Z3
+pub struct CompiledTheorem<'a> {
+    /// Facts assumed while proving: quantifier domains, parameter bindings,
+    /// the implementation formula of each application, and preconditions
+    pub assumptions: Vec<Bool<'a>>,
+    /// The statement to prove
+    pub goal: Bool<'a>,
+    /// Variables to report in a counterexample: name, value, Rust type name
+    pub model_vars: Vec<(String, Dynamic<'a>, Option<String>)>,
+}
+
+/// Compile `statement` about `spec_function`, implemented by `func`
+///
+/// `requires` are the function's preconditions; they're assumed for every
+/// application of the spec function.
+pub fn compile_theorem<'a>(
+    translator: &'a Z3Translator,
+    statement: &MathExpr,
+    spec_function: &str,
+    func: &ItemFn,
+    requires: &[Contract],
+) -> Result<CompiledTheorem<'a>, TranslationError> {
+    let mut compiler = TheoremCompiler {
+        translator,
+        spec_function,
+        func,
+        requires,
+        vars: HashMap::new(),
+        scoped: Vec::new(),
+        applications: Vec::new(),
+        assumptions: Vec::new(),
+        model_vars: Vec::new(),
+    };
+
+    // ∀h ∈ ℕ: body — h is free, so the theorem holds iff ¬body is unsatisfiable
+    let mut body = statement;
+    while let MathExpr::Quantified { quantifier: Quantifier::Forall, vars, domain, body: inner } = body {
+        for name in vars {
+            let var = Int::new_const(translator.context(), name.as_str());
+            if let Some(domain) = domain {
+                let constraint = compiler.domain_constraint(&var, domain)?;
+                compiler.assumptions.push(constraint);
+            }
+            compiler.model_vars.push((name.clone(), var.clone().into(), None));
+            compiler.vars.insert(name.clone(), var);
+        }
+        body = inner;
+    }
+
+    let goal = compiler.compile_bool(body)?;
+    Ok(CompiledTheorem {
+        assumptions: compiler.assumptions,
+        goal,
+        model_vars: compiler.model_vars,
+    })
+}
+
+struct TheoremCompiler<'a, 'f> {
+    translator: &'a Z3Translator,
+    spec_function: &'f str,
+    func: &'f ItemFn,
+    requires: &'f [Contract],
+    /// Math variables in scope
+    vars: HashMap<String, Int<'a>>,
+    /// Variables bound by nested quantifiers (they can't flow into the implementation)
+    scoped: Vec<String>,
+    /// Applications of the spec function: rendered arguments and result
+    applications: Vec<(String, Dynamic<'a>)>,
+    assumptions: Vec<Bool<'a>>,
+    model_vars: Vec<(String, Dynamic<'a>, Option<String>)>,
+}
+
+impl<'a> TheoremCompiler<'a, '_> {
+    fn compile_bool(&mut self, expr: &MathExpr) -> Result<Bool<'a>, TranslationError> {
+        self.compile(expr)?.as_bool()
+            .ok_or_else(|| TranslationError::TypeError(format!("Expected a proposition: {}", expr)))
+    }
+
+    fn compile_int(&mut self, expr: &MathExpr) -> Result<Int<'a>, TranslationError> {
+        self.compile(expr)?.as_int()
+            .ok_or_else(|| TranslationError::TypeError(format!("Expected a number: {}", expr)))
+    }
+
+    fn compile(&mut self, expr: &MathExpr) -> Result<Dynamic<'a>, TranslationError> {
+        let ctx = self.translator.context();
+        Ok(match expr {
+            MathExpr::Number(digits) => Int::from_str(ctx, digits)
+                .ok_or_else(|| TranslationError::UnsupportedLiteral(format!("Non-integer number {}", digits)))?
+                .into(),
+            MathExpr::Var(name) => self.variable(name).into(),
+            MathExpr::Call { name, args } if name.eq_ignore_ascii_case(self.spec_function) => self.apply(args)?,
+            MathExpr::Call { name, .. } => {
+                return Err(TranslationError::UnsupportedExpression(format!("Unknown function {}", name)));
+            }
+            MathExpr::Unary { op: UnaryOp::Neg, expr } => self.compile_int(expr)?.unary_minus().into(),
+            MathExpr::Unary { op: UnaryOp::Not, expr } => self.compile_bool(expr)?.not().into(),
+            MathExpr::Binary { op, left, right } => self.compile_binary(*op, left, right)?,
+            MathExpr::Floor(expr) => self.compile_int(expr)?.into(),
+            MathExpr::Ceil(expr) => match &**expr {
+                // ⌈a / b⌉ = ⌊(a + b - 1) / b⌋
+                MathExpr::Binary { op: BinaryOp::Div, left, right } => {
+                    let (a, b) = (self.compile_int(left)?, self.compile_int(right)?);
+                    let one = Int::from_i64(ctx, 1);
+                    Int::add(ctx, &[&a, &b, &one.unary_minus()]).div(&b).into()
+                }
+                expr => self.compile_int(expr)?.into(),
+            },
+            MathExpr::Cases(rows) => self.compile_cases(rows)?,
+            MathExpr::Member { element, set, negated } => {
+                let element = self.compile_int(element)?;
+                let member = self.domain_constraint(&element, set)?;
+                if *negated { member.not().into() } else { member.into() }
+            }
+            MathExpr::Quantified { quantifier, vars, domain, body } => self.compile_quantified(*quantifier, vars, domain.as_ref(), body)?,
+            MathExpr::Field { .. } | MathExpr::Index { .. } | MathExpr::Cardinality(_) | MathExpr::Sum { .. } => {
+                return Err(TranslationError::UnsupportedExpression(format!("Unsupported in theorems: {}", expr)));
+            }
+        })
+    }
+
+    /// A variable or named constant (`H` → `HALVING_INTERVAL`); unknown names are free
+    fn variable(&mut self, name: &str) -> Int<'a> {
+        let ctx = self.translator.context();
+        if let Some(var) = self.vars.get(name) {
+            return var.clone();
+        }
+        let constant_name = math::CONSENSUS_CONSTANTS.iter()
+            .find(|(math_name, _)| *math_name == name)
+            .map_or(name, |(_, rust_name)| rust_name);
+        if let Some((_, value)) = self.translator.constant(constant_name) {
+            return Int::from_str(ctx, &value.to_string()).expect("valid integer");
+        }
+        let var = Int::new_const(ctx, name);
+        self.model_vars.push((name.to_string(), var.clone().into(), None));
+        self.vars.insert(name.to_string(), var.clone());
+        var
+    }
+
+    fn compile_binary(&mut self, op: BinaryOp, left: &MathExpr, right: &MathExpr) -> Result<Dynamic<'a>, TranslationError> {
+        let ctx = self.translator.context();
+
+        // x × 2^{-e} is a right shift, 2^e a left shift of 1
+        if op == BinaryOp::Mul {
+            for (value, factor) in [(left, right), (right, left)] {
+                if let Some(exponent) = negative_power_of_two(factor) {
+                    let (value, exponent) = (self.compile_int(value)?, self.compile_int(exponent)?);
+                    return Ok(self.translator.int_shr(&value, &exponent, None).into());
+                }
+            }
+        }
+        if op == BinaryOp::Pow {
+            return self.compile_power(left, right);
+        }
+
+        match op {
+            BinaryOp::And | BinaryOp::Or | BinaryOp::Implies | BinaryOp::Iff => {
+                let (l, r) = (self.compile_bool(left)?, self.compile_bool(right)?);
+                Ok(match op {
+                    BinaryOp::And => Bool::and(ctx, &[&l, &r]),
+                    BinaryOp::Or => Bool::or(ctx, &[&l, &r]),
+                    BinaryOp::Implies => l.implies(&r),
+                    _ => l.iff(&r),
+                }.into())
+            }
+            BinaryOp::Eq | BinaryOp::Ne => {
+                let (l, r) = (self.compile(left)?, self.compile(right)?);
+                let eq = l._safe_eq(&r)
+                    .map_err(|_| TranslationError::TypeError(format!("Cannot compare {} and {}", left, right)))?;
+                Ok(if op == BinaryOp::Eq { eq } else { eq.not() }.into())
+            }
+            _ => {
+                let (l, r) = (self.compile_int(left)?, self.compile_int(right)?);
+                Ok(match op {
+                    BinaryOp::Add => Int::add(ctx, &[&l, &r]).into(),
+                    BinaryOp::Sub => Int::sub(ctx, &[&l, &r]).into(),
+                    BinaryOp::Mul => Int::mul(ctx, &[&l, &r]).into(),
+                    BinaryOp::Div => l.div(&r).into(),
+                    BinaryOp::Mod => l.modulo(&r).into(),
+                    BinaryOp::Lt => l.lt(&r).into(),
+                    BinaryOp::Le => l.le(&r).into(),
+                    BinaryOp::Gt => l.gt(&r).into(),
+                    BinaryOp::Ge => l.ge(&r).into(),
+                    _ => unreachable!("handled above"),
+                })
+            }
+        }
+    }
+
+    /// `2^e` (a shift) or `b^n` for a literal exponent
+    fn compile_power(&mut self, base: &MathExpr, exponent: &MathExpr) -> Result<Dynamic<'a>, TranslationError> {
+        // Largest literal exponent expanded into a product (`b^n` is `n` factors)
+        const MAX_LITERAL_EXPONENT: u32 = 128;
+        let ctx = self.translator.context();
+        if *base == MathExpr::Number("2".to_string()) && !matches!(exponent, MathExpr::Unary { op: UnaryOp::Neg, .. }) {
+            let exponent = self.compile_int(exponent)?;
+            return Ok(self.translator.int_shl(&Int::from_i64(ctx, 1), &exponent, None).into());
+        }
+        let MathExpr::Number(digits) = exponent else {
+            return Err(TranslationError::UnsupportedExpression(format!("Unsupported power: {}", MathExpr::Binary {
+                op: BinaryOp::Pow,
+                left: Box::new(base.clone()),
+                right: Box::new(exponent.clone()),
+            })));
+        };
+        let n: u32 = digits.parse().ok()
+            .filter(|n| *n <= MAX_LITERAL_EXPONENT)
+            .ok_or_else(|| TranslationError::UnsupportedLiteral(format!("Unsupported exponent {}", digits)))?;
+        let base = self.compile_int(base)?;
+        let factors: Vec<&Int<'a>> = std::iter::repeat_n(&base, n as usize).collect();
+        Ok(if factors.is_empty() { Int::from_i64(ctx, 1) } else { Int::mul(ctx, &factors) }.into())
+    }
+
+    /// Nested if-then-else; the last row is the fallback
+    fn compile_cases(&mut self, rows: &[(MathExpr, Option<MathExpr>)]) -> Result<Dynamic<'a>, TranslationError> {
+        let ((last, _), rest) = rows.split_last()
+            .ok_or_else(|| TranslationError::ParseError("Empty cases".to_string()))?;
+        let mut value = self.compile(last)?;
+        for (row_value, condition) in rest.iter().rev() {
+            let condition = match condition {
+                Some(condition) => self.compile_bool(condition)?,
+                None => return Err(TranslationError::ParseError("\"otherwise\" must be the last case".to_string())),
+            };
+            value = condition.ite(&self.compile(row_value)?, &value);
+        }
+        Ok(value)
+    }
+
+    fn compile_quantified(&mut self, quantifier: Quantifier, names: &[String], domain: Option<&MathSet>, body: &MathExpr) -> Result<Dynamic<'a>, TranslationError> {
+        let ctx = self.translator.context();
+        let bound: Vec<Int<'a>> = names.iter().map(|name| Int::new_const(ctx, format!("{}!bound", name))).collect();
+        let shadowed: Vec<Option<Int<'a>>> = names.iter().zip(&bound)
+            .map(|(name, var)| self.vars.insert(name.clone(), var.clone()))
+            .collect();
+        self.scoped.extend(names.iter().cloned());
+
+        let mut constraints = Vec::new();
+        let mut result = Ok(());
+        if let Some(domain) = domain {
+            for var in &bound {
+                match self.domain_constraint(var, domain) {
+                    Ok(constraint) => constraints.push(constraint),
+                    Err(e) => result = Err(e),
+                }
+            }
+        }
+        let body = result.and_then(|_| self.compile_bool(body));
+
+        self.scoped.truncate(self.scoped.len() - names.len());
+        for (name, previous) in names.iter().zip(shadowed) {
+            match previous {
+                Some(var) => self.vars.insert(name.clone(), var),
+                None => self.vars.remove(name),
+            };
+        }
+
+        let body = body?;
+        let domain = Bool::and(ctx, &constraints.iter().collect::<Vec<_>>());
+        let bound: Vec<Dynamic<'a>> = bound.into_iter().map(Dynamic::from).collect();
+        let bound_refs: Vec<&dyn Ast<'a>> = bound.iter().map(|var| var as &dyn Ast<'a>).collect();
+        let patterns = triggers(ctx, &bound, &body);
+        let pattern_refs: Vec<&Pattern<'a>> = patterns.iter().collect();
+        Ok(match quantifier {
+            Quantifier::Forall => forall_const(ctx, &bound_refs, &pattern_refs, &domain.implies(&body)),
+            Quantifier::Exists => exists_const(ctx, &bound_refs, &pattern_refs, &Bool::and(ctx, &[&domain, &body])),
+        }.into())
+    }
+
+    /// Membership of `value` in a number set
+    fn domain_constraint(&self, value: &Int<'a>, set: &MathSet) -> Result<Bool<'a>, TranslationError> {
+        let ctx = self.translator.context();
+        match set {
+            MathSet::Naturals => Ok(value.ge(&Int::from_i64(ctx, 0))),
+            MathSet::Integers => Ok(Bool::from_bool(ctx, true)),
+            MathSet::Booleans => Ok(Bool::or(ctx, &[&value._eq(&Int::from_i64(ctx, 0)), &value._eq(&Int::from_i64(ctx, 1))])),
+            MathSet::Reals | MathSet::Named(_) => {
+                Err(TranslationError::UnsupportedExpression(format!("Unsupported domain {}", set)))
+            }
+        }
+    }
+
+    /// The spec function applied to `args`: a fresh copy of the implementation
+    ///
+    /// Parameters are bound to the arguments (positionally) and constrained to
+    /// their Rust types' ranges; the translated body and the preconditions are
+    /// assumed. Identical applications share one copy.
+    fn apply(&mut self, args: &[MathExpr]) -> Result<Dynamic<'a>, TranslationError> {
+        let rendered = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().join(", ");
+        if let Some((_, result)) = self.applications.iter().find(|(key, _)| *key == rendered) {
+            return Ok(result.clone());
+        }
+
+        let params: Vec<(String, syn::Type)> = self.func.sig.inputs.iter()
+            .filter_map(|input| match input {
+                syn::FnArg::Typed(pat_type) => match &*pat_type.pat {
+                    syn::Pat::Ident(ident) => Some((ident.ident.to_string(), (*pat_type.ty).clone())),
+                    _ => None,
+                },
+                syn::FnArg::Receiver(_) => None,
+            })
+            .collect();
+        if params.len() != args.len() {
+            return Err(TranslationError::TypeError(format!(
+                "{}({}) has {} arguments, but {} takes {}",
+                self.spec_function, rendered, args.len(), self.func.sig.ident, params.len()
+            )));
+        }
+        let mut arg_values = Vec::new();
+        for arg in args {
+            let mut mentions_scoped = false;
+            arg.visit(&mut |e| {
+                if let MathExpr::Var(name) = e {
+                    mentions_scoped |= self.scoped.contains(name);
+                }
+            });
+            if mentions_scoped {
+                return Err(TranslationError::UnsupportedExpression(format!(
+                    "{}({}) under a nested quantifier", self.spec_function, rendered
+                )));
+            }
+            arg_values.push(self.compile(arg)?);
+        }
+
+        // The first application uses the Rust names; later ones get a suffix
+        let suffix = match self.applications.len() {
+            0 => String::new(),
+            n => format!("_{}", n + 1),
+        };
+        let mut body_vars = HashMap::new();
+        for ((name, ty), arg) in params.iter().zip(&arg_values) {
+            let param = self.fresh_var(name, &suffix, ty, &mut body_vars);
+            let binding = self.bind_math_value(&param, ty, arg)?;
+            self.assumptions.push(binding);
+            let type_name = IntType::from_type(ty).map(|t| t.rust_name());
+            self.model_vars.push((format!("{}{}", name, suffix), param, type_name));
+        }
+        let return_type = match &self.func.sig.output {
+            syn::ReturnType::Type(_, ty) => (**ty).clone(),
+            syn::ReturnType::Default => syn::parse_quote!(()),
+        };
+        let result = self.fresh_var("result", &suffix, &return_type, &mut body_vars);
+
+        for contract in self.requires {
+            let mut vars = body_vars.clone();
+            if let Some(condition) = self.translator.translate_expr_with_vars(&contract.condition, &mut vars)?.as_bool() {
+                self.assumptions.push(condition);
+            }
+        }
+        match self.translator.translate_function_body(self.func, &mut body_vars)? {
+            Some(formula) => self.assumptions.push(formula),
+            None => {
+                return Err(TranslationError::UnsupportedExpression(format!(
+                    "The body of {} could not be translated", self.func.sig.ident
+                )));
+            }
+        }
+
+        let value = self.math_value(&result, &return_type);
+        self.model_vars.push((format!("{}({})", self.spec_function, rendered), result, IntType::from_type(&return_type).map(|t| t.rust_name())));
+        self.applications.push((rendered, value.clone()));
+        Ok(value)
+    }
+
+    /// Declare `name` for the body translation, backed by the Z3 constant `name + suffix`
+    fn fresh_var(&self, name: &str, suffix: &str, ty: &syn::Type, body_vars: &mut HashMap<String, Dynamic<'a>>) -> Dynamic<'a> {
+        // Registers the Rust name's type, which the body translation looks up
+        let var = self.translator.declare_var(name, ty, body_vars);
+        if suffix.is_empty() {
+            return var;
+        }
+        let fresh = self.translator.declare_var(&format!("{}{}", name, suffix), ty, &mut HashMap::new());
+        body_vars.insert(name.to_string(), fresh.clone());
+        fresh
+    }
+
+    /// Constrain a parameter to equal a math value (and lie in its type's range)
+    fn bind_math_value(&self, param: &Dynamic<'a>, ty: &syn::Type, value: &Dynamic<'a>) -> Result<Bool<'a>, TranslationError> {
+        let param_value = self.math_value(param, ty);
+        let eq = param_value._safe_eq(value)
+            .map_err(|_| TranslationError::TypeError(format!("Argument doesn't match parameter type {}", quote::quote!(#ty))))?;
+        Ok(match (param.as_int(), IntType::from_type(ty)) {
+            (Some(int), Some(int_ty)) => Bool::and(self.translator.context(), &[&eq, &self.translator.int_in_range(&int, int_ty)]),
+            _ => eq,
+        })
+    }
+
+    /// A Rust value as a math value: bit-vectors become unbounded integers
+    fn math_value(&self, value: &Dynamic<'a>, ty: &syn::Type) -> Dynamic<'a> {
+        match value.as_bv() {
+            Some(bv) => {
+                let signed = IntType::from_type(ty).is_some_and(|t| t.signed);
+                Int::from_bv(&bv, signed).into()
+            }
+            None => value.clone(),
+        }
+    }
+}
+
+/// `e` if `expr` is `2^{-e}`
+fn negative_power_of_two(expr: &MathExpr) -> Option<&MathExpr> {
+    match expr {
+        MathExpr::Binary { op: BinaryOp::Pow, left, right } if **left == MathExpr::Number("2".to_string()) => match &**right {
+            MathExpr::Unary { op: UnaryOp::Neg, expr } => Some(expr),
+            _ => None,
+        },
+        _ => None,
+    }
+}
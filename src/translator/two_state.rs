@@ -0,0 +1,118 @@
+//! Two-state contracts for functions taking `&mut` parameters
+//!
+//! A mutable reference parameter `x` has two values: the one it points to on
+//! entry (its pre-state) and the one it points to on return (its
+//! post-state). In `requires` and at the start of the body, `x` (or `*x`) is
+//! the pre-state; assignments through the reference update it like a local,
+//! and every exit records that the post-state constant `x'` equals the value
+//! at that point (see [`Z3Translator::exit_state`]). In `ensures`, `x` is the
+//! post-state and `old(e)` evaluates `e` in the pre-state, so
+//! `*x == old(*x) + y` specifies `*x += y`.
+//!
+//! A call `f(&mut x, ..)` leaves `x` as `f`'s post-state function for that
+//! parameter applied to the call's arguments, which `f`'s `ensures` constrains
+//! (see `translator::calls`).
+
+use std::collections::HashMap;
+use syn::Expr;
+use z3::ast::{Ast, Bool, Dynamic};
+use z3::FuncDecl;
+use crate::translator::z3_translator::{IntType, TranslationError, Z3Translator};
+
+/// Key of a `&mut` parameter's post-state constant in a body's variables
+pub(crate) fn post_state_key(name: &str) -> String {
+    format!("{}'", name)
+}
+
+/// Key of a `&mut` parameter's pre-state in an `ensures`' variables
+pub(crate) fn pre_state_key(name: &str) -> String {
+    format!("old({})", name)
+}
+
+/// Check if a parameter type is a mutable reference (`&mut T`)
+pub fn is_mut_reference(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Reference(reference) if reference.mutability.is_some())
+}
+
+/// The argument of `old(e)`, if `call` is one
+pub(crate) fn old_argument(call: &syn::ExprCall) -> Option<&Expr> {
+    match &*call.func {
+        Expr::Path(path) if path.path.is_ident("old") && call.args.len() == 1 => call.args.first(),
+        _ => None,
+    }
+}
+
+impl Z3Translator {
+    /// The post-state constant of the `&mut` parameter `name`, with the sort of its pre-state
+    fn post_state_const<'a>(&'a self, name: &str, pre: &Dynamic<'a>) -> Dynamic<'a> {
+        FuncDecl::new(self.context(), post_state_key(name), &[], &pre.get_sort()).apply(&[])
+    }
+
+    /// Add the post-state constants of the `&mut` parameters to a body's variables
+    ///
+    /// The parameters must already be declared in `vars`.
+    pub fn declare_post_states<'a>(&'a self, param_types: &HashMap<String, syn::Type>, vars: &mut HashMap<String, Dynamic<'a>>) {
+        for (name, ty) in param_types {
+            if let (true, Some(pre)) = (is_mut_reference(ty), vars.get(name)) {
+                let post = self.post_state_const(name, pre);
+                vars.insert(post_state_key(name), post);
+            }
+        }
+    }
+
+    /// Make the `&mut` parameters in `vars` stand for their post-states, as in an `ensures`
+    ///
+    /// The pre-states stay reachable through `old(..)`. Returns the post-states
+    /// with their parameter types.
+    pub fn enter_post_state<'a>(&'a self, param_types: &HashMap<String, syn::Type>, vars: &mut HashMap<String, Dynamic<'a>>) -> Vec<(Dynamic<'a>, syn::Type)> {
+        let mut post_states = Vec::new();
+        for (name, ty) in param_types {
+            if let (true, Some(pre)) = (is_mut_reference(ty), vars.get(name).cloned()) {
+                let post = self.post_state_const(name, &pre);
+                vars.insert(pre_state_key(name), pre);
+                vars.insert(name.clone(), post.clone());
+                post_states.push((post, ty.clone()));
+            }
+        }
+        post_states
+    }
+
+    /// `x' == x` for every `&mut` parameter `x` with a post-state in `vars`; `None` if there are none
+    pub(crate) fn exit_state<'a>(&'a self, vars: &HashMap<String, Dynamic<'a>>) -> Option<Bool<'a>> {
+        let mut names: Vec<&str> = vars.keys()
+            .filter_map(|key| key.strip_suffix('\''))
+            .collect();
+        names.sort_unstable();
+        let equalities: Vec<Bool<'a>> = names.into_iter()
+            .filter_map(|name| vars[&post_state_key(name)]._safe_eq(vars.get(name)?).ok())
+            .collect();
+        match equalities.len() {
+            0 => None,
+            _ => {
+                let refs: Vec<&Bool<'a>> = equalities.iter().collect();
+                Some(Bool::and(self.context(), &refs))
+            }
+        }
+    }
+
+    /// What an exit returns, together with the state of the `&mut` parameters when it's taken
+    pub(crate) fn with_exit_state<'a>(&'a self, returns: Option<Bool<'a>>, vars: &HashMap<String, Dynamic<'a>>) -> Option<Bool<'a>> {
+        match (returns, self.exit_state(vars)) {
+            (Some(returns), Some(state)) => Some(Bool::and(self.context(), &[&returns, &state])),
+            (returns, state) => returns.or(state),
+        }
+    }
+
+    /// Translate the argument of `old(e)` with the `&mut` parameters at their pre-states
+    ///
+    /// Outside an `ensures` there is no separate pre-state and `old(e)` is `e`.
+    pub(crate) fn translate_old<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>, expected: Option<IntType>) -> Result<Dynamic<'a>, TranslationError> {
+        let mut pre_vars = vars.clone();
+        for (key, value) in vars.iter() {
+            if let Some(name) = key.strip_prefix("old(").and_then(|key| key.strip_suffix(')')) {
+                pre_vars.insert(name.to_string(), value.clone());
+            }
+        }
+        self.translate_typed(expr, &mut pre_vars, expected)
+    }
+}
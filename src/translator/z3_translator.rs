@@ -16,20 +16,152 @@
 
 #[cfg(feature = "z3")]
 use z3::{Config, Context, Sort};
-use z3::ast::{Ast, Int, Bool};
-use syn::{Expr, Stmt, Block, ItemFn};
-use crate::parser::contracts::Contract;
+use z3::ast::{Ast, Int, Bool, BV, Datatype, Dynamic};
+use syn::{Expr, Block, ItemFn};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+use crate::parser::contracts::{Contract, ContractType};
+#[cfg(feature = "z3")]
+use crate::translator::calls::FunctionSummary;
+#[cfg(feature = "z3")]
+use crate::translator::datatypes::ValueType;
+#[cfg(feature = "z3")]
+use crate::translator::loops::place_root;
+#[cfg(feature = "z3")]
+use crate::translator::two_state::old_argument;
+#[cfg(feature = "z3")]
+use crate::translator::quantifiers::quantified_closure;
+
+/// Shift amounts covered by the `2^k` table when the shifted value's type is unknown
+#[cfg(feature = "z3")]
+const POW2_TABLE: u32 = 128;
+
+/// Arithmetic semantics used when translating integer expressions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArithmeticMode {
+    /// Unbounded mathematical integers (`z3::ast::Int`)
+    #[default]
+    Integer,
+    /// Fixed-width bit-vectors sized from each value's declared Rust type.
+    /// Wrapping, shifts, and signed/unsigned operators follow Rust semantics.
+    BitPrecise,
+}
+
+/// A fixed-width Rust integer type (width and signedness)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IntType {
+    /// Width in bits
+    pub bits: u32,
+    /// Whether the type is signed (two's complement)
+    pub signed: bool,
+}
+
+impl IntType {
+    pub const U64: IntType = IntType { bits: 64, signed: false };
+
+    /// Resolve an integer type from its Rust name (`usize`/`isize` are assumed 64-bit)
+    pub fn from_name(name: &str) -> Option<Self> {
+        let (bits, signed) = match name {
+            "u8" => (8, false),
+            "u16" => (16, false),
+            "u32" => (32, false),
+            "u64" | "usize" => (64, false),
+            "u128" => (128, false),
+            "i8" => (8, true),
+            "i16" => (16, true),
+            "i32" => (32, true),
+            "i64" | "isize" => (64, true),
+            "i128" => (128, true),
+            // Type aliases used in Bitcoin consensus code (blvm-consensus/src/types.rs)
+            "Natural" => (64, false),
+            "Integer" => (64, true),
+            _ => return None,
+        };
+        Some(IntType { bits, signed })
+    }
+
+    /// Resolve an integer type from a Rust type, looking through references
+    pub fn from_type(ty: &syn::Type) -> Option<Self> {
+        match ty {
+            syn::Type::Path(type_path) => {
+                let segment = type_path.path.segments.last()?;
+                if !segment.arguments.is_empty() {
+                    return None;
+                }
+                Self::from_name(&segment.ident.to_string())
+            }
+            syn::Type::Reference(reference) => Self::from_type(&reference.elem),
+            syn::Type::Paren(paren) => Self::from_type(&paren.elem),
+            syn::Type::Group(group) => Self::from_type(&group.elem),
+            _ => None,
+        }
+    }
+
+    /// Smallest representable value
+    pub fn min_value(&self) -> i128 {
+        if self.signed {
+            -(1i128 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    /// Largest representable value
+    pub fn max_value(&self) -> u128 {
+        if self.signed {
+            (1u128 << (self.bits - 1)) - 1
+        } else if self.bits == 128 {
+            u128::MAX
+        } else {
+            (1u128 << self.bits) - 1
+        }
+    }
+
+    /// Name of the primitive type, e.g. `u64`
+    pub fn rust_name(&self) -> String {
+        format!("{}{}", if self.signed { 'i' } else { 'u' }, self.bits)
+    }
+
+    /// Check whether every value of `other` is representable in this type
+    pub fn contains(&self, other: &IntType) -> bool {
+        self.min_value() <= other.min_value() && self.max_value() >= other.max_value()
+    }
+}
 
 #[cfg(feature = "z3")]
 /// Z3 translator for Rust expressions
 pub struct Z3Translator {
     ctx: Context,
+    mode: ArithmeticMode,
+    /// Declared integer types of translated variables (for bit widths and signedness)
+    var_types: RefCell<HashMap<String, IntType>>,
+    /// Types of translated variables holding datatype values (for `x.unwrap()`)
+    value_types: RefCell<HashMap<String, ValueType>>,
+    /// Contracts of callees, by function name (see `translator::calls`)
+    summaries: HashMap<String, FunctionSummary>,
+    /// Functions called by the expressions translated so far
+    called: RefCell<BTreeSet<String>>,
+    /// Enums modelled as datatypes, by name (see `translator::datatypes`)
+    enums: HashMap<String, syn::ItemEnum>,
+    /// Structs modelled as datatypes, by name
+    structs: HashMap<String, syn::ItemStruct>,
+    /// Named constants (e.g. `MAX_MONEY`) with their types and values
+    constants: HashMap<String, (IntType, i128)>,
+    /// Shapes of the datatype sorts declared so far, by sort name
+    datatypes: RefCell<HashMap<String, ValueType>>,
+    /// Number of recursive `pow2` functions declared (see `pow2`)
+    pow2_decls: Cell<usize>,
 }
 
 #[cfg(feature = "z3")]
 impl Z3Translator {
-    /// Create a new Z3 translator
+    /// Create a new Z3 translator using unbounded integer semantics
     pub fn new() -> Self {
+        Self::with_mode(ArithmeticMode::Integer)
+    }
+
+    /// Create a new Z3 translator with the given arithmetic semantics
+    pub fn with_mode(mode: ArithmeticMode) -> Self {
         let mut cfg = Config::new();
         cfg.set_proof_generation(true);
         cfg.set_model_generation(true);
@@ -37,64 +169,375 @@ impl Z3Translator {
         
         Z3Translator {
             ctx,
+            mode,
+            var_types: RefCell::new(HashMap::new()),
+            value_types: RefCell::new(HashMap::new()),
+            summaries: HashMap::new(),
+            called: RefCell::new(BTreeSet::new()),
+            enums: HashMap::new(),
+            structs: HashMap::new(),
+            constants: HashMap::new(),
+            datatypes: RefCell::new(HashMap::new()),
+            pow2_decls: Cell::new(0),
+        }
+    }
+
+    /// Model calls to `summary.name` by the callee's contracts
+    pub fn add_summary(&mut self, summary: FunctionSummary) {
+        self.summaries.insert(summary.name.clone(), summary);
+    }
+
+    /// Get the contract summary of a callee, if registered
+    pub fn summary(&self, name: &str) -> Option<&FunctionSummary> {
+        self.summaries.get(name)
+    }
+
+    /// Model values of an enum as a datatype with one constructor per variant
+    ///
+    /// Generic enums aren't modelled; their values stay unknown integers.
+    pub fn add_enum(&mut self, item: syn::ItemEnum) {
+        if item.generics.params.is_empty() {
+            self.enums.insert(item.ident.to_string(), item);
+        }
+    }
+
+    /// Get the definition of a registered enum
+    pub(crate) fn enum_definition(&self, name: &str) -> Option<&syn::ItemEnum> {
+        self.enums.get(name)
+    }
+
+    /// Model values of a struct as a datatype with one field per struct field
+    ///
+    /// Generic structs aren't modelled; their values stay unknown integers.
+    pub fn add_struct(&mut self, item: syn::ItemStruct) {
+        if item.generics.params.is_empty() {
+            self.structs.insert(item.ident.to_string(), item);
         }
     }
 
+    /// Get the definition of a registered struct
+    pub(crate) fn struct_definition(&self, name: &str) -> Option<&syn::ItemStruct> {
+        self.structs.get(name)
+    }
+
+    /// Translate references to a named constant to its value
+    pub fn add_constant(&mut self, name: &str, ty: IntType, value: i128) {
+        self.constants.insert(name.to_string(), (ty, value));
+    }
+
+    /// Get the type and value of a named constant
+    pub(crate) fn constant(&self, name: &str) -> Option<(IntType, i128)> {
+        self.constants.get(name).copied()
+    }
+
+    /// The constant a path names, e.g. `MAX_MONEY` or `constants::MAX_MONEY`
+    fn path_constant(&self, path: &syn::Path) -> Option<(IntType, i128)> {
+        self.constant(&path.segments.last()?.ident.to_string())
+    }
+
+    /// Remember the shape of a declared datatype sort
+    pub(crate) fn remember_datatype(&self, sort: &Sort<'_>, ty: &ValueType) {
+        self.datatypes.borrow_mut().insert(sort.to_string(), ty.clone());
+    }
+
+    /// The shape of a declared datatype sort
+    pub(crate) fn datatype_type(&self, sort: &Sort<'_>) -> Option<ValueType> {
+        self.datatypes.borrow().get(&sort.to_string()).cloned()
+    }
+
+    /// Record that a translated expression calls `name`
+    pub(crate) fn record_call(&self, name: &str) {
+        self.called.borrow_mut().insert(name.to_string());
+    }
+
+    /// Functions called by the expressions translated so far
+    pub fn called_functions(&self) -> Vec<String> {
+        self.called.borrow().iter().cloned().collect()
+    }
+
+    /// Set the declared type of a variable, returning the previous one
+    pub(crate) fn set_var_type(&self, name: &str, ty: Option<IntType>) -> Option<IntType> {
+        let mut var_types = self.var_types.borrow_mut();
+        match ty {
+            Some(ty) => var_types.insert(name.to_string(), ty),
+            None => var_types.remove(name),
+        }
+    }
+
+    /// Get the type of a variable holding a datatype value, if known
+    pub(crate) fn var_value_type(&self, name: &str) -> Option<ValueType> {
+        self.value_types.borrow().get(name).cloned()
+    }
+
+    /// Remember the type of a variable's value if it is a datatype
+    pub(crate) fn remember_value_type(&self, name: &str, value: &Dynamic<'_>) {
+        match self.datatype_type(&value.get_sort()) {
+            Some(ty) => self.value_types.borrow_mut().insert(name.to_string(), ty),
+            None => self.value_types.borrow_mut().remove(name),
+        };
+    }
+
+    /// Set (or clear) the datatype type of a variable, as returned by [`Self::var_value_type`]
+    pub(crate) fn set_value_type(&self, name: &str, ty: Option<ValueType>) {
+        match ty {
+            Some(ty) => self.value_types.borrow_mut().insert(name.to_string(), ty),
+            None => self.value_types.borrow_mut().remove(name),
+        };
+    }
+
     /// Get the Z3 context
     pub fn context(&self) -> &Context {
         &self.ctx
     }
 
+    /// Get the arithmetic semantics used by this translator
+    pub fn mode(&self) -> ArithmeticMode {
+        self.mode
+    }
+
+    /// Get the declared integer type of a variable, if known
+    pub fn var_type(&self, name: &str) -> Option<IntType> {
+        self.var_types.borrow().get(name).copied()
+    }
+
+    /// Declare a variable of the given Rust type and add it to the variable map
+    ///
+    /// `bool` becomes a Z3 Bool, integer types become an Int (or a bit-vector of the
+    /// type's width in bit-precise mode), `Option`, `Result`, `()`, registered
+    /// enums and sequences become datatypes, and anything else falls back to an Int.
+    pub fn declare_var<'a>(&'a self, name: &str, ty: &syn::Type, vars: &mut HashMap<String, Dynamic<'a>>) -> Dynamic<'a> {
+        let value_ty = self.value_type(ty);
+        if let Some(int_ty) = value_ty.int_type() {
+            self.var_types.borrow_mut().insert(name.to_string(), int_ty);
+        }
+        let var = self.value_const(name, &value_ty);
+        self.remember_value_type(name, &var);
+        vars.insert(name.to_string(), var.clone());
+        var
+    }
+
+    /// Create an integer-valued constant with the sort for the current mode
+    pub(crate) fn int_const(&self, name: &str, ty: Option<IntType>) -> Dynamic<'_> {
+        match self.mode {
+            ArithmeticMode::Integer => Int::new_const(&self.ctx, name).into(),
+            ArithmeticMode::BitPrecise => {
+                let bits = ty.map_or(64, |t| t.bits);
+                BV::new_const(&self.ctx, name, bits).into()
+            }
+        }
+    }
+
+    /// A fresh constant with the same sort as `value`, named after `name`
+    ///
+    /// Stands for an unknown new value of a variable, e.g. one assigned in a loop.
+    pub(crate) fn fresh_value<'a>(&'a self, name: &str, value: &Dynamic<'a>) -> Dynamic<'a> {
+        if let Some(bv) = value.as_bv() {
+            BV::fresh_const(&self.ctx, name, bv.get_size()).into()
+        } else if value.as_bool().is_some() {
+            Bool::fresh_const(&self.ctx, name).into()
+        } else if value.as_int().is_some() {
+            Int::fresh_const(&self.ctx, name).into()
+        } else {
+            Datatype::fresh_const(&self.ctx, name, &value.get_sort()).into()
+        }
+    }
+
+    /// Create a possibly negative integer literal with the sort for the current mode
+    pub(crate) fn signed_literal(&self, value: i128, ty: Option<IntType>) -> Result<Dynamic<'_>, TranslationError> {
+        let magnitude = self.int_literal(value.unsigned_abs(), ty)?;
+        if value < 0 {
+            return self.translate_unary_op(syn::UnOp::Neg(Default::default()), magnitude);
+        }
+        Ok(magnitude)
+    }
+
+    /// Create an integer literal with the sort for the current mode
+    pub(crate) fn int_literal(&self, value: u128, ty: Option<IntType>) -> Result<Dynamic<'_>, TranslationError> {
+        let digits = value.to_string();
+        let literal: Option<Dynamic<'_>> = match self.mode {
+            ArithmeticMode::Integer => Int::from_str(&self.ctx, &digits).map(Into::into),
+            ArithmeticMode::BitPrecise => {
+                let bits = ty.map_or(64, |t| t.bits);
+                BV::from_str(&self.ctx, bits, &digits).map(Into::into)
+            }
+        };
+        literal.ok_or_else(|| TranslationError::ParseError(format!("Invalid integer literal: {}", digits)))
+    }
+
     /// Translate a Rust expression to a Z3 expression
     /// 
     /// Uses a variable map to ensure same variable name = same Z3 variable within one expression
-    pub fn translate_expr_with_vars<'a>(&'a self, expr: &Expr, vars: &mut std::collections::HashMap<String, z3::ast::Int<'a>>) -> Result<z3::ast::Dynamic<'a>, TranslationError> {
+    pub fn translate_expr_with_vars<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        self.translate_typed(expr, vars, None)
+    }
+
+    /// Translate an expression whose integer type is expected to be `expected`
+    ///
+    /// The expected type sizes untyped literals and free variables in bit-precise mode.
+    pub(crate) fn translate_typed<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>, expected: Option<IntType>) -> Result<Dynamic<'a>, TranslationError> {
         match expr {
-            Expr::Lit(lit) => self.translate_literal(&lit.lit),
+            Expr::Lit(lit) => self.translate_literal(&lit.lit, expected),
             Expr::Path(path) => {
-                let name = path_to_string(&path.path);
-                
-                // Check if this is a known constant
-                if let Some(constant_value) = resolve_constant(&name) {
-                    return Ok(Int::from_i64(&self.ctx, constant_value).into());
+                // `None` or an enum variant
+                if let Some(value) = self.translate_constructor(expr, vars, None) {
+                    return value;
                 }
+                let name = path_to_string(&path.path);
                 
                 // Get or create variable
-                let var = vars.entry(name.clone()).or_insert_with(|| {
-                    let symbol = z3::Symbol::String(name);
-                    Int::new_const(&self.ctx, symbol)
-                });
-                Ok(var.clone().into())
+                if let Some(var) = vars.get(&name) {
+                    return Ok(var.clone());
+                }
+                if let Some((ty, value)) = self.path_constant(&path.path) {
+                    return self.signed_literal(value, expected.or(Some(ty)));
+                }
+                if let (ArithmeticMode::BitPrecise, Some(ty)) = (self.mode, expected) {
+                    self.var_types.borrow_mut().insert(name.clone(), ty);
+                }
+                let var = self.int_const(&name, expected);
+                vars.insert(name, var.clone());
+                Ok(var)
             }
-            Expr::Binary(bin) => {
-                let left = self.translate_expr_with_vars(&bin.left, vars)?;
-                let right = self.translate_expr_with_vars(&bin.right, vars)?;
-                self.translate_binary_op(bin.op, left, right)
+            Expr::Binary(bin) => self.translate_binary(bin, vars, expected),
+            Expr::MethodCall(method) => self.translate_method_call(method, vars),
+            Expr::Call(call) => {
+                if let Some(arg) = old_argument(call) {
+                    return self.translate_old(arg, vars, expected);
+                }
+                if let Some((quantifier, closure)) = quantified_closure(call) {
+                    return self.translate_quantified(quantifier, closure, vars);
+                }
+                match self.translate_constructor(expr, vars, None) {
+                    Some(value) => value,
+                    None => match self.translate_seq_literal(expr, vars, None) {
+                        Some(value) => value,
+                        None => self.translate_call(call, vars, expected),
+                    },
+                }
             }
-            Expr::MethodCall(method) => self.translate_method_call(method),
-            Expr::Call(call) => self.translate_call(call),
+            Expr::Array(_) | Expr::Repeat(_) => self.translate_seq_literal(expr, vars, None)
+                .unwrap_or_else(|| Err(TranslationError::UnsupportedExpression(format!("{:?}", expr)))),
+            Expr::Index(index) => self.translate_index(index, vars),
+            Expr::Field(field) => self.translate_field(field, vars),
+            Expr::Struct(_) | Expr::Tuple(_) => self.translate_constructor(expr, vars, None)
+                .unwrap_or_else(|| Err(TranslationError::UnsupportedExpression(format!("{:?}", expr)))),
+            Expr::Try(try_expr) => self.translate_try_value(try_expr, vars),
+            Expr::If(if_expr) => self.translate_if_value(if_expr, vars, expected.map(|ty| ValueType::Int(Some(ty))).as_ref()),
+            Expr::Match(match_expr) => self.translate_match_value(match_expr, vars, expected.map(|ty| ValueType::Int(Some(ty))).as_ref()),
+            Expr::Block(block) => self.translate_block_value(&block.block, vars, expected.map(|ty| ValueType::Int(Some(ty))).as_ref()),
             Expr::Unary(unary) => {
-                let expr = self.translate_expr_with_vars(&unary.expr, vars)?;
+                let expr = self.translate_typed(&unary.expr, vars, expected)?;
                 self.translate_unary_op(unary.op, expr)
             }
-            Expr::Paren(paren) => self.translate_expr_with_vars(&paren.expr, vars),
+            Expr::Paren(paren) => self.translate_typed(&paren.expr, vars, expected),
+            // References are modelled as the value they point to
+            Expr::Reference(reference) => self.translate_typed(&reference.expr, vars, expected),
+            Expr::Cast(cast) => self.translate_cast(cast, vars),
+            Expr::Macro(mac) if mac.mac.path.is_ident("matches") => self.translate_matches(&mac.mac, vars),
+            Expr::Macro(mac) if mac.mac.path.is_ident("vec") => self.translate_seq_literal(expr, vars, None)
+                .unwrap_or_else(|| Err(TranslationError::UnsupportedExpression(format!("{:?}", expr)))),
             _ => Err(TranslationError::UnsupportedExpression(format!("{:?}", expr))),
         }
     }
     
     /// Translate a Rust expression to a Z3 expression (public API)
-    pub fn translate_expr(&self, expr: &Expr) -> Result<z3::ast::Dynamic<'_>, TranslationError> {
-        let mut vars: std::collections::HashMap<String, z3::ast::Int<'_>> = std::collections::HashMap::new();
+    pub fn translate_expr(&self, expr: &Expr) -> Result<Dynamic<'_>, TranslationError> {
+        let mut vars: HashMap<String, Dynamic<'_>> = HashMap::new();
         self.translate_expr_with_vars(expr, &mut vars)
     }
 
+    /// Infer the integer type of an expression from declared variables, suffixes, and casts
+    pub(crate) fn infer_int_type(&self, expr: &Expr) -> Option<IntType> {
+        match expr {
+            Expr::Lit(lit) => match &lit.lit {
+                syn::Lit::Int(int_lit) => IntType::from_name(int_lit.suffix()),
+                _ => None,
+            },
+            Expr::Path(path) => self.var_type(&path_to_string(&path.path))
+                .or_else(|| Some(self.path_constant(&path.path)?.0)),
+            Expr::Cast(cast) => IntType::from_type(&cast.ty),
+            Expr::Call(call) => match old_argument(call) {
+                Some(arg) => self.infer_int_type(arg),
+                None => self.call_return_type(call),
+            },
+            Expr::MethodCall(method) => self.method_value_type(method)?.int_type(),
+            Expr::Index(index) => self.seq_elem_type(&index.expr)?.int_type(),
+            Expr::Field(field) => self.field_value_type(field)?.int_type(),
+            Expr::Try(try_expr) => match &*try_expr.expr {
+                Expr::Call(call) => match self.call_value_type(call)? {
+                    ValueType::Result(ok, _) | ValueType::Option(ok) => ok.int_type(),
+                    _ => None,
+                },
+                _ => None,
+            },
+            Expr::Paren(paren) => self.infer_int_type(&paren.expr),
+            Expr::Group(group) => self.infer_int_type(&group.expr),
+            Expr::Reference(reference) => self.infer_int_type(&reference.expr),
+            Expr::Unary(unary) => self.infer_int_type(&unary.expr),
+            Expr::Binary(bin) => match bin.op {
+                syn::BinOp::Add(_) | syn::BinOp::Sub(_) | syn::BinOp::Mul(_) |
+                syn::BinOp::Div(_) | syn::BinOp::Rem(_) | syn::BinOp::BitAnd(_) |
+                syn::BinOp::BitOr(_) | syn::BinOp::BitXor(_) => {
+                    self.infer_int_type(&bin.left).or_else(|| self.infer_int_type(&bin.right))
+                }
+                syn::BinOp::Shl(_) | syn::BinOp::Shr(_) => self.infer_int_type(&bin.left),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Translate a binary expression, sizing both operands to a common integer type
+    fn translate_binary<'a>(&'a self, bin: &syn::ExprBinary, vars: &mut HashMap<String, Dynamic<'a>>, expected: Option<IntType>) -> Result<Dynamic<'a>, TranslationError> {
+        let (left, right, operand_ty) = match bin.op {
+            syn::BinOp::Shl(_) | syn::BinOp::Shr(_) => {
+                // The shift amount has its own type, independent of the shifted value
+                let operand_ty = self.infer_int_type(&bin.left).or(expected);
+                let left = self.translate_typed(&bin.left, vars, operand_ty)?;
+                let right_ty = self.infer_int_type(&bin.right);
+                let right = self.translate_typed(&bin.right, vars, right_ty)?;
+                (left, right, operand_ty)
+            }
+            syn::BinOp::And(_) | syn::BinOp::Or(_) => {
+                let left = self.translate_typed(&bin.left, vars, None)?;
+                let right = self.translate_typed(&bin.right, vars, None)?;
+                (left, right, None)
+            }
+            syn::BinOp::Eq(_) | syn::BinOp::Ne(_) if self.infer_int_type(&bin.left).is_none() && self.infer_int_type(&bin.right).is_none() => {
+                // `result == None`: a constructor takes its type from the other side
+                let (left, right) = match self.translate_typed(&bin.left, vars, None) {
+                    Ok(left) => {
+                        let right = self.translate_hinted(&bin.right, vars, self.type_of_value(&left).as_ref())?;
+                        (left, right)
+                    }
+                    Err(_) => {
+                        let right = self.translate_typed(&bin.right, vars, None)?;
+                        let left = self.translate_hinted(&bin.left, vars, self.type_of_value(&right).as_ref())?;
+                        (left, right)
+                    }
+                };
+                (left, right, None)
+            }
+            _ => {
+                let operand_ty = self.infer_int_type(&bin.left)
+                    .or_else(|| self.infer_int_type(&bin.right))
+                    .or(if is_comparison(bin.op) { None } else { expected });
+                let left = self.translate_typed(&bin.left, vars, operand_ty)?;
+                let right = self.translate_typed(&bin.right, vars, operand_ty)?;
+                (left, right, operand_ty)
+            }
+        };
+        self.translate_binary_op(bin.op, left, right, operand_ty)
+    }
+
     /// Translate a literal (integer, boolean, etc.)
-    fn translate_literal(&self, lit: &syn::Lit) -> Result<z3::ast::Dynamic<'_>, TranslationError> {
+    fn translate_literal(&self, lit: &syn::Lit, expected: Option<IntType>) -> Result<Dynamic<'_>, TranslationError> {
         match lit {
             syn::Lit::Int(int_lit) => {
-                let value = int_lit.base10_parse::<i64>()
+                let value = int_lit.base10_parse::<u128>()
                     .map_err(|e| TranslationError::ParseError(e.to_string()))?;
-                Ok(Int::from_i64(&self.ctx, value).into())
+                let ty = IntType::from_name(int_lit.suffix()).or(expected);
+                self.int_literal(value, ty)
             }
             syn::Lit::Bool(bool_lit) => {
                 Ok(Bool::from_bool(&self.ctx, bool_lit.value).into())
@@ -103,8 +546,28 @@ impl Z3Translator {
         }
     }
 
+    /// Translate an `as` cast between integer types
+    ///
+    /// In bit-precise mode the value is sign/zero-extended or truncated to the target
+    /// width; in integer mode casts are value-preserving.
+    fn translate_cast<'a>(&'a self, cast: &syn::ExprCast, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        let source_ty = self.infer_int_type(&cast.expr);
+        let value = self.translate_typed(&cast.expr, vars, source_ty)?;
+        match (value.as_bv(), IntType::from_type(&cast.ty)) {
+            (Some(bv), Some(target_ty)) => {
+                let signed = source_ty.is_some_and(|t| t.signed);
+                Ok(resize_bv(&bv, target_ty.bits, signed).into())
+            }
+            _ => Ok(value),
+        }
+    }
+
     /// Translate a binary operation given already-translated operands
-    fn translate_binary_op<'a>(&'a self, op: syn::BinOp, left: z3::ast::Dynamic<'a>, right: z3::ast::Dynamic<'a>) -> Result<z3::ast::Dynamic<'a>, TranslationError> {
+    pub(crate) fn translate_binary_op<'a>(&'a self, op: syn::BinOp, left: Dynamic<'a>, right: Dynamic<'a>, operand_ty: Option<IntType>) -> Result<Dynamic<'a>, TranslationError> {
+        if let (Some(left_bv), Some(right_bv)) = (left.as_bv(), right.as_bv()) {
+            let signed = operand_ty.is_some_and(|t| t.signed);
+            return self.translate_bv_binary_op(op, left_bv, right_bv, signed);
+        }
         match op {
             syn::BinOp::Add(_) => {
                 let left_int = left.as_int().ok_or_else(|| TranslationError::TypeError("Expected Int".to_string()))?;
@@ -128,44 +591,25 @@ impl Z3Translator {
                 Ok(left_int.div(&right_int).into())
             }
             syn::BinOp::Shr(_) => {
-                // Right shift: a >> b is equivalent to a / 2^b for non-negative values
-                // For simplicity with Z3, we model this using uninterpreted function
-                // or approximate for common cases
                 let left_int = left.as_int().ok_or_else(|| TranslationError::TypeError("Expected Int".to_string()))?;
                 let right_int = right.as_int().ok_or_else(|| TranslationError::TypeError("Expected Int".to_string()))?;
-                
-                // Create an uninterpreted shift function
-                // This allows Z3 to reason about shift operations abstractly
-                let shift_fn = z3::FuncDecl::new(
-                    &self.ctx,
-                    "shr",
-                    &[&Sort::int(&self.ctx), &Sort::int(&self.ctx)],
-                    &Sort::int(&self.ctx),
-                );
-                Ok(shift_fn.apply(&[&left_int, &right_int]).into())
+                Ok(self.int_shr(&left_int, &right_int, operand_ty).into())
             }
             syn::BinOp::Shl(_) => {
-                // Left shift: a << b is equivalent to a * 2^b
                 let left_int = left.as_int().ok_or_else(|| TranslationError::TypeError("Expected Int".to_string()))?;
                 let right_int = right.as_int().ok_or_else(|| TranslationError::TypeError("Expected Int".to_string()))?;
-                
-                let shift_fn = z3::FuncDecl::new(
-                    &self.ctx,
-                    "shl",
-                    &[&Sort::int(&self.ctx), &Sort::int(&self.ctx)],
-                    &Sort::int(&self.ctx),
-                );
-                Ok(shift_fn.apply(&[&left_int, &right_int]).into())
-            }
-            syn::BinOp::Eq(_) => {
-                // Equality comparison
-                let eq = left._eq(&right);
-                Ok(eq.into())
-            }
-            syn::BinOp::Ne(_) => {
-                // Inequality
-                let eq = left._eq(&right);
-                Ok(eq.not().into())
+                Ok(self.int_shl(&left_int, &right_int, operand_ty).into())
+            }
+            syn::BinOp::Eq(_) | syn::BinOp::Ne(_) => {
+                let eq = match (self.seq_parts(&left), self.seq_parts(&right)) {
+                    // Sequences are equal when their elements below the length are
+                    (Some(left), Some(right)) => self.seq_eq(&left, &right)?,
+                    _ => safe_eq(&left, &right)?,
+                };
+                match op {
+                    syn::BinOp::Eq(_) => Ok(eq.into()),
+                    _ => Ok(eq.not().into()),
+                }
             }
             syn::BinOp::Lt(_) => {
                 let left_int = left.as_int().ok_or_else(|| TranslationError::TypeError("Expected Int".to_string()))?;
@@ -201,34 +645,172 @@ impl Z3Translator {
         }
     }
 
-    /// Translate a method call (e.g., vec.len(), opt.is_some())
-    fn translate_method_call(&self, method: &syn::ExprMethodCall) -> Result<z3::ast::Dynamic<'_>, TranslationError> {
-        let method_name = method.method.to_string();
-        
-        match method_name.as_str() {
-            "len" => {
-                // vec.len() - for now, treat as integer variable
-                // In full implementation, would track array/vector types
-                let receiver = self.translate_expr(&method.receiver)?;
-                // Return the length as an integer (simplified)
-                Ok(receiver)
+    /// Integer-mode `value >> amount`: floor division by `2^amount`
+    ///
+    /// For a value of type `ty` the amount is taken modulo the width, as Rust
+    /// does when overflow checks are off (with them on, an out-of-range amount
+    /// is an overflow obligation).
+    pub(crate) fn int_shr<'a>(&'a self, value: &Int<'a>, amount: &Int<'a>, ty: Option<IntType>) -> Int<'a> {
+        self.int_shift(value, amount, ty, |value, power| value.div(power), |value, pow2| {
+            // Beyond the table, values below 2^128 are shifted out entirely
+            let zero = Int::from_i64(&self.ctx, 0);
+            let bound = self.power_of_two(POW2_TABLE);
+            let small = Bool::and(&self.ctx, &[&value.lt(&bound), &value.ge(&bound.unary_minus())]);
+            small.ite(&value.lt(&zero).ite(&Int::from_i64(&self.ctx, -1), &zero), &value.div(pow2))
+        })
+    }
+
+    /// Integer-mode `value << amount`: multiplication by `2^amount`, wrapping to `ty`
+    ///
+    /// Bits shifted out are lost without a panic, so for a typed value the
+    /// product wraps; the amount is taken modulo the width as for `int_shr`.
+    pub(crate) fn int_shl<'a>(&'a self, value: &Int<'a>, amount: &Int<'a>, ty: Option<IntType>) -> Int<'a> {
+        let product = |value: &Int<'a>, power: &Int<'a>| match ty {
+            Some(ty) => self.wrap_int(&Int::mul(&self.ctx, &[value, power]), ty),
+            None => Int::mul(&self.ctx, &[value, power]),
+        };
+        self.int_shift(value, amount, ty, product, |value, pow2| Int::mul(&self.ctx, &[value, pow2]))
+    }
+
+    /// Shift by a constant amount directly, and by a symbolic one through a
+    /// table of `2^k` cases
+    ///
+    /// Typed amounts are reduced modulo the width, so the table is exhaustive.
+    /// Untyped amounts (e.g. from Orange Paper theorems) beyond the table use
+    /// `beyond` with the recursively defined `pow2(amount)`.
+    fn int_shift<'a>(
+        &'a self,
+        value: &Int<'a>,
+        amount: &Int<'a>,
+        ty: Option<IntType>,
+        shift: impl Fn(&Int<'a>, &Int<'a>) -> Int<'a>,
+        beyond: impl Fn(&Int<'a>, &Int<'a>) -> Int<'a>,
+    ) -> Int<'a> {
+        let (amount, cases) = match ty {
+            Some(ty) => (amount.modulo(&Int::from_u64(&self.ctx, u64::from(ty.bits))), ty.bits),
+            None => (amount.clone(), POW2_TABLE),
+        };
+        let amount = amount.simplify();
+        if let Some(k) = amount.as_i64().and_then(|k| u32::try_from(k).ok()).filter(|k| *k < cases) {
+            return shift(value, &self.power_of_two(k));
+        }
+        let mut shifted = match ty {
+            // Unreachable: the amount is below the width
+            Some(_) => shift(value, &self.power_of_two(cases - 1)),
+            None => beyond(value, &self.pow2(&amount)),
+        };
+        for k in (0..cases).rev() {
+            let is_k = amount._eq(&Int::from_u64(&self.ctx, u64::from(k)));
+            shifted = is_k.ite(&shift(value, &self.power_of_two(k)), &shifted);
+        }
+        shifted
+    }
+
+    /// The literal `2^k`
+    fn power_of_two(&self, k: u32) -> Int<'_> {
+        let digits = match 1u128.checked_shl(k) {
+            Some(power) => power.to_string(),
+            // 2^128, the largest power needed (the modulus for 128-bit types)
+            None => "340282366920938463463374607431768211456".to_string(),
+        };
+        Int::from_str(&self.ctx, &digits).expect("valid integer")
+    }
+
+    /// `2^n` (1 for `n <= 0`) by a recursive definition, for amounts beyond the table
+    fn pow2<'a>(&'a self, n: &Int<'a>) -> Int<'a> {
+        // A declaration and its definition belong together, so each use gets its own
+        let index = self.pow2_decls.replace(self.pow2_decls.get() + 1);
+        let int_sort = Sort::int(&self.ctx);
+        let pow2 = z3::RecFuncDecl::new(&self.ctx, format!("pow2!{}", index), &[&int_sort], &int_sort);
+        let arg = Int::new_const(&self.ctx, format!("pow2!{}!n", index));
+        let recurse = pow2.apply(&[&(&arg - 1i64)]).as_int().expect("pow2 returns Int");
+        let one = Int::from_i64(&self.ctx, 1);
+        pow2.add_def(&[&arg], &arg.le(&Int::from_i64(&self.ctx, 0)).ite(&one, &(recurse * 2i64)));
+        pow2.apply(&[n]).as_int().expect("pow2 returns Int")
+    }
+
+    /// Wrap an integer into the range of `ty`, as two's complement arithmetic does
+    fn wrap_int<'a>(&'a self, value: &Int<'a>, ty: IntType) -> Int<'a> {
+        let modulus = self.power_of_two(ty.bits);
+        if !ty.signed {
+            return value.modulo(&modulus);
+        }
+        let half = self.power_of_two(ty.bits - 1);
+        Int::add(&self.ctx, &[value, &half]).modulo(&modulus) - half
+    }
+
+    /// Translate a binary operation on bit-vector operands
+    ///
+    /// Division, remainder, right shift, and comparisons pick the signed or unsigned
+    /// Z3 operator from the operand type; everything else wraps modulo 2^width.
+    fn translate_bv_binary_op<'a>(&'a self, op: syn::BinOp, left: BV<'a>, right: BV<'a>, signed: bool) -> Result<Dynamic<'a>, TranslationError> {
+        let right = match op {
+            // The shift amount may have a different width than the shifted value
+            // and is taken modulo the width, as Rust does when overflow checks are off
+            syn::BinOp::Shl(_) | syn::BinOp::Shr(_) => {
+                let width = left.get_size();
+                resize_bv(&right, width, false).bvand(&BV::from_u64(&self.ctx, u64::from(width - 1), width))
             }
-            "is_some" | "is_none" => {
-                // Option checks - would need Option type handling
-                Err(TranslationError::UnsupportedExpression("Option methods not yet supported".to_string()))
+            _ if left.get_size() != right.get_size() => {
+                return Err(TranslationError::TypeError(format!(
+                    "Mismatched integer widths: {} and {} bits",
+                    left.get_size(),
+                    right.get_size()
+                )));
             }
-            _ => Err(TranslationError::UnsupportedExpression(format!("Method call: {}", method_name))),
-        }
+            _ => right,
+        };
+        let result: Dynamic<'a> = match op {
+            syn::BinOp::Add(_) => left.bvadd(&right).into(),
+            syn::BinOp::Sub(_) => left.bvsub(&right).into(),
+            syn::BinOp::Mul(_) => left.bvmul(&right).into(),
+            syn::BinOp::Div(_) if signed => left.bvsdiv(&right).into(),
+            syn::BinOp::Div(_) => left.bvudiv(&right).into(),
+            syn::BinOp::Rem(_) if signed => left.bvsrem(&right).into(),
+            syn::BinOp::Rem(_) => left.bvurem(&right).into(),
+            syn::BinOp::BitAnd(_) => left.bvand(&right).into(),
+            syn::BinOp::BitOr(_) => left.bvor(&right).into(),
+            syn::BinOp::BitXor(_) => left.bvxor(&right).into(),
+            syn::BinOp::Shl(_) => left.bvshl(&right).into(),
+            syn::BinOp::Shr(_) if signed => left.bvashr(&right).into(),
+            syn::BinOp::Shr(_) => left.bvlshr(&right).into(),
+            syn::BinOp::Eq(_) => left._eq(&right).into(),
+            syn::BinOp::Ne(_) => left._eq(&right).not().into(),
+            syn::BinOp::Lt(_) if signed => left.bvslt(&right).into(),
+            syn::BinOp::Lt(_) => left.bvult(&right).into(),
+            syn::BinOp::Le(_) if signed => left.bvsle(&right).into(),
+            syn::BinOp::Le(_) => left.bvule(&right).into(),
+            syn::BinOp::Gt(_) if signed => left.bvsgt(&right).into(),
+            syn::BinOp::Gt(_) => left.bvugt(&right).into(),
+            syn::BinOp::Ge(_) if signed => left.bvsge(&right).into(),
+            syn::BinOp::Ge(_) => left.bvuge(&right).into(),
+            _ => return Err(TranslationError::UnsupportedOperator(format!("{:?}", op))),
+        };
+        Ok(result)
     }
 
-    /// Translate a function call
-    fn translate_call(&self, _call: &syn::ExprCall) -> Result<z3::ast::Dynamic<'_>, TranslationError> {
-        // Function calls need more context - defer for now
-        Err(TranslationError::UnsupportedExpression("Function calls not yet supported".to_string()))
+    /// Translate a method call (e.g., vec.len(), opt.is_some())
+    fn translate_method_call<'a>(&'a self, method: &syn::ExprMethodCall, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Dynamic<'a>, TranslationError> {
+        if let Some(value) = self.translate_datatype_method(method, vars) {
+            return value;
+        }
+        if let Some(value) = self.translate_seq_method(method, vars) {
+            return value;
+        }
+        Err(TranslationError::UnsupportedExpression(format!("Method call: {}", method.method)))
     }
 
     /// Translate a unary operation given already-translated operand
-    fn translate_unary_op<'a>(&'a self, op: syn::UnOp, expr: z3::ast::Dynamic<'a>) -> Result<z3::ast::Dynamic<'a>, TranslationError> {
+    fn translate_unary_op<'a>(&'a self, op: syn::UnOp, expr: Dynamic<'a>) -> Result<Dynamic<'a>, TranslationError> {
+        if let Some(bv_expr) = expr.as_bv() {
+            return match op {
+                // `!x` on an integer is bitwise complement
+                syn::UnOp::Not(_) => Ok(bv_expr.bvnot().into()),
+                syn::UnOp::Neg(_) => Ok(bv_expr.bvneg().into()),
+                syn::UnOp::Deref(_) => Ok(expr),
+                _ => Err(TranslationError::UnsupportedExpression(format!("Unsupported unary op: {:?}", op))),
+            };
+        }
         match op {
             syn::UnOp::Not(_) => {
                 let bool_expr = expr.as_bool().ok_or_else(|| TranslationError::TypeError("Expected Bool".to_string()))?;
@@ -247,55 +829,150 @@ impl Z3Translator {
     }
 
     /// Translate a contract condition to Z3
-    pub fn translate_contract(&self, contract: &Contract) -> Result<z3::ast::Dynamic<'_>, TranslationError> {
-        let (expr, _) = self.translate_contract_with_types(contract, &std::collections::HashMap::new(), None)?;
+    pub fn translate_contract(&self, contract: &Contract) -> Result<Dynamic<'_>, TranslationError> {
+        let (expr, _) = self.translate_contract_with_types(contract, &HashMap::new(), None)?;
         Ok(expr)
     }
     
     /// Translate a contract condition to Z3 with type information
     /// Returns the expression and type constraints
-    pub fn translate_contract_with_types(&self, contract: &Contract, param_types: &std::collections::HashMap<String, syn::Type>, return_type: Option<&syn::Type>) -> Result<(z3::ast::Dynamic<'_>, Vec<z3::ast::Bool<'_>>), TranslationError> {
-        let mut vars: std::collections::HashMap<String, z3::ast::Int<'_>> = std::collections::HashMap::new();
+    pub fn translate_contract_with_types(&self, contract: &Contract, param_types: &HashMap<String, syn::Type>, return_type: Option<&syn::Type>) -> Result<(Dynamic<'_>, Vec<Bool<'_>>), TranslationError> {
+        let mut vars: HashMap<String, Dynamic<'_>> = HashMap::new();
+        let type_constraints = self.declare_typed_vars(param_types, return_type, &mut vars);
+        if contract.contract_type == ContractType::Ensures {
+            self.enter_post_state(param_types, &mut vars);
+        }
+        let expr = self.translate_expr_with_vars(&contract.condition, &mut vars)?;
+        Ok((expr, type_constraints))
+    }
+
+    /// Declare parameters (and `result`, if the return type is known) and
+    /// return the range constraints implied by their Rust types
+    pub fn declare_typed_vars<'a>(&'a self, param_types: &HashMap<String, syn::Type>, return_type: Option<&syn::Type>, vars: &mut HashMap<String, Dynamic<'a>>) -> Vec<Bool<'a>> {
         let mut type_constraints = Vec::new();
         
         // Pre-create variables with type constraints for parameters
         for (name, ty) in param_types {
-            let symbol = z3::Symbol::String(name.clone());
-            let var = Int::new_const(&self.ctx, symbol);
-            vars.insert(name.clone(), var);
-            
-            // Add type-based constraints
-            if is_unsigned_type(ty) {
-                // u8, u16, u32, u64, usize, Natural -> >= 0
-                let var_ref = vars.get(name).unwrap();
-                type_constraints.push(var_ref.ge(&Int::from_i64(&self.ctx, 0)));
-            }
-            // For signed types (i8, i16, i32, i64, isize, Integer), no constraint
-            // For other types, we'd need more sophisticated handling
+            let var = self.declare_var(name, ty, vars);
+            type_constraints.extend(self.range_constraint(&var, ty));
         }
         
         // Pre-create "result" variable if return type is known (for ensures contracts)
         if let Some(return_ty) = return_type {
-            let symbol = z3::Symbol::String("result".to_string());
-            let var = Int::new_const(&self.ctx, symbol);
-            vars.insert("result".to_string(), var);
-            
-            // Add type-based constraints for return value
-            if is_unsigned_type(return_ty) {
-                let var_ref = vars.get("result").unwrap();
-                type_constraints.push(var_ref.ge(&Int::from_i64(&self.ctx, 0)));
-            }
+            let var = self.declare_var("result", return_ty, vars);
+            type_constraints.extend(self.range_constraint(&var, return_ty));
         }
         
-        let expr = self.translate_expr_with_vars(&contract.condition, &mut vars)?;
-        Ok((expr, type_constraints))
+        type_constraints
+    }
+
+    /// Range constraint for a variable of the given Rust type
+    ///
+    /// e.g. `0 <= x <= u64::MAX` for `x: u64`, and the same for the payload of
+    /// `x: Option<u64>` or the elements of `x: Vec<u64>`. Bit-vectors already
+    /// encode their range, and other types have no constraint. An array
+    /// `x: [T; N]` also has length `N`.
+    pub(crate) fn range_constraint<'a>(&'a self, var: &Dynamic<'a>, ty: &syn::Type) -> Option<Bool<'a>> {
+        let constraints: Vec<Bool<'a>> = self.value_in_range(var, &self.value_type(ty)).into_iter()
+            .chain(self.fixed_length(var, ty))
+            .collect();
+        let constraints: Vec<&Bool<'a>> = constraints.iter().collect();
+        (!constraints.is_empty()).then(|| Bool::and(&self.ctx, &constraints))
+    }
+
+    /// Build `min <= value <= max` for the given integer type
+    pub(crate) fn int_in_range<'a>(&'a self, value: &Int<'a>, ty: IntType) -> Bool<'a> {
+        let min = Int::from_str(&self.ctx, &ty.min_value().to_string()).expect("valid integer");
+        let max = Int::from_str(&self.ctx, &ty.max_value().to_string()).expect("valid integer");
+        Bool::and(&self.ctx, &[&value.ge(&min), &value.le(&max)])
+    }
+
+    /// Bind a `let` statement's variable to its translated initializer
+    ///
+    /// Handles `let x = expr;` and `let x: T = expr;`. Bindings whose initializer
+    /// can't be translated are skipped, leaving the variable unconstrained.
+    pub(crate) fn bind_local<'a>(&'a self, local: &syn::Local, vars: &mut HashMap<String, Dynamic<'a>>) {
+        if let Some((name, value)) = self.translate_local(local, vars) {
+            vars.insert(name, value);
+        }
+    }
+
+    /// The variable a `let` statement binds and its value, computed from `vars`
+    pub(crate) fn translate_local<'a>(&'a self, local: &syn::Local, vars: &mut HashMap<String, Dynamic<'a>>) -> Option<(String, Dynamic<'a>)> {
+        let init = local.init.as_ref()?;
+        let (ident, declared_ty) = match &local.pat {
+            syn::Pat::Ident(ident) => (ident, None),
+            syn::Pat::Type(pat_type) => match &*pat_type.pat {
+                syn::Pat::Ident(ident) => (ident, Some(&*pat_type.ty)),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let var_name = ident.ident.to_string();
+        let var_ty = declared_ty
+            .and_then(IntType::from_type)
+            .or_else(|| self.infer_int_type(&init.expr));
+        // Translate the init expression
+        let z3_expr = match declared_ty {
+            Some(ty) => self.translate_value(&init.expr, vars, &self.value_type(ty)),
+            None => self.translate_typed(&init.expr, vars, var_ty),
+        }.ok()?;
+        if let Some(ty) = var_ty {
+            self.var_types.borrow_mut().insert(var_name.clone(), ty);
+        }
+        self.remember_value_type(&var_name, &z3_expr);
+        Some((var_name, z3_expr))
     }
     
+    /// The variable an assignment (`x = e`, `x += e`, ...) sets and its new value
+    ///
+    /// Returns `None` if `expr` isn't an assignment to a local variable or one
+    /// of its fields (`x.f = e` sets `x`). A variable whose new value can't be
+    /// translated becomes unconstrained. Changes to a sequence (`v.push(x)`,
+    /// `v[i] = x`, ...) count as assignments to it, and so does passing a
+    /// variable to a function by `&mut` (`f(&mut x)`).
+    pub(crate) fn assignment<'a>(&'a self, expr: &Expr, vars: &mut HashMap<String, Dynamic<'a>>) -> Option<(String, Dynamic<'a>)> {
+        if let Some(update) = self.seq_assignment(expr, vars) {
+            return Some(update);
+        }
+        if let Some(update) = self.call_assignment(expr, vars) {
+            return Some(update);
+        }
+        let (target, value) = match expr {
+            Expr::Assign(assign) => (&*assign.left, (*assign.right).clone()),
+            Expr::Binary(bin) => {
+                let op = compound_operator(bin.op)?;
+                let value = Expr::Binary(syn::ExprBinary {
+                    attrs: Vec::new(),
+                    left: bin.left.clone(),
+                    op,
+                    right: bin.right.clone(),
+                });
+                (&*bin.left, value)
+            }
+            _ => return None,
+        };
+        let target = match target {
+            Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Deref(_)) => &*unary.expr,
+            _ => target,
+        };
+        let name = place_root(target)?;
+
+        let new_value = self.translate_typed(&value, vars, self.infer_int_type(target)).ok();
+        let value = match (new_value, self.place_value(target, vars)) {
+            (Some(new_value), Some(old)) if new_value.get_sort() != old.get_sort() => self.fresh_value(&name, &old),
+            (Some(new_value), _) => new_value,
+            (None, Some(old)) => self.fresh_value(&name, &old),
+            (None, None) => return None,
+        };
+        self.assign_place(target, value, vars)
+    }
+
     /// Translate a function body to a Z3 formula that relates inputs to result
     /// 
     /// This is the KEY for verifying ensures: we translate the implementation
     /// to a Z3 formula and prove: requires && implementation => ensures
-    pub fn translate_function_body<'a>(&'a self, func: &ItemFn, vars: &mut std::collections::HashMap<String, z3::ast::Int<'a>>) -> Result<Option<z3::ast::Bool<'a>>, TranslationError> {
+    pub fn translate_function_body<'a>(&'a self, func: &ItemFn, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Option<Bool<'a>>, TranslationError> {
         // Extract the function body
         let body = &func.block;
         
@@ -303,274 +980,82 @@ impl Z3Translator {
         // result == <body_expression>
         self.translate_block_to_result_formula(body, vars)
     }
-    
-    /// Translate a block to a formula: result == <final_expression>
-    /// 
-    /// Handles:
-    /// - let bindings (variable assignments)
-    /// - if expressions with early returns
-    /// - debug_assert! macros (skipped)
-    /// - final implicit return
-    fn translate_block_to_result_formula<'a>(&'a self, block: &Block, vars: &mut std::collections::HashMap<String, z3::ast::Int<'a>>) -> Result<Option<z3::ast::Bool<'a>>, TranslationError> {
-        let mut formulas: Vec<z3::ast::Bool<'a>> = Vec::new();
-        let mut early_return_conditions: Vec<(z3::ast::Bool<'a>, z3::ast::Bool<'a>)> = Vec::new();
-        
-        // Process statements to build variable bindings and collect return conditions
-        for stmt in &block.stmts {
-            match stmt {
-                Stmt::Local(local) => {
-                    // let x = expr;
-                    if let Some(init) = &local.init {
-                        if let syn::Pat::Ident(ident) = &local.pat {
-                            let var_name = ident.ident.to_string();
-                            // Translate the init expression
-                            if let Ok(z3_expr) = self.translate_expr_with_vars(&init.expr, vars) {
-                                if let Some(int_val) = z3_expr.as_int() {
-                                    vars.insert(var_name, int_val);
-                                }
-                            }
-                        }
-                    }
-                }
-                Stmt::Expr(expr, Some(_)) => {
-                    // Statement expression with semicolon (e.g., if cond { return x; })
-                    if let Expr::If(if_expr) = expr {
-                        // Handle if statement with potential early return
-                        if let Some((cond, result_formula)) = self.translate_if_with_early_return(if_expr, vars)? {
-                            early_return_conditions.push((cond, result_formula));
-                        }
-                    }
-                }
-                Stmt::Expr(expr, None) => {
-                    // Final expression (implicit return) - no semicolon
-                    if let Ok(z3_expr) = self.translate_expr_with_vars(expr, vars) {
-                        if let Some(int_val) = z3_expr.as_int() {
-                            let result_var = vars.get("result").ok_or_else(|| {
-                                TranslationError::UnsupportedExpression("No result variable".to_string())
-                            })?;
-                            
-                            // This is the final return
-                            // Build formula: (no early returns) => result == this_expr
-                            if early_return_conditions.is_empty() {
-                                return Ok(Some(result_var._eq(&int_val)));
-                            } else {
-                                // Combine all conditions:
-                                // (cond1 => result == val1) && (cond2 => result == val2) && (!cond1 && !cond2 => result == final)
-                                let mut all_conditions = Vec::new();
-                                let mut negated_conds = Vec::new();
-                                
-                                for (cond, result_formula) in &early_return_conditions {
-                                    // cond => result_formula
-                                    all_conditions.push(cond.implies(result_formula));
-                                    negated_conds.push(cond.not());
-                                }
-                                
-                                // (!cond1 && !cond2 && ...) => result == final_expr
-                                if !negated_conds.is_empty() {
-                                    let refs: Vec<&z3::ast::Bool> = negated_conds.iter().collect();
-                                    let no_early_return = Bool::and(&self.ctx, &refs);
-                                    all_conditions.push(no_early_return.implies(&result_var._eq(&int_val)));
-                                }
-                                
-                                if !all_conditions.is_empty() {
-                                    let refs: Vec<&z3::ast::Bool> = all_conditions.iter().collect();
-                                    return Ok(Some(Bool::and(&self.ctx, &refs)));
-                                }
-                                
-                                return Ok(Some(result_var._eq(&int_val)));
-                            }
-                        }
-                    }
-                }
-                Stmt::Item(_) => {
-                    // Skip items (nested functions, etc.)
-                }
-                Stmt::Macro(_) => {
-                    // Skip macros (debug_assert!, etc.)
-                }
-            }
-        }
-        
-        // Check if the last statement is a return or expression
-        if let Some(Stmt::Expr(expr, None)) = block.stmts.last() {
-            return self.translate_return_expr(expr, vars);
-        }
-        
-        // Handle case where there are only early returns (no final expression)
-        if !early_return_conditions.is_empty() {
-            let mut all_conditions = Vec::new();
-            for (cond, result_formula) in &early_return_conditions {
-                all_conditions.push(cond.implies(result_formula));
-            }
-            let refs: Vec<&z3::ast::Bool> = all_conditions.iter().collect();
-            return Ok(Some(Bool::and(&self.ctx, &refs)));
-        }
-        
-        // No clear return expression found
-        Ok(None)
-    }
-    
-    /// Handle if statement with potential early return
-    /// Returns (condition, result_formula) if there's an early return
-    fn translate_if_with_early_return<'a>(&'a self, if_expr: &syn::ExprIf, vars: &mut std::collections::HashMap<String, z3::ast::Int<'a>>) -> Result<Option<(z3::ast::Bool<'a>, z3::ast::Bool<'a>)>, TranslationError> {
-        // Translate condition
-        let cond_z3 = self.translate_expr_with_vars(&if_expr.cond, vars)?;
-        let cond_bool = match cond_z3.as_bool() {
-            Some(b) => b,
-            None => return Ok(None),
-        };
-        
-        // Check if the then branch has an early return
-        for stmt in &if_expr.then_branch.stmts {
-            if let Stmt::Expr(Expr::Return(ret), _) = stmt {
-                if let Some(return_expr) = &ret.expr {
-                    if let Ok(z3_expr) = self.translate_expr_with_vars(return_expr, vars) {
-                        if let Some(int_val) = z3_expr.as_int() {
-                            let result_var = vars.get("result").ok_or_else(|| {
-                                TranslationError::UnsupportedExpression("No result variable".to_string())
-                            })?;
-                            return Ok(Some((cond_bool, result_var._eq(&int_val))));
-                        }
-                    }
-                }
-            }
-        }
-        
-        Ok(None)
-    }
-    
-    /// Translate a return expression to: result == <expr>
-    fn translate_return_expr<'a>(&'a self, expr: &Expr, vars: &mut std::collections::HashMap<String, z3::ast::Int<'a>>) -> Result<Option<z3::ast::Bool<'a>>, TranslationError> {
-        match expr {
-            Expr::Return(ret) => {
-                if let Some(return_expr) = &ret.expr {
-                    let z3_expr = self.translate_expr_with_vars(return_expr, vars)?;
-                    if let Some(int_val) = z3_expr.as_int() {
-                        // result == return_expr
-                        let result_var = vars.get("result").ok_or_else(|| {
-                            TranslationError::UnsupportedExpression("No result variable".to_string())
-                        })?;
-                        return Ok(Some(result_var._eq(&int_val)));
-                    }
-                }
-            }
-            Expr::If(if_expr) => {
-                // if condition { then_expr } else { else_expr }
-                // Translates to: (condition => result == then_expr) && (!condition => result == else_expr)
-                return self.translate_if_to_formula(if_expr, vars);
-            }
-            _ => {
-                // Direct expression (implicit return)
-                if let Ok(z3_expr) = self.translate_expr_with_vars(expr, vars) {
-                    if let Some(int_val) = z3_expr.as_int() {
-                        let result_var = vars.get("result").ok_or_else(|| {
-                            TranslationError::UnsupportedExpression("No result variable".to_string())
-                        })?;
-                        return Ok(Some(result_var._eq(&int_val)));
-                    }
-                }
-            }
-        }
-        
-        Ok(None)
-    }
-    
-    /// Translate an if expression to: (cond => result == then) && (!cond => result == else)
-    fn translate_if_to_formula<'a>(&'a self, if_expr: &syn::ExprIf, vars: &mut std::collections::HashMap<String, z3::ast::Int<'a>>) -> Result<Option<z3::ast::Bool<'a>>, TranslationError> {
-        // Translate condition
-        let cond_z3 = self.translate_expr_with_vars(&if_expr.cond, vars)?;
-        let cond_bool = cond_z3.as_bool().ok_or_else(|| {
-            TranslationError::TypeError("If condition must be boolean".to_string())
-        })?;
-        
-        // Translate then branch
-        let then_formula = self.translate_block_to_result_formula(&if_expr.then_branch, vars)?;
-        
-        // Translate else branch (if present)
-        let else_formula = if let Some((_, else_branch)) = &if_expr.else_branch {
-            match &**else_branch {
-                Expr::Block(block) => self.translate_block_to_result_formula(&block.block, vars)?,
-                Expr::If(nested_if) => self.translate_if_to_formula(nested_if, vars)?,
-                _ => {
-                    let z3_expr = self.translate_expr_with_vars(else_branch, vars)?;
-                    if let Some(int_val) = z3_expr.as_int() {
-                        let result_var = vars.get("result").ok_or_else(|| {
-                            TranslationError::UnsupportedExpression("No result variable".to_string())
-                        })?;
-                        Some(result_var._eq(&int_val))
-                    } else {
-                        None
-                    }
-                }
-            }
-        } else {
-            None
-        };
-        
-        // Build the formula: (cond => then) && (!cond => else)
-        match (then_formula, else_formula) {
-            (Some(then_f), Some(else_f)) => {
-                // (cond => then) && (!cond => else)
-                let then_impl = cond_bool.implies(&then_f);
-                let else_impl = cond_bool.not().implies(&else_f);
-                Ok(Some(Bool::and(&self.ctx, &[&then_impl, &else_impl])))
-            }
-            (Some(then_f), None) => {
-                // Only then branch matters (cond => then)
-                Ok(Some(cond_bool.implies(&then_f)))
-            }
-            (None, Some(else_f)) => {
-                // Only else branch matters (!cond => else)
-                Ok(Some(cond_bool.not().implies(&else_f)))
-            }
-            (None, None) => Ok(None),
+
+    /// Translate a block to a formula relating the result to the inputs
+    ///
+    /// Each exit (`return`, `?`, a branch or `match` arm that returns, or the
+    /// final expression) contributes `taken ⟹ result == value`; see
+    /// `translator::control_flow`. Statements update the variables (see
+    /// `translator::overflow`), and the facts they establish (e.g. a loop's
+    /// invariant on exit) hold unless an earlier exit was taken. Reaching the
+    /// end of the block returns too, with the `&mut` parameters as they are
+    /// then (see `translator::two_state`).
+    fn translate_block_to_result_formula<'a>(&'a self, block: &Block, vars: &mut HashMap<String, Dynamic<'a>>) -> Result<Option<Bool<'a>>, TranslationError> {
+        let mut exits = self.block_exits(block, vars, true)?;
+        if let Some(state) = self.exit_state(vars) {
+            exits.fact(&state);
         }
+        Ok(exits.formula())
     }
 }
 
-/// Resolve common Bitcoin consensus constants
-/// Returns the constant value if known, None otherwise
-fn resolve_constant(name: &str) -> Option<i64> {
-    match name {
-        // Economic constants (from blvm-consensus/src/constants.rs)
-        "INITIAL_SUBSIDY" => Some(50_0000_0000), // 50 BTC in satoshis
-        "MAX_MONEY" => Some(21_000_000_0000_0000), // 21M BTC in satoshis
-        "HALVING_INTERVAL" => Some(210_000),
-        "SATOSHIS_PER_BTC" => Some(100_000_000),
-        
-        // Transaction constants
-        "MAX_BLOCK_SIZE" => Some(1_000_000), // 1MB
-        "MAX_TX_SIZE" => Some(100_000), // Conservative limit
-        
-        // Script constants
-        "MAX_SCRIPT_SIZE" => Some(10_000),
-        "MAX_STACK_SIZE" => Some(1000),
-        
-        _ => None,
+/// Check if a type is `bool` (looking through references)
+pub(crate) fn is_bool_type(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(type_path) => type_path.path.is_ident("bool"),
+        syn::Type::Reference(reference) => is_bool_type(&reference.elem),
+        _ => false,
     }
 }
 
-/// Check if a type is unsigned
-/// Handles both primitive types (u8, u16, u32, u64, u128, usize) and common type aliases
-fn is_unsigned_type(ty: &syn::Type) -> bool {
-    if let syn::Type::Path(type_path) = ty {
-        if let Some(segment) = type_path.path.segments.last() {
-            let type_name = segment.ident.to_string();
-            
-            // Check primitive unsigned types
-            if type_name.starts_with('u') && (type_name == "u8" || type_name == "u16" || 
-                type_name == "u32" || type_name == "u64" || type_name == "u128" || type_name == "usize") {
-                return true;
-            }
-            
-            // Check common type aliases used in Bitcoin consensus code
-            // Natural = u64, Integer = i64 (from blvm-consensus/src/types.rs)
-            if type_name == "Natural" {
-                return true; // Natural is u64
-            }
+/// The operator applied by a compound assignment, e.g. `+` for `+=`
+pub(crate) fn compound_operator(op: syn::BinOp) -> Option<syn::BinOp> {
+    use syn::BinOp;
+    let op = match op {
+        BinOp::AddAssign(_) => BinOp::Add(Default::default()),
+        BinOp::SubAssign(_) => BinOp::Sub(Default::default()),
+        BinOp::MulAssign(_) => BinOp::Mul(Default::default()),
+        BinOp::DivAssign(_) => BinOp::Div(Default::default()),
+        BinOp::RemAssign(_) => BinOp::Rem(Default::default()),
+        BinOp::BitXorAssign(_) => BinOp::BitXor(Default::default()),
+        BinOp::BitAndAssign(_) => BinOp::BitAnd(Default::default()),
+        BinOp::BitOrAssign(_) => BinOp::BitOr(Default::default()),
+        BinOp::ShlAssign(_) => BinOp::Shl(Default::default()),
+        BinOp::ShrAssign(_) => BinOp::Shr(Default::default()),
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// Check if a binary operator is a comparison (its result is Bool, not its operand type)
+fn is_comparison(op: syn::BinOp) -> bool {
+    matches!(
+        op,
+        syn::BinOp::Eq(_) | syn::BinOp::Ne(_) | syn::BinOp::Lt(_) |
+        syn::BinOp::Le(_) | syn::BinOp::Gt(_) | syn::BinOp::Ge(_)
+    )
+}
+
+/// Sign/zero-extend or truncate a bit-vector to `bits`
+fn resize_bv<'a>(bv: &BV<'a>, bits: u32, signed: bool) -> BV<'a> {
+    let size = bv.get_size();
+    if bits > size {
+        if signed {
+            bv.sign_ext(bits - size)
+        } else {
+            bv.zero_ext(bits - size)
         }
+    } else if bits < size {
+        bv.extract(bits - 1, 0)
+    } else {
+        bv.clone()
     }
-    false
+}
+
+/// Build `left == right`, reporting a type error if the sorts differ
+fn safe_eq<'a>(left: &Dynamic<'a>, right: &Dynamic<'a>) -> Result<Bool<'a>, TranslationError> {
+    left._safe_eq(right)
+        .map_err(|e| TranslationError::TypeError(format!("Cannot compare values of different sorts: {:?}", e)))
 }
 
 /// Convert a path to a string representation
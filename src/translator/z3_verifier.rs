@@ -15,12 +15,30 @@
 //! If Z3 proves this implication, the implementation is mathematically locked to the spec.
 
 #[cfg(feature = "z3")]
-use z3::{Context, Solver, SatResult, Sort};
+use z3::{Params, Solver, SatResult};
 #[cfg(feature = "z3")]
-use z3::ast::{Ast, Int, Bool, forall_const};
+use z3::ast::{Ast, Bool, Int};
 use crate::parser::contracts::{Contract, ContractType};
 #[cfg(feature = "z3")]
-use crate::translator::z3_translator::Z3Translator;
+use crate::translator::z3_translator::{ArithmeticMode, IntType, Z3Translator};
+#[cfg(feature = "z3")]
+use crate::translator::calls::FunctionSummary;
+#[cfg(feature = "z3")]
+use crate::translator::datatypes::ValueType;
+#[cfg(feature = "z3")]
+use crate::translator::overflow::ObligationKind;
+#[cfg(feature = "z3")]
+use crate::translator::sequences::{SeqParts, INDEX};
+#[cfg(feature = "z3")]
+use crate::translator::theorem::compile_theorem;
+#[cfg(feature = "z3")]
+use crate::translator::two_state::pre_state_key;
+#[cfg(feature = "z3")]
+use crate::parser::math::MathExpr;
+#[cfg(feature = "z3")]
+use z3::ast::Dynamic;
+#[cfg(feature = "z3")]
+use std::collections::HashMap;
 
 #[cfg(feature = "z3")]
 /// Result of Z3 verification
@@ -42,31 +60,111 @@ pub enum VerificationResult {
     },
 }
 
+#[cfg(feature = "z3")]
+/// Result of checking one implicit obligation (overflow or callee precondition)
+#[derive(Debug, Clone)]
+pub struct ObligationResult {
+    /// Operator or cast, e.g. `+`, `<<`, `as u32`, or `requires of f` for a call to `f`
+    pub operation: String,
+    /// Source text of the operation
+    pub expression: String,
+    /// Line of the operation (1-based, 0 if unknown)
+    pub line: usize,
+    /// Column of the operation (1-based)
+    pub column: usize,
+    /// Verified if the operation is always safe under the preconditions
+    pub result: VerificationResult,
+}
+
 #[cfg(feature = "z3")]
 /// Counterexample from Z3 model
 #[derive(Debug, Clone)]
 pub struct Counterexample {
-    /// Variable assignments that violate the property
-    pub assignments: std::collections::HashMap<String, String>,
+    /// Variable assignments that violate the property, rendered as Rust literals
+    ///
+    /// Parameters come first (in signature order), then `let`-bound locals, then `result`.
+    pub assignments: Vec<(String, String)>,
+}
+
+#[cfg(feature = "z3")]
+impl std::fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let assignments: Vec<_> = self.assignments.iter()
+            .map(|(name, value)| format!("{} = {}", name, value))
+            .collect();
+        write!(f, "{}", assignments.join(", "))
+    }
 }
 
+#[cfg(feature = "z3")]
+/// A variable to evaluate in the model, with its Rust integer type name if known
+type ModelVar<'a> = (String, Dynamic<'a>, Option<String>);
+
 #[cfg(feature = "z3")]
 /// Z3 verifier for contracts
 pub struct Z3Verifier {
     translator: Z3Translator,
+    /// Per-check solver timeout (none = unbounded)
+    timeout: Option<std::time::Duration>,
 }
 
 #[cfg(feature = "z3")]
 impl Z3Verifier {
     /// Create a new Z3 verifier
     pub fn new() -> Self {
-        let translator = Z3Translator::new();
+        Self::with_mode(ArithmeticMode::Integer)
+    }
+
+    /// Create a new Z3 verifier with the given arithmetic semantics
+    pub fn with_mode(mode: ArithmeticMode) -> Self {
+        let translator = Z3Translator::with_mode(mode);
         
         Z3Verifier {
             translator,
+            timeout: None,
         }
     }
 
+    /// Limit each solver check to the given duration
+    ///
+    /// Checks that run out of time report `VerificationResult::Unknown`.
+    pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Model calls to these functions by their contracts (see `translator::calls`)
+    pub fn with_summaries(mut self, summaries: impl IntoIterator<Item = FunctionSummary>) -> Self {
+        for summary in summaries {
+            self.translator.add_summary(summary);
+        }
+        self
+    }
+
+    /// Model values of these enums as datatypes (see `translator::datatypes`)
+    pub fn with_enums(mut self, enums: impl IntoIterator<Item = syn::ItemEnum>) -> Self {
+        for item in enums {
+            self.translator.add_enum(item);
+        }
+        self
+    }
+
+    /// Model values of these structs as datatypes (see `translator::datatypes`)
+    pub fn with_structs(mut self, structs: impl IntoIterator<Item = syn::ItemStruct>) -> Self {
+        for item in structs {
+            self.translator.add_struct(item);
+        }
+        self
+    }
+
+    /// Translate references to these named constants to their values
+    pub fn with_constants(mut self, constants: impl IntoIterator<Item = (String, IntType, i128)>) -> Self {
+        for (name, ty, value) in constants {
+            self.translator.add_constant(&name, ty, value);
+        }
+        self
+    }
+
     /// Verify a contract
     ///
     /// For requires: checks if precondition can be violated
@@ -85,8 +183,23 @@ impl Z3Verifier {
             (std::collections::HashMap::new(), None)
         };
         
+        // Variables to report in a counterexample
+        let mut model_vars = HashMap::new();
+        let type_constraints = self.translator.declare_typed_vars(&param_types, None, &mut model_vars);
+        let result_constraint = return_type.as_ref().and_then(|ty| {
+            let result = self.translator.declare_var("result", ty, &mut model_vars);
+            self.translator.range_constraint(&result, ty)
+        });
+        // An ensures is about the `&mut` parameters on return, and `old(x)` on entry
+        let post_state_constraints: Vec<Bool<'_>> = match contract.contract_type {
+            ContractType::Ensures => self.translator.enter_post_state(&param_types, &mut model_vars).iter()
+                .filter_map(|(post, ty)| self.translator.range_constraint(post, ty))
+                .collect(),
+            ContractType::Requires => Vec::new(),
+        };
+        
         // Translate contract to Z3 with type information
-        let (z3_expr, type_constraints) = match self.translator.translate_contract_with_types(contract, &param_types, return_type.as_ref()) {
+        let (z3_expr, _) = match self.translator.translate_contract_with_types(contract, &param_types, return_type.as_ref()) {
             Ok((expr, constraints)) => (expr, constraints),
             Err(e) => {
                 return VerificationResult::Error {
@@ -109,13 +222,18 @@ impl Z3Verifier {
 
         // Create solver for this verification
         let ctx = self.translator.context();
-        let mut solver = Solver::new(ctx);
+        let solver = self.new_solver();
         
         // Add type constraints first (e.g., u64 >= 0)
         for constraint in &type_constraints {
             solver.assert(constraint);
         }
         
+        // Why the body couldn't be translated, if it couldn't
+        let mut body_error = None;
+        // Whether the body gives `result`'s value
+        let mut result_defined = false;
+        
         // For ensures contracts:
         // 1. Add requires constraints (assume preconditions hold)
         // 2. Add implementation formula (translate function body to Z3)
@@ -147,31 +265,56 @@ impl Z3Verifier {
                 let mut body_vars = std::collections::HashMap::new();
                 
                 // Initialize parameter variables
-                for (name, _ty) in &param_types {
-                    let symbol = z3::Symbol::String(name.clone());
-                    let var = z3::ast::Int::new_const(ctx, symbol);
-                    body_vars.insert(name.clone(), var);
+                for (name, ty) in &param_types {
+                    self.translator.declare_var(name, ty, &mut body_vars);
                 }
+                self.translator.declare_post_states(&param_types, &mut body_vars);
                 
                 // Initialize result variable
-                let result_symbol = z3::Symbol::String("result".to_string());
-                let result_var = z3::ast::Int::new_const(ctx, result_symbol);
-                body_vars.insert("result".to_string(), result_var);
-                
-                // Add axioms for uninterpreted functions (shift operations)
-                // This allows Z3 to reason about bit shifts mathematically
-                add_shift_axioms(ctx, &mut solver);
+                if let Some(return_ty) = &return_type {
+                    self.translator.declare_var("result", return_ty, &mut body_vars);
+                } else {
+                    let result_var = z3::ast::Int::new_const(ctx, "result");
+                    body_vars.insert("result".to_string(), result_var.into());
+                }
                 
                 // Translate function body to Z3 formula
                 // This constrains 'result' to match the actual computation
-                if let Ok(Some(impl_formula)) = self.translator.translate_function_body(func, &mut body_vars) {
-                    solver.assert(&impl_formula);
+                match self.translator.translate_function_body(func, &mut body_vars) {
+                    Ok(Some(impl_formula)) => {
+                        solver.assert(&impl_formula);
+                        result_defined = true;
+                    }
+                    Ok(None) => {}
+                    Err(e) => body_error = Some(e),
+                }
+                // Locals bound while translating the body, leaving out the
+                // post-states (already there) and a unit function's `result`
+                for (name, value) in body_vars {
+                    if !name.ends_with('\'') && name != "result" {
+                        model_vars.entry(name).or_insert(value);
+                    }
                 }
                 // If translation fails, we still verify based on type constraints and requires
-                // This allows partial verification for complex functions
+                // This allows partial verification for complex functions, but a
+                // counterexample would be meaningless
+            }
+        }
+        
+        // The range of a `result` computed by the body follows from the body
+        // (overflow is a separate obligation), and assuming it anyway can make
+        // Z3 give up on quantified sequence ranges
+        if !result_defined {
+            for constraint in result_constraint.iter().chain(&post_state_constraints) {
+                solver.assert(constraint);
             }
         }
         
+        // Assume the contracts of the functions called by the body or the contract
+        for axiom in self.translator.call_axioms() {
+            solver.assert(&axiom);
+        }
+        
         // Add negated ensures contract to solver
         // We're checking: requires && implementation && !ensures is UNSAT
         // If UNSAT: requires && implementation => ensures (postcondition holds)
@@ -184,29 +327,284 @@ impl Z3Verifier {
                 // Negation is unsatisfiable, so property holds
                 VerificationResult::Verified
             }
+            SatResult::Sat if body_error.is_some() => {
+                VerificationResult::Error {
+                    error: format!("Translation error in function body: {}", body_error.expect("checked above")),
+                }
+            }
             SatResult::Sat => {
                 // Negation is satisfiable, so property fails
-                let counterexample = self.extract_counterexample(&solver);
+                // A requires is about the inputs alone, so `result` means nothing there
+                if contract.contract_type == ContractType::Requires {
+                    model_vars.remove("result");
+                }
+                let vars = self.model_vars(func_sig, &param_types, return_type.as_ref(), model_vars);
+                let counterexample = self.extract_counterexample(&solver, &vars);
                 VerificationResult::Failed {
                     counterexample,
                 }
             }
             SatResult::Unknown => {
                 VerificationResult::Unknown {
-                    reason: "Z3 solver returned Unknown".to_string(),
+                    reason: unknown_reason(&solver),
                 }
             }
         }
     }
     
+    /// Verify that no arithmetic operation in the function body can overflow
+    ///
+    /// Each `+`, `-`, `*`, `/`, `%`, unary `-`, shift and narrowing `as` cast is
+    /// checked separately, assuming the requires contracts and the parameter
+    /// types' ranges. Operations that can't be translated are `Unknown`.
+    pub fn verify_overflow_obligations(&mut self, func: &syn::ItemFn, requires_contracts: &[Contract]) -> Vec<ObligationResult> {
+        self.verify_obligations(func, requires_contracts, ObligationKind::Overflow)
+    }
+    
+    /// Verify that every call in the function body satisfies the callee's requires
+    ///
+    /// Only calls to functions registered with [`Z3Verifier::with_summaries`]
+    /// have preconditions. Each call is checked separately, under the caller's
+    /// requires contracts and the conditions on the path leading to it.
+    pub fn verify_call_preconditions(&mut self, func: &syn::ItemFn, requires_contracts: &[Contract]) -> Vec<ObligationResult> {
+        self.verify_obligations(func, requires_contracts, ObligationKind::Precondition)
+    }
+    
+    /// Verify the `#[invariant]` and `#[decreases]` contracts of every loop
+    ///
+    /// Each invariant is checked on entry and after an arbitrary iteration, and
+    /// each measure must be non-negative and decrease on every iteration.
+    pub fn verify_loop_obligations(&mut self, func: &syn::ItemFn, requires_contracts: &[Contract]) -> Vec<ObligationResult> {
+        self.verify_obligations(func, requires_contracts, ObligationKind::Loop)
+    }
+    
+    /// Verify that every index `v[i]` in the function body is below `v`'s length
+    ///
+    /// Each index is checked separately, under the requires contracts and the
+    /// conditions on the path leading to it.
+    pub fn verify_bounds_obligations(&mut self, func: &syn::ItemFn, requires_contracts: &[Contract]) -> Vec<ObligationResult> {
+        self.verify_obligations(func, requires_contracts, ObligationKind::Bounds)
+    }
+    
+    fn verify_obligations(&mut self, func: &syn::ItemFn, requires_contracts: &[Contract], kind: ObligationKind) -> Vec<ObligationResult> {
+        let param_types = extract_parameter_types(func);
+        let return_type = extract_return_type(func);
+        
+        let mut body_vars = std::collections::HashMap::new();
+        let type_constraints = self.translator.declare_typed_vars(&param_types, None, &mut body_vars);
+        let obligations = self.translator.collect_obligations(func, &mut body_vars);
+        let vars = self.model_vars(Some(func), &param_types, None, body_vars);
+        let call_axioms = self.translator.call_axioms();
+        
+        let mut results = Vec::new();
+        for obligation in obligations.into_iter().filter(|o| o.kind == kind) {
+            let Some(condition) = &obligation.condition else {
+                results.push(ObligationResult {
+                    result: VerificationResult::Unknown {
+                        reason: "the operation's type or operands couldn't be translated".to_string(),
+                    },
+                    operation: obligation.operation,
+                    expression: obligation.expression,
+                    line: obligation.line,
+                    column: obligation.column,
+                });
+                continue;
+            };
+            let solver = self.new_solver();
+            for constraint in &type_constraints {
+                solver.assert(constraint);
+            }
+            // Assume preconditions hold
+            for requires_contract in requires_contracts {
+                if let Ok((requires_expr, _)) = self.translator.translate_contract_with_types(requires_contract, &param_types, return_type.as_ref()) {
+                    if let Some(requires_bool) = requires_expr.as_bool() {
+                        solver.assert(&requires_bool);
+                    }
+                }
+            }
+            for axiom in &call_axioms {
+                solver.assert(axiom);
+            }
+            
+            // Look for inputs that reach the operation and make it unsafe
+            solver.assert(&condition.not());
+            let result = match solver.check() {
+                SatResult::Unsat => VerificationResult::Verified,
+                SatResult::Sat => VerificationResult::Failed {
+                    counterexample: self.extract_counterexample(&solver, &vars),
+                },
+                SatResult::Unknown => VerificationResult::Unknown {
+                    reason: unknown_reason(&solver),
+                },
+            };
+            results.push(ObligationResult {
+                operation: obligation.operation,
+                expression: obligation.expression,
+                line: obligation.line,
+                column: obligation.column,
+                result,
+            });
+        }
+        results
+    }
+    
+    /// Verify an Orange Paper theorem against a function's implementation
+    ///
+    /// The statement is compiled directly to Z3 (no Rust contract in between).
+    /// Each application of `spec_function` is bound to the translated body of
+    /// `func`, assuming its requires contracts; the theorem holds if its
+    /// negation is unsatisfiable.
+    pub fn verify_theorem(&mut self, statement: &MathExpr, spec_function: &str, func: &syn::ItemFn, requires_contracts: &[Contract]) -> VerificationResult {
+        let theorem = match compile_theorem(&self.translator, statement, spec_function, func, requires_contracts) {
+            Ok(theorem) => theorem,
+            Err(e) => {
+                return VerificationResult::Error {
+                    error: format!("Translation error: {}", e),
+                };
+            }
+        };
+        
+        let solver = self.new_solver();
+        for assumption in &theorem.assumptions {
+            solver.assert(assumption);
+        }
+        for axiom in self.translator.call_axioms() {
+            solver.assert(&axiom);
+        }
+        solver.assert(&theorem.goal.not());
+        
+        match solver.check() {
+            SatResult::Unsat => VerificationResult::Verified,
+            SatResult::Sat => VerificationResult::Failed {
+                counterexample: self.extract_counterexample(&solver, &theorem.model_vars),
+            },
+            SatResult::Unknown => VerificationResult::Unknown {
+                reason: unknown_reason(&solver),
+            },
+        }
+    }
+    
+    /// Create a solver with the configured parameters
+    fn new_solver(&self) -> Solver<'_> {
+        let ctx = self.translator.context();
+        let solver = Solver::new(ctx);
+        if let Some(timeout) = self.timeout {
+            let mut params = Params::new(ctx);
+            let millis = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
+            params.set_u32("timeout", millis);
+            solver.set_params(&params);
+        }
+        solver
+    }
+    
+    /// Order the variables for a counterexample and attach their Rust types
+    ///
+    /// Parameters follow the signature, locals are sorted by name, and `result` comes last.
+    fn model_vars<'a>(&self, func_sig: Option<&syn::ItemFn>, param_types: &HashMap<String, syn::Type>, return_type: Option<&syn::Type>, mut vars: HashMap<String, Dynamic<'a>>) -> Vec<ModelVar<'a>> {
+        let mut param_names: Vec<String> = match func_sig {
+            Some(func) => func.sig.inputs.iter()
+                .filter_map(|input| match input {
+                    syn::FnArg::Typed(pat_type) => match &*pat_type.pat {
+                        syn::Pat::Ident(ident) => Some(ident.ident.to_string()),
+                        _ => None,
+                    },
+                    syn::FnArg::Receiver(_) => None,
+                })
+                .collect(),
+            None => param_types.keys().cloned().collect(),
+        };
+        if func_sig.is_none() {
+            param_names.sort();
+        }
+        
+        let mut ordered = Vec::new();
+        for name in param_names {
+            // A `&mut` parameter's value on entry comes before its value on return
+            let rust_type = param_types.get(&name).and_then(int_type_name);
+            for key in [pre_state_key(&name), name] {
+                if let Some(value) = vars.remove(&key) {
+                    ordered.push((key, value, rust_type.clone()));
+                }
+            }
+        }
+        let result = vars.remove("result");
+        let mut locals: Vec<_> = vars.into_iter().collect();
+        locals.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, value) in locals {
+            let rust_type = self.translator.var_type(&name).map(|ty| ty.rust_name());
+            ordered.push((name, value, rust_type));
+        }
+        if let Some(value) = result {
+            ordered.push(("result".to_string(), value, return_type.and_then(int_type_name)));
+        }
+        ordered
+    }
+    
     /// Extract counterexample from Z3 model
-    fn extract_counterexample(&self, solver: &Solver<'_>) -> Option<Counterexample> {
-        let _model = solver.get_model()?;
-        // Model extraction is simplified - would iterate over variables in full impl
-        Some(Counterexample {
-            assignments: std::collections::HashMap::new(),
+    ///
+    /// Evaluates every variable in the model and renders it in its Rust type.
+    fn extract_counterexample(&self, solver: &Solver<'_>, vars: &[ModelVar<'_>]) -> Option<Counterexample> {
+        let model = solver.get_model()?;
+        let assignments = vars.iter()
+            .filter_map(|(name, value, rust_type)| {
+                Some((name.clone(), self.render_model_value(&model, value, rust_type.as_deref())?))
+            })
+            .collect();
+        Some(Counterexample { assignments })
+    }
+    
+    /// Render a value in a model as a Rust expression
+    ///
+    /// Datatypes are rendered as their constructor applied to their fields,
+    /// e.g. `Err(ConsensusError::InvalidAmount(5))` or `TxOut { value: 5 }`.
+    fn render_model_value(&self, model: &z3::Model<'_>, value: &Dynamic<'_>, rust_type: Option<&str>) -> Option<String> {
+        if let Some(parts) = self.translator.seq_parts(value) {
+            return self.render_seq(model, &parts);
+        }
+        let value = model.eval(value, true)?;
+        if value.as_datatype().is_none() {
+            return Some(render_value(&value, rust_type));
+        }
+        let Ok(constructor) = value.safe_decl() else {
+            return Some(value.to_string());
+        };
+        let fields = value.children().iter()
+            .map(|field| self.render_model_value(model, field, None))
+            .collect::<Option<Vec<String>>>()?;
+        let name = constructor.name();
+        let named_fields = match self.translator.type_of_value(&value) {
+            Some(ValueType::Struct(struct_name)) => self.translator.struct_definition(&struct_name)
+                .filter(|item| matches!(item.fields, syn::Fields::Named(_)))
+                .map(|item| item.fields.iter().filter_map(|field| field.ident.as_ref()).map(|ident| ident.to_string()).collect::<Vec<_>>()),
+            _ => None,
+        };
+        Some(match (named_fields, fields.len()) {
+            (Some(names), _) => {
+                let fields: Vec<String> = names.iter().zip(&fields).map(|(name, field)| format!("{}: {}", name, field)).collect();
+                format!("{} {{ {} }}", name, fields.join(", "))
+            }
+            (None, 0) => name,
+            (None, _) => format!("{}({})", name, fields.join(", ")),
         })
     }
+    
+    /// Render a sequence in a model as `[a, b, c]`, showing at most 16 elements
+    fn render_seq(&self, model: &z3::Model<'_>, parts: &SeqParts<'_>) -> Option<String> {
+        const SHOWN: u64 = 16;
+        let len = model.eval(&parts.len, true)?;
+        let len = len.as_int().and_then(|len| len.as_u64())
+            .or_else(|| len.as_bv().and_then(|len| len.as_u64()))?;
+        let suffix = parts.elem.int_type().map(|ty| ty.rust_name());
+        let mut elements = Vec::new();
+        for i in 0..len.min(SHOWN) {
+            let index = self.translator.int_literal(u128::from(i), Some(INDEX)).ok()?;
+            elements.push(self.render_model_value(model, &parts.elems.select(&index), suffix.as_deref())?);
+        }
+        if len > SHOWN {
+            return Some(format!("[{}, ..] (len {})", elements.join(", "), len));
+        }
+        Some(format!("[{}]", elements.join(", ")))
+    }
 
     /// Reset the solver (for verifying multiple contracts)
     /// Note: Since we create solvers on-demand, this is a no-op
@@ -228,6 +626,50 @@ fn extract_parameter_types(func: &syn::ItemFn) -> std::collections::HashMap<Stri
     types
 }
 
+/// Why the solver returned Unknown (e.g. "timeout")
+#[cfg(feature = "z3")]
+fn unknown_reason(solver: &Solver<'_>) -> String {
+    solver.get_reason_unknown()
+        .unwrap_or_else(|| "Z3 solver returned Unknown".to_string())
+}
+
+/// Rust integer type name to use as a literal suffix
+///
+/// Primitive names are kept as written; aliases such as `Natural` map to their
+/// underlying primitive.
+#[cfg(feature = "z3")]
+fn int_type_name(ty: &syn::Type) -> Option<String> {
+    let int_ty = IntType::from_type(ty)?;
+    if let syn::Type::Path(type_path) = ty {
+        if type_path.path.is_ident("usize") || type_path.path.is_ident("isize") {
+            return type_path.path.get_ident().map(|ident| ident.to_string());
+        }
+    }
+    Some(int_ty.rust_name())
+}
+
+/// Render an evaluated boolean or integer as a Rust literal, e.g. `42u64`, `-1i32` or `true`
+#[cfg(feature = "z3")]
+fn render_value(value: &Dynamic<'_>, rust_type: Option<&str>) -> String {
+    if let Some(b) = value.as_bool() {
+        return b.as_bool().map(|b| b.to_string()).unwrap_or_else(|| b.to_string());
+    }
+    let signed = rust_type.and_then(IntType::from_name).is_some_and(|ty| ty.signed);
+    let int = value.as_int().or_else(|| value.as_bv().map(|bv| Int::from_bv(&bv, signed)));
+    match int {
+        Some(int) => {
+            // Z3 prints negative numerals as `(- 5)`
+            let numeral = int.simplify().to_string();
+            let digits = match numeral.strip_prefix("(- ").and_then(|n| n.strip_suffix(')')) {
+                Some(magnitude) => format!("-{}", magnitude),
+                None => numeral,
+            };
+            format!("{}{}", digits, rust_type.unwrap_or(""))
+        }
+        None => value.to_string(),
+    }
+}
+
 /// Extract return type from function signature
 fn extract_return_type(func: &syn::ItemFn) -> Option<syn::Type> {
     if let syn::ReturnType::Type(_, ty) = &func.sig.output {
@@ -237,78 +679,6 @@ fn extract_return_type(func: &syn::ItemFn) -> Option<syn::Type> {
     }
 }
 
-/// Add axioms for bit shift operations
-/// 
-/// Since we use uninterpreted functions for shifts (to avoid Real numbers),
-/// we need to add axioms that capture the mathematical properties of shifts:
-/// 
-/// For right shift (>>): 
-///   - shr(a, b) >= 0 when a >= 0 (non-negativity preserving)
-///   - shr(a, b) <= a when a >= 0 and b >= 0 (monotonically decreasing)
-///   - shr(a, 0) == a (identity)
-/// 
-/// For left shift (<<):
-///   - shl(a, b) >= a when a >= 0 and b >= 0 (monotonically increasing)
-///   - shl(a, 0) == a (identity)
-#[cfg(feature = "z3")]
-fn add_shift_axioms(ctx: &Context, solver: &mut Solver) {
-    let int_sort = Sort::int(ctx);
-    
-    // Create bound variables for universal quantification
-    let a = Int::new_const(ctx, "axiom_a");
-    let b = Int::new_const(ctx, "axiom_b");
-    let zero = Int::from_i64(ctx, 0);
-    
-    // Get the shr function declaration (must match what's used in translator)
-    let shr_fn = z3::FuncDecl::new(ctx, "shr", &[&int_sort, &int_sort], &int_sort);
-    let shl_fn = z3::FuncDecl::new(ctx, "shl", &[&int_sort, &int_sort], &int_sort);
-    
-    // Axiom 1: shr(a, b) >= 0 when a >= 0 and b >= 0
-    // ∀a,b: (a >= 0 ∧ b >= 0) → shr(a,b) >= 0
-    let shr_result = shr_fn.apply(&[&a, &b]);
-    let shr_result_int = shr_result.as_int().unwrap();
-    let premise1 = Bool::and(ctx, &[&a.ge(&zero), &b.ge(&zero)]);
-    let conclusion1 = shr_result_int.ge(&zero);
-    let axiom1 = premise1.implies(&conclusion1);
-    
-    // Use forall quantification
-    let bound_a = a.clone();
-    let bound_b = b.clone();
-    let forall1 = forall_const(ctx, &[&bound_a, &bound_b], &[], &axiom1);
-    solver.assert(&forall1);
-    
-    // Axiom 2: shr(a, b) <= a when a >= 0 and b >= 0
-    // ∀a,b: (a >= 0 ∧ b >= 0) → shr(a,b) <= a
-    let conclusion2 = shr_result_int.le(&a);
-    let axiom2 = premise1.implies(&conclusion2);
-    let forall2 = forall_const(ctx, &[&bound_a, &bound_b], &[], &axiom2);
-    solver.assert(&forall2);
-    
-    // Axiom 3: shr(a, 0) == a (identity for shift by 0)
-    // ∀a: shr(a, 0) == a
-    let shr_by_zero = shr_fn.apply(&[&a, &zero]);
-    let shr_by_zero_int = shr_by_zero.as_int().unwrap();
-    let axiom3 = shr_by_zero_int._eq(&a);
-    let forall3 = forall_const(ctx, &[&bound_a], &[], &axiom3);
-    solver.assert(&forall3);
-    
-    // Axiom 4: shl(a, b) >= a when a >= 0 and b >= 0
-    // ∀a,b: (a >= 0 ∧ b >= 0) → shl(a,b) >= a
-    let shl_result = shl_fn.apply(&[&a, &b]);
-    let shl_result_int = shl_result.as_int().unwrap();
-    let conclusion4 = shl_result_int.ge(&a);
-    let axiom4 = premise1.implies(&conclusion4);
-    let forall4 = forall_const(ctx, &[&bound_a, &bound_b], &[], &axiom4);
-    solver.assert(&forall4);
-    
-    // Axiom 5: shl(a, 0) == a (identity for shift by 0)
-    let shl_by_zero = shl_fn.apply(&[&a, &zero]);
-    let shl_by_zero_int = shl_by_zero.as_int().unwrap();
-    let axiom5 = shl_by_zero_int._eq(&a);
-    let forall5 = forall_const(ctx, &[&bound_a], &[], &axiom5);
-    solver.assert(&forall5);
-}
-
 #[cfg(not(feature = "z3"))]
 /// Stub implementation when Z3 feature is disabled
 pub struct Z3Verifier;
@@ -336,3 +706,710 @@ pub enum VerificationResult {
 
 
 
+#[cfg(all(test, feature = "z3"))]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn ensures(condition: syn::Expr) -> Contract {
+        Contract {
+            contract_type: ContractType::Ensures,
+            condition,
+            comment: None,
+        }
+    }
+
+    fn requires(condition: syn::Expr) -> Contract {
+        Contract {
+            contract_type: ContractType::Requires,
+            condition,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_bit_precise_addition_wraps() {
+        let func: syn::ItemFn = parse_quote! {
+            fn increment(a: u64) -> u64 { a + 1 }
+        };
+        let contract = ensures(parse_quote! { result > a });
+
+        // Holds over unbounded integers, but u64::MAX + 1 wraps to 0
+        let mut verifier = Z3Verifier::new();
+        let result = verifier.verify_contract_with_context(&contract, Some(&func), &[]);
+        assert!(matches!(result, VerificationResult::Verified));
+
+        let mut verifier = Z3Verifier::with_mode(ArithmeticMode::BitPrecise);
+        let result = verifier.verify_contract_with_context(&contract, Some(&func), &[]);
+        assert!(matches!(result, VerificationResult::Failed { .. }));
+    }
+
+    #[test]
+    fn test_counterexample_assignments() {
+        let func: syn::ItemFn = parse_quote! {
+            fn offset(a: u64, b: i32) -> u64 {
+                let c = a + 1;
+                c
+            }
+        };
+        let contract = ensures(parse_quote! { result > a });
+
+        let mut verifier = Z3Verifier::with_mode(ArithmeticMode::BitPrecise);
+        let VerificationResult::Failed { counterexample: Some(ce) } = verifier.verify_contract_with_context(&contract, Some(&func), &[]) else {
+            panic!("expected a counterexample");
+        };
+        let names: Vec<_> = ce.assignments.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b", "c", "result"]);
+        assert_eq!(ce.assignments[0].1, "18446744073709551615u64");
+        assert!(ce.assignments[1].1.ends_with("i32"));
+        assert_eq!(ce.assignments[2].1, "0u64");
+        assert_eq!(ce.assignments[3].1, "0u64");
+
+        // A failed requires reports only the inputs
+        let contract = requires(parse_quote! { a > 5 });
+        let VerificationResult::Failed { counterexample: Some(ce) } = verifier.verify_contract_with_context(&contract, Some(&func), &[]) else {
+            panic!("expected a counterexample");
+        };
+        let names: Vec<_> = ce.assignments.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_bit_precise_signed_shift() {
+        let func: syn::ItemFn = parse_quote! {
+            fn halve(x: i64) -> i64 { x >> 1 }
+        };
+        // Arithmetic shift preserves the sign of an i64
+        let contract = ensures(parse_quote! { (x < 0) == (result < 0) });
+
+        let mut verifier = Z3Verifier::with_mode(ArithmeticMode::BitPrecise);
+        let result = verifier.verify_contract_with_context(&contract, Some(&func), &[]);
+        assert!(matches!(result, VerificationResult::Verified));
+    }
+
+    #[test]
+    fn test_overflow_obligations() {
+        let func: syn::ItemFn = parse_quote! {
+            fn scale(a: u64, b: u32, s: u32) -> u64 {
+                if s >= 64 {
+                    return 0;
+                }
+                let c = b as u8;
+                (a + 1) << s
+            }
+        };
+        let requires = vec![Contract {
+            contract_type: ContractType::Requires,
+            condition: parse_quote! { a < 1000 },
+            comment: None,
+        }];
+
+        for mode in [ArithmeticMode::Integer, ArithmeticMode::BitPrecise] {
+            let mut verifier = Z3Verifier::with_mode(mode);
+            let results = verifier.verify_overflow_obligations(&func, &requires);
+            let status: Vec<_> = results
+                .iter()
+                .map(|r| (r.operation.as_str(), matches!(r.result, VerificationResult::Verified)))
+                .collect();
+            // The cast truncates, the addition is bounded by the requires, and
+            // the early return keeps the shift amount in range
+            assert_eq!(status, vec![("as u8", false), ("+", true), ("<<", true)]);
+        }
+
+        // Without the precondition the addition can overflow
+        let mut verifier = Z3Verifier::new();
+        let results = verifier.verify_overflow_obligations(&func, &[]);
+        assert!(matches!(results[1].result, VerificationResult::Failed { .. }));
+
+        // An addition whose operands can't be translated is never proven
+        let func: syn::ItemFn = parse_quote! {
+            fn f(a: u64, b: u64) -> u64 {
+                a.wrapping_add(b) + b.checked_add(1).unwrap()
+            }
+        };
+        let results = verifier.verify_overflow_obligations(&func, &[]);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].operation, "+");
+        assert!(matches!(results[0].result, VerificationResult::Unknown { .. }), "{:?}", results[0].result);
+
+        // Division by zero, `MIN / -1` and `-MIN`
+        let func: syn::ItemFn = parse_quote! {
+            fn g(a: i64, b: i64) -> i64 {
+                if b == 0 {
+                    return 0;
+                }
+                -(a / b) + a % 7
+            }
+        };
+        let requires = vec![Contract {
+            contract_type: ContractType::Requires,
+            condition: parse_quote! { a > -1000 && a < 1000 },
+            comment: None,
+        }];
+        let results = verifier.verify_overflow_obligations(&func, &requires);
+        let status: Vec<_> = results
+            .iter()
+            .map(|r| (r.operation.as_str(), matches!(r.result, VerificationResult::Verified)))
+            .collect();
+        // `%` isn't modelled on unbounded integers, so the sum can't be proven
+        assert_eq!(status, vec![("/", true), ("-", true), ("%", true), ("+", false)]);
+        assert!(matches!(results[3].result, VerificationResult::Unknown { .. }));
+        let func: syn::ItemFn = parse_quote! {
+            fn h(a: i64, b: i64) -> i64 { -a + a / b }
+        };
+        let results = verifier.verify_overflow_obligations(&func, &[]);
+        assert!(results.iter().all(|r| matches!(r.result, VerificationResult::Failed { .. })), "{:?}", results);
+    }
+
+    #[test]
+    fn test_verify_theorem_against_body() {
+        use crate::parser::math::parse_math;
+
+        let func: syn::ItemFn = parse_quote! {
+            fn get_block_subsidy(height: u64) -> i64 {
+                let halvings = height / 210000;
+                if halvings >= 64 {
+                    return 0;
+                }
+                5000000000 >> halvings
+            }
+        };
+        let bounded = parse_math(r"\forall h \in \mathbb{N}: \text{GetBlockSubsidy}(h) \geq 0 \land \text{GetBlockSubsidy}(h) \leq 50 \times C").unwrap();
+        let mut verifier = Z3Verifier::new()
+            .with_constants([("SATOSHIS_PER_BTC".to_string(), IntType::U64, 100_000_000)]);
+        let result = verifier.verify_theorem(&bounded, "GetBlockSubsidy", &func, &[]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+
+        let wrong = parse_math(r"\forall h \in \mathbb{N}: h \geq 1 \implies \text{GetBlockSubsidy}(h) = 0").unwrap();
+        let VerificationResult::Failed { counterexample: Some(ce) } = verifier.verify_theorem(&wrong, "GetBlockSubsidy", &func, &[]) else {
+            panic!("expected a counterexample");
+        };
+        let names: Vec<_> = ce.assignments.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["h", "height", "GetBlockSubsidy(h)"]);
+
+        // Two applications, each bound to its own copy of the body
+        let double: syn::ItemFn = parse_quote! {
+            fn double(x: u64) -> u64 {
+                x + x
+            }
+        };
+        let monotonic = parse_math(r"\forall a, b \in \mathbb{N}: a \leq b \implies \text{Double}(a) \leq \text{Double}(b)").unwrap();
+        let result = verifier.verify_theorem(&monotonic, "Double", &double, &[]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+
+        // Literal exponents expand into products, up to a bound
+        let squared = parse_math(r"\forall a \in \mathbb{N}: \text{Double}(a)^{2} = 4 \times a^{2}").unwrap();
+        let result = verifier.verify_theorem(&squared, "Double", &double, &[]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+        let huge = parse_math(r"\forall a \in \mathbb{N}: \text{Double}(a)^{1000000} \geq 0").unwrap();
+        let result = verifier.verify_theorem(&huge, "Double", &double, &[]);
+        assert!(matches!(result, VerificationResult::Error { ref error } if error.contains("exponent")), "{:?}", result);
+    }
+
+
+    #[test]
+    fn test_exact_shifts() {
+        use crate::parser::math::parse_math;
+
+        // The halving schedule is exactly the Orange Paper's formula
+        let schedule = parse_math(r"\forall h \in \mathbb{N}: \text{GetBlockSubsidy}(h) = 50 \times C \times 2^{-\lfloor h / H \rfloor}").unwrap();
+        let constants = [
+            ("SATOSHIS_PER_BTC".to_string(), IntType::U64, 100_000_000),
+            ("HALVING_INTERVAL".to_string(), IntType::U64, 210_000),
+        ];
+        let func: syn::ItemFn = parse_quote! {
+            fn get_block_subsidy(height: u64) -> u64 {
+                let halvings = height / HALVING_INTERVAL;
+                if halvings >= 64 {
+                    return 0;
+                }
+                (50 * SATOSHIS_PER_BTC) >> halvings
+            }
+        };
+        let mut verifier = Z3Verifier::new().with_constants(constants.clone());
+        let result = verifier.verify_theorem(&schedule, "GetBlockSubsidy", &func, &[]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+
+        // Without the guard the amount wraps modulo 64, as in a release build
+        let unguarded: syn::ItemFn = parse_quote! {
+            fn get_block_subsidy(height: u64) -> u64 {
+                (50 * SATOSHIS_PER_BTC) >> (height / HALVING_INTERVAL)
+            }
+        };
+        let mut verifier = Z3Verifier::new().with_constants(constants);
+        let result = verifier.verify_theorem(&schedule, "GetBlockSubsidy", &unguarded, &[]);
+        assert!(matches!(result, VerificationResult::Failed { .. }), "{:?}", result);
+
+        // Left shifts drop the bits shifted out, in both modes
+        let func: syn::ItemFn = parse_quote! {
+            fn low_bits(x: u8) -> u8 { (x << 4) >> 4 }
+        };
+        for mode in [ArithmeticMode::Integer, ArithmeticMode::BitPrecise] {
+            let mut verifier = Z3Verifier::with_mode(mode);
+            let result = verifier.verify_contract_with_context(&ensures(parse_quote! { result == x - x / 16 * 16 }), Some(&func), &[]);
+            assert!(matches!(result, VerificationResult::Verified), "{:?}: {:?}", mode, result);
+        }
+    }
+
+    #[test]
+    fn test_calls_use_callee_contracts() {
+        let callee: syn::ItemFn = parse_quote! {
+            #[requires(height <= 6930000)]
+            #[ensures(result <= 5000000000)]
+            fn get_block_subsidy(height: u64) -> u64 {
+                5000000000 >> (height / 210000)
+            }
+        };
+        let caller: syn::ItemFn = parse_quote! {
+            fn block_reward(height: u64, fees: u64) -> u64 {
+                let subsidy = get_block_subsidy(height);
+                subsidy + fees
+            }
+        };
+        let bounded = ensures(parse_quote! { result <= fees + 5000000000 });
+        let in_range = requires(parse_quote! { height <= 1000000 });
+
+        // The callee's ensures is only assumed where its requires holds
+        let mut verifier = Z3Verifier::new().with_summaries([FunctionSummary::from_fn(&callee)]);
+        let result = verifier.verify_contract_with_context(&bounded, Some(&caller), std::slice::from_ref(&in_range));
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+        let result = verifier.verify_contract_with_context(&bounded, Some(&caller), &[]);
+        assert!(matches!(result, VerificationResult::Failed { .. }), "{:?}", result);
+
+        // ... and must be established at the call site
+        let results = verifier.verify_call_preconditions(&caller, &[]);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].operation, "requires of get_block_subsidy");
+        assert!(matches!(results[0].result, VerificationResult::Failed { .. }));
+        let results = verifier.verify_call_preconditions(&caller, &[in_range]);
+        assert!(matches!(results[0].result, VerificationResult::Verified));
+    }
+
+    #[test]
+    fn test_unknown_callee_is_uninterpreted() {
+        let func: syn::ItemFn = parse_quote! {
+            fn wrapper(x: u64) -> u64 { lookup(x) }
+        };
+        let mut verifier = Z3Verifier::new();
+        let result = verifier.verify_contract_with_context(&ensures(parse_quote! { result == lookup(x) }), Some(&func), &[]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+        let result = verifier.verify_contract_with_context(&ensures(parse_quote! { result <= x }), Some(&func), &[]);
+        assert!(matches!(result, VerificationResult::Failed { .. }), "{:?}", result);
+    }
+
+    #[test]
+    fn test_loop_invariants() {
+        let func: syn::ItemFn = parse_quote! {
+            fn double(n: u64) -> u64 {
+                let mut total = 0;
+                #[invariant(total == 2 * i)]
+                for i in 0..n {
+                    total += 2;
+                }
+                total
+            }
+        };
+        let mut verifier = Z3Verifier::new();
+        let results = verifier.verify_loop_obligations(&func, &[]);
+        let status: Vec<_> = results
+            .iter()
+            .map(|r| (r.operation.as_str(), matches!(r.result, VerificationResult::Verified)))
+            .collect();
+        assert_eq!(status, vec![("invariant on entry", true), ("invariant preserved", true)]);
+        // After the loop the counter has reached `n`
+        let result = verifier.verify_contract_with_context(&ensures(parse_quote! { result == 2 * n }), Some(&func), &[]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+
+        let wrong: syn::ItemFn = parse_quote! {
+            fn double(n: u64) -> u64 {
+                let mut total = 0;
+                #[invariant(total == i)]
+                for i in 0..n {
+                    total += 2;
+                }
+                total
+            }
+        };
+        let results = verifier.verify_loop_obligations(&wrong, &[]);
+        assert!(matches!(results[0].result, VerificationResult::Verified));
+        assert!(matches!(results[1].result, VerificationResult::Failed { .. }));
+
+        let countdown: syn::ItemFn = parse_quote! {
+            fn halvings(mut height: u64) -> u64 {
+                let mut count = 0;
+                #[decreases(height)]
+                while height >= 210000 {
+                    height -= 210000;
+                    count += 1;
+                }
+                count
+            }
+        };
+        let results = verifier.verify_loop_obligations(&countdown, &[]);
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0].result, VerificationResult::Verified), "{:?}", results[0].result);
+
+        let stuck: syn::ItemFn = parse_quote! {
+            fn halvings(mut height: u64) -> u64 {
+                #[decreases(height)]
+                while height >= 210000 {
+                    height = height / 2 + 105000;
+                }
+                height
+            }
+        };
+        let results = verifier.verify_loop_obligations(&stuck, &[]);
+        assert!(matches!(results[0].result, VerificationResult::Failed { .. }));
+
+        // Contracts that aren't checked are never proven
+        let early_exit: syn::ItemFn = parse_quote! {
+            fn first_halving(mut height: u64) -> u64 {
+                #[invariant(height <= 210000)]
+                #[decreases(height)]
+                while height > 0 {
+                    if height == 1 {
+                        break;
+                    }
+                    height -= 1;
+                }
+                height
+            }
+        };
+        let results = verifier.verify_loop_obligations(&early_exit, &[]);
+        let status: Vec<_> = results
+            .iter()
+            .map(|r| (r.operation.as_str(), matches!(r.result, VerificationResult::Unknown { .. })))
+            .collect();
+        assert_eq!(status, vec![("invariant", true), ("decreases", true)]);
+
+        let opaque: syn::ItemFn = parse_quote! {
+            fn count(n: u64) -> u64 {
+                let mut total = 0;
+                #[invariant(total <= lookup_table().len())]
+                for _ in 0..n {
+                    total += 1;
+                }
+                total
+            }
+        };
+        let results = verifier.verify_loop_obligations(&opaque, &[]);
+        assert!(matches!(results[0].result, VerificationResult::Unknown { .. }), "{:?}", results[0].result);
+    }
+
+    #[test]
+    fn test_match_if_let_and_try() {
+        let classify: syn::ItemFn = parse_quote! {
+            fn reward(height: u64) -> u64 {
+                match height / 210000 {
+                    0 => 50,
+                    1 | 2 => 25,
+                    n if n >= 64 => 0,
+                    _ => 12,
+                }
+            }
+        };
+        let mut verifier = Z3Verifier::new();
+        let result = verifier.verify_contract_with_context(&ensures(parse_quote! { result <= 50 }), Some(&classify), &[]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+        let result = verifier.verify_contract_with_context(&ensures(parse_quote! { result != 12 }), Some(&classify), &[]);
+        assert!(matches!(result, VerificationResult::Failed { .. }), "{:?}", result);
+
+        let capped: syn::ItemFn = parse_quote! {
+            fn fee(amount: Option<u64>) -> u64 {
+                if let Some(a) = amount {
+                    if a > 100 {
+                        return 100;
+                    }
+                    a
+                } else {
+                    0
+                }
+            }
+        };
+        let result = verifier.verify_contract_with_context(&ensures(parse_quote! { result <= 100 }), Some(&capped), &[]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+
+        // `?` returns the callee's error; otherwise its value is unwrapped
+        let error: syn::ItemEnum = parse_quote! {
+            enum ConsensusError { TooLarge, Invalid(u64) }
+        };
+        let callee: syn::ItemFn = parse_quote! {
+            fn checked_half(x: u64) -> Result<u64, ConsensusError> {
+                Ok(x / 2)
+            }
+        };
+        let caller: syn::ItemFn = parse_quote! {
+            fn reward(x: u64) -> Result<u64, ConsensusError> {
+                let half = checked_half(x)?;
+                if half > 10 {
+                    return Err(ConsensusError::TooLarge);
+                }
+                Ok(half)
+            }
+        };
+        let mut verifier = Z3Verifier::new()
+            .with_enums([error])
+            .with_summaries([FunctionSummary::from_fn(&callee)]);
+        let bounded = ensures(parse_quote! { match result { Ok(v) => v <= 10, Err(_) => true } });
+        let result = verifier.verify_contract_with_context(&bounded, Some(&caller), &[]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+        let too_strict = ensures(parse_quote! { match result { Ok(v) => v <= 5, Err(_) => true } });
+        let VerificationResult::Failed { counterexample: Some(ce) } = verifier.verify_contract_with_context(&too_strict, Some(&caller), &[]) else {
+            panic!("expected a counterexample");
+        };
+        let (_, result) = ce.assignments.last().expect("result is reported");
+        assert!(result.starts_with("Ok("), "{}", result);
+
+        // An arm that can't be modelled is an error, not a counterexample
+        let sliced: syn::ItemFn = parse_quote! {
+            fn first(x: &[u64]) -> u64 {
+                match x {
+                    [a, ..] => *a,
+                    _ => 0,
+                }
+            }
+        };
+        let result = verifier.verify_contract_with_context(&ensures(parse_quote! { result == 0 }), Some(&sliced), &[]);
+        assert!(matches!(result, VerificationResult::Error { .. }), "{:?}", result);
+    }
+
+    #[test]
+    fn test_option_and_result_contracts() {
+        use crate::parser::contracts::rewrite_implications;
+        use quote::quote;
+
+        let condition = |tokens: proc_macro2::TokenStream| -> syn::Expr {
+            syn::parse2(rewrite_implications(tokens)).expect("contract parses")
+        };
+        let error: syn::ItemEnum = parse_quote! {
+            enum ConsensusError { BadSubsidy, TooLarge(u64) }
+        };
+        let subsidy: syn::ItemFn = parse_quote! {
+            fn checked_subsidy(height: u64) -> Result<u64, ConsensusError> {
+                if height > 6930000 {
+                    return Err(ConsensusError::BadSubsidy);
+                }
+                Ok(5000000000 / (height / 210000 + 1))
+            }
+        };
+        let mut verifier = Z3Verifier::new().with_enums([error]);
+        for holds in [
+            quote! { result.is_ok() ==> result.unwrap() <= 5000000000 },
+            quote! { matches!(result, Err(ConsensusError::BadSubsidy)) ==> height > 6930000 },
+            quote! { height <= 6930000 ==> result.is_ok() && result.unwrap_or(0) > 0 },
+            quote! { result.is_err() == (result.ok() == None) },
+        ] {
+            let result = verifier.verify_contract_with_context(&ensures(condition(holds.clone())), Some(&subsidy), &[]);
+            assert!(matches!(result, VerificationResult::Verified), "{}: {:?}", holds, result);
+        }
+        let wrong = condition(quote! { height <= 6930001 ==> result.is_ok() });
+        let result = verifier.verify_contract_with_context(&ensures(wrong), Some(&subsidy), &[]);
+        assert!(matches!(result, VerificationResult::Failed { .. }), "{:?}", result);
+
+        // `None` takes its type from the other side of `==`
+        let double: syn::ItemFn = parse_quote! {
+            fn double(amount: Option<u64>) -> Option<u64> {
+                let a = amount?;
+                if a > 1000 {
+                    return None;
+                }
+                Some(a * 2)
+            }
+        };
+        let result = verifier.verify_contract_with_context(&ensures(condition(quote! { amount == None ==> result == None })), Some(&double), &[]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+        let result = verifier.verify_contract_with_context(&ensures(condition(quote! { result.unwrap_or(0) >= amount.unwrap_or(0) })), Some(&double), &[]);
+        assert!(matches!(result, VerificationResult::Failed { .. }), "{:?}", result);
+    }
+
+    #[test]
+    fn test_sequences() {
+        use crate::parser::contracts::rewrite_implications;
+        use quote::quote;
+
+        let mut verifier = Z3Verifier::new();
+
+        // `fee == sum(inputs) - sum(outputs)`: the sums in the body and the contract agree
+        let fee: syn::ItemFn = parse_quote! {
+            fn fee(inputs: &[u64], outputs: &[u64]) -> u64 {
+                let total_in: u64 = inputs.iter().sum();
+                let total_out: u64 = outputs.iter().sum();
+                total_in - total_out
+            }
+        };
+        let sums_ordered = requires(parse_quote!(outputs.iter().sum::<u64>() <= inputs.iter().sum::<u64>()));
+        let contract = ensures(parse_quote!(result == inputs.iter().sum::<u64>() - outputs.iter().sum::<u64>()));
+        let result = verifier.verify_contract_with_context(&contract, Some(&fee), &[sums_ordered]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+
+        // Indexing is checked against the length, under the requires
+        let at: syn::ItemFn = parse_quote! {
+            fn at(v: &Vec<u64>, i: usize) -> u64 {
+                v[i]
+            }
+        };
+        let unchecked = verifier.verify_bounds_obligations(&at, &[]);
+        assert!(matches!(unchecked[0].result, VerificationResult::Failed { .. }), "{:?}", unchecked);
+        let checked = verifier.verify_bounds_obligations(&at, &[requires(parse_quote!(i < v.len()))]);
+        assert!(matches!(checked[0].result, VerificationResult::Verified), "{:?}", checked);
+
+        // Pushing extends the length; `all` and `any` quantify over the elements
+        let pushed: syn::ItemFn = parse_quote! {
+            fn pushed(v: Vec<u64>, x: u64) -> Vec<u64> {
+                let mut w = v;
+                w.push(x);
+                w
+            }
+        };
+        for holds in [
+            quote! { result.len() == v.len() + 1 },
+            quote! { result[v.len()] == x },
+            quote! { v.iter().all(|y| *y < x) ==> result.iter().all(|y| *y <= x) },
+            quote! { result.iter().any(|y| *y == x) },
+        ] {
+            let condition = syn::parse2(rewrite_implications(holds.clone())).expect("contract parses");
+            let result = verifier.verify_contract_with_context(&ensures(condition), Some(&pushed), &[]);
+            assert!(matches!(result, VerificationResult::Verified), "{}: {:?}", holds, result);
+        }
+        let result = verifier.verify_contract_with_context(&ensures(parse_quote!(result[0] == x)), Some(&pushed), &[]);
+        let VerificationResult::Failed { counterexample: Some(counterexample) } = result else {
+            panic!("expected a counterexample, got {:?}", result);
+        };
+        let (_, v) = counterexample.assignments.iter().find(|(name, _)| name == "v").expect("v in the counterexample");
+        assert!(v.starts_with('['), "{}", v);
+    }
+
+    #[test]
+    fn test_structs() {
+        let structs: [syn::ItemStruct; 2] = [
+            parse_quote! { struct TxOut { value: u64, script: Vec<u8> } },
+            parse_quote! { struct Transaction { version: i32, outputs: Vec<TxOut> } },
+        ];
+        let mut verifier = Z3Verifier::new().with_structs(structs);
+
+        // Fields are read in contracts and set in bodies, also inside sequences
+        let add_output: syn::ItemFn = parse_quote! {
+            fn add_output(tx: Transaction, value: u64) -> Transaction {
+                let mut tx = tx;
+                tx.outputs.push(TxOut { value, script: Vec::new() });
+                tx.version = 2;
+                tx
+            }
+        };
+        for holds in [
+            parse_quote!(result.outputs.len() == tx.outputs.len() + 1),
+            parse_quote!(result.outputs[tx.outputs.len()].value == value),
+            parse_quote!(result.version == 2),
+        ] {
+            let result = verifier.verify_contract_with_context(&ensures(holds), Some(&add_output), &[]);
+            assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+        }
+
+        // `..base` keeps the other fields; counterexamples show the fields by name
+        let clear_script: syn::ItemFn = parse_quote! {
+            fn clear_script(out: TxOut) -> TxOut {
+                TxOut { script: Vec::new(), ..out }
+            }
+        };
+        let result = verifier.verify_contract_with_context(&ensures(parse_quote!(result.value == out.value)), Some(&clear_script), &[]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+        let result = verifier.verify_contract_with_context(&ensures(parse_quote!(result.value < out.value)), Some(&clear_script), &[]);
+        let VerificationResult::Failed { counterexample: Some(counterexample) } = result else {
+            panic!("expected a counterexample, got {:?}", result);
+        };
+        let (_, out) = counterexample.assignments.iter().find(|(name, _)| name == "out").expect("out in the counterexample");
+        assert!(out.starts_with("TxOut { value: "), "{}", out);
+    }
+
+    #[test]
+    fn test_two_state_contracts() {
+        let mut verifier = Z3Verifier::new();
+
+        // In an ensures, `x` is the value on return and `old(x)` the value on entry
+        let add_to: syn::ItemFn = parse_quote! {
+            fn add_to(x: &mut u64, y: u64) {
+                *x += y;
+            }
+        };
+        let result = verifier.verify_contract_with_context(&ensures(parse_quote! { *x == old(*x) + y }), Some(&add_to), &[]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+        let result = verifier.verify_contract_with_context(&ensures(parse_quote! { *x == old(*x) }), Some(&add_to), &[]);
+        let VerificationResult::Failed { counterexample: Some(counterexample) } = result else {
+            panic!("expected a counterexample, got {:?}", result);
+        };
+        let names: Vec<&str> = counterexample.assignments.iter().map(|(name, _)| name.as_str()).collect();
+        assert!(names.contains(&"x") && names.contains(&"old(x)"), "{:?}", names);
+
+        // Each exit has its own post-state
+        let append: syn::ItemFn = parse_quote! {
+            fn append(v: &mut Vec<u64>, item: u64) -> bool {
+                if item == 0 {
+                    return false;
+                }
+                v.push(item);
+                true
+            }
+        };
+        for holds in [
+            parse_quote!(!result || v.len() == old(v.len()) + 1),
+            parse_quote!(!result || v[old(v.len())] == item),
+            parse_quote!(result || v.len() == old(v.len())),
+        ] {
+            let result = verifier.verify_contract_with_context(&ensures(holds), Some(&append), &[]);
+            assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+        }
+
+        // A caller sees the effect of passing `&mut` through the callee's ensures
+        let bump: syn::ItemFn = parse_quote! {
+            #[requires(*x < 1000)]
+            #[ensures(*x == old(*x) + 1)]
+            fn bump(x: &mut u64) {
+                *x += 1;
+            }
+        };
+        let bump_twice: syn::ItemFn = parse_quote! {
+            fn bump_twice(n: &mut u64) {
+                bump(n);
+                bump(&mut *n);
+            }
+        };
+        let mut verifier = Z3Verifier::new().with_summaries([FunctionSummary::from_fn(&bump)]);
+        let twice = ensures(parse_quote! { *n == old(*n) + 2 });
+        let result = verifier.verify_contract_with_context(&twice, Some(&bump_twice), &[requires(parse_quote! { *n < 999 })]);
+        assert!(matches!(result, VerificationResult::Verified), "{:?}", result);
+        let result = verifier.verify_contract_with_context(&ensures(parse_quote! { *n == old(*n) + 1 }), Some(&bump_twice), &[requires(parse_quote! { *n < 999 })]);
+        assert!(matches!(result, VerificationResult::Failed { .. }), "{:?}", result);
+    }
+
+    #[test]
+    fn test_quantifiers() {
+        use crate::parser::contracts::rewrite_implications;
+        use quote::quote;
+
+        let mut verifier = Z3Verifier::new().with_structs([parse_quote! { struct TxOut { value: u64 } }]);
+        let add_output: syn::ItemFn = parse_quote! {
+            fn add_output(outs: Vec<TxOut>, value: u64) -> Vec<TxOut> {
+                let mut outs = outs;
+                outs.push(TxOut { value });
+                outs
+            }
+        };
+        let contract = |tokens: proc_macro2::TokenStream| syn::parse2(rewrite_implications(tokens)).expect("contract parses");
+        let bounded = [
+            requires(contract(quote! { forall(|i: usize| i < outs.len() ==> outs[i].value <= 100) })),
+            requires(parse_quote!(value <= 100)),
+        ];
+        for (condition, holds) in [
+            (quote! { forall(|i: usize| i < result.len() ==> result[i].value <= 100) }, true),
+            (quote! { forall(|i: usize| i < result.len() ==> result[i].value <= 99) }, false),
+            (quote! { exists(|i: usize| i < result.len() && result[i].value == value) }, true),
+            (quote! { exists(|i: usize| i < outs.len() && outs[i].value == value) }, false),
+        ] {
+            let result = verifier.verify_contract_with_context(&ensures(contract(condition.clone())), Some(&add_output), &bounded);
+            match holds {
+                true => assert!(matches!(result, VerificationResult::Verified), "{}: {:?}", condition, result),
+                false => assert!(matches!(result, VerificationResult::Failed { .. }), "{}: {:?}", condition, result),
+            }
+        }
+    }
+}
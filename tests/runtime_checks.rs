@@ -0,0 +1,29 @@
+//! Contracts of `#[spec_locked]` functions, checked at run time with the
+//! `runtime-checks` feature (and compiling either way)
+
+use blvm_spec_lock::{ensures, requires, spec_locked};
+
+#[spec_locked("6.2", spec_path = "tests/fixtures/THE_ORANGE_PAPER.md")]
+#[ensures(result.len() == outputs.len())]
+pub fn keep_outputs(outputs: Vec<u64>) -> Vec<u64> {
+    outputs
+}
+
+#[spec_locked("6.2", spec_path = "tests/fixtures/THE_ORANGE_PAPER.md")]
+#[requires(input >= output)]
+#[ensures(result <= input)]
+pub fn calculate_fee(input: u64, output: u64) -> u64 {
+    input - output
+}
+
+#[test]
+fn test_consumed_parameter() {
+    assert_eq!(keep_outputs(vec![1, 2, 3]), vec![1, 2, 3]);
+}
+
+#[test]
+#[cfg(all(feature = "runtime-checks", debug_assertions))]
+#[should_panic(expected = "requires `input >= output` violated (Orange Paper Section 6.2)")]
+fn test_violated_requires() {
+    calculate_fee(1, 2);
+}